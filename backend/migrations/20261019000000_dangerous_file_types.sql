@@ -0,0 +1,7 @@
+-- Whether a file's type is dangerous to render inline from the content origin
+-- (e.g., HTML or SVG that can run scripts). This is detected by sniffing the
+-- file's content when its upload is finalized.
+
+ALTER TABLE files ADD COLUMN dangerous boolean NOT NULL DEFAULT FALSE;
+
+ALTER TABLE trashed_files ADD COLUMN dangerous boolean NOT NULL DEFAULT FALSE;
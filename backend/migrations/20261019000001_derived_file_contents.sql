@@ -0,0 +1,17 @@
+-- File contents derived from other file contents, such as thumbnails. These are
+-- keyed by the source content's hash rather than by file, so files with the
+-- same content can share derived contents.
+
+CREATE TABLE derived_file_contents (
+    created_at timestamptz(3) NOT NULL DEFAULT now(),
+    source_content_hash bytea NOT NULL,
+    variant text NOT NULL,
+    content_id bytea NOT NULL
+        REFERENCES file_contents (id) ON DELETE CASCADE,
+    type text NOT NULL,
+
+    PRIMARY KEY (source_content_hash, variant)
+);
+
+CREATE INDEX derived_file_contents_by_content_id
+    ON derived_file_contents (content_id);
@@ -0,0 +1,19 @@
+-- Stored file contents are periodically re-read and checked against their
+-- hashes to detect corruption in storage.
+
+ALTER TABLE users
+    ADD COLUMN admin boolean NOT NULL DEFAULT FALSE;
+
+ALTER TABLE file_contents
+    ADD COLUMN scrubbed_at timestamptz(3);
+
+CREATE INDEX file_contents_by_scrubbed_at
+    ON file_contents (scrubbed_at NULLS FIRST)
+    WHERE complete;
+
+CREATE TABLE corrupt_file_contents (
+    detected_at timestamptz(3) NOT NULL DEFAULT now(),
+    content_id bytea PRIMARY KEY
+        REFERENCES file_contents (id) ON DELETE CASCADE,
+    reason text NOT NULL
+);
@@ -0,0 +1,5 @@
+-- An upload can specify the hash its content is expected to have, so the
+-- upload can't be finalized if its content was corrupted in transit.
+
+ALTER TABLE file_contents
+    ADD COLUMN expected_hash bytea;
@@ -0,0 +1,8 @@
+-- Each user can have one token for signing into the WebDAV server, since WebDAV
+-- clients can't use browser sessions.
+
+CREATE TABLE webdav_tokens (
+    created_at timestamptz(3) NOT NULL DEFAULT now(),
+    user_id bytea PRIMARY KEY REFERENCES users (id) ON DELETE CASCADE,
+    token_hash bytea UNIQUE NOT NULL
+);
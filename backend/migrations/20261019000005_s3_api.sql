@@ -0,0 +1,33 @@
+-- Users sign requests to the S3-compatible API with access keys. SigV4
+-- signatures can only be verified by recomputing them, so secrets can't be
+-- hashed.
+
+CREATE TABLE s3_access_keys (
+    created_at timestamptz(3) NOT NULL DEFAULT now(),
+    id text PRIMARY KEY,
+    user_id bytea NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    secret text NOT NULL
+);
+
+CREATE INDEX s3_access_keys_by_user_id ON s3_access_keys (user_id);
+
+-- An S3 multipart upload is an incomplete file whose content's parts can be
+-- uploaded in any order. Each part is stored at index `part_number - 1` until
+-- the upload is completed, when the parts are renumbered to be contiguous.
+
+CREATE TABLE s3_multipart_uploads (
+    created_at timestamptz(3) NOT NULL DEFAULT now(),
+    content_id bytea PRIMARY KEY
+        REFERENCES file_contents (id) ON DELETE CASCADE
+);
+
+CREATE TABLE s3_multipart_parts (
+    modified_at timestamptz(3) NOT NULL DEFAULT now(),
+    content_id bytea NOT NULL
+        REFERENCES s3_multipart_uploads (content_id) ON DELETE CASCADE,
+    part_number integer NOT NULL CHECK (part_number BETWEEN 1 AND 10000),
+    size integer NOT NULL,
+    hash bytea NOT NULL,
+
+    PRIMARY KEY (content_id, part_number)
+);
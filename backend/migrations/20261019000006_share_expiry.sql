@@ -0,0 +1,18 @@
+-- Shares can expire. An expired share is treated as unshared right away, and a
+-- background job later clears `shared` so the flag stays accurate.
+
+ALTER TABLE files
+    ADD COLUMN share_expires_at timestamptz(3),
+    ADD CONSTRAINT share_expires_only_when_shared
+        CHECK (shared OR share_expires_at IS NULL);
+
+ALTER TABLE folders
+    ADD COLUMN share_expires_at timestamptz(3),
+    ADD CONSTRAINT share_expires_only_when_shared
+        CHECK (shared OR share_expires_at IS NULL);
+
+CREATE INDEX files_by_share_expires_at ON files (share_expires_at)
+    WHERE share_expires_at IS NOT NULL;
+
+CREATE INDEX folders_by_share_expires_at ON folders (share_expires_at)
+    WHERE share_expires_at IS NOT NULL;
@@ -0,0 +1,38 @@
+-- Shares can require a password. Visitors who enter it get an unlock token in a
+-- cookie on the content origin, and failed attempts are recorded so guessing
+-- can be rate-limited.
+
+ALTER TABLE files
+    ADD COLUMN share_password_hash text,
+    ADD CONSTRAINT share_password_only_when_shared
+        CHECK (shared OR share_password_hash IS NULL);
+
+ALTER TABLE folders
+    ADD COLUMN share_password_hash text,
+    ADD CONSTRAINT share_password_only_when_shared
+        CHECK (shared OR share_password_hash IS NULL);
+
+-- `share_id` is the ID of the shared file or folder. It has no foreign key since
+-- it can reference either table.
+CREATE TABLE share_unlocks (
+    created_at timestamptz(3) NOT NULL DEFAULT now(),
+    expires_at timestamptz(3) NOT NULL,
+    token_hash bytea NOT NULL,
+    share_id bytea NOT NULL,
+    PRIMARY KEY (token_hash, share_id)
+);
+
+CREATE INDEX share_unlocks_by_share_id ON share_unlocks (share_id);
+
+CREATE INDEX share_unlocks_by_expires_at ON share_unlocks (expires_at);
+
+CREATE TABLE share_unlock_attempts (
+    attempted_at timestamptz(3) NOT NULL DEFAULT now(),
+    share_id bytea NOT NULL
+);
+
+CREATE INDEX share_unlock_attempts_by_share_id
+    ON share_unlock_attempts (share_id, attempted_at);
+
+CREATE INDEX share_unlock_attempts_by_attempted_at
+    ON share_unlock_attempts (attempted_at);
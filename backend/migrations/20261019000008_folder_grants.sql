@@ -0,0 +1,15 @@
+-- Users can grant other users access to their folders. A grant applies to the
+-- folder and everything inside it. Each role includes the permissions of the
+-- roles before it.
+
+CREATE TYPE folder_role AS ENUM ('viewer', 'uploader', 'editor');
+
+CREATE TABLE folder_grants (
+    created_at timestamptz(3) NOT NULL DEFAULT now(),
+    folder_id bytea NOT NULL REFERENCES folders (id) ON DELETE CASCADE,
+    user_id bytea NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    role folder_role NOT NULL,
+    PRIMARY KEY (folder_id, user_id)
+);
+
+CREATE INDEX folder_grants_by_user_id ON folder_grants (user_id);
@@ -0,0 +1,37 @@
+-- Upload links let anyone with the link upload files into a folder without
+-- being able to see what's in it. Files uploaded through a link belong to the
+-- folder's owner. A `NULL` limit means there is no limit.
+
+CREATE TABLE upload_links (
+    created_at timestamptz(3) NOT NULL DEFAULT now(),
+    id bytea PRIMARY KEY,
+    token_hash bytea UNIQUE NOT NULL,
+    folder_id bytea NOT NULL REFERENCES folders (id) ON DELETE CASCADE,
+    expires_at timestamptz(3),
+    max_file_size bigint CHECK (max_file_size > 0),
+    max_file_count integer CHECK (max_file_count > 0),
+    allowed_types text[],
+    file_count integer NOT NULL DEFAULT 0,
+
+    CONSTRAINT file_count_within_max
+        CHECK (file_count <= max_file_count)
+);
+
+CREATE INDEX upload_links_by_folder_id ON upload_links (folder_id);
+
+CREATE INDEX upload_links_by_expires_at ON upload_links (expires_at)
+    WHERE expires_at IS NOT NULL;
+
+-- An incomplete file started through an upload link. Only uploads started
+-- through a link can be continued through it.
+
+CREATE TABLE upload_link_uploads (
+    created_at timestamptz(3) NOT NULL DEFAULT now(),
+    content_id bytea PRIMARY KEY
+        REFERENCES file_contents (id) ON DELETE CASCADE,
+    upload_link_id bytea NOT NULL
+        REFERENCES upload_links (id) ON DELETE CASCADE
+);
+
+CREATE INDEX upload_link_uploads_by_upload_link_id
+    ON upload_link_uploads (upload_link_id);
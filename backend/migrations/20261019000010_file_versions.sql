@@ -0,0 +1,24 @@
+-- When a file's content is replaced, its previous content is kept as a version
+-- of the file so it can be downloaded or restored later. `created_at` is when
+-- the content was replaced, and `modified_at` is when the file was last
+-- modified before then. Versions don't count toward folder sizes.
+
+CREATE TABLE file_versions (
+    created_at timestamptz(3) NOT NULL DEFAULT now(),
+    modified_at timestamptz(3) NOT NULL,
+    id bytea PRIMARY KEY,
+    file_id bytea NOT NULL,
+    file_complete boolean NOT NULL DEFAULT TRUE CHECK (file_complete),
+    content_id bytea NOT NULL REFERENCES file_contents (id),
+    size bigint NOT NULL,
+    type text NOT NULL,
+    dangerous boolean NOT NULL,
+
+    CONSTRAINT foreign_file FOREIGN KEY (file_id, file_complete)
+        REFERENCES files (id, complete)
+        ON DELETE CASCADE
+);
+
+CREATE INDEX file_versions_by_file_id ON file_versions (file_id, created_at);
+CREATE INDEX file_versions_by_created_at ON file_versions (created_at);
+CREATE INDEX file_versions_by_content_id ON file_versions (content_id);
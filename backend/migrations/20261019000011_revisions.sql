@@ -0,0 +1,6 @@
+-- A counter incremented whenever a file or folder is renamed, moved, or (for files) has its content
+-- replaced, so clients can detect concurrent changes using `ETag` and `If-Match` headers.
+
+ALTER TABLE files ADD COLUMN revision bigint NOT NULL DEFAULT 0;
+
+ALTER TABLE folders ADD COLUMN revision bigint NOT NULL DEFAULT 0;
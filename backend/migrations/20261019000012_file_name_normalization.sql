@@ -0,0 +1,139 @@
+-- File and folder names are now normalized to NFC before they're stored or compared, so names that
+-- look the same can't coexist. Existing names are normalized to match.
+
+-- Adds a number to a name before its extension (e.g., `notes (2).txt`), the same way conflicting
+-- names are numbered when they're created.
+CREATE FUNCTION pg_temp.numbered_name(name text, number integer) RETURNS text
+    LANGUAGE sql IMMUTABLE
+    RETURN CASE
+        WHEN strpos(substr(name, 2), '.') = 0 THEN format('%s (%s)', name, number)
+        ELSE format(
+            '%s (%s)%s',
+            substring(name FROM '^(.+)\.[^.]*$'),
+            number,
+            substring(name FROM '^.+(\.[^.]*)$')
+        )
+    END;
+
+-- Names that would become the same as another name in their folder once normalized are numbered
+-- first so they stay unique. A name that's already normalized keeps its name, or otherwise the
+-- item with the lowest ID does.
+DO $$
+DECLARE
+    folder record;
+    file record;
+    number integer;
+    new_name text;
+BEGIN
+    FOR folder IN
+        SELECT id, owner_id, parent_id_path, normalize(name, NFC) AS normalized_name
+            FROM folders AS renamed
+            WHERE name IS NOT NFC NORMALIZED
+                AND EXISTS (
+                    SELECT FROM folders
+                        WHERE owner_id = renamed.owner_id
+                            AND parent_id_path = renamed.parent_id_path
+                            AND id != renamed.id
+                            AND normalize(name, NFC) = normalize(renamed.name, NFC)
+                            AND (name IS NFC NORMALIZED OR id < renamed.id)
+                )
+    LOOP
+        number := 2;
+
+        LOOP
+            new_name := pg_temp.numbered_name(folder.normalized_name, number);
+
+            EXIT WHEN NOT EXISTS (
+                SELECT FROM folders
+                    WHERE owner_id = folder.owner_id
+                        AND parent_id_path = folder.parent_id_path
+                        AND normalize(name, NFC) = new_name
+            );
+
+            number := number + 1;
+        END LOOP;
+
+        UPDATE folders SET name = new_name
+            WHERE id = folder.id;
+
+        -- Everything inside the folder has its name in their paths.
+        UPDATE folders SET parent_name_path[cardinality(folder.parent_id_path) + 1] = new_name
+            WHERE owner_id = folder.owner_id
+                AND parent_id_path[cardinality(folder.parent_id_path) + 1] = folder.id;
+
+        UPDATE files SET parent_name_path[cardinality(folder.parent_id_path) + 1] = new_name
+            WHERE owner_id = folder.owner_id
+                AND parent_id_path[cardinality(folder.parent_id_path) + 1] = folder.id;
+    END LOOP;
+
+    FOR file IN
+        SELECT id, complete, owner_id, parent_id_path, normalize(name, NFC) AS normalized_name
+            FROM files AS renamed
+            WHERE name IS NOT NFC NORMALIZED
+                AND EXISTS (
+                    SELECT FROM files
+                        WHERE owner_id = renamed.owner_id
+                            AND parent_id_path = renamed.parent_id_path
+                            AND complete = renamed.complete
+                            AND id != renamed.id
+                            AND normalize(name, NFC) = normalize(renamed.name, NFC)
+                            AND (name IS NFC NORMALIZED OR id < renamed.id)
+                )
+    LOOP
+        number := 2;
+
+        LOOP
+            new_name := pg_temp.numbered_name(file.normalized_name, number);
+
+            EXIT WHEN NOT EXISTS (
+                SELECT FROM files
+                    WHERE owner_id = file.owner_id
+                        AND parent_id_path = file.parent_id_path
+                        AND complete = file.complete
+                        AND normalize(name, NFC) = new_name
+            );
+
+            number := number + 1;
+        END LOOP;
+
+        UPDATE files SET name = new_name
+            WHERE id = file.id AND complete = file.complete;
+    END LOOP;
+END
+$$;
+
+UPDATE folders SET
+    name = normalize(name, NFC),
+    parent_name_path = ARRAY(
+        SELECT normalize(parent_name, NFC)
+            FROM unnest(parent_name_path) WITH ORDINALITY AS parents (parent_name, i)
+            ORDER BY i
+    )
+    WHERE name IS NOT NFC NORMALIZED
+        OR EXISTS (
+            SELECT FROM unnest(parent_name_path) AS parent_name
+                WHERE parent_name IS NOT NFC NORMALIZED
+        );
+
+UPDATE files SET
+    name = normalize(name, NFC),
+    parent_name_path = ARRAY(
+        SELECT normalize(parent_name, NFC)
+            FROM unnest(parent_name_path) WITH ORDINALITY AS parents (parent_name, i)
+            ORDER BY i
+    )
+    WHERE name IS NOT NFC NORMALIZED
+        OR EXISTS (
+            SELECT FROM unnest(parent_name_path) AS parent_name
+                WHERE parent_name IS NOT NFC NORMALIZED
+        );
+
+UPDATE trashed_folders SET name = normalize(name, NFC)
+    WHERE name IS NOT NFC NORMALIZED;
+
+UPDATE trashed_files SET name = normalize(name, NFC)
+    WHERE name IS NOT NFC NORMALIZED;
+
+-- Whether the user wants file and folder names in their folders to be restricted to names that are
+-- also valid on Windows and macOS.
+ALTER TABLE users ADD COLUMN strict_file_names boolean NOT NULL DEFAULT FALSE;
@@ -0,0 +1,25 @@
+-- Users can register WebAuthn security keys as a second authentication factor.
+-- The public key is stored as the COSE key the authenticator returned.
+
+CREATE TABLE webauthn_credentials (
+    created_at timestamptz(3) NOT NULL DEFAULT now(),
+    last_used_at timestamptz(3),
+    id bytea PRIMARY KEY,
+    user_id bytea NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    name text NOT NULL,
+    public_key bytea NOT NULL,
+    sign_count bigint NOT NULL
+);
+
+CREATE INDEX webauthn_credentials_by_user_id ON webauthn_credentials (user_id);
+
+-- Each WebAuthn registration or assertion must sign a fresh challenge the
+-- server issued, which is deleted once used or expired.
+
+CREATE TABLE webauthn_challenges (
+    created_at timestamptz(3) NOT NULL DEFAULT now(),
+    challenge bytea PRIMARY KEY,
+    user_id bytea NOT NULL REFERENCES users (id) ON DELETE CASCADE
+);
+
+CREATE INDEX webauthn_challenges_by_created_at ON webauthn_challenges (created_at);
@@ -0,0 +1,10 @@
+-- Users can register WebAuthn credentials as passkeys, which are discoverable
+-- and verify the user on their own, so they can be used without a password.
+
+ALTER TABLE webauthn_credentials
+    ADD COLUMN passkey boolean NOT NULL DEFAULT FALSE;
+
+-- Passkey sign-in challenges are issued before the user is known.
+
+ALTER TABLE webauthn_challenges
+    ALTER COLUMN user_id DROP NOT NULL;
@@ -0,0 +1,14 @@
+-- Users can create personal API tokens for scripts and CI. They're stored as
+-- sessions so every route that authenticates a session accepts them, but unlike
+-- browser sessions, they have a name, are limited to scopes, and can expire.
+
+CREATE TYPE api_token_scope AS ENUM ('read_files', 'write_files', 'manage_shares', 'account');
+
+ALTER TABLE sessions
+    ADD COLUMN name text,
+    ADD COLUMN scopes api_token_scope[],
+    ADD COLUMN expires_at timestamptz(3),
+    ADD CHECK ((name IS NULL) = (scopes IS NULL)),
+    ADD CHECK (expires_at IS NULL OR scopes IS NOT NULL);
+
+CREATE INDEX sessions_by_expires_at ON sessions (expires_at);
@@ -0,0 +1,51 @@
+-- File Garden is an OAuth 2.0 authorization server, so third-party apps can act
+-- on users' behalf. Any user can register apps as OAuth clients. Clients
+-- without a secret are public (e.g., native or browser apps) and rely on PKCE
+-- alone.
+
+CREATE TABLE oauth_clients (
+    created_at timestamptz(3) NOT NULL DEFAULT now(),
+    id bytea PRIMARY KEY,
+    user_id bytea NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    name text NOT NULL,
+    redirect_uris text[] NOT NULL,
+    secret_hash bytea
+);
+
+CREATE INDEX oauth_clients_by_user_id ON oauth_clients (user_id);
+
+-- Authorization codes are exchanged once for tokens within 10 minutes.
+
+CREATE TABLE oauth_authorization_codes (
+    created_at timestamptz(3) NOT NULL DEFAULT now(),
+    code_hash bytea PRIMARY KEY,
+    client_id bytea NOT NULL REFERENCES oauth_clients (id) ON DELETE CASCADE,
+    user_id bytea NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    redirect_uri text NOT NULL,
+    scopes api_token_scope[] NOT NULL,
+    code_challenge text NOT NULL
+);
+
+CREATE INDEX oauth_authorization_codes_by_created_at ON oauth_authorization_codes (created_at);
+
+-- A grant is a user's consent for a client to access their account with
+-- certain scopes. Its refresh token is replaced each time it's used, and its
+-- access tokens are stored as sessions so they're authenticated like API tokens.
+
+CREATE TABLE oauth_grants (
+    created_at timestamptz(3) NOT NULL DEFAULT now(),
+    id bytea PRIMARY KEY,
+    client_id bytea NOT NULL REFERENCES oauth_clients (id) ON DELETE CASCADE,
+    user_id bytea NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    scopes api_token_scope[] NOT NULL,
+    refresh_token_hash bytea UNIQUE NOT NULL
+);
+
+CREATE INDEX oauth_grants_by_client_id ON oauth_grants (client_id);
+CREATE INDEX oauth_grants_by_user_id ON oauth_grants (user_id);
+
+ALTER TABLE sessions
+    ADD COLUMN oauth_grant_id bytea REFERENCES oauth_grants (id) ON DELETE CASCADE,
+    ADD CHECK (oauth_grant_id IS NULL OR scopes IS NOT NULL);
+
+CREATE INDEX sessions_by_oauth_grant_id ON sessions (oauth_grant_id);
@@ -0,0 +1,29 @@
+-- Users can link an account at an external OpenID Connect provider and sign in
+-- with it instead of a password. Identities are keyed by issuer so changing the
+-- configured provider can't match users to another provider's accounts.
+
+CREATE TABLE oidc_identities (
+    created_at timestamptz(3) NOT NULL DEFAULT now(),
+    user_id bytea PRIMARY KEY REFERENCES users (id) ON DELETE CASCADE,
+    issuer text NOT NULL,
+    subject text NOT NULL,
+    UNIQUE (issuer, subject)
+);
+
+-- Each redirect to the provider has a state to protect the callback from CSRF,
+-- a nonce to bind the ID token to it, and a PKCE code verifier. A linking user
+-- is set when linking an identity rather than signing in. Once the provider
+-- identifies the user signing in, they're set as the identified user so 2FA
+-- can be retried without going through the provider again.
+
+CREATE TABLE oidc_authorizations (
+    created_at timestamptz(3) NOT NULL DEFAULT now(),
+    state_hash bytea PRIMARY KEY,
+    nonce text NOT NULL,
+    code_verifier text NOT NULL,
+    linking_user_id bytea REFERENCES users (id) ON DELETE CASCADE,
+    identified_user_id bytea REFERENCES users (id) ON DELETE CASCADE,
+    CHECK (linking_user_id IS NULL OR identified_user_id IS NULL)
+);
+
+CREATE INDEX oidc_authorizations_by_created_at ON oidc_authorizations (created_at);
@@ -0,0 +1,5 @@
+-- Browser sessions remember the user agent they were created from, so users can
+-- tell their sessions apart.
+
+ALTER TABLE sessions
+    ADD COLUMN user_agent text;
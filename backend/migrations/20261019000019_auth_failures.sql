@@ -0,0 +1,12 @@
+-- Failed attempts to authenticate are counted per user and per client IP
+-- address, so passwords and OTPs can't be guessed by brute force. Each subject is
+-- locked out for exponentially longer the more it keeps failing.
+
+CREATE TABLE auth_failures (
+    subject text PRIMARY KEY,
+    failure_count integer NOT NULL DEFAULT 1,
+    failed_at timestamptz(3) NOT NULL DEFAULT now(),
+    locked_until timestamptz(3)
+);
+
+CREATE INDEX auth_failures_by_failed_at ON auth_failures (failed_at);
@@ -0,0 +1,11 @@
+-- Users are emailed when security-sensitive changes are made to their account.
+-- Each email links to a notice the user can use to sign out everywhere if they
+-- didn't make the change.
+
+CREATE TABLE security_notices (
+    created_at timestamptz(3) NOT NULL DEFAULT now(),
+    token_hash bytea PRIMARY KEY,
+    user_id bytea NOT NULL REFERENCES users (id) ON DELETE CASCADE
+);
+
+CREATE INDEX security_notices_by_created_at ON security_notices (created_at);
@@ -0,0 +1,5 @@
+-- TOTP backup codes are now stored as salted hashes rather than in plain text. Hashing them can't be
+-- done in SQL, so the backend hashes any remaining plain text codes when it starts.
+
+ALTER TABLE totp
+    RENAME COLUMN unused_backup_codes TO unused_backup_code_hashes;
@@ -0,0 +1,6 @@
+-- TOTP secrets are now encrypted at rest, and each remembers the ID of the key it was encrypted with
+-- so keys can be rotated. Encrypting them can't be done in SQL, so the backend encrypts any secrets
+-- still stored in plain text (with a null key ID) when it starts.
+
+ALTER TABLE totp
+    ADD COLUMN secret_key_id text;
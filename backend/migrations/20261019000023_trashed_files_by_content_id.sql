@@ -0,0 +1,4 @@
+-- Derived file contents are cleaned up once no file, trashed file or file version
+-- has their source content, which needs trashed files to be found by content.
+
+CREATE INDEX trashed_files_by_content_id ON trashed_files (content_id);
@@ -0,0 +1,7 @@
+-- A trashed file keeps the ID it had before it was trashed, which isn't the ID
+-- of any file content. It's the trashed file's content that has to exist.
+
+ALTER TABLE trashed_files DROP CONSTRAINT trashed_files_id_fkey;
+
+ALTER TABLE trashed_files ADD CONSTRAINT trashed_files_content_id_fkey
+    FOREIGN KEY (content_id) REFERENCES file_contents (id);
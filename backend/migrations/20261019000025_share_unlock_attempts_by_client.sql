@@ -0,0 +1,13 @@
+-- Failed attempts to unlock a share are counted per client, so one client
+-- guessing passwords can't lock everyone else out of the share. `client` is keyed
+-- the same way as client IP address subjects in `auth_failures`. Attempts made
+-- before this can't be attributed to a client, so they're discarded.
+
+DELETE FROM share_unlock_attempts;
+
+ALTER TABLE share_unlock_attempts ADD COLUMN client text NOT NULL;
+
+DROP INDEX share_unlock_attempts_by_share_id;
+
+CREATE INDEX share_unlock_attempts_by_share_id_and_client
+    ON share_unlock_attempts (share_id, client, attempted_at);
@@ -0,0 +1,8 @@
+-- File contents that couldn't be processed into derived file contents (e.g.,
+-- because they couldn't be decoded as images), so they're never read again to
+-- try. Like derived file contents, these are keyed by the content's hash.
+
+CREATE TABLE unprocessable_file_contents (
+    created_at timestamptz(3) NOT NULL DEFAULT now(),
+    source_content_hash bytea PRIMARY KEY
+);
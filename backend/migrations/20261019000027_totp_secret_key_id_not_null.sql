@@ -0,0 +1,36 @@
+-- Every TOTP secret in `totp` now has the ID of the key it was encrypted with. Encrypting secrets
+-- can't be done in SQL, so any still stored in plain text are moved to `plain_text_totp`, and the
+-- backend encrypts them and moves them back when it starts.
+
+CREATE TABLE plain_text_totp (
+    created_at timestamptz(3) NOT NULL,
+    user_id bytea PRIMARY KEY REFERENCES users (id) ON DELETE CASCADE,
+    secret bytea NOT NULL,
+    otp_used_last text NOT NULL,
+    otp_used_2nd_to_last text,
+    unused_backup_code_hashes text[] NOT NULL
+);
+
+INSERT INTO plain_text_totp (
+    created_at,
+    user_id,
+    secret,
+    otp_used_last,
+    otp_used_2nd_to_last,
+    unused_backup_code_hashes
+)
+    SELECT
+        created_at,
+        user_id,
+        secret,
+        otp_used_last,
+        otp_used_2nd_to_last,
+        unused_backup_code_hashes
+    FROM totp
+    WHERE secret_key_id IS NULL;
+
+DELETE FROM totp
+    WHERE secret_key_id IS NULL;
+
+ALTER TABLE totp
+    ALTER COLUMN secret_key_id SET NOT NULL;
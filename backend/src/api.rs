@@ -8,13 +8,15 @@ pub(crate) use response::Error;
 use routes::ROUTER;
 
 mod captcha;
-mod cookie;
-mod db_helpers;
-mod extract;
+pub(crate) mod cookie;
+pub(crate) mod db_helpers;
+pub(crate) mod extract;
 mod json;
+pub(crate) mod rate_limit;
 mod response;
 mod routes;
-mod validation;
+mod user_agent;
+pub(crate) mod validation;
 
 /// Routes a request to an API endpoint.
 pub(super) async fn handle(request: Request) -> axum::response::Response {
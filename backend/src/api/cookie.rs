@@ -17,6 +17,28 @@ impl CookieWrapper for SessionCookie<'_> {
     const MAX_AGE: Duration = Duration::days(60);
 }
 
+/// A cookie on the content origin identifying which password-protected shares a visitor has
+/// unlocked. It's kept separate from [`SessionCookie`] since it's set on a different authority and
+/// isn't tied to any user.
+#[derive(From, AsRef, AsMut, Clone, PartialEq, Debug)]
+pub(crate) struct ShareUnlockCookie<'c>(Cookie<'c>);
+
+impl CookieWrapper for ShareUnlockCookie<'_> {
+    const NAME: &'static str = "share_unlock";
+    const MAX_AGE: Duration = Duration::days(1);
+}
+
+/// A cookie holding the state of a request to sign in with the external OpenID Connect provider, so
+/// only the browser that started signing in can finish it. Otherwise, someone could send another
+/// user the provider's redirect for their own account and sign them into it.
+#[derive(From, AsRef, AsMut, Clone, PartialEq, Debug)]
+pub(crate) struct OidcAuthorizationCookie<'c>(Cookie<'c>);
+
+impl CookieWrapper for OidcAuthorizationCookie<'_> {
+    const NAME: &'static str = "oidc_authorization";
+    const MAX_AGE: Duration = Duration::minutes(10);
+}
+
 /// A trait for a type that wraps a [`Cookie`], adding convenience methods on top of it. Each type
 /// implementing this represents a type of cookie with a constant name, as well as other constant
 /// cookie attributes.
@@ -1,15 +1,45 @@
 //! Helper functions that perform common database operations.
 
-use sqlx::PgTransaction;
+use std::{env::VarError, sync::LazyLock};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{Acquire, PgTransaction};
+use strum_macros::{EnumString, IntoStaticStr};
 
 use crate::{
-    api,
+    WEBSITE_ORIGIN,
+    api::{
+        self,
+        extract::{IfMatch, UserAgent},
+        user_agent::describe_device,
+        validation::FileName,
+    },
     crypto::hash_without_salt,
     db::{TxError, TxResult},
-    id::Token,
+    email::SecurityNotice,
+    file_type::SniffedType,
+    id::{FolderBrowseKey, NewFileId, NewFileVersionId, NewFolderId, Token, WebAuthnChallenge},
+    oidc,
+    storage::WrittenContent,
 };
 
-/// Creates a new user session and returns its token.
+/// The most previous versions of its content to keep for each file. When a file has more, the
+/// oldest are pruned.
+static MAX_FILE_VERSIONS: LazyLock<i64> =
+    LazyLock::new(|| match dotenvy::var("MAX_FILE_VERSIONS") {
+        Err(dotenvy::Error::EnvVar(VarError::NotPresent)) => 20,
+
+        max_file_versions => max_file_versions
+            .ok()
+            .and_then(|max_file_versions| max_file_versions.parse().ok())
+            .filter(|max_file_versions| *max_file_versions >= 0)
+            .expect(
+                "environment variable `MAX_FILE_VERSIONS` should be a non-negative integer if set",
+            ),
+    });
+
+/// Creates a new user session from a user agent and returns its token.
 ///
 /// # Errors
 ///
@@ -17,6 +47,7 @@ use crate::{
 pub(crate) async fn create_session<E>(
     tx: &mut PgTransaction<'static>,
     user_id: &[u8],
+    user_agent: &UserAgent,
 ) -> TxResult<Token, E>
 where
     E: From<sqlx::Error>,
@@ -25,10 +56,11 @@ where
     let token_hash = hash_without_salt(&token);
 
     sqlx::query!(
-        "INSERT INTO sessions (token_hash, user_id)
-            VALUES ($1, $2)",
+        "INSERT INTO sessions (token_hash, user_id, user_agent)
+            VALUES ($1, $2, $3)",
         token_hash.as_ref(),
         user_id,
+        user_agent.as_deref(),
     )
     .execute(tx.as_mut())
     .await?;
@@ -36,8 +68,293 @@ where
     Ok(token)
 }
 
-/// `SELECT`s a folder's ID and name paths if the user has permission to modify the folder's
-/// contents.
+/// Checks whether a user has an existing session created from the same user agent, meaning it's
+/// probably a device they've signed in from before.
+///
+/// # Errors
+///
+/// Returns an error if a database query fails.
+pub(crate) async fn is_known_device(
+    tx: &mut PgTransaction<'static>,
+    user_id: &[u8],
+    user_agent: &UserAgent,
+) -> sqlx::Result<bool> {
+    Ok(sqlx::query!(
+        "SELECT TRUE AS exists FROM sessions
+            WHERE user_id = $1 AND user_agent IS NOT DISTINCT FROM $2 AND scopes IS NULL
+            LIMIT 1",
+        user_id,
+        user_agent.as_deref(),
+    )
+    .fetch_optional(tx.as_mut())
+    .await?
+    .is_some())
+}
+
+/// Creates a notice about a security-sensitive change to a user's account, returning the details to
+/// email them. The notice links to a page where they can sign out everywhere if they didn't make
+/// the change, which works for 7 days.
+///
+/// This must be called before any change to the user's email, so the notice goes to the email they
+/// had when the change was made.
+///
+/// # Errors
+///
+/// Returns an error if a database query fails.
+pub(crate) async fn create_security_notice<E>(
+    tx: &mut PgTransaction<'static>,
+    user_id: &[u8],
+    user_agent: &UserAgent,
+) -> TxResult<SecurityNotice, E>
+where
+    E: From<sqlx::Error>,
+{
+    let user = sqlx::query!(
+        "SELECT name, email FROM users
+            WHERE id = $1",
+        user_id,
+    )
+    .fetch_one(tx.as_mut())
+    .await?;
+
+    let token = Token::generate();
+    let token_hash = hash_without_salt(&token);
+
+    sqlx::query!(
+        "INSERT INTO security_notices (token_hash, user_id)
+            VALUES ($1, $2)",
+        token_hash.as_ref(),
+        user_id,
+    )
+    .execute(tx.as_mut())
+    .await?;
+
+    Ok(SecurityNotice {
+        user_name: user.name,
+        email: user.email,
+        time: Utc::now().format("%B %-d, %Y at %H:%M UTC").to_string(),
+        device: user_agent.as_deref().and_then(describe_device),
+        revoke_sessions_url: format!("{}/revoke-sessions?token={token}", *WEBSITE_ORIGIN),
+    })
+}
+
+/// Issues a new WebAuthn challenge for a registration or assertion to sign. The challenge is issued
+/// for a user unless it's for signing in with a passkey, where the user isn't known yet.
+///
+/// # Errors
+///
+/// Returns an error if a database query fails.
+pub(crate) async fn create_webauthn_challenge<E>(
+    tx: &mut PgTransaction<'static>,
+    user_id: Option<&[u8]>,
+) -> TxResult<WebAuthnChallenge, E>
+where
+    E: From<sqlx::Error>,
+{
+    let challenge = WebAuthnChallenge::generate();
+
+    match sqlx::query!(
+        "INSERT INTO webauthn_challenges (challenge, user_id)
+            VALUES ($1, $2)",
+        challenge.as_slice(),
+        user_id,
+    )
+    .execute(tx.as_mut())
+    .await
+    {
+        Err(sqlx::Error::Database(error))
+            if error.constraint() == Some("webauthn_challenges_pkey") =>
+        {
+            return Err(TxError::Retry);
+        }
+        result => result?,
+    };
+
+    Ok(challenge)
+}
+
+/// Uses up a WebAuthn challenge issued for a user (or for no user if `None`), returning whether it
+/// was issued, unused, and unexpired. Challenges expire after 5 minutes.
+///
+/// # Errors
+///
+/// Returns an error if a database query fails.
+pub(crate) async fn consume_webauthn_challenge<E>(
+    tx: &mut PgTransaction<'static>,
+    challenge: &[u8],
+    user_id: Option<&[u8]>,
+) -> TxResult<bool, E>
+where
+    E: From<sqlx::Error>,
+{
+    Ok(sqlx::query!(
+        "DELETE FROM webauthn_challenges
+            WHERE challenge = $1
+                AND user_id IS NOT DISTINCT FROM $2
+                AND created_at > now() - interval '5 minutes'",
+        challenge,
+        user_id,
+    )
+    .execute(tx.as_mut())
+    .await?
+    .rows_affected()
+        != 0)
+}
+
+/// Stores a new authorization request for the OpenID Connect provider until the user is redirected
+/// back, for linking an identity to a user (or for signing in if `None`). Authorization requests
+/// expire after 10 minutes.
+///
+/// # Errors
+///
+/// Returns an error if a database query fails.
+pub(crate) async fn create_oidc_authorization<E>(
+    tx: &mut PgTransaction<'static>,
+    authorization: &oidc::Authorization,
+    linking_user_id: Option<&[u8]>,
+) -> TxResult<(), E>
+where
+    E: From<sqlx::Error>,
+{
+    let state_hash = hash_without_salt(&authorization.state);
+
+    sqlx::query!(
+        "INSERT INTO oidc_authorizations (state_hash, nonce, code_verifier, linking_user_id)
+            VALUES ($1, $2, $3, $4)",
+        state_hash.as_ref(),
+        authorization.nonce,
+        authorization.code_verifier,
+        linking_user_id,
+    )
+    .execute(tx.as_mut())
+    .await?;
+
+    Ok(())
+}
+
+/// A role another user can be granted on a folder, giving them access to the folder and everything
+/// inside it. Each role includes the permissions of the roles before it. A folder's owner can
+/// always do anything with it.
+#[derive(
+    sqlx::Type, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug,
+)]
+#[sqlx(type_name = "folder_role", rename_all = "lowercase")]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum FolderRole {
+    /// Can view files.
+    Viewer,
+
+    /// Can also upload files and create folders.
+    Uploader,
+
+    /// Can also rename, move, and delete files and folders.
+    Editor,
+}
+
+/// A scope granting a personal API token access to a group of API routes. API tokens can never
+/// access routes that view or change the user's credentials.
+#[derive(
+    sqlx::Type,
+    Deserialize,
+    Serialize,
+    EnumString,
+    IntoStaticStr,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Debug,
+)]
+#[sqlx(type_name = "api_token_scope", rename_all = "snake_case")]
+#[serde(rename_all = "camelCase")]
+#[strum(serialize_all = "camelCase")]
+pub(crate) enum ApiTokenScope {
+    /// Can view files and folders.
+    ReadFiles,
+
+    /// Can upload, change, and delete files and folders.
+    WriteFiles,
+
+    /// Can view and change shares, upload links, and folder grants.
+    ManageShares,
+
+    /// Can view and change the user's profile and settings.
+    Account,
+}
+
+/// What to do when something with the same name already exists where a file or folder is being put.
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum OnConflict {
+    /// Fail with [`api::Error::AlreadyExists`].
+    #[default]
+    Fail,
+
+    /// Add a number to the name (e.g., `notes (2).txt`) so it's no longer taken.
+    Rename,
+
+    /// Replace what's already there.
+    Replace,
+}
+
+/// The most numbered names to try for a file or folder before giving up because they're all taken.
+const MAX_NAME_NUMBER: u32 = 100;
+
+/// A folder's ID and name paths, along with its owner.
+#[derive(Debug)]
+pub(crate) struct FolderPaths {
+    /// The ID of the user who owns the folder.
+    pub(crate) owner_id: Vec<u8>,
+
+    /// The folder's ID path, including its own ID.
+    pub(crate) id_path: Vec<Vec<u8>>,
+
+    /// The folder's name path, including its own name.
+    pub(crate) name_path: Vec<String>,
+}
+
+/// Checks if a user has at least a certain role on a folder owned by a certain user.
+/// `folder_id_path` is the folder's ID path including its own ID, or empty for the owner's root
+/// folder. Owners have every role, and other users have the best role granted to them on the folder
+/// or any of its ancestors.
+///
+/// # Errors
+///
+/// Returns an error if a database query fails.
+pub(crate) async fn has_role<E>(
+    tx: &mut PgTransaction<'static>,
+    user_id: &[u8],
+    owner_id: &[u8],
+    folder_id_path: &[Vec<u8>],
+    role: FolderRole,
+) -> TxResult<bool, E>
+where
+    E: From<sqlx::Error>,
+{
+    if user_id == owner_id {
+        return Ok(true);
+    }
+
+    if folder_id_path.is_empty() {
+        return Ok(false);
+    }
+
+    let granted_role = sqlx::query_scalar!(
+        r#"SELECT max(role) AS "role: FolderRole" FROM folder_grants
+            WHERE user_id = $1 AND folder_id = ANY($2)"#,
+        user_id,
+        folder_id_path,
+    )
+    .fetch_one(tx.as_mut())
+    .await?;
+
+    Ok(granted_role.is_some_and(|granted_role| granted_role >= role))
+}
+
+/// `SELECT`s a folder's ID and name paths and its owner if the user has at least a certain role on
+/// it, such as to modify its contents. If `folder_id` is [`None`], the user's root folder is used.
 ///
 /// # Errors
 ///
@@ -45,13 +362,21 @@ where
 pub(crate) async fn query_folder_paths_to_modify_contents(
     tx: &mut PgTransaction<'static>,
     user_id: &[u8],
-    folder_id: &[u8],
-) -> TxResult<(Vec<Vec<u8>>, Vec<String>), api::Error> {
+    folder_id: Option<&[u8]>,
+    role: FolderRole,
+) -> TxResult<FolderPaths, api::Error> {
+    let Some(folder_id) = folder_id else {
+        return Ok(FolderPaths {
+            owner_id: user_id.to_vec(),
+            id_path: vec![],
+            name_path: vec![],
+        });
+    };
+
     let Some(folder) = sqlx::query!(
-        "SELECT name, parent_id_path, parent_name_path FROM folders
-            WHERE id = $1 AND owner_id = $2",
+        "SELECT owner_id, name, parent_id_path, parent_name_path FROM folders
+            WHERE id = $1",
         folder_id,
-        user_id,
     )
     .fetch_optional(tx.as_mut())
     .await?
@@ -62,8 +387,1209 @@ pub(crate) async fn query_folder_paths_to_modify_contents(
     let mut id_path = folder.parent_id_path;
     id_path.push(folder_id.to_vec());
 
+    if !has_role(tx, user_id, &folder.owner_id, &id_path, role).await? {
+        return Err(TxError::Abort(api::Error::AccessDenied));
+    }
+
     let mut name_path = folder.parent_name_path;
     name_path.push(folder.name);
 
-    Ok((id_path, name_path))
+    Ok(FolderPaths {
+        owner_id: folder.owner_id,
+        id_path,
+        name_path,
+    })
+}
+
+/// Checks that a new file or folder name is allowed in a user's folders, which requires the name
+/// to pass [`FileName::check_strict`] if the user has opted into strict file names.
+///
+/// # Errors
+///
+/// Returns an error if a database query fails or if the name isn't allowed.
+pub(crate) async fn check_file_name(
+    tx: &mut PgTransaction<'static>,
+    owner_id: &[u8],
+    name: &FileName,
+) -> TxResult<(), api::Error> {
+    let strict_file_names = sqlx::query_scalar!(
+        "SELECT strict_file_names FROM users
+            WHERE id = $1",
+        owner_id,
+    )
+    .fetch_one(tx.as_mut())
+    .await?;
+
+    if strict_file_names {
+        name.check_strict()
+            .map_err(api::Error::FileNameNotAllowed)?;
+    }
+
+    Ok(())
+}
+
+/// Creates a folder, returning its ID, browse key, and creation timestamp.
+///
+/// # Errors
+///
+/// Returns an error if a database query fails, if the user doesn't have access to the parent
+/// folder, if the name isn't allowed there, or if something with the same name already exists
+/// there.
+pub(crate) async fn create_folder(
+    tx: &mut PgTransaction<'static>,
+    user_id: &[u8],
+    parent_id: Option<&[u8]>,
+    name: &FileName,
+) -> TxResult<(NewFolderId, FolderBrowseKey, DateTime<Utc>), api::Error> {
+    let parent =
+        query_folder_paths_to_modify_contents(tx, user_id, parent_id, FolderRole::Uploader).await?;
+
+    check_file_name(tx, &parent.owner_id, name).await?;
+
+    let folder_id = NewFolderId::generate();
+    let browse_key = FolderBrowseKey::generate();
+
+    let folder = match sqlx::query!(
+        "INSERT INTO folders
+            (id, name, owner_id, parent_id_path, parent_name_path, browse_key)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING created_at",
+        folder_id.as_slice(),
+        name.as_str(),
+        parent.owner_id,
+        parent.id_path.as_slice(),
+        parent.name_path.as_slice(),
+        browse_key.as_slice(),
+    )
+    .fetch_one(tx.as_mut())
+    .await
+    {
+        Err(sqlx::Error::Database(error)) if error.constraint() == Some("folders_by_name_path") => {
+            return Err(TxError::Abort(api::Error::AlreadyExists));
+        }
+
+        Err(sqlx::Error::Database(error)) if error.constraint() == Some("folders_pkey") => {
+            return Err(TxError::Retry);
+        }
+
+        result => result?,
+    };
+
+    Ok((folder_id, browse_key, folder.created_at))
+}
+
+/// Adds a number to a file or folder name before its extension (e.g., `notes (2).txt`), or returns
+/// [`None`] if that would make the name too long.
+pub(crate) fn numbered_name(name: &FileName, number: u32) -> Option<FileName> {
+    let (stem, extension) = match name.rfind('.') {
+        Some(index) if index != 0 => name.split_at(index),
+        _ => (name.as_str(), ""),
+    };
+
+    format!("{stem} ({number}){extension}").try_into().ok()
+}
+
+/// Whether something is a file or a folder. Files and folders don't share names, so a file and a
+/// folder in the same folder can have the same name.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum ItemKind {
+    /// A file.
+    File,
+
+    /// A folder.
+    Folder,
+}
+
+/// Gets the ID of the file (complete or not) or folder with a name in a folder, ignoring the item
+/// with the ID `except_id`.
+///
+/// # Errors
+///
+/// Returns an error if a database query fails.
+async fn query_item_named(
+    tx: &mut PgTransaction<'static>,
+    kind: ItemKind,
+    parent: &FolderPaths,
+    name: &str,
+    except_id: Option<&[u8]>,
+) -> TxResult<Option<Vec<u8>>, api::Error> {
+    let item_id = match kind {
+        ItemKind::File => {
+            sqlx::query_scalar!(
+                "SELECT id FROM files
+                    WHERE owner_id = $1
+                        AND parent_name_path = $2
+                        AND name = $3
+                        AND id IS DISTINCT FROM $4
+                    ORDER BY complete DESC
+                    LIMIT 1",
+                parent.owner_id,
+                parent.name_path.as_slice(),
+                name,
+                except_id,
+            )
+            .fetch_optional(tx.as_mut())
+            .await?
+        }
+
+        ItemKind::Folder => {
+            sqlx::query_scalar!(
+                "SELECT id FROM folders
+                    WHERE owner_id = $1
+                        AND parent_name_path = $2
+                        AND name = $3
+                        AND id IS DISTINCT FROM $4",
+                parent.owner_id,
+                parent.name_path.as_slice(),
+                name,
+                except_id,
+            )
+            .fetch_optional(tx.as_mut())
+            .await?
+        }
+    };
+
+    Ok(item_id)
+}
+
+/// Finds the first name for a file or folder that isn't taken in a folder, adding a number to it if
+/// necessary, while ignoring the item with the ID `except_id`.
+///
+/// # Errors
+///
+/// Returns an error if a database query fails or if every numbered name is taken.
+pub(crate) async fn available_name(
+    tx: &mut PgTransaction<'static>,
+    kind: ItemKind,
+    parent: &FolderPaths,
+    name: &FileName,
+    except_id: Option<&[u8]>,
+) -> TxResult<FileName, api::Error> {
+    let mut available_name = name.clone();
+    let mut number = 1;
+
+    while query_item_named(tx, kind, parent, &available_name, except_id)
+        .await?
+        .is_some()
+    {
+        number += 1;
+
+        if number > MAX_NAME_NUMBER {
+            return Err(TxError::Abort(api::Error::AlreadyExists));
+        }
+
+        let Some(numbered_name) = numbered_name(name, number) else {
+            return Err(TxError::Abort(api::Error::AlreadyExists));
+        };
+
+        available_name = numbered_name;
+    }
+
+    Ok(available_name)
+}
+
+/// Resolves a name conflict for a file being put in a folder, returning the name to put it at. If
+/// the file is being moved, its ID should be specified so it doesn't conflict with itself.
+///
+/// With [`OnConflict::Replace`], a complete file already at the name is deleted, so the user must
+/// be able to edit the folder. With [`OnConflict::Fail`], the name is returned as is, leaving the
+/// conflict to be detected when the file is put there.
+///
+/// # Errors
+///
+/// Returns an error if a database query fails, if every numbered name is taken, or if the file to
+/// replace can't be deleted.
+pub(crate) async fn resolve_file_name_conflict(
+    tx: &mut PgTransaction<'static>,
+    user_id: &[u8],
+    parent: &FolderPaths,
+    name: &FileName,
+    file_id: Option<&[u8]>,
+    on_conflict: OnConflict,
+) -> TxResult<FileName, api::Error> {
+    match on_conflict {
+        OnConflict::Fail => Ok(name.clone()),
+
+        OnConflict::Rename => available_name(tx, ItemKind::File, parent, name, file_id).await,
+
+        OnConflict::Replace => {
+            // Only complete files are replaced, since a file being uploaded doesn't conflict yet.
+            let existing_file_id = sqlx::query_scalar!(
+                "SELECT id FROM files
+                    WHERE owner_id = $1
+                        AND parent_name_path = $2
+                        AND name = $3
+                        AND complete
+                        AND id IS DISTINCT FROM $4",
+                parent.owner_id,
+                parent.name_path.as_slice(),
+                name.as_str(),
+                file_id,
+            )
+            .fetch_optional(tx.as_mut())
+            .await?;
+
+            if let Some(existing_file_id) = existing_file_id {
+                delete_file(tx, user_id, &existing_file_id, &IfMatch::default()).await?;
+            }
+
+            Ok(name.clone())
+        }
+    }
+}
+
+/// Resolves a name conflict for a folder being put in another folder, returning the name to put it
+/// at. If the folder is being moved, its ID should be specified so it doesn't conflict with itself.
+///
+/// With [`OnConflict::Replace`], a folder already at the name is moved to the trash along with
+/// everything in it, so the user must be able to edit the parent folder. With [`OnConflict::Fail`],
+/// the name is returned as is, leaving the conflict to be detected when the folder is put there.
+///
+/// # Errors
+///
+/// Returns an error if a database query fails, if every numbered name is taken, or if the folder to
+/// replace contains the folder being moved or can't be trashed.
+pub(crate) async fn resolve_folder_name_conflict(
+    tx: &mut PgTransaction<'static>,
+    user_id: &[u8],
+    parent: &FolderPaths,
+    name: &FileName,
+    folder_id: Option<&[u8]>,
+    on_conflict: OnConflict,
+) -> TxResult<FileName, api::Error> {
+    match on_conflict {
+        OnConflict::Fail => Ok(name.clone()),
+
+        OnConflict::Rename => available_name(tx, ItemKind::Folder, parent, name, folder_id).await,
+
+        OnConflict::Replace => {
+            let Some(existing_folder_id) =
+                query_item_named(tx, ItemKind::Folder, parent, name, folder_id).await?
+            else {
+                return Ok(name.clone());
+            };
+
+            // Replacing a folder with something inside it would trash what's being moved.
+            if let Some(folder_id) = folder_id {
+                let is_inside_existing_folder = sqlx::query!(
+                    "SELECT TRUE AS exists FROM folders
+                        WHERE id = $1 AND $2 = ANY(parent_id_path)",
+                    folder_id,
+                    existing_folder_id,
+                )
+                .fetch_optional(tx.as_mut())
+                .await?
+                .is_some();
+
+                if is_inside_existing_folder {
+                    return Err(TxError::Abort(api::Error::AlreadyExists));
+                }
+            }
+
+            trash_folder(tx, user_id, &existing_folder_id).await?;
+
+            Ok(name.clone())
+        }
+    }
+}
+
+/// Inserts a complete file content that was stored by [`crate::storage::write_stream`].
+///
+/// # Errors
+///
+/// Returns an error if a database query fails.
+pub(crate) async fn insert_written_content<E>(
+    tx: &mut PgTransaction<'static>,
+    content_id: &[u8],
+    content: &WrittenContent,
+) -> TxResult<(), E>
+where
+    E: From<sqlx::Error>,
+{
+    sqlx::query!(
+        "INSERT INTO file_contents (
+            id,
+            complete,
+            hash,
+            original_size,
+            encoded_size,
+            part_count,
+            decoded_part_sizes
+        )
+            VALUES ($1, TRUE, $2, $3, $3, $4, $5)",
+        content_id,
+        content.hash,
+        content.size,
+        content.part_count(),
+        content.part_sizes.as_slice(),
+    )
+    .execute(tx.as_mut())
+    .await?;
+
+    Ok(())
+}
+
+/// Puts a complete file content at a name in a folder (or the user's root folder if `parent_id` is
+/// [`None`]), either replacing the content of the complete file already there or creating a new
+/// file. A replaced content is kept as a version of the file. Keeps folder sizes consistent.
+/// Returns whether a new file was created.
+///
+/// # Errors
+///
+/// Returns an error if a database query fails, if the user doesn't have access to the parent
+/// folder, or if the name isn't allowed there.
+pub(crate) async fn put_file(
+    tx: &mut PgTransaction<'static>,
+    user_id: &[u8],
+    parent_id: Option<&[u8]>,
+    name: &FileName,
+    content_id: &[u8],
+    size: i64,
+    file_type: &SniffedType,
+) -> TxResult<bool, api::Error> {
+    // This can replace an existing file's content, which only editors are allowed to do.
+    let parent =
+        query_folder_paths_to_modify_contents(tx, user_id, parent_id, FolderRole::Editor).await?;
+
+    if let Some(file) = sqlx::query!(
+        "SELECT id, size, content_id FROM files
+            WHERE owner_id = $1 AND parent_name_path = $2 AND name = $3 AND complete",
+        parent.owner_id,
+        parent.name_path.as_slice(),
+        name.as_str(),
+    )
+    .fetch_optional(tx.as_mut())
+    .await?
+    {
+        if file.content_id != content_id {
+            record_file_version(tx, &file.id).await?;
+        }
+
+        sqlx::query!(
+            "UPDATE files
+                SET modified_at = now(),
+                    revision = revision + 1,
+                    content_id = $1,
+                    size = $2,
+                    type = $3,
+                    dangerous = $4
+                WHERE id = $5 AND complete",
+            content_id,
+            size,
+            file_type.mime_type,
+            file_type.dangerous,
+            file.id,
+        )
+        .execute(tx.as_mut())
+        .await?;
+
+        add_to_folder_sizes(tx, &parent.id_path, size - file.size).await?;
+
+        return Ok(false);
+    }
+
+    check_file_name(tx, &parent.owner_id, name).await?;
+
+    let file_id = NewFileId::generate();
+
+    match sqlx::query!(
+        "INSERT INTO files (
+            created_at,
+            id,
+            complete,
+            name,
+            owner_id,
+            parent_id_path,
+            parent_name_path,
+            size,
+            content_id,
+            type,
+            dangerous
+        )
+            VALUES (now(), $1, TRUE, $2, $3, $4, $5, $6, $7, $8, $9)",
+        file_id.as_slice(),
+        name.as_str(),
+        parent.owner_id,
+        parent.id_path.as_slice(),
+        parent.name_path.as_slice(),
+        size,
+        content_id,
+        file_type.mime_type,
+        file_type.dangerous,
+    )
+    .execute(tx.as_mut())
+    .await
+    {
+        // A file with the same name may have been created concurrently, in which case retrying
+        // replaces its content instead.
+        Err(sqlx::Error::Database(error))
+            if matches!(
+                error.constraint(),
+                Some("files_pkey" | "files_by_name_path"),
+            ) =>
+        {
+            return Err(TxError::Retry);
+        }
+        result => result?,
+    };
+
+    add_to_folder_sizes(tx, &parent.id_path, size).await?;
+
+    Ok(true)
+}
+
+/// Moves a complete file to a new parent folder and optionally renames it, keeping folder sizes
+/// consistent. The user must be able to edit both the file and the new parent folder, and files
+/// can't be moved to a different owner's folder. Returns the file's new revision.
+///
+/// # Errors
+///
+/// Returns an error if a database query fails, if the user doesn't have access to the file or the
+/// new parent folder, if the `If-Match` precondition fails, if the new name isn't allowed, or if
+/// something with the same name already exists there.
+pub(crate) async fn move_file(
+    tx: &mut PgTransaction<'static>,
+    user_id: &[u8],
+    file_id: &[u8],
+    new_parent_id: Option<&[u8]>,
+    new_name: Option<&FileName>,
+    if_match: &IfMatch,
+) -> TxResult<i64, api::Error> {
+    // A file's complete and incomplete rows are always in the same folder, so either row will do.
+    let Some(file) = sqlx::query!(
+        "SELECT owner_id, parent_id_path, name, revision FROM files
+            WHERE id = $1
+            ORDER BY complete DESC
+            LIMIT 1",
+        file_id,
+    )
+    .fetch_optional(tx.as_mut())
+    .await?
+    else {
+        return Err(TxError::Abort(api::Error::AccessDenied));
+    };
+
+    if !has_role(
+        tx,
+        user_id,
+        &file.owner_id,
+        &file.parent_id_path,
+        FolderRole::Editor,
+    )
+    .await?
+    {
+        return Err(TxError::Abort(api::Error::AccessDenied));
+    }
+
+    let new_parent =
+        query_folder_paths_to_modify_contents(tx, user_id, new_parent_id, FolderRole::Editor)
+            .await?;
+
+    if new_parent.owner_id != file.owner_id {
+        return Err(TxError::Abort(api::Error::AccessDenied));
+    }
+
+    if_match.check(file.revision)?;
+
+    if let Some(new_name) = new_name
+        && new_name.as_str() != file.name
+    {
+        check_file_name(tx, &file.owner_id, new_name).await?;
+    }
+
+    let size = match sqlx::query_scalar!(
+        "UPDATE files
+            SET parent_id_path = $1,
+                parent_name_path = $2,
+                name = coalesce($3, name),
+                revision = revision + 1
+            WHERE owner_id = $4 AND id = $5
+            RETURNING size",
+        new_parent.id_path.as_slice(),
+        new_parent.name_path.as_slice(),
+        new_name.map(|new_name| new_name.as_str()),
+        file.owner_id,
+        file_id,
+    )
+    .fetch_optional(tx.as_mut())
+    .await
+    {
+        Err(sqlx::Error::Database(error)) if error.constraint() == Some("files_by_name_path") => {
+            return Err(TxError::Abort(api::Error::AlreadyExists));
+        }
+
+        Err(error) => return Err(error.into()),
+
+        Ok(None) => return Err(TxError::Abort(api::Error::AccessDenied)),
+
+        Ok(Some(size)) => size,
+    };
+
+    add_to_folder_sizes(tx, &file.parent_id_path, -size).await?;
+    add_to_folder_sizes(tx, &new_parent.id_path, size).await?;
+
+    Ok(file.revision + 1)
+}
+
+/// Moves a folder to a new parent folder and optionally renames it, updating the paths of
+/// everything inside it and keeping folder sizes consistent. The user must be able to edit both the
+/// folder's current and new parent folders, and folders can't be moved to a different owner's
+/// folder. Returns the folder's new revision.
+///
+/// # Errors
+///
+/// Returns an error if a database query fails, if the user doesn't have access to the folder or the
+/// new parent folder, if the `If-Match` precondition fails, if the new parent folder is inside the
+/// folder, if the new name isn't allowed, or if something with the same name already exists there.
+pub(crate) async fn move_folder(
+    tx: &mut PgTransaction<'static>,
+    user_id: &[u8],
+    folder_id: &[u8],
+    new_parent_id: Option<&[u8]>,
+    new_name: Option<&FileName>,
+    if_match: &IfMatch,
+) -> TxResult<i64, api::Error> {
+    let Some(current_folder) = sqlx::query!(
+        "SELECT owner_id, parent_id_path, name, revision FROM folders
+            WHERE id = $1",
+        folder_id,
+    )
+    .fetch_optional(tx.as_mut())
+    .await?
+    else {
+        return Err(TxError::Abort(api::Error::AccessDenied));
+    };
+
+    if !has_role(
+        tx,
+        user_id,
+        &current_folder.owner_id,
+        &current_folder.parent_id_path,
+        FolderRole::Editor,
+    )
+    .await?
+    {
+        return Err(TxError::Abort(api::Error::AccessDenied));
+    }
+
+    let new_parent =
+        query_folder_paths_to_modify_contents(tx, user_id, new_parent_id, FolderRole::Editor)
+            .await?;
+
+    if new_parent.owner_id != current_folder.owner_id {
+        return Err(TxError::Abort(api::Error::AccessDenied));
+    }
+
+    if_match.check(current_folder.revision)?;
+
+    if let Some(new_name) = new_name
+        && new_name.as_str() != current_folder.name
+    {
+        check_file_name(tx, &current_folder.owner_id, new_name).await?;
+    }
+
+    let owner_id = current_folder.owner_id;
+
+    if new_parent
+        .id_path
+        .iter()
+        .any(|parent_id| parent_id == folder_id)
+    {
+        return Err(TxError::Abort(api::Error::BodyDataInvalid(
+            "a folder can't be moved into itself".into(),
+        )));
+    }
+
+    let folder = match sqlx::query!(
+        "UPDATE folders
+            SET parent_id_path = $1,
+                parent_name_path = $2,
+                name = coalesce($3, name),
+                revision = revision + 1
+            WHERE owner_id = $4 AND id = $5
+            RETURNING
+                name,
+                size,
+                OLD.parent_id_path AS old_parent_id_path,
+                OLD.parent_name_path AS old_parent_name_path,
+                OLD.name AS old_name",
+        new_parent.id_path.as_slice(),
+        new_parent.name_path.as_slice(),
+        new_name.map(|new_name| new_name.as_str()),
+        owner_id,
+        folder_id,
+    )
+    .fetch_optional(tx.as_mut())
+    .await
+    {
+        Err(sqlx::Error::Database(error)) if error.constraint() == Some("folders_by_name_path") => {
+            return Err(TxError::Abort(api::Error::AlreadyExists));
+        }
+
+        Err(error) => return Err(error.into()),
+
+        Ok(None) => return Err(TxError::Abort(api::Error::AccessDenied)),
+
+        Ok(Some(folder)) => folder,
+    };
+
+    add_to_folder_sizes(tx, &folder.old_parent_id_path, -folder.size).await?;
+    add_to_folder_sizes(tx, &new_parent.id_path, folder.size).await?;
+
+    let mut old_folder_id_path = folder.old_parent_id_path;
+    old_folder_id_path.push(folder_id.to_vec());
+
+    let mut new_folder_id_path = new_parent.id_path;
+    new_folder_id_path.push(folder_id.to_vec());
+
+    let mut old_folder_name_path = folder.old_parent_name_path;
+    old_folder_name_path.push(folder.old_name);
+
+    let mut new_folder_name_path = new_parent.name_path;
+    new_folder_name_path.push(folder.name);
+
+    sqlx::query!(
+        "UPDATE folders
+            SET parent_id_path = $1 || parent_id_path[array_length($2::bytea[], 1) + 1:],
+                parent_name_path = $3 || parent_name_path[array_length($4::text[], 1) + 1:]
+            WHERE owner_id = $5 AND parent_id_path >= $2 AND parent_id_path < $2 || NULL",
+        new_folder_id_path.as_slice(),
+        old_folder_id_path.as_slice(),
+        new_folder_name_path.as_slice(),
+        old_folder_name_path.as_slice(),
+        owner_id,
+    )
+    .execute(tx.as_mut())
+    .await?;
+
+    sqlx::query!(
+        "UPDATE files
+            SET parent_id_path = $1 || parent_id_path[array_length($2::bytea[], 1) + 1:],
+                parent_name_path = $3 || parent_name_path[array_length($4::text[], 1) + 1:]
+            WHERE owner_id = $5 AND parent_id_path >= $2 AND parent_id_path < $2 || NULL",
+        new_folder_id_path.as_slice(),
+        old_folder_id_path.as_slice(),
+        new_folder_name_path.as_slice(),
+        old_folder_name_path.as_slice(),
+        owner_id,
+    )
+    .execute(tx.as_mut())
+    .await?;
+
+    Ok(current_folder.revision + 1)
+}
+
+/// Deletes a complete file, keeping folder sizes consistent. Any incomplete replacement file with
+/// the same ID isn't deleted. The user must be able to edit the file.
+///
+/// # Errors
+///
+/// Returns an error if a database query fails, if the user doesn't have access to the file, or if
+/// the `If-Match` precondition fails.
+pub(crate) async fn delete_file(
+    tx: &mut PgTransaction<'static>,
+    user_id: &[u8],
+    file_id: &[u8],
+    if_match: &IfMatch,
+) -> TxResult<(), api::Error> {
+    let Some(current_file) = sqlx::query!(
+        "SELECT owner_id, parent_id_path, revision FROM files
+            WHERE id = $1 AND complete",
+        file_id,
+    )
+    .fetch_optional(tx.as_mut())
+    .await?
+    else {
+        return Err(TxError::Abort(api::Error::AccessDenied));
+    };
+
+    if !has_role(
+        tx,
+        user_id,
+        &current_file.owner_id,
+        &current_file.parent_id_path,
+        FolderRole::Editor,
+    )
+    .await?
+    {
+        return Err(TxError::Abort(api::Error::AccessDenied));
+    }
+
+    if_match.check(current_file.revision)?;
+
+    let version_content_ids = sqlx::query_scalar!(
+        "DELETE FROM file_versions
+            WHERE file_id = $1
+            RETURNING content_id",
+        file_id,
+    )
+    .fetch_all(tx.as_mut())
+    .await?;
+
+    for content_id in version_content_ids {
+        mark_content_maybe_unused(tx, &content_id).await?;
+    }
+
+    let Some(file) = sqlx::query!(
+        "DELETE FROM files
+            WHERE id = $1 AND complete
+            RETURNING size, parent_id_path, content_id",
+        file_id,
+    )
+    .fetch_optional(tx.as_mut())
+    .await?
+    else {
+        return Err(TxError::Abort(api::Error::AccessDenied));
+    };
+
+    add_to_folder_sizes(tx, &file.parent_id_path, -file.size).await?;
+    mark_content_maybe_unused(tx, &file.content_id).await?;
+
+    Ok(())
+}
+
+/// Deletes a folder and everything inside it, keeping folder sizes consistent. The user must be
+/// able to edit the folder's parent folder.
+///
+/// # Errors
+///
+/// Returns an error if a database query fails or if the user doesn't have access to the folder.
+pub(crate) async fn delete_folder(
+    tx: &mut PgTransaction<'static>,
+    user_id: &[u8],
+    folder_id: &[u8],
+) -> TxResult<(), api::Error> {
+    let Some(current_folder) = sqlx::query!(
+        "SELECT owner_id, parent_id_path FROM folders
+            WHERE id = $1",
+        folder_id,
+    )
+    .fetch_optional(tx.as_mut())
+    .await?
+    else {
+        return Err(TxError::Abort(api::Error::AccessDenied));
+    };
+
+    if !has_role(
+        tx,
+        user_id,
+        &current_folder.owner_id,
+        &current_folder.parent_id_path,
+        FolderRole::Editor,
+    )
+    .await?
+    {
+        return Err(TxError::Abort(api::Error::AccessDenied));
+    }
+
+    let owner_id = current_folder.owner_id;
+
+    let Some(folder) = sqlx::query!(
+        "DELETE FROM folders
+            WHERE id = $1
+            RETURNING size, parent_id_path",
+        folder_id,
+    )
+    .fetch_optional(tx.as_mut())
+    .await?
+    else {
+        return Err(TxError::Abort(api::Error::AccessDenied));
+    };
+
+    add_to_folder_sizes(tx, &folder.parent_id_path, -folder.size).await?;
+
+    let mut folder_id_path = folder.parent_id_path;
+    folder_id_path.push(folder_id.to_vec());
+
+    sqlx::query!(
+        "DELETE FROM folders
+            WHERE owner_id = $1 AND parent_id_path >= $2 AND parent_id_path < $2 || NULL",
+        owner_id,
+        folder_id_path.as_slice(),
+    )
+    .execute(tx.as_mut())
+    .await?;
+
+    let version_content_ids = sqlx::query_scalar!(
+        "DELETE FROM file_versions
+            USING files
+            WHERE file_versions.file_id = files.id
+                AND files.owner_id = $1
+                AND files.parent_id_path >= $2
+                AND files.parent_id_path < $2 || NULL
+            RETURNING file_versions.content_id",
+        owner_id,
+        folder_id_path.as_slice(),
+    )
+    .fetch_all(tx.as_mut())
+    .await?;
+
+    for content_id in version_content_ids {
+        mark_content_maybe_unused(tx, &content_id).await?;
+    }
+
+    // This includes any incomplete files, since they'd have nowhere to go once finalized.
+    let content_ids = sqlx::query!(
+        "DELETE FROM files
+            WHERE owner_id = $1 AND parent_id_path >= $2 AND parent_id_path < $2 || NULL
+            RETURNING content_id",
+        owner_id,
+        folder_id_path.as_slice(),
+    )
+    .fetch_all(tx.as_mut())
+    .await?;
+
+    for file in content_ids {
+        mark_content_maybe_unused(tx, &file.content_id).await?;
+    }
+
+    Ok(())
+}
+
+/// Moves a folder and everything inside it to the trash, keeping folder sizes consistent. The user
+/// must be able to edit the folder's parent folder.
+///
+/// The folder becomes a root of the trash, with its original parent path kept so it can be
+/// restored. Everything inside it keeps its path relative to the folder. File versions and
+/// incomplete files aren't kept.
+///
+/// # Errors
+///
+/// Returns an error if a database query fails or if the user doesn't have access to the folder.
+pub(crate) async fn trash_folder(
+    tx: &mut PgTransaction<'static>,
+    user_id: &[u8],
+    folder_id: &[u8],
+) -> TxResult<(), api::Error> {
+    let Some(folder) = sqlx::query!(
+        "SELECT owner_id, parent_id_path, size FROM folders
+            WHERE id = $1",
+        folder_id,
+    )
+    .fetch_optional(tx.as_mut())
+    .await?
+    else {
+        return Err(TxError::Abort(api::Error::AccessDenied));
+    };
+
+    if !has_role(
+        tx,
+        user_id,
+        &folder.owner_id,
+        &folder.parent_id_path,
+        FolderRole::Editor,
+    )
+    .await?
+    {
+        return Err(TxError::Abort(api::Error::AccessDenied));
+    }
+
+    // The trashed paths start at the folder itself, one past the end of its parent path.
+    let trashed_path_start = i32::try_from(folder.parent_id_path.len() + 1)
+        .expect("folder depth should fit in an `i32`");
+
+    let mut folder_id_path = folder.parent_id_path.clone();
+    folder_id_path.push(folder_id.to_vec());
+
+    sqlx::query!(
+        "INSERT INTO trashed_folders (
+            trashed_at,
+            created_at,
+            id,
+            name,
+            owner_id,
+            parent_id_path,
+            original_parent_id_path,
+            browse_key,
+            size,
+            was_shared
+        )
+            SELECT
+                CASE WHEN id = $2 THEN now() END,
+                created_at,
+                id,
+                name,
+                owner_id,
+                parent_id_path[$4:],
+                CASE WHEN id = $2 THEN parent_id_path END,
+                browse_key,
+                size,
+                shared
+                FROM folders
+                WHERE owner_id = $1
+                    AND (
+                        id = $2
+                        OR (parent_id_path >= $3 AND parent_id_path < $3 || NULL::bytea)
+                    )",
+        folder.owner_id,
+        folder_id,
+        folder_id_path.as_slice(),
+        trashed_path_start,
+    )
+    .execute(tx.as_mut())
+    .await?;
+
+    let version_content_ids = sqlx::query_scalar!(
+        "DELETE FROM file_versions
+            USING files
+            WHERE file_versions.file_id = files.id
+                AND files.owner_id = $1
+                AND files.parent_id_path >= $2
+                AND files.parent_id_path < $2 || NULL::bytea
+            RETURNING file_versions.content_id",
+        folder.owner_id,
+        folder_id_path.as_slice(),
+    )
+    .fetch_all(tx.as_mut())
+    .await?;
+
+    for content_id in version_content_ids {
+        mark_content_maybe_unused(tx, &content_id).await?;
+    }
+
+    sqlx::query!(
+        "INSERT INTO trashed_files (
+            trashed_at,
+            created_at,
+            modified_at,
+            id,
+            name,
+            owner_id,
+            parent_id_path,
+            original_id,
+            size,
+            content_id,
+            type,
+            was_shared,
+            dangerous
+        )
+            SELECT
+                NULL,
+                created_at,
+                modified_at,
+                id,
+                name,
+                owner_id,
+                parent_id_path[$3:],
+                id,
+                size,
+                content_id,
+                type,
+                shared,
+                dangerous
+                FROM files
+                WHERE owner_id = $1
+                    AND complete
+                    AND parent_id_path >= $2
+                    AND parent_id_path < $2 || NULL::bytea",
+        folder.owner_id,
+        folder_id_path.as_slice(),
+        trashed_path_start,
+    )
+    .execute(tx.as_mut())
+    .await?;
+
+    // Incomplete files would have nowhere to go once finalized.
+    let incomplete_content_ids = sqlx::query_scalar!(
+        "DELETE FROM files
+            WHERE owner_id = $1
+                AND parent_id_path >= $2
+                AND parent_id_path < $2 || NULL::bytea
+            RETURNING CASE WHEN NOT complete THEN content_id END",
+        folder.owner_id,
+        folder_id_path.as_slice(),
+    )
+    .fetch_all(tx.as_mut())
+    .await?;
+
+    for content_id in incomplete_content_ids.into_iter().flatten() {
+        mark_content_maybe_unused(tx, &content_id).await?;
+    }
+
+    sqlx::query!(
+        "DELETE FROM folders
+            WHERE owner_id = $1
+                AND (id = $2 OR (parent_id_path >= $3 AND parent_id_path < $3 || NULL::bytea))",
+        folder.owner_id,
+        folder_id,
+        folder_id_path.as_slice(),
+    )
+    .execute(tx.as_mut())
+    .await?;
+
+    add_to_folder_sizes(tx, &folder.parent_id_path, -folder.size).await?;
+
+    Ok(())
+}
+
+/// Keeps a complete file's current content as a new version of the file, which must happen before
+/// the content is replaced. Then prunes the file's oldest versions beyond the maximum number kept.
+///
+/// # Errors
+///
+/// Returns an error if a database query fails.
+pub(crate) async fn record_file_version<E>(
+    tx: &mut PgTransaction<'static>,
+    file_id: &[u8],
+) -> TxResult<(), E>
+where
+    E: From<sqlx::Error>,
+{
+    let version_id = NewFileVersionId::generate();
+
+    match sqlx::query!(
+        "INSERT INTO file_versions (id, file_id, modified_at, content_id, size, type, dangerous)
+            SELECT $1, id, modified_at, content_id, size, type, dangerous FROM files
+                WHERE id = $2 AND complete",
+        version_id.as_slice(),
+        file_id,
+    )
+    .execute(tx.as_mut())
+    .await
+    {
+        Err(sqlx::Error::Database(error)) if error.constraint() == Some("file_versions_pkey") => {
+            return Err(TxError::Retry);
+        }
+        result => result?,
+    };
+
+    let pruned_content_ids = sqlx::query_scalar!(
+        "DELETE FROM file_versions
+            WHERE file_id = $1 AND id NOT IN (
+                SELECT id FROM file_versions
+                    WHERE file_id = $1
+                    ORDER BY created_at DESC
+                    LIMIT $2
+            )
+            RETURNING content_id",
+        file_id,
+        *MAX_FILE_VERSIONS,
+    )
+    .fetch_all(tx.as_mut())
+    .await?;
+
+    for content_id in pruned_content_ids {
+        mark_content_maybe_unused(tx, &content_id).await?;
+    }
+
+    Ok(())
+}
+
+/// Adds a number of bytes (which may be negative) to the sizes of the folders in an ID path.
+///
+/// # Errors
+///
+/// Returns an error if a database query fails.
+pub(crate) async fn add_to_folder_sizes<E>(
+    tx: &mut PgTransaction<'static>,
+    folder_id_path: &[Vec<u8>],
+    size: i64,
+) -> TxResult<(), E>
+where
+    E: From<sqlx::Error>,
+{
+    if folder_id_path.is_empty() || size == 0 {
+        return Ok(());
+    }
+
+    sqlx::query!(
+        "UPDATE folders
+            SET size = size + $1
+            WHERE id = ANY($2)",
+        size,
+        folder_id_path,
+    )
+    .execute(tx.as_mut())
+    .await?;
+
+    Ok(())
+}
+
+/// Marks a file content as possibly no longer used by any file, so it can be checked and cleaned up
+/// later.
+///
+/// # Errors
+///
+/// Returns an error if a database query fails.
+pub(crate) async fn mark_content_maybe_unused<E>(
+    tx: &mut PgTransaction<'static>,
+    content_id: &[u8],
+) -> TxResult<(), E>
+where
+    E: From<sqlx::Error>,
+{
+    let mut savepoint = tx.begin().await?;
+
+    match sqlx::query!(
+        "INSERT INTO maybe_unused_file_contents (id, started_checking)
+            VALUES ($1, false)",
+        content_id,
+    )
+    .execute(savepoint.as_mut())
+    .await
+    {
+        Err(sqlx::Error::Database(error))
+            if error.constraint() == Some("maybe_unused_file_contents_pkey") =>
+        {
+            // The file content is already marked maybe unused.
+            savepoint.rollback().await?;
+        }
+
+        result => {
+            result?;
+            savepoint.commit().await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Discards the incomplete files still being uploaded through upload links, since they can't be
+/// finished once the links are gone.
+///
+/// # Errors
+///
+/// Returns an error if a database query fails.
+pub(crate) async fn discard_link_uploads<E>(
+    tx: &mut PgTransaction<'static>,
+    upload_link_ids: &[Vec<u8>],
+) -> TxResult<(), E>
+where
+    E: From<sqlx::Error>,
+{
+    let content_ids = sqlx::query_scalar!(
+        "DELETE FROM files
+            USING upload_link_uploads
+            WHERE files.content_id = upload_link_uploads.content_id
+                AND NOT files.complete
+                AND upload_link_uploads.upload_link_id = ANY($1)
+            RETURNING files.content_id",
+        upload_link_ids,
+    )
+    .fetch_all(tx.as_mut())
+    .await?;
+
+    for content_id in content_ids {
+        mark_content_maybe_unused(tx, &content_id).await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn folder_roles_are_ordered_by_access() {
+        assert!(FolderRole::Viewer < FolderRole::Uploader);
+        assert!(FolderRole::Uploader < FolderRole::Editor);
+
+        // `has_role` compares the highest role granted by the database, which orders roles by their
+        // declaration in the `folder_role` type, so it must declare them in the same order.
+        assert!(
+            include_str!("../../migrations/20261019000008_folder_grants.sql")
+                .contains("CREATE TYPE folder_role AS ENUM ('viewer', 'uploader', 'editor');"),
+        );
+    }
 }
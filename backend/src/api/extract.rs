@@ -1,15 +1,32 @@
 //! Types and traits for extracting data from requests using `axum` extractors.
 
-use std::str;
+use std::{
+    convert::Infallible,
+    env::VarError,
+    net::{IpAddr, SocketAddr},
+    str,
+    sync::LazyLock,
+};
 
 use axum::{
-    extract::{FromRequestParts, OptionalFromRequestParts},
-    http::{header::COOKIE, request},
+    extract::{
+        ConnectInfo, FromRequest, FromRequestParts, MatchedPath, OptionalFromRequestParts, Request,
+    },
+    http::{
+        HeaderName,
+        header::{AUTHORIZATION, COOKIE, IF_MATCH, USER_AGENT},
+        request,
+    },
 };
 use axum_macros::FromRequestParts;
 use ring::digest::Digest;
 
-use crate::{api, crypto::hash_without_salt, id::Token};
+use crate::{
+    api::{self, db_helpers::ApiTokenScope, response::revision_etag, routes::api_token_scope},
+    crypto::hash_without_salt,
+    db,
+    id::Token,
+};
 
 use super::cookie::{CookieWrapper, SessionCookie};
 
@@ -25,7 +42,45 @@ pub(crate) struct Query<T>(pub T);
 #[from_request(via(axum::extract::Path), rejection(api::Error))]
 pub(crate) struct Path<T>(pub T);
 
-/// Extractor for the user's hashed authentication token.
+/// Equivalent to [`axum::body::Bytes`] as an extractor, but fails with an [`api::Error`] JSON
+/// response instead of a plain text response.
+#[derive(Clone, Debug)]
+pub(crate) struct Bytes(pub axum::body::Bytes);
+
+impl<S> FromRequest<S> for Bytes
+where
+    S: Sync + Send,
+{
+    type Rejection = api::Error;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let bytes = axum::body::Bytes::from_request(request, state).await?;
+
+        Ok(Self(bytes))
+    }
+}
+
+/// How many days a browser session can go unused before it expires. Unlike personal API tokens,
+/// which expire only when their user says so, browser sessions expire once they're idle this long.
+pub(crate) static MAX_SESSION_IDLE_DAYS: LazyLock<i32> =
+    LazyLock::new(|| match dotenvy::var("MAX_SESSION_IDLE_DAYS") {
+        Err(dotenvy::Error::EnvVar(VarError::NotPresent)) => 30,
+
+        max_idle => max_idle
+            .ok()
+            .and_then(|max_idle| max_idle.parse().ok())
+            .filter(|max_idle| *max_idle > 0)
+            .expect(
+                "environment variable `MAX_SESSION_IDLE_DAYS` should be a positive integer if set",
+            ),
+    });
+
+/// Extractor for the user's hashed authentication token, from either the session cookie or a
+/// personal API token or OAuth access token in the `Authorization` header. Fails if the session
+/// doesn't exist or is expired, or if an API token lacks the scope the route requires.
+///
+/// This also records that the session was used, so every authenticated route keeps its session
+/// from going idle.
 pub(crate) struct AuthToken(pub Digest);
 
 impl<S> FromRequestParts<S> for AuthToken
@@ -38,18 +93,80 @@ where
         parts: &mut request::Parts,
         _state: &S,
     ) -> Result<Self, Self::Rejection> {
-        let Some(token) = parts
+        let bearer_token = parts
             .headers
-            .get(COOKIE)
-            .and_then(|header_value| str::from_utf8(header_value.as_bytes()).ok())
-            .and_then(SessionCookie::from_header)
-            .and_then(|session_cookie| session_cookie.as_ref().value().parse::<Token>().ok())
-        else {
+            .get(AUTHORIZATION)
+            .and_then(|header_value| header_value.to_str().ok())
+            .and_then(|header_value| header_value.split_once(' '))
+            .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("Bearer"))
+            .map(|(_, token)| token.trim());
+
+        let token = match bearer_token {
+            Some(token) => token.parse::<Token>().ok(),
+            None => parts
+                .headers
+                .get(COOKIE)
+                .and_then(|header_value| str::from_utf8(header_value.as_bytes()).ok())
+                .and_then(SessionCookie::from_header)
+                .and_then(|session_cookie| session_cookie.as_ref().value().parse::<Token>().ok()),
+        };
+
+        let Some(token) = token else {
             return Err(api::Error::AuthFailed);
         };
 
         let token_hash = hash_without_salt(&token);
 
+        // API tokens (including OAuth access tokens) are stored as sessions so every route accepts
+        // them like any other session, which means this is the one place their expiration and
+        // scopes can be checked.
+        let Some(session) = sqlx::query!(
+            r#"SELECT
+                scopes AS "scopes: Vec<ApiTokenScope>",
+                (
+                    coalesce(expires_at <= now(), FALSE)
+                    OR (scopes IS NULL AND accessed_at <= now() - make_interval(days => $2))
+                ) AS "is_expired!",
+                accessed_at <= now() - interval '5 minutes' AS "is_access_outdated!"
+                FROM sessions
+                WHERE token_hash = $1"#,
+            token_hash.as_ref(),
+            *MAX_SESSION_IDLE_DAYS,
+        )
+        .fetch_optional(db::pool())
+        .await?
+        else {
+            return Err(api::Error::AuthFailed);
+        };
+
+        if session.is_expired {
+            return Err(api::Error::AuthFailed);
+        }
+
+        if let Some(scopes) = session.scopes {
+            let required_scope = parts
+                .extensions
+                .get::<MatchedPath>()
+                .and_then(|route| api_token_scope(&parts.method, route.as_str()));
+
+            if !required_scope.is_some_and(|scope| scopes.contains(&scope)) {
+                return Err(api::Error::ApiTokenScopeMissing);
+            }
+        }
+
+        // Writing on every request would be wasteful, and the access time doesn't need to be
+        // any more precise than this for idle expiration or for users to recognize sessions.
+        if session.is_access_outdated {
+            sqlx::query!(
+                "UPDATE sessions
+                    SET accessed_at = now()
+                    WHERE token_hash = $1",
+                token_hash.as_ref(),
+            )
+            .execute(db::pool())
+            .await?;
+        }
+
         Ok(Self(token_hash))
     }
 }
@@ -71,3 +188,151 @@ where
         }
     }
 }
+
+/// Extractor for the entity tags in a request's `If-Match` headers, used to make sure a file or
+/// folder hasn't changed since the client last saw it. The default has no `If-Match` headers.
+#[derive(Clone, Default, Debug)]
+pub(crate) struct IfMatch(pub Option<Vec<String>>);
+
+impl IfMatch {
+    /// Checks the precondition against a file's or folder's current revision.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`api::Error::PreconditionFailed`] if none of the entity tags match.
+    pub(crate) fn check(&self, revision: i64) -> Result<(), api::Error> {
+        let Some(etags) = &self.0 else {
+            return Ok(());
+        };
+
+        let etag = revision_etag(revision);
+
+        if etags.iter().any(|tag| tag == "*" || *tag == etag) {
+            Ok(())
+        } else {
+            Err(api::Error::PreconditionFailed)
+        }
+    }
+}
+
+impl<S> FromRequestParts<S> for IfMatch
+where
+    S: Sync + Send,
+{
+    type Rejection = api::Error;
+
+    async fn from_request_parts(
+        parts: &mut request::Parts,
+        _state: &S,
+    ) -> Result<Self, Self::Rejection> {
+        let mut headers = parts.headers.get_all(IF_MATCH).iter().peekable();
+
+        if headers.peek().is_none() {
+            return Ok(Self(None));
+        }
+
+        // Weak entity tags are kept as is, so they never match, as required for `If-Match`.
+        let etags = headers
+            .filter_map(|header_value| header_value.to_str().ok())
+            .flat_map(|header_value| header_value.split(','))
+            .map(|etag| etag.trim().to_owned())
+            .filter(|etag| !etag.is_empty())
+            .collect();
+
+        Ok(Self(Some(etags)))
+    }
+}
+
+/// Extractor for the request's `User-Agent` header, if it has a valid one. It's truncated to
+/// [`UserAgent::MAX_LENGTH`] characters since it's stored with the session it creates.
+#[derive(Clone, Default, Debug)]
+pub(crate) struct UserAgent(pub Option<String>);
+
+impl UserAgent {
+    /// The maximum number of characters of a user agent to keep.
+    pub(crate) const MAX_LENGTH: usize = 512;
+
+    /// Gets the user agent as a string slice, if there is one.
+    pub(crate) fn as_deref(&self) -> Option<&str> {
+        self.0.as_deref()
+    }
+}
+
+impl<S> FromRequestParts<S> for UserAgent
+where
+    S: Sync + Send,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut request::Parts,
+        _state: &S,
+    ) -> Result<Self, Self::Rejection> {
+        let user_agent = parts
+            .headers
+            .get(USER_AGENT)
+            .and_then(|header_value| header_value.to_str().ok())
+            .map(str::trim)
+            .filter(|user_agent| !user_agent.is_empty())
+            .map(|user_agent| user_agent.chars().take(Self::MAX_LENGTH).collect());
+
+        Ok(Self(user_agent))
+    }
+}
+
+/// The header a trusted reverse proxy sets to the client's IP address, if the server is behind one.
+/// Without a proxy, this must be unset, or else clients could set it to whatever they want.
+static CLIENT_IP_HEADER: LazyLock<Option<HeaderName>> =
+    LazyLock::new(|| match dotenvy::var("CLIENT_IP_HEADER") {
+        Err(dotenvy::Error::EnvVar(VarError::NotPresent)) => None,
+
+        header_name => Some(
+            header_name
+                .ok()
+                .and_then(|header_name| header_name.parse().ok())
+                .expect("environment variable `CLIENT_IP_HEADER` should be a header name if set"),
+        ),
+    });
+
+/// Extractor for the IP address of the client that sent the request. If the `CLIENT_IP_HEADER`
+/// environment variable is set, this is read from that header, taking the last address if there
+/// are multiple since that's the one the trusted proxy added. Otherwise, it's the address of the
+/// connection.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub(crate) struct ClientIp(pub IpAddr);
+
+impl<S> FromRequestParts<S> for ClientIp
+where
+    S: Sync + Send,
+{
+    type Rejection = api::Error;
+
+    async fn from_request_parts(
+        parts: &mut request::Parts,
+        _state: &S,
+    ) -> Result<Self, Self::Rejection> {
+        let header_ip = CLIENT_IP_HEADER.as_ref().and_then(|header_name| {
+            parts
+                .headers
+                .get_all(header_name)
+                .iter()
+                .next_back()
+                .and_then(|header_value| header_value.to_str().ok())
+                .and_then(|header_value| header_value.rsplit(',').next())
+                .and_then(|ip| ip.trim().parse().ok())
+        });
+
+        let connection_ip = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(address)| address.ip());
+
+        let Some(ip) = header_ip.or(connection_ip) else {
+            return Err(api::Error::Internal(
+                "the connection's address is unknown".into(),
+            ));
+        };
+
+        Ok(Self(ip.to_canonical()))
+    }
+}
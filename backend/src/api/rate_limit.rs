@@ -0,0 +1,343 @@
+//! Limits on failed authentication attempts, so passwords and OTPs can't be guessed by brute force.
+//!
+//! Failures are counted per user and per client IP address. Once either has failed more than a few
+//! times, it's locked out for a time that doubles with each further failure, and a failure count
+//! resets a day after its last failure. Counts are stored in the database so every server instance
+//! shares them. They're recorded outside of the request's transaction, since that transaction is
+//! rolled back when authentication fails.
+//!
+//! Each attempt is counted as a failure from before it's verified until it turns out not to be one.
+//! Otherwise, concurrent attempts could all get past the limit before any of their failures were
+//! recorded.
+
+use std::{
+    mem,
+    net::{IpAddr, Ipv6Addr},
+};
+
+use lettre::{Address, message::Mailbox};
+
+use crate::{
+    api::{self, extract::ClientIp, validation::UserEmail},
+    db::{self, TxResult},
+    email::{AuthFailuresMessage, MessageTemplate},
+    id::Id,
+};
+
+/// How many times a user can fail to authenticate before being locked out.
+const USER_FREE_FAILURES: i32 = 5;
+
+/// How many times a client IP address can fail to authenticate before being locked out. This is
+/// higher than for users since many people can share an IP address.
+const CLIENT_FREE_FAILURES: i32 = 20;
+
+/// How many seconds the first lockout lasts.
+const BASE_LOCKOUT_SECS: f64 = 30.0;
+
+/// The most seconds a lockout can last.
+const MAX_LOCKOUT_SECS: f64 = 60.0 * 60.0;
+
+/// Something whose failed authentication attempts are counted together.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Subject<'a> {
+    /// A user, for failures to authenticate as them.
+    User(&'a [u8]),
+
+    /// An email address that doesn't belong to any user. Failures are counted for these too, so
+    /// whether an email address is locked out doesn't reveal if it belongs to a user.
+    UnknownEmail(&'a str),
+
+    /// A client IP address, for its failures to authenticate as anyone.
+    Client(IpAddr),
+}
+
+impl Subject<'_> {
+    /// Gets the key the subject's failures are stored under.
+    pub(crate) fn key(&self) -> String {
+        match self {
+            Self::User(user_id) => format!("user:{}", Id::from(user_id)),
+            Self::UnknownEmail(email) => format!("email:{email}"),
+            Self::Client(IpAddr::V4(ip)) => format!("ip:{ip}"),
+
+            // A single IPv6 client typically has a whole /64 prefix to itself, so any address in it
+            // must count as the same client.
+            Self::Client(IpAddr::V6(ip)) => {
+                let prefix = Ipv6Addr::from_bits(ip.to_bits() & (u128::MAX << 64));
+                format!("ip:{prefix}/64")
+            }
+        }
+    }
+
+    /// Gets how many times the subject can fail to authenticate before being locked out.
+    const fn free_failures(&self) -> i32 {
+        match self {
+            Self::User(_) | Self::UnknownEmail(_) => USER_FREE_FAILURES,
+            Self::Client(_) => CLIENT_FREE_FAILURES,
+        }
+    }
+}
+
+/// An attempt to authenticate, counted as a failure for each of its subjects until it's dropped
+/// without being [failed](Self::fail).
+#[must_use = "an attempt stops being counted as soon as it's dropped"]
+#[derive(Debug)]
+pub(crate) struct Attempt {
+    /// The attempt's counts toward each subject's limit.
+    counts: Vec<AttemptCount>,
+}
+
+/// An attempt's count toward one subject's limit.
+#[derive(Debug)]
+struct AttemptCount {
+    /// The key the subject's failures are stored under.
+    key: String,
+
+    /// Whether counting the attempt locked the subject out.
+    locked_out: bool,
+
+    /// The ID of the user to email if the attempt fails, if counting it newly locked them out.
+    user_to_email: Option<Vec<u8>>,
+}
+
+impl Attempt {
+    /// Records the attempt as a failure, emailing any user it newly locked out.
+    ///
+    /// # Errors
+    ///
+    /// Returns an internal error if a database query fails.
+    pub(crate) async fn fail(mut self) -> Result<(), api::Error> {
+        for count in mem::take(&mut self.counts) {
+            db::transaction!(async |tx| -> TxResult<_> {
+                sqlx::query!(
+                    "UPDATE auth_failures
+                        SET failed_at = now()
+                        WHERE subject = $1",
+                    count.key,
+                )
+                .execute(tx.as_mut())
+                .await?;
+
+                Ok(())
+            })
+            .await?;
+
+            if let Some(user_id) = count.user_to_email {
+                send_lockout_email(&user_id).await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for Attempt {
+    fn drop(&mut self) {
+        let counts = mem::take(&mut self.counts);
+
+        if counts.is_empty() {
+            return;
+        }
+
+        tokio::spawn(async move {
+            for count in counts {
+                let result = db::transaction!(async |tx| -> TxResult<_> {
+                    sqlx::query!(
+                        "UPDATE auth_failures
+                            SET failure_count = failure_count - 1,
+                                locked_until = CASE WHEN $2 THEN NULL ELSE locked_until END
+                            WHERE subject = $1",
+                        count.key,
+                        count.locked_out,
+                    )
+                    .execute(tx.as_mut())
+                    .await?;
+
+                    Ok(())
+                })
+                .await;
+
+                if let Err(error) = result {
+                    eprintln!("Uncounting an authentication attempt failed: {error}");
+                }
+            }
+        });
+    }
+}
+
+/// Starts an attempt to authenticate as the subjects, counting it toward each subject's limit and
+/// locking out any subject it takes over the limit. The returned attempt must be
+/// [failed](Attempt::fail) if authentication fails, or else dropped.
+///
+/// # Errors
+///
+/// Returns [`api::Error::TooManyRequests`] if any subject is locked out, or an internal error if a
+/// database query fails.
+pub(crate) async fn begin_attempt(subjects: &[Subject<'_>]) -> Result<Attempt, api::Error> {
+    let mut attempt = Attempt {
+        counts: Vec::with_capacity(subjects.len()),
+    };
+
+    for subject in subjects {
+        let key = subject.key();
+
+        let free_failures = subject.free_failures();
+
+        // The count is only updated if the subject isn't locked out, so the check and the update
+        // can't be interleaved with another attempt's. The time of the last failure is only updated
+        // once the attempt fails.
+        let failure_count = db::transaction!(async |tx| -> TxResult<_> {
+            let Some(failure_count) = sqlx::query_scalar!(
+                "INSERT INTO auth_failures AS failures (subject)
+                    VALUES ($1)
+                    ON CONFLICT (subject) DO UPDATE
+                        SET failure_count = CASE
+                                WHEN failures.failed_at > now() - interval '1 day'
+                                    THEN failures.failure_count + 1
+                                ELSE 1
+                            END,
+                            failed_at = CASE
+                                WHEN failures.failed_at > now() - interval '1 day'
+                                    THEN failures.failed_at
+                                ELSE now()
+                            END
+                        WHERE failures.locked_until IS NULL OR failures.locked_until <= now()
+                    RETURNING failure_count",
+                key,
+            )
+            .fetch_optional(tx.as_mut())
+            .await?
+            else {
+                return Ok(None);
+            };
+
+            let excess_failures = failure_count - free_failures;
+
+            if excess_failures > 0 {
+                let lockout_secs =
+                    (BASE_LOCKOUT_SECS * 2_f64.powi(excess_failures - 1)).min(MAX_LOCKOUT_SECS);
+
+                sqlx::query!(
+                    "UPDATE auth_failures
+                        SET locked_until = now() + make_interval(secs => $1)
+                        WHERE subject = $2",
+                    lockout_secs,
+                    key,
+                )
+                .execute(tx.as_mut())
+                .await?;
+            }
+
+            Ok(Some(failure_count))
+        })
+        .await?;
+
+        let Some(failure_count) = failure_count else {
+            // Dropping the attempt uncounts it for the subjects it was already counted for.
+            drop(attempt);
+
+            return Err(lockout_error(&key).await?);
+        };
+
+        let excess_failures = failure_count - free_failures;
+
+        let user_to_email = match subject {
+            Subject::User(user_id) if excess_failures == 1 => Some(user_id.to_vec()),
+            _ => None,
+        };
+
+        attempt.counts.push(AttemptCount {
+            key,
+            locked_out: excess_failures > 0,
+            user_to_email,
+        });
+    }
+
+    Ok(attempt)
+}
+
+/// Gets the error for a subject being locked out.
+///
+/// # Errors
+///
+/// Returns an error if a database query fails.
+async fn lockout_error(key: &str) -> sqlx::Result<api::Error> {
+    let retry_after = sqlx::query_scalar!(
+        "SELECT ceil(extract(epoch FROM locked_until - now()))::bigint FROM auth_failures
+            WHERE subject = $1",
+        key,
+    )
+    .fetch_optional(db::pool())
+    .await?
+    .flatten()
+    .unwrap_or_default();
+
+    // The lockout may have just ended, but the attempt still has to be retried.
+    Ok(api::Error::TooManyRequests(
+        retry_after.max(1).unsigned_abs(),
+    ))
+}
+
+/// Handles a failed attempt to authenticate as a user that doesn't exist, limiting it the same way
+/// as a failure for a user that does exist.
+///
+/// # Errors
+///
+/// Returns [`api::Error::TooManyRequests`] if the email address or client is locked out, or an
+/// internal error if a database query fails.
+pub(crate) async fn record_unknown_user_failure(
+    email: Option<&UserEmail>,
+    ClientIp(client_ip): ClientIp,
+) -> Result<(), api::Error> {
+    let email_subject = email.map(|email| Subject::UnknownEmail(email.as_str()));
+    let subjects: Vec<_> = email_subject
+        .into_iter()
+        .chain([Subject::Client(client_ip)])
+        .collect();
+
+    // The error isn't kept across the `await` below since API errors aren't `Send`.
+    let attempt = begin_attempt(&subjects).await?;
+    attempt.fail().await
+}
+
+/// Emails a user to let them know they've been locked out.
+///
+/// # Errors
+///
+/// Returns an internal error if a database query fails.
+async fn send_lockout_email(user_id: &[u8]) -> Result<(), api::Error> {
+    let Some(user) = sqlx::query!(
+        "SELECT name, email FROM users
+            WHERE id = $1",
+        user_id,
+    )
+    .fetch_optional(db::pool())
+    .await?
+    else {
+        return Ok(());
+    };
+
+    if let Ok(email) = user.email.parse::<Address>() {
+        AuthFailuresMessage {
+            user_name: &user.name,
+        }
+        .to(Mailbox::new(Some(user.name.clone()), email))
+        .send();
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn groups_ipv6_clients_by_prefix() {
+        let client = |ip: &str| Subject::Client(ip.parse().expect("IP should be valid")).key();
+
+        assert_eq!(client("203.0.113.7"), "ip:203.0.113.7");
+        assert_eq!(client("2001:db8:1:2:3:4:5:6"), "ip:2001:db8:1:2::/64");
+        assert_eq!(client("2001:db8:1:2::1"), client("2001:db8:1:2:ffff::"));
+        assert_ne!(client("2001:db8:1:2::1"), client("2001:db8:1:3::1"));
+    }
+}
@@ -3,15 +3,18 @@
 use std::error::Error as _;
 
 use axum::{
-    extract::rejection::{JsonRejection, PathRejection, QueryRejection},
-    http::StatusCode,
-    response::IntoResponse,
+    extract::rejection::{BytesRejection, JsonRejection, PathRejection, QueryRejection},
+    http::{StatusCode, header::RETRY_AFTER},
+    response::{AppendHeaders, IntoResponse},
 };
 use serde::Serialize;
 use strum_macros::IntoStaticStr;
 use thiserror::Error;
+use tokio::task::JoinError;
 
-use super::Json;
+use crate::{crypto::TotpSecretDecryptionError, oidc, processing};
+
+use super::{Json, validation::FileNameError};
 
 pub(crate) mod body;
 
@@ -30,6 +33,11 @@ pub(crate) enum Error {
     #[error("A conflicting resource already exists.")]
     AlreadyExists,
 
+    /// The request is authenticated with a personal API token that doesn't have the scope required
+    /// to access the requested resource.
+    #[error("Your API token doesn't have the scope required to access the requested resource.")]
+    ApiTokenScopeMissing,
+
     /// Authentication credentials are required but either unspecified, invalid, or don't match any
     /// user.
     #[error("You must be signed in to access the requested resource.")]
@@ -47,6 +55,11 @@ pub(crate) enum Error {
     #[error("CAPTCHA verification failed.")]
     CaptchaFailed,
 
+    /// The uploaded content doesn't match the SHA-256 digest expected by the request that started
+    /// the upload.
+    #[error("The uploaded content doesn't match its expected SHA-256 digest.")]
+    DigestMismatch,
+
     /// An email verification token or code specified in the request is incorrect.
     #[error("Incorrect email verification token or code.")]
     EmailVerificationWrong,
@@ -59,6 +72,16 @@ pub(crate) enum Error {
     #[error("Nonexistent user or incorrect first-factor authentication credentials.")]
     FirstFactorCredentialsWrong,
 
+    /// The file or folder name isn't allowed because the owner of the folder it's in has opted
+    /// into strict file names.
+    #[error("File name not allowed: {0}.")]
+    FileNameNotAllowed(FileNameError),
+
+    /// The uploaded file's type, as detected from its content, isn't one of the types allowed where
+    /// it was uploaded.
+    #[error("The uploaded file's type isn't allowed here.")]
+    FileTypeNotAllowed,
+
     /// An internal error occurred on the server which is unknown or expected never to happen.
     ///
     /// For security, this must not expose error details to clients since there's no way to tell if
@@ -82,10 +105,25 @@ pub(crate) enum Error {
     #[error("Invalid URI path: {0}")]
     PathDataInvalid(String),
 
+    /// The request's `If-Match` precondition failed because the resource was changed since the
+    /// client last saw it.
+    #[error("The resource has been modified since it was last retrieved.")]
+    PreconditionFailed,
+
     /// The request URI query doesn't match the required target type.
     #[error("Invalid URI query: {0}")]
     QueryDataInvalid(String),
 
+    /// Signing in with the external OpenID Connect provider failed, either because the
+    /// authorization request is expired or nonexistent, or because the provider didn't verify the
+    /// user's identity.
+    #[error("Authentication with the identity provider failed. Please try again.")]
+    OidcFailed,
+
+    /// Signing in with an external OpenID Connect provider isn't enabled on this server.
+    #[error("Signing in with an external identity provider isn't enabled.")]
+    OidcUnavailable,
+
     /// The requested API route exists, but the specified resource was not found.
     #[error("Resource not found.")]
     ResourceNotFound,
@@ -99,33 +137,63 @@ pub(crate) enum Error {
     #[error("Incorrect or missing second-factor authentication credentials.")]
     SecondFactorCredentialsWrong,
 
+    /// Authentication was refused without checking the credentials because there have been too many
+    /// failed attempts recently, either for the user or from the client. Contains how many seconds
+    /// to wait before trying again, which is also sent in the `Retry-After` header.
+    #[error("Too many failed attempts. Please try again in {0} seconds.")]
+    TooManyRequests(u64),
+
     /// The OTP specified in the TOTP setup request is incorrect.
     #[error("Incorrect verification code for TOTP setup.")]
     TotpSetupWrong,
+
+    /// The upload link used has already had as many files uploaded through it as it allows.
+    #[error("No more files can be uploaded through this link.")]
+    UploadLimitReached,
+
+    /// The request tried to finalize a file upload before all of the file's content was uploaded.
+    #[error("The file's content hasn't been fully uploaded yet.")]
+    UploadIncomplete,
+
+    /// The WebAuthn credential specified in the registration request is invalid, unsupported, or
+    /// doesn't sign a challenge issued for the registration.
+    #[error("Invalid passkey or security key registration.")]
+    WebAuthnSetupWrong,
 }
 
 impl Error {
     /// Gets the HTTP response status code corresponding to the API error.
-    const fn status(&self) -> StatusCode {
+    pub(crate) const fn status(&self) -> StatusCode {
         match self {
             Self::AccessDenied => StatusCode::FORBIDDEN,
             Self::AlreadyExists => StatusCode::CONFLICT,
+            Self::ApiTokenScopeMissing => StatusCode::FORBIDDEN,
             Self::AuthFailed => StatusCode::UNAUTHORIZED,
             Self::BodyDataInvalid(_) => StatusCode::BAD_REQUEST,
             Self::BodyTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
             Self::CaptchaFailed => StatusCode::FORBIDDEN,
+            Self::DigestMismatch => StatusCode::UNPROCESSABLE_ENTITY,
             Self::EmailVerificationWrong => StatusCode::FORBIDDEN,
             Self::FirstFactorCredentialsWrong => StatusCode::FORBIDDEN,
+            Self::FileNameNotAllowed(_) => StatusCode::BAD_REQUEST,
+            Self::FileTypeNotAllowed => StatusCode::UNSUPPORTED_MEDIA_TYPE,
             Self::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
             Self::JsonContentType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
             Self::JsonSyntax(_) => StatusCode::BAD_REQUEST,
             Self::MethodNotAllowed => StatusCode::METHOD_NOT_ALLOWED,
+            Self::OidcFailed => StatusCode::FORBIDDEN,
+            Self::OidcUnavailable => StatusCode::NOT_FOUND,
             Self::PathDataInvalid(_) => StatusCode::BAD_REQUEST,
+            Self::PreconditionFailed => StatusCode::PRECONDITION_FAILED,
             Self::QueryDataInvalid(_) => StatusCode::BAD_REQUEST,
             Self::ResourceNotFound => StatusCode::NOT_FOUND,
             Self::RouteNotFound => StatusCode::NOT_FOUND,
             Self::SecondFactorCredentialsWrong => StatusCode::FORBIDDEN,
+            Self::TooManyRequests(_) => StatusCode::TOO_MANY_REQUESTS,
             Self::TotpSetupWrong => StatusCode::FORBIDDEN,
+            Self::UploadIncomplete => StatusCode::CONFLICT,
+            Self::UploadLimitReached => StatusCode::FORBIDDEN,
+            Self::WebAuthnSetupWrong => StatusCode::FORBIDDEN,
         }
     }
 
@@ -184,18 +252,66 @@ impl From<JsonRejection> for Error {
     }
 }
 
+impl From<BytesRejection> for Error {
+    fn from(error: BytesRejection) -> Self {
+        if error.status() == StatusCode::PAYLOAD_TOO_LARGE {
+            return Self::BodyTooLarge;
+        }
+
+        Self::Internal(error.into())
+    }
+}
+
 impl From<sqlx::Error> for Error {
     fn from(error: sqlx::Error) -> Self {
         Self::Internal(error.into())
     }
 }
 
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Self::Internal(error.into())
+    }
+}
+
+impl From<TotpSecretDecryptionError> for Error {
+    fn from(error: TotpSecretDecryptionError) -> Self {
+        Self::Internal(error.into())
+    }
+}
+
+impl From<JoinError> for Error {
+    fn from(error: JoinError) -> Self {
+        Self::Internal(error.into())
+    }
+}
+
+impl From<processing::Error> for Error {
+    fn from(error: processing::Error) -> Self {
+        Self::Internal(error.into())
+    }
+}
+
+impl From<oidc::Error> for Error {
+    fn from(error: oidc::Error) -> Self {
+        match error {
+            oidc::Error::CodeRejected | oidc::Error::IdToken => Self::OidcFailed,
+            error => Self::Internal(error.into()),
+        }
+    }
+}
+
 impl From<reqwest::Error> for Error {
     fn from(error: reqwest::Error) -> Self {
         Self::Internal(error.into())
     }
 }
 
+/// Formats a file's or folder's revision as an entity tag for the `ETag` header.
+pub(crate) fn revision_etag(revision: i64) -> String {
+    format!("\"{revision}\"")
+}
+
 /// An API error's response body.
 #[derive(Serialize, Debug)]
 #[serde(rename_all = "camelCase")]
@@ -209,12 +325,17 @@ struct ErrorBody {
 
 impl IntoResponse for Error {
     fn into_response(self) -> axum::response::Response {
+        let retry_after = match self {
+            Self::TooManyRequests(retry_after) => Some((RETRY_AFTER, retry_after.to_string())),
+            _ => None,
+        };
+
         let body = ErrorBody {
             code: self.code(),
             message: self.to_string(),
         };
 
-        (self.status(), Json(body)).into_response()
+        (self.status(), AppendHeaders(retry_after), Json(body)).into_response()
     }
 }
 
@@ -1,9 +1,13 @@
 //! General types for use in API response body types.
 
+use base64::{Engine as _, engine::general_purpose::STANDARD};
 use chrono::{DateTime, Utc};
 use serde::Serialize;
 
-use crate::id::Id;
+use crate::{
+    api::db_helpers::{ApiTokenScope, FolderRole},
+    id::Id,
+};
 
 /// A reference to a user.
 #[derive(Serialize, Debug)]
@@ -28,6 +32,213 @@ pub(crate) struct Session<IdType = Vec<u8>> {
     /// The timestamp this session was first created.
     pub created_at: DateTime<Utc>,
 
-    /// The timestamp this session was last used.
+    /// The timestamp this session was last used, to within a few minutes.
     pub accessed_at: DateTime<Utc>,
+
+    /// The `User-Agent` header of the request that created this session, if it had one.
+    pub user_agent: Option<String>,
+
+    /// An approximate description of the device this session was created on, like "Firefox on
+    /// Windows", if it could be recognized from the user agent.
+    pub device: Option<String>,
+}
+
+/// A reference to a personal API token. Never includes the token itself.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ApiToken {
+    /// The token's ID.
+    pub id: Id,
+
+    /// The user's name for the token.
+    pub name: String,
+
+    /// The scopes the token grants access to.
+    pub scopes: Vec<ApiTokenScope>,
+
+    /// The timestamp this token was created.
+    pub created_at: DateTime<Utc>,
+
+    /// The timestamp this token was last used.
+    pub accessed_at: DateTime<Utc>,
+
+    /// When the token expires, or [`None`] if it doesn't expire.
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// A third-party app registered to act on users' behalf through OAuth. Never includes the client's
+/// secret.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct OAuthClient {
+    /// The client's ID.
+    pub id: Id,
+
+    /// The client's name, shown to users when they're asked to authorize it.
+    pub name: String,
+
+    /// The URIs the client can be redirected to with an authorization code.
+    pub redirect_uris: Vec<String>,
+
+    /// Whether the client has a secret, rather than being a public client relying on PKCE alone.
+    pub confidential: bool,
+
+    /// The timestamp this client was registered.
+    pub created_at: DateTime<Utc>,
+}
+
+/// A user's authorization for an OAuth client to act on their behalf.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct OAuthGrant {
+    /// The grant's ID.
+    pub id: Id,
+
+    /// The authorized client.
+    pub client: OAuthGrantClient,
+
+    /// The scopes the client was granted access to.
+    pub scopes: Vec<ApiTokenScope>,
+
+    /// The timestamp the user authorized the client.
+    pub created_at: DateTime<Utc>,
+}
+
+/// A reference to the client of an [`OAuthGrant`].
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct OAuthGrantClient {
+    /// The client's ID.
+    pub id: Id,
+
+    /// The client's name.
+    pub name: String,
+}
+
+/// A user's linked account at the external OpenID Connect provider.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct OidcIdentity {
+    /// The provider's issuer identifier.
+    pub issuer: String,
+
+    /// The account's subject identifier at the provider.
+    pub subject: String,
+
+    /// The timestamp this account was linked.
+    pub created_at: DateTime<Utc>,
+}
+
+/// A reference to an access key for the S3-compatible API. Never includes the key's secret.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct S3AccessKey {
+    /// The access key's ID.
+    pub id: String,
+
+    /// The timestamp this access key was created.
+    pub created_at: DateTime<Utc>,
+}
+
+/// A user's WebAuthn credential, either a passkey or a security key. Never includes the
+/// credential's public key.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct WebAuthnCredential {
+    /// The credential's ID.
+    pub id: Id,
+
+    /// The user's name for the credential.
+    pub name: String,
+
+    /// Whether the credential is a passkey rather than a security key.
+    pub passkey: bool,
+
+    /// The timestamp this credential was registered.
+    pub created_at: DateTime<Utc>,
+
+    /// The timestamp this credential was last used to sign in, if ever.
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+/// A reference to a WebAuthn credential in WebAuthn options, matching the WebAuthn API's
+/// `PublicKeyCredentialDescriptorJSON`.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct WebAuthnCredentialDescriptor {
+    /// The credential type, which is always `public-key`.
+    pub r#type: &'static str,
+
+    /// The credential's ID.
+    pub id: Id,
+}
+
+impl From<Vec<u8>> for WebAuthnCredentialDescriptor {
+    fn from(id: Vec<u8>) -> Self {
+        Self {
+            r#type: "public-key",
+            id: id.into(),
+        }
+    }
+}
+
+/// A user's access to another user's folder.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct FolderGrant {
+    /// The user granted access.
+    pub user: User,
+
+    /// The user's role on the folder.
+    pub role: FolderRole,
+
+    /// The timestamp this access was first granted.
+    pub created_at: DateTime<Utc>,
+}
+
+/// A link anyone can use to upload files to a folder without seeing what's in it. Never includes
+/// the link's token.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct UploadLink {
+    /// The upload link's ID.
+    pub id: Id,
+
+    /// The timestamp this upload link was created.
+    pub created_at: DateTime<Utc>,
+
+    /// When the upload link expires, or [`None`] if it doesn't expire.
+    pub expires_at: Option<DateTime<Utc>>,
+
+    /// The maximum size in bytes of each file uploaded through the link, or [`None`] for no limit.
+    pub max_file_size: Option<i64>,
+
+    /// The maximum number of files that can be uploaded through the link, or [`None`] for no limit.
+    pub max_file_count: Option<i32>,
+
+    /// The types of files that can be uploaded through the link, or [`None`] to allow any type.
+    pub allowed_types: Option<Vec<String>>,
+
+    /// The number of files uploaded through the link so far.
+    pub file_count: i32,
+}
+
+/// A SHA-256 digest of a file's content, in multiple encodings for convenience.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Sha256Digest {
+    /// The digest in lowercase hexadecimal.
+    pub hex: String,
+
+    /// The digest in RFC-4648-compliant Base64.
+    pub base64: String,
+}
+
+impl From<&[u8]> for Sha256Digest {
+    fn from(digest: &[u8]) -> Self {
+        Self {
+            hex: digest.iter().map(|byte| format!("{byte:02x}")).collect(),
+            base64: STANDARD.encode(digest),
+        }
+    }
 }
@@ -4,19 +4,25 @@ use std::sync::LazyLock;
 
 use axum::{
     Router,
+    extract::DefaultBodyLimit,
+    http::Method,
     routing::{delete, get, patch, post, put},
 };
 
-use crate::api;
+use crate::api::{self, db_helpers::ApiTokenScope};
 
 mod v0 {
     //! The routes for version 1 of the HTTP API.
 
+    pub(crate) mod corrupt_file_contents;
     pub(crate) mod email_change_requests;
     pub(crate) mod files;
     pub(crate) mod folders;
+    pub(crate) mod oauth;
     pub(crate) mod password_reset;
+    pub(crate) mod security_notices;
     pub(crate) mod sessions;
+    pub(crate) mod upload_links;
     pub(crate) mod user_requests;
     pub(crate) mod users;
 }
@@ -24,6 +30,10 @@ mod v0 {
 /// The API router.
 pub(super) static ROUTER: LazyLock<Router> = LazyLock::new(|| {
     let v0_router = Router::new()
+        .route(
+            "/corrupt-file-contents",
+            get(v0::corrupt_file_contents::get),
+        )
         .route(
             "/email-change-requests/{token}",
             get(v0::email_change_requests::email_change_request::get),
@@ -32,14 +42,64 @@ pub(super) static ROUTER: LazyLock<Router> = LazyLock::new(|| {
             "/email-change-requests/{token}/verify",
             post(v0::email_change_requests::email_change_request::verify::post),
         )
-        .route("/files/{file_id}", delete(v0::files::file::delete))
+        .route("/files", post(v0::files::post))
+        .route(
+            "/files/{file_id}",
+            get(v0::files::file::get).delete(v0::files::file::delete),
+        )
+        .route(
+            "/files/{file_id}/finalize",
+            post(v0::files::file::finalize::post),
+        )
         .route("/files/{file_id}/move", post(v0::files::file::r#move::post))
         .route("/files/{file_id}/name", put(v0::files::file::name::put))
+        .route(
+            "/files/{file_id}/parts",
+            post(v0::files::file::parts::post)
+                .layer(DefaultBodyLimit::max(v0::files::file::parts::MAX_PART_SIZE)),
+        )
         .route(
             "/files/{file_id}/share",
-            delete(v0::files::file::share::delete).post(v0::files::file::share::post),
+            get(v0::files::file::share::get)
+                .delete(v0::files::file::share::delete)
+                .patch(v0::files::file::share::patch)
+                .post(v0::files::file::share::post),
+        )
+        .route(
+            "/files/{file_id}/share/password",
+            put(v0::files::file::share::password::put)
+                .delete(v0::files::file::share::password::delete),
+        )
+        .route(
+            "/files/{file_id}/thumbnail",
+            get(v0::files::file::thumbnail::get),
+        )
+        .route(
+            "/files/{file_id}/versions",
+            get(v0::files::file::versions::get).delete(v0::files::file::versions::delete),
+        )
+        .route(
+            "/files/{file_id}/versions/{version_id}",
+            delete(v0::files::file::versions::version::delete),
+        )
+        .route(
+            "/files/{file_id}/versions/{version_id}/content",
+            get(v0::files::file::versions::version::content::get),
+        )
+        .route(
+            "/files/{file_id}/versions/{version_id}/restore",
+            post(v0::files::file::versions::version::restore::post),
         )
         .route("/folders", post(v0::folders::post))
+        .route(
+            "/folders/{folder_id}/grants",
+            get(v0::folders::folder::grants::get),
+        )
+        .route(
+            "/folders/{folder_id}/grants/{user_id}",
+            put(v0::folders::folder::grants::grant::put)
+                .delete(v0::folders::folder::grants::grant::delete),
+        )
         .route(
             "/folders/{folder_id}/name",
             put(v0::folders::folder::name::put),
@@ -50,8 +110,35 @@ pub(super) static ROUTER: LazyLock<Router> = LazyLock::new(|| {
         )
         .route(
             "/folders/{folder_id}/share",
-            delete(v0::folders::folder::share::delete).post(v0::folders::folder::share::post),
+            get(v0::folders::folder::share::get)
+                .delete(v0::folders::folder::share::delete)
+                .patch(v0::folders::folder::share::patch)
+                .post(v0::folders::folder::share::post),
         )
+        .route(
+            "/folders/{folder_id}/share/password",
+            put(v0::folders::folder::share::password::put)
+                .delete(v0::folders::folder::share::password::delete),
+        )
+        .route(
+            "/folders/{folder_id}/upload-links",
+            get(v0::folders::folder::upload_links::get)
+                .post(v0::folders::folder::upload_links::post),
+        )
+        .route(
+            "/folders/{folder_id}/upload-links/{upload_link_id}",
+            delete(v0::folders::folder::upload_links::upload_link::delete),
+        )
+        .route(
+            "/oauth/authorizations",
+            post(v0::oauth::authorizations::post),
+        )
+        .route(
+            "/oauth/clients/{client_id}",
+            get(v0::oauth::clients::client::get),
+        )
+        .route("/oauth/revoke", post(v0::oauth::revoke::post))
+        .route("/oauth/token", post(v0::oauth::token::post))
         .route(
             "/password-reset",
             get(v0::password_reset::get).post(v0::password_reset::post),
@@ -60,7 +147,45 @@ pub(super) static ROUTER: LazyLock<Router> = LazyLock::new(|| {
             "/password-reset/password",
             post(v0::password_reset::password::post),
         )
+        .route(
+            "/security-notices/{token}",
+            get(v0::security_notices::security_notice::get),
+        )
+        .route(
+            "/security-notices/{token}/revoke-sessions",
+            post(v0::security_notices::security_notice::revoke_sessions::post),
+        )
         .route("/sessions", post(v0::sessions::post))
+        .route("/sessions/oidc", post(v0::sessions::oidc::post))
+        .route(
+            "/sessions/oidc-authorization",
+            post(v0::sessions::oidc_authorization::post),
+        )
+        .route(
+            "/sessions/passkey-challenge",
+            post(v0::sessions::passkey_challenge::post),
+        )
+        .route(
+            "/sessions/webauthn-challenge",
+            post(v0::sessions::webauthn_challenge::post),
+        )
+        .route(
+            "/upload-links/{token}",
+            get(v0::upload_links::upload_link::get),
+        )
+        .route(
+            "/upload-links/{token}/files",
+            post(v0::upload_links::upload_link::files::post),
+        )
+        .route(
+            "/upload-links/{token}/files/{file_id}/finalize",
+            post(v0::upload_links::upload_link::files::file::finalize::post),
+        )
+        .route(
+            "/upload-links/{token}/files/{file_id}/parts",
+            post(v0::upload_links::upload_link::files::file::parts::post)
+                .layer(DefaultBodyLimit::max(v0::files::file::parts::MAX_PART_SIZE)),
+        )
         .route(
             "/user-requests",
             get(v0::user_requests::get).post(v0::user_requests::post),
@@ -75,29 +200,147 @@ pub(super) static ROUTER: LazyLock<Router> = LazyLock::new(|| {
         )
         .route("/users", post(v0::users::post))
         .route("/users/me", get(v0::users::me::get))
+        .route(
+            "/users/me/api-tokens",
+            get(v0::users::me::api_tokens::get).post(v0::users::me::api_tokens::post),
+        )
+        .route(
+            "/users/me/api-tokens/{api_token_id}",
+            delete(v0::users::me::api_tokens::api_token::delete),
+        )
         .route(
             "/users/me/email-change-request",
             post(v0::users::me::email_change_request::post),
         )
+        .route(
+            "/users/me/corrupt-files",
+            get(v0::users::me::corrupt_files::get),
+        )
+        .route(
+            "/users/me/folder-grants",
+            get(v0::users::me::folder_grants::get),
+        )
         .route("/users/me/name", put(v0::users::me::name::put))
+        .route(
+            "/users/me/oauth-clients",
+            get(v0::users::me::oauth_clients::get).post(v0::users::me::oauth_clients::post),
+        )
+        .route(
+            "/users/me/oauth-clients/{client_id}",
+            delete(v0::users::me::oauth_clients::oauth_client::delete),
+        )
+        .route(
+            "/users/me/oauth-grants",
+            get(v0::users::me::oauth_grants::get),
+        )
+        .route(
+            "/users/me/oauth-grants/{grant_id}",
+            delete(v0::users::me::oauth_grants::oauth_grant::delete),
+        )
+        .route(
+            "/users/me/oidc-authorization",
+            post(v0::users::me::oidc_authorization::post),
+        )
+        .route(
+            "/users/me/oidc-identity",
+            get(v0::users::me::oidc_identity::get)
+                .delete(v0::users::me::oidc_identity::delete)
+                .post(v0::users::me::oidc_identity::post),
+        )
         .route("/users/me/password", patch(v0::users::me::password::patch))
+        .route(
+            "/users/me/s3-access-keys",
+            get(v0::users::me::s3_access_keys::get).post(v0::users::me::s3_access_keys::post),
+        )
+        .route(
+            "/users/me/s3-access-keys/{access_key_id}",
+            delete(v0::users::me::s3_access_keys::s3_access_key::delete),
+        )
         .route("/users/me/sessions", get(v0::users::me::sessions::get))
         .route(
             "/users/me/sessions/{session_id}",
             delete(v0::users::me::sessions::session::delete),
         )
-        .route("/users/me/settings", get(v0::users::me::settings::get))
+        .route(
+            "/users/me/settings",
+            get(v0::users::me::settings::get).patch(v0::users::me::settings::patch),
+        )
         .route(
             "/users/me/totp",
             delete(v0::users::me::totp::delete).post(v0::users::me::totp::post),
         )
+        .route(
+            "/users/me/totp/backup-codes",
+            post(v0::users::me::totp::backup_codes::post),
+        )
         .route(
             "/users/me/verify-credentials",
             post(v0::users::me::verify_credentials::post),
         )
+        .route(
+            "/users/me/webauthn-challenge",
+            post(v0::users::me::webauthn_challenge::post),
+        )
+        .route(
+            "/users/me/webauthn-credentials",
+            get(v0::users::me::webauthn_credentials::get)
+                .post(v0::users::me::webauthn_credentials::post),
+        )
+        .route(
+            "/users/me/webauthn-credentials/{credential_id}",
+            delete(v0::users::me::webauthn_credentials::webauthn_credential::delete),
+        )
+        .route(
+            "/users/me/webdav-token",
+            delete(v0::users::me::webdav_token::delete).post(v0::users::me::webdav_token::post),
+        )
         .route("/users/{user_id}", get(v0::users::user::get))
         .fallback(|| async { api::Error::RouteNotFound })
         .method_not_allowed_fallback(|| async { api::Error::MethodNotAllowed });
 
     Router::new().nest("/api/v0", v0_router)
 });
+
+/// Gets the scope a personal API token or OAuth access token needs to make a request to a route, or
+/// [`None`] if such tokens can't access the route at all. This must be kept in sync with
+/// [`ROUTER`], and routes are inaccessible to API tokens unless listed here, so tokens can never
+/// change credentials or reach admin routes.
+pub(super) fn api_token_scope(method: &Method, route: &str) -> Option<ApiTokenScope> {
+    let files_scope = if *method == Method::GET || *method == Method::HEAD {
+        ApiTokenScope::ReadFiles
+    } else {
+        ApiTokenScope::WriteFiles
+    };
+
+    Some(match route.strip_prefix("/api/v0")? {
+        "/files"
+        | "/files/{file_id}"
+        | "/files/{file_id}/finalize"
+        | "/files/{file_id}/move"
+        | "/files/{file_id}/name"
+        | "/files/{file_id}/parts"
+        | "/files/{file_id}/thumbnail"
+        | "/files/{file_id}/versions"
+        | "/files/{file_id}/versions/{version_id}"
+        | "/files/{file_id}/versions/{version_id}/content"
+        | "/files/{file_id}/versions/{version_id}/restore"
+        | "/folders"
+        | "/folders/{folder_id}/move"
+        | "/folders/{folder_id}/name"
+        | "/users/me/corrupt-files"
+        | "/users/me/folder-grants" => files_scope,
+
+        "/files/{file_id}/share"
+        | "/files/{file_id}/share/password"
+        | "/folders/{folder_id}/grants"
+        | "/folders/{folder_id}/grants/{user_id}"
+        | "/folders/{folder_id}/share"
+        | "/folders/{folder_id}/share/password"
+        | "/folders/{folder_id}/upload-links"
+        | "/folders/{folder_id}/upload-links/{upload_link_id}" => ApiTokenScope::ManageShares,
+
+        "/users/me" | "/users/me/name" | "/users/me/settings" => ApiTokenScope::Account,
+
+        _ => return None,
+    })
+}
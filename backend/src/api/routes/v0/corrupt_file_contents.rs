@@ -0,0 +1,81 @@
+//! The set of file contents the scrub job found to be corrupt in storage.
+
+use axum::http::StatusCode;
+use axum_macros::debug_handler;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::{
+    api::{self, Json, extract::AuthToken, response::Response},
+    db::{self, TxError, TxResult},
+    id::Id,
+};
+
+/// Lists all corrupt file contents. Only admins can access this.
+///
+/// # Errors
+///
+/// See [`crate::api::Error`].
+#[debug_handler]
+pub(crate) async fn get(AuthToken(token_hash): AuthToken) -> impl Response<GetResponse> {
+    let contents = db::transaction!(async |tx| -> TxResult<_, api::Error> {
+        let Some(user) = sqlx::query!(
+            "SELECT users.admin FROM users
+                INNER JOIN sessions ON sessions.user_id = users.id
+                WHERE sessions.token_hash = $1",
+            token_hash.as_ref(),
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        else {
+            return Err(TxError::Abort(api::Error::AuthFailed));
+        };
+
+        if !user.admin {
+            return Err(TxError::Abort(api::Error::AccessDenied));
+        }
+
+        Ok(sqlx::query_as!(
+            CorruptFileContent,
+            r#"SELECT
+                corrupt_file_contents.content_id AS id,
+                corrupt_file_contents.detected_at,
+                corrupt_file_contents.reason,
+                count(files.id) AS "file_count!"
+                FROM corrupt_file_contents
+                LEFT JOIN files ON files.content_id = corrupt_file_contents.content_id
+                GROUP BY corrupt_file_contents.content_id
+                ORDER BY corrupt_file_contents.detected_at"#,
+        )
+        .fetch_all(tx.as_mut())
+        .await?)
+    })
+    .await?;
+
+    Ok((StatusCode::OK, Json(GetResponse { values: contents })))
+}
+
+/// A corrupt file content.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CorruptFileContent {
+    /// The file content's ID.
+    id: Id,
+
+    /// When the corruption was detected.
+    detected_at: DateTime<Utc>,
+
+    /// Why the file content is considered corrupt.
+    reason: String,
+
+    /// The number of files with the file content.
+    file_count: i64,
+}
+
+/// A `GET` response body for this API route.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct GetResponse {
+    /// All corrupt file contents, oldest first.
+    values: Vec<CorruptFileContent>,
+}
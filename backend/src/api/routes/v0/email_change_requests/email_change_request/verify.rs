@@ -5,25 +5,35 @@ use axum_macros::debug_handler;
 use serde::Serialize;
 
 use crate::{
-    api::{self, Json, extract::Path, response::Response},
+    api::{
+        self, Json,
+        db_helpers::create_security_notice,
+        extract::{Path, UserAgent},
+        response::Response,
+    },
     crypto::hash_without_salt,
     db::{self, TxError, TxResult},
+    email::{EmailChangedMessage, MessageTemplate},
     id::Token,
 };
 
 /// A request path for this API route.
 type PathParams = Path<Token>;
 
-/// Performs the email change from an email change request.
+/// Performs the email change from an email change request, notifying the user at their previous
+/// email address.
 ///
 /// # Errors
 ///
 /// See [`crate::api::Error`].
 #[debug_handler]
-pub(crate) async fn post(Path(token): PathParams) -> impl Response<PostResponse> {
+pub(crate) async fn post(
+    Path(token): PathParams,
+    user_agent: UserAgent,
+) -> impl Response<PostResponse> {
     let token_hash = hash_without_salt(&token);
 
-    db::transaction!(async |tx| -> TxResult<_, api::Error> {
+    let (notice, new_email) = db::transaction!(async |tx| -> TxResult<_, api::Error> {
         let Some(email_change_request) = sqlx::query!(
             "DELETE FROM email_change_requests
                 WHERE token_hash = $1
@@ -52,6 +62,8 @@ pub(crate) async fn post(Path(token): PathParams) -> impl Response<PostResponse>
         .execute(tx.as_mut())
         .await?;
 
+        let notice = create_security_notice(tx, &email_change_request.user_id, &user_agent).await?;
+
         sqlx::query!(
             "UPDATE users
                 SET email = $1
@@ -62,10 +74,19 @@ pub(crate) async fn post(Path(token): PathParams) -> impl Response<PostResponse>
         .execute(tx.as_mut())
         .await?;
 
-        Ok(())
+        Ok((notice, email_change_request.email))
     })
     .await?;
 
+    if let Some(mailbox) = notice.mailbox() {
+        EmailChangedMessage {
+            notice: &notice,
+            new_email: &new_email,
+        }
+        .to(mailbox)
+        .send();
+    }
+
     Ok((StatusCode::OK, Json(PostResponse {})))
 }
 
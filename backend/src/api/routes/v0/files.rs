@@ -1,3 +1,245 @@
 //! The set of all files.
 
 pub(crate) mod file;
+
+use axum::http::header::LOCATION;
+use axum_macros::debug_handler;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::PgTransaction;
+
+use crate::{
+    api::{
+        self, Json,
+        db_helpers::{
+            FolderPaths, FolderRole, ItemKind, OnConflict, available_name, check_file_name,
+            query_folder_paths_to_modify_contents,
+        },
+        extract::AuthToken,
+        response::Response,
+        validation::{FileName, Sha256DigestInput},
+    },
+    crypto::serialize_hash_state,
+    db::{self, TxError, TxResult},
+    file_type::UNKNOWN_TYPE,
+    id::{Id, NewFileContentId, NewFileId},
+};
+
+/// A `POST` request body for this API route.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub(crate) struct PostRequest {
+    /// The ID of the folder to create a file under, or [`None`] for the root directory.
+    parent_id: Option<Id>,
+
+    /// The file's name.
+    name: FileName,
+
+    /// The size of the file's content in bytes.
+    size: u64,
+
+    /// The SHA-256 digest the file's content is expected to have. If specified, finalizing the
+    /// upload fails unless the uploaded content matches it.
+    sha256: Option<Sha256DigestInput>,
+
+    /// What to do if a file with the same name already exists in the folder.
+    #[serde(default)]
+    on_conflict: OnConflict,
+}
+
+/// Starts uploading a new file. The file doesn't appear in its parent folder until its content is
+/// fully uploaded and the upload is finalized. A file uploaded to another user's folder belongs to
+/// that user.
+///
+/// With `onConflict: "replace"`, uploading to the name of an existing file replaces that file's
+/// content once the upload is finalized, keeping its previous content as a version. This requires
+/// permission to edit the folder.
+///
+/// # Errors
+///
+/// See [`crate::api::Error`].
+#[debug_handler]
+pub(crate) async fn post(
+    AuthToken(token_hash): AuthToken,
+    Json(body): Json<PostRequest>,
+) -> impl Response<PostResponse> {
+    let Ok(size) = i64::try_from(body.size) else {
+        return Err(api::Error::BodyTooLarge);
+    };
+
+    let (file_id, name) = db::transaction!(async |tx| -> TxResult<_, api::Error> {
+        let Some(session) = sqlx::query!(
+            "SELECT user_id FROM sessions
+                WHERE token_hash = $1",
+            token_hash.as_ref(),
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        else {
+            return Err(TxError::Abort(api::Error::AuthFailed));
+        };
+
+        let parent = query_folder_paths_to_modify_contents(
+            tx,
+            &session.user_id,
+            body.parent_id.as_deref().map(Vec::as_slice),
+            if body.on_conflict == OnConflict::Replace {
+                FolderRole::Editor
+            } else {
+                FolderRole::Uploader
+            },
+        )
+        .await?;
+
+        let (file_id, _, name) = start_upload(
+            tx,
+            &parent,
+            &body.name,
+            size,
+            body.sha256.as_ref().map(|digest| digest.as_slice()),
+            body.on_conflict,
+        )
+        .await?;
+
+        Ok((file_id, name))
+    })
+    .await?;
+
+    Ok((
+        StatusCode::CREATED,
+        [(LOCATION, format!("/api/v0/files/{file_id}"))],
+        Json(PostResponse {
+            id: file_id,
+            name,
+            size: body.size,
+        }),
+    ))
+}
+
+/// A `POST` response body for this API route.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PostResponse {
+    /// The new file's ID, or the existing file's ID if its content is being replaced.
+    id: Id,
+
+    /// The new file's name, which has a number added if the requested name was taken and
+    /// `onConflict` is `"rename"`.
+    name: FileName,
+
+    /// The size of the new file's content in bytes.
+    size: u64,
+}
+
+/// Creates a new incomplete file in a folder for its content to be uploaded to, returning the
+/// file's ID, its content's ID, and the name it was given. The caller must check the user has
+/// access to the folder, and that they can edit it if `on_conflict` is [`OnConflict::Replace`].
+///
+/// With [`OnConflict::Replace`], if a complete file with the same name exists, the new incomplete
+/// file gets the same ID, so finalizing the upload replaces the complete file's content.
+///
+/// # Errors
+///
+/// Returns an error if a database query fails, if the name isn't allowed in the folder, or if it
+/// conflicts with another file in the folder that can't be resolved.
+pub(crate) async fn start_upload(
+    tx: &mut PgTransaction<'static>,
+    parent: &FolderPaths,
+    name: &FileName,
+    size: i64,
+    expected_hash: Option<&[u8]>,
+    on_conflict: OnConflict,
+) -> TxResult<(Id, NewFileContentId, FileName), api::Error> {
+    check_file_name(tx, &parent.owner_id, name).await?;
+
+    // Check for conflicts early rather than after the whole file is uploaded.
+    let existing_files = sqlx::query!(
+        "SELECT id, complete FROM files
+            WHERE owner_id = $1 AND parent_name_path = $2 AND name = $3",
+        parent.owner_id,
+        parent.name_path.as_slice(),
+        name.as_str(),
+    )
+    .fetch_all(tx.as_mut())
+    .await?;
+
+    let (file_id, name): (Id, _) = match on_conflict {
+        _ if existing_files.is_empty() => (NewFileId::generate().to_vec().into(), name.clone()),
+
+        OnConflict::Fail => return Err(TxError::Abort(api::Error::AlreadyExists)),
+
+        OnConflict::Rename => (
+            NewFileId::generate().to_vec().into(),
+            available_name(tx, ItemKind::File, parent, name, None).await?,
+        ),
+
+        OnConflict::Replace => match existing_files.as_slice() {
+            [existing_file] if existing_file.complete => {
+                (existing_file.id.clone().into(), name.clone())
+            }
+
+            // The file is already being uploaded or replaced.
+            _ => return Err(TxError::Abort(api::Error::AlreadyExists)),
+        },
+    };
+
+    let content_id = NewFileContentId::generate();
+    let partial_hash = serialize_hash_state(&Sha256::new());
+
+    match sqlx::query!(
+        "INSERT INTO file_contents
+            (id, complete, partial_hash, expected_hash, original_size, decoded_part_sizes)
+            VALUES ($1, FALSE, $2, $3, $4, '{}')",
+        content_id.as_slice(),
+        partial_hash,
+        expected_hash,
+        size,
+    )
+    .execute(tx.as_mut())
+    .await
+    {
+        Err(sqlx::Error::Database(error)) if error.constraint() == Some("file_contents_pkey") => {
+            return Err(TxError::Retry);
+        }
+        result => result?,
+    };
+
+    match sqlx::query!(
+        "INSERT INTO files (
+            created_at,
+            id,
+            name,
+            owner_id,
+            parent_id_path,
+            parent_name_path,
+            size,
+            content_id,
+            type
+        )
+            VALUES (now(), $1, $2, $3, $4, $5, $6, $7, $8)",
+        file_id.as_slice(),
+        name.as_str(),
+        parent.owner_id,
+        parent.id_path.as_slice(),
+        parent.name_path.as_slice(),
+        size,
+        content_id.as_slice(),
+        UNKNOWN_TYPE,
+    )
+    .execute(tx.as_mut())
+    .await
+    {
+        Err(sqlx::Error::Database(error)) if error.constraint() == Some("files_by_name_path") => {
+            return Err(TxError::Abort(api::Error::AlreadyExists));
+        }
+
+        Err(sqlx::Error::Database(error)) if error.constraint() == Some("files_pkey") => {
+            return Err(TxError::Retry);
+        }
+
+        result => result?,
+    };
+
+    Ok((file_id, content_id, name))
+}
@@ -1,38 +1,44 @@
 //! A file.
 
-use axum::http::StatusCode;
+use axum::http::{StatusCode, header::ETAG};
 use axum_macros::debug_handler;
+use chrono::{DateTime, Utc};
 use serde::Serialize;
-use sqlx::Connection;
 
 use crate::{
     api::{
         self, Json,
-        extract::{AuthToken, Path},
-        response::Response,
+        db_helpers::{FolderRole, delete_file, has_role},
+        extract::{AuthToken, IfMatch, Path},
+        response::{Response, body::Sha256Digest, revision_etag},
     },
     db::{self, TxError, TxResult},
     id::Id,
 };
 
+pub(crate) mod finalize;
 pub(crate) mod r#move;
 pub(crate) mod name;
+pub(crate) mod parts;
 pub(crate) mod share;
+pub(crate) mod thumbnail;
+pub(crate) mod versions;
 
 /// A request path for this API route.
 type PathParams = Path<Id>;
 
-/// Deletes a file.
+/// Gets a file's metadata. The `ETag` header is set to the file's revision, so it can be used with
+/// `If-Match` when modifying the file.
 ///
 /// # Errors
 ///
 /// See [`crate::api::Error`].
 #[debug_handler]
-pub(crate) async fn delete(
+pub(crate) async fn get(
     Path(file_id): PathParams,
     AuthToken(token_hash): AuthToken,
-) -> impl Response<DeleteResponse> {
-    db::transaction!(async |tx| -> TxResult<_, api::Error> {
+) -> impl Response<GetResponse> {
+    let file = db::transaction!(async |tx| -> TxResult<_, api::Error> {
         let Some(session) = sqlx::query!(
             "SELECT user_id FROM sessions
                 WHERE token_hash = $1",
@@ -44,13 +50,25 @@ pub(crate) async fn delete(
             return Err(TxError::Abort(api::Error::AuthFailed));
         };
 
-        // Don't delete any incomplete replacement files for the same ID.
         let Some(file) = sqlx::query!(
-            "DELETE FROM files
-                WHERE id = $1 AND complete AND owner_id = $2
-                RETURNING size, parent_id_path, content_id",
+            r#"SELECT
+                files.owner_id,
+                files.parent_id_path,
+                files.created_at,
+                files.modified_at,
+                files.name,
+                files.parent_id_path[array_upper(files.parent_id_path, 1)] AS parent_id,
+                files.size,
+                files.type,
+                files.shared
+                    AND (files.share_expires_at IS NULL OR files.share_expires_at > now())
+                    AS "shared!",
+                files.revision,
+                file_contents.hash AS "hash!"
+                FROM files
+                INNER JOIN file_contents ON file_contents.id = files.content_id
+                WHERE files.id = $1 AND files.complete"#,
             file_id.as_slice(),
-            session.user_id,
         )
         .fetch_optional(tx.as_mut())
         .await?
@@ -58,40 +76,97 @@ pub(crate) async fn delete(
             return Err(TxError::Abort(api::Error::AccessDenied));
         };
 
-        if !file.parent_id_path.is_empty() {
-            sqlx::query!(
-                "UPDATE folders
-                    SET size = size - $1
-                    WHERE id = ANY($2)",
-                file.size,
-                file.parent_id_path.as_slice(),
-            )
-            .execute(tx.as_mut())
-            .await?;
+        if !has_role(
+            tx,
+            &session.user_id,
+            &file.owner_id,
+            &file.parent_id_path,
+            FolderRole::Viewer,
+        )
+        .await?
+        {
+            return Err(TxError::Abort(api::Error::AccessDenied));
         }
 
-        let mut savepoint = tx.begin().await?;
+        Ok(file)
+    })
+    .await?;
+
+    Ok((
+        StatusCode::OK,
+        [(ETAG, revision_etag(file.revision))],
+        Json(GetResponse {
+            id: file_id,
+            created_at: file.created_at,
+            modified_at: file.modified_at,
+            name: file.name,
+            parent_id: file.parent_id.map(Id::from),
+            size: file.size,
+            r#type: file.r#type,
+            shared: file.shared,
+            sha256: file.hash.as_slice().into(),
+        }),
+    ))
+}
+
+/// A `GET` response body for this API route.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct GetResponse {
+    /// The file's ID.
+    id: Id,
+
+    /// When the file was created.
+    created_at: DateTime<Utc>,
+
+    /// When the file was last modified.
+    modified_at: DateTime<Utc>,
+
+    /// The file's name.
+    name: String,
+
+    /// The ID of the file's parent folder, or [`None`] for the root directory.
+    parent_id: Option<Id>,
+
+    /// The size of the file's content in bytes.
+    size: i64,
+
+    /// The file's MIME type.
+    r#type: String,
+
+    /// Whether the file is shared.
+    shared: bool,
+
+    /// The SHA-256 digest of the file's content.
+    sha256: Sha256Digest,
+}
 
-        match sqlx::query!(
-            "INSERT INTO maybe_unused_file_contents (id, started_checking)
-                VALUES ($1, false)",
-            file.content_id,
+/// Deletes a file. If the `If-Match` header is set, the file must not have changed since its `ETag`
+/// was retrieved.
+///
+/// # Errors
+///
+/// See [`crate::api::Error`].
+#[debug_handler]
+pub(crate) async fn delete(
+    Path(file_id): PathParams,
+    AuthToken(token_hash): AuthToken,
+    if_match: IfMatch,
+) -> impl Response<DeleteResponse> {
+    db::transaction!(async |tx| -> TxResult<_, api::Error> {
+        let Some(session) = sqlx::query!(
+            "SELECT user_id FROM sessions
+                WHERE token_hash = $1",
+            token_hash.as_ref(),
         )
-        .execute(savepoint.as_mut())
-        .await
-        {
-            Err(sqlx::Error::Database(error))
-                if error.constraint() == Some("maybe_unused_file_contents_pkey") =>
-            {
-                // The file content is already marked maybe unused.
-                savepoint.rollback().await?;
-            }
-
-            result => {
-                result?;
-                savepoint.commit().await?;
-            }
-        }
+        .fetch_optional(tx.as_mut())
+        .await?
+        else {
+            return Err(TxError::Abort(api::Error::AuthFailed));
+        };
+
+        // Don't delete any incomplete replacement files for the same ID.
+        delete_file(tx, &session.user_id, &file_id, &if_match).await?;
 
         Ok(())
     })
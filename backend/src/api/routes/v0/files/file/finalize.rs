@@ -0,0 +1,257 @@
+//! See [`post`].
+
+use axum::http::StatusCode;
+use axum_macros::debug_handler;
+use serde::Serialize;
+use sha2::Digest;
+use sqlx::PgTransaction;
+
+use crate::{
+    api::{
+        self, Json,
+        db_helpers::{FolderRole, add_to_folder_sizes, has_role, record_file_version},
+        extract::{AuthToken, Path},
+        response::{Response, body::Sha256Digest},
+    },
+    crypto::deserialize_hash_state,
+    db::{self, TxError, TxResult},
+    file_type::sniff_content,
+    id::Id,
+    processing::{self, SourceContent},
+};
+
+/// A request path for this API route.
+type PathParams = Path<Id>;
+
+/// Finalizes a file's upload after all of its content is uploaded, detecting the file's type from
+/// its content and adding the file to its parent folder (or replacing the content of the file it
+/// was started to replace). If the upload was started with an expected digest, the content must
+/// match it. Thumbnails of images start generating in the background.
+///
+/// # Errors
+///
+/// See [`crate::api::Error`].
+#[debug_handler]
+pub(crate) async fn post(
+    Path(file_id): PathParams,
+    AuthToken(token_hash): AuthToken,
+) -> impl Response<PostResponse> {
+    let (response, source) = db::transaction!(async |tx| -> TxResult<_, api::Error> {
+        let Some(session) = sqlx::query!(
+            "SELECT user_id FROM sessions
+                WHERE token_hash = $1",
+            token_hash.as_ref(),
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        else {
+            return Err(TxError::Abort(api::Error::AuthFailed));
+        };
+
+        let Some(file) = sqlx::query!(
+            "SELECT owner_id, parent_id_path FROM files
+                WHERE id = $1 AND NOT complete",
+            file_id.as_slice(),
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        else {
+            return Err(TxError::Abort(api::Error::AccessDenied));
+        };
+
+        if !has_role(
+            tx,
+            &session.user_id,
+            &file.owner_id,
+            &file.parent_id_path,
+            FolderRole::Uploader,
+        )
+        .await?
+        {
+            return Err(TxError::Abort(api::Error::AccessDenied));
+        }
+
+        finalize_upload(tx, &file_id).await
+    })
+    .await?;
+
+    if processing::is_decodable_image(&response.r#type) {
+        processing::generate_thumbnails(source);
+    }
+
+    Ok((StatusCode::OK, Json(response)))
+}
+
+/// A `POST` response body for this API route.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PostResponse {
+    /// The file's name.
+    pub name: String,
+
+    /// The size of the file's content in bytes.
+    pub size: i64,
+
+    /// The file's MIME type, as detected from its content.
+    pub r#type: String,
+
+    /// The SHA-256 digest of the file's content.
+    pub sha256: Sha256Digest,
+}
+
+/// Finalizes an incomplete file's upload, returning the response body along with the file's content
+/// so thumbnails can be generated for it. The caller must check the user has access to the file.
+///
+/// # Errors
+///
+/// Returns an error if a database query or storage read fails, if the file isn't an incomplete
+/// file, if its content isn't fully uploaded or doesn't match its expected digest, or if something
+/// with the same name was added to its parent folder in the meantime.
+pub(crate) async fn finalize_upload(
+    tx: &mut PgTransaction<'static>,
+    file_id: &[u8],
+) -> TxResult<(PostResponse, SourceContent), api::Error> {
+    let Some(file) = sqlx::query!(
+        r#"SELECT
+            files.name,
+            files.parent_id_path,
+            files.size,
+            file_contents.id AS content_id,
+            file_contents.encoded_size,
+            file_contents.part_count,
+            file_contents.partial_hash AS "partial_hash!",
+            file_contents.expected_hash
+            FROM files
+            INNER JOIN file_contents ON file_contents.id = files.content_id
+            WHERE files.id = $1 AND NOT files.complete"#,
+        file_id,
+    )
+    .fetch_optional(tx.as_mut())
+    .await?
+    else {
+        return Err(TxError::Abort(api::Error::AccessDenied));
+    };
+
+    if file.encoded_size != file.size {
+        return Err(TxError::Abort(api::Error::UploadIncomplete));
+    }
+
+    let Some(hasher) = deserialize_hash_state(&file.partial_hash) else {
+        return Err(TxError::Abort(api::Error::Internal(
+            "invalid partial hash state".into(),
+        )));
+    };
+    let hash = hasher.finalize();
+
+    if file
+        .expected_hash
+        .is_some_and(|expected_hash| expected_hash != hash.as_slice())
+    {
+        return Err(TxError::Abort(api::Error::DigestMismatch));
+    }
+
+    let file_type = sniff_content(&file.name, &file.content_id, file.part_count).await?;
+
+    // If the upload replaces a complete file's content, the files share an ID.
+    let replaced_size = sqlx::query_scalar!(
+        "SELECT size FROM files
+            WHERE id = $1 AND complete",
+        file_id,
+    )
+    .fetch_optional(tx.as_mut())
+    .await?;
+
+    if replaced_size.is_some() {
+        // The complete file is kept and given the new content instead.
+        sqlx::query!(
+            "DELETE FROM files
+                WHERE id = $1 AND NOT complete",
+            file_id,
+        )
+        .execute(tx.as_mut())
+        .await?;
+    } else {
+        sqlx::query!(
+            "UPDATE files
+                SET modified_at = now(),
+                    type = $1,
+                    dangerous = $2
+                WHERE id = $3 AND NOT complete",
+            file_type.mime_type,
+            file_type.dangerous,
+            file_id,
+        )
+        .execute(tx.as_mut())
+        .await?;
+    }
+
+    // Unless the upload replaces a file's content, this cascades to mark the file complete too.
+    match sqlx::query!(
+        "UPDATE file_contents
+            SET modified_at = now(),
+                complete = TRUE,
+                hash = $1,
+                partial_hash = NULL
+            WHERE id = $2",
+        hash.as_slice(),
+        file.content_id,
+    )
+    .execute(tx.as_mut())
+    .await
+    {
+        Err(sqlx::Error::Database(error)) if error.constraint() == Some("files_by_name_path") => {
+            return Err(TxError::Abort(api::Error::AlreadyExists));
+        }
+        result => result?,
+    };
+
+    if let Some(replaced_size) = replaced_size {
+        record_file_version(tx, file_id).await?;
+
+        sqlx::query!(
+            "UPDATE files
+                SET modified_at = now(),
+                    revision = revision + 1,
+                    content_id = $1,
+                    size = $2,
+                    type = $3,
+                    dangerous = $4
+                WHERE id = $5 AND complete",
+            file.content_id,
+            file.size,
+            file_type.mime_type,
+            file_type.dangerous,
+            file_id,
+        )
+        .execute(tx.as_mut())
+        .await?;
+
+        add_to_folder_sizes(tx, &file.parent_id_path, file.size - replaced_size).await?;
+    } else if !file.parent_id_path.is_empty() {
+        sqlx::query!(
+            "UPDATE folders
+                SET size = size + $1
+                WHERE id = ANY($2)",
+            file.size,
+            file.parent_id_path.as_slice(),
+        )
+        .execute(tx.as_mut())
+        .await?;
+    }
+
+    let source = SourceContent {
+        id: file.content_id,
+        hash: hash.to_vec(),
+        part_count: file.part_count,
+        size: file.size,
+    };
+
+    let response = PostResponse {
+        name: file.name,
+        size: file.size,
+        r#type: file_type.mime_type,
+        sha256: hash.as_slice().into(),
+    };
+
+    Ok((response, source))
+}
@@ -1,14 +1,15 @@
 //! A file's name.
 
-use axum::http::StatusCode;
+use axum::http::{StatusCode, header::ETAG};
 use axum_macros::debug_handler;
 use serde::{Deserialize, Serialize};
 
 use crate::{
     api::{
         self, Json,
-        extract::{AuthToken, Path},
-        response::Response,
+        db_helpers::{FolderRole, check_file_name, has_role},
+        extract::{AuthToken, IfMatch, Path},
+        response::{Response, revision_etag},
         validation::FileName,
     },
     db::{self, TxError, TxResult},
@@ -26,7 +27,8 @@ pub(crate) struct PutRequest {
     name: FileName,
 }
 
-/// Renames a file.
+/// Renames a file. If the `If-Match` header is set, the file must not have changed since its `ETag`
+/// was retrieved.
 ///
 /// # Errors
 ///
@@ -35,9 +37,10 @@ pub(crate) struct PutRequest {
 pub(crate) async fn put(
     Path(file_id): PathParams,
     AuthToken(token_hash): AuthToken,
+    if_match: IfMatch,
     Json(body): Json<PutRequest>,
 ) -> impl Response<PutResponse> {
-    db::transaction!(async |tx| -> TxResult<_, api::Error> {
+    let revision = db::transaction!(async |tx| -> TxResult<_, api::Error> {
         let Some(session) = sqlx::query!(
             "SELECT user_id FROM sessions
                 WHERE token_hash = $1",
@@ -49,13 +52,43 @@ pub(crate) async fn put(
             return Err(TxError::Abort(api::Error::AuthFailed));
         };
 
+        // A file's complete and incomplete rows are always in the same folder, so either will do.
+        let Some(file) = sqlx::query!(
+            "SELECT owner_id, parent_id_path, revision FROM files
+                WHERE id = $1
+                ORDER BY complete DESC
+                LIMIT 1",
+            file_id.as_slice(),
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        else {
+            return Err(TxError::Abort(api::Error::AccessDenied));
+        };
+
+        if !has_role(
+            tx,
+            &session.user_id,
+            &file.owner_id,
+            &file.parent_id_path,
+            FolderRole::Editor,
+        )
+        .await?
+        {
+            return Err(TxError::Abort(api::Error::AccessDenied));
+        }
+
+        if_match.check(file.revision)?;
+
+        check_file_name(tx, &file.owner_id, &body.name).await?;
+
         let is_name_updated = match sqlx::query!(
             "UPDATE files
-                SET name = $1
-                WHERE id = $2 AND owner_id = $3",
+                SET name = $1,
+                    revision = revision + 1
+                WHERE id = $2",
             body.name.as_str(),
             file_id.as_slice(),
-            session.user_id,
         )
         .execute(tx.as_mut())
         .await
@@ -75,11 +108,15 @@ pub(crate) async fn put(
             return Err(TxError::Abort(api::Error::AccessDenied));
         }
 
-        Ok(())
+        Ok(file.revision + 1)
     })
     .await?;
 
-    Ok((StatusCode::OK, Json(PutResponse { name: body.name })))
+    Ok((
+        StatusCode::OK,
+        [(ETAG, revision_etag(revision))],
+        Json(PutResponse { name: body.name }),
+    ))
 }
 
 /// A `PUT` response body for this API route.
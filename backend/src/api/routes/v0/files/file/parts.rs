@@ -0,0 +1,164 @@
+//! The uploaded parts of a file's content.
+
+use axum::http::StatusCode;
+use axum_macros::debug_handler;
+use serde::Serialize;
+use sha2::Digest;
+use sqlx::PgTransaction;
+
+use crate::{
+    api::{
+        self, Json,
+        db_helpers::{FolderRole, has_role},
+        extract::{AuthToken, Bytes, Path},
+        response::Response,
+    },
+    crypto::{deserialize_hash_state, serialize_hash_state},
+    db::{self, TxError, TxResult},
+    id::Id,
+    storage,
+};
+
+/// The maximum size of an uploaded part in bytes.
+pub(crate) const MAX_PART_SIZE: usize = 16 * 1024 * 1024;
+
+/// A request path for this API route.
+type PathParams = Path<Id>;
+
+/// Uploads the next part of an incomplete file's content. The request body is the part's raw bytes.
+///
+/// # Errors
+///
+/// See [`crate::api::Error`].
+#[debug_handler]
+pub(crate) async fn post(
+    Path(file_id): PathParams,
+    AuthToken(token_hash): AuthToken,
+    Bytes(part): Bytes,
+) -> impl Response<PostResponse> {
+    let uploaded_size = db::transaction!(async |tx| -> TxResult<_, api::Error> {
+        let Some(session) = sqlx::query!(
+            "SELECT user_id FROM sessions
+                WHERE token_hash = $1",
+            token_hash.as_ref(),
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        else {
+            return Err(TxError::Abort(api::Error::AuthFailed));
+        };
+
+        let Some(file) = sqlx::query!(
+            "SELECT owner_id, parent_id_path FROM files
+                WHERE id = $1 AND NOT complete",
+            file_id.as_slice(),
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        else {
+            return Err(TxError::Abort(api::Error::AccessDenied));
+        };
+
+        if !has_role(
+            tx,
+            &session.user_id,
+            &file.owner_id,
+            &file.parent_id_path,
+            FolderRole::Uploader,
+        )
+        .await?
+        {
+            return Err(TxError::Abort(api::Error::AccessDenied));
+        }
+
+        append_part(tx, &file_id, &part).await
+    })
+    .await?;
+
+    Ok((StatusCode::OK, Json(PostResponse { uploaded_size })))
+}
+
+/// A `POST` response body for this API route.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PostResponse {
+    /// The number of bytes of the file's content uploaded so far.
+    uploaded_size: i64,
+}
+
+/// Appends the next part to an incomplete file's content, returning the number of bytes of the
+/// content uploaded so far. The caller must check the user has access to the file.
+///
+/// # Errors
+///
+/// Returns an error if a database query or the storage write fails, if the file isn't an incomplete
+/// file, if the part is empty, or if the part would make the content larger than the size it was
+/// started with.
+pub(crate) async fn append_part(
+    tx: &mut PgTransaction<'static>,
+    file_id: &[u8],
+    part: &[u8],
+) -> TxResult<i64, api::Error> {
+    if part.is_empty() {
+        return Err(TxError::Abort(api::Error::BodyDataInvalid(
+            "part is empty".into(),
+        )));
+    }
+
+    let part_size = i32::try_from(part.len()).map_err(|_| api::Error::BodyTooLarge)?;
+
+    // The content is locked until the transaction ends, since the part is written to storage before
+    // then. Otherwise, a concurrent upload could overwrite this part in storage and then have to
+    // retry, leaving the stored part mismatched with the hash and size recorded for it.
+    let Some(content) = sqlx::query!(
+        r#"SELECT
+            file_contents.id,
+            file_contents.original_size,
+            file_contents.encoded_size,
+            file_contents.part_count,
+            file_contents.partial_hash AS "partial_hash!"
+            FROM files
+            INNER JOIN file_contents ON file_contents.id = files.content_id
+            WHERE files.id = $1 AND NOT files.complete
+            FOR UPDATE OF file_contents"#,
+        file_id,
+    )
+    .fetch_optional(tx.as_mut())
+    .await?
+    else {
+        return Err(TxError::Abort(api::Error::AccessDenied));
+    };
+
+    let uploaded_size = content.encoded_size + i64::from(part_size);
+
+    if uploaded_size > content.original_size {
+        return Err(TxError::Abort(api::Error::BodyTooLarge));
+    }
+
+    let Some(mut hasher) = deserialize_hash_state(&content.partial_hash) else {
+        return Err(TxError::Abort(api::Error::Internal(
+            "invalid partial hash state".into(),
+        )));
+    };
+    hasher.update(part);
+
+    storage::write_part(&content.id, content.part_count, part).await?;
+
+    sqlx::query!(
+        "UPDATE file_contents
+            SET modified_at = now(),
+                partial_hash = $1,
+                encoded_size = $2,
+                part_count = part_count + 1,
+                decoded_part_sizes = array_append(decoded_part_sizes, $3)
+            WHERE id = $4",
+        serialize_hash_state(&hasher),
+        uploaded_size,
+        part_size,
+        content.id,
+    )
+    .execute(tx.as_mut())
+    .await?;
+
+    Ok(uploaded_size)
+}
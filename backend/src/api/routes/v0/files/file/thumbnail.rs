@@ -0,0 +1,135 @@
+//! A thumbnail of an image file.
+
+use axum::{
+    body::Body,
+    http::{
+        StatusCode,
+        header::{CACHE_CONTROL, CONTENT_LENGTH, CONTENT_TYPE, HeaderName},
+    },
+};
+use axum_macros::debug_handler;
+use serde::Deserialize;
+
+use crate::{
+    api::{
+        self,
+        db_helpers::{FolderRole, has_role},
+        extract::{AuthToken, Path, Query},
+    },
+    db::{self, TxError, TxResult},
+    id::Id,
+    processing::{self, ImageOutputFormat, SourceContent, THUMBNAIL_SIZES},
+    storage,
+};
+
+/// A request path for this API route.
+type PathParams = Path<Id>;
+
+/// A `GET` request query for this API route.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct GetQuery {
+    /// The width and height in pixels the thumbnail should fit within. Must be one of
+    /// [`THUMBNAIL_SIZES`].
+    size: u32,
+
+    /// The thumbnail's image format.
+    #[serde(default)]
+    format: ImageOutputFormat,
+}
+
+/// Gets a thumbnail of an image file, generating it if necessary. Unlike other API routes, the
+/// response body is the thumbnail image itself rather than JSON.
+///
+/// # Errors
+///
+/// See [`crate::api::Error`].
+#[debug_handler]
+pub(crate) async fn get(
+    Path(file_id): PathParams,
+    AuthToken(token_hash): AuthToken,
+    Query(query): Query<GetQuery>,
+) -> Result<(StatusCode, [(HeaderName, String); 3], Body), api::Error> {
+    if !THUMBNAIL_SIZES.contains(&query.size) {
+        return Err(api::Error::QueryDataInvalid(format!(
+            "size must be one of {THUMBNAIL_SIZES:?}",
+        )));
+    }
+
+    let (source, file_type) = db::transaction!(async |tx| -> TxResult<_, api::Error> {
+        let Some(session) = sqlx::query!(
+            "SELECT user_id FROM sessions
+                WHERE token_hash = $1",
+            token_hash.as_ref(),
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        else {
+            return Err(TxError::Abort(api::Error::AuthFailed));
+        };
+
+        let Some(file) = sqlx::query!(
+            r#"SELECT
+                files.owner_id,
+                files.parent_id_path,
+                files.type,
+                file_contents.id AS content_id,
+                file_contents.hash AS "hash!",
+                file_contents.part_count,
+                file_contents.original_size AS size
+                FROM files
+                INNER JOIN file_contents ON file_contents.id = files.content_id
+                WHERE files.id = $1 AND files.complete"#,
+            file_id.as_slice(),
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        else {
+            return Err(TxError::Abort(api::Error::AccessDenied));
+        };
+
+        if !has_role(
+            tx,
+            &session.user_id,
+            &file.owner_id,
+            &file.parent_id_path,
+            FolderRole::Viewer,
+        )
+        .await?
+        {
+            return Err(TxError::Abort(api::Error::AccessDenied));
+        }
+
+        let source = SourceContent {
+            id: file.content_id,
+            hash: file.hash,
+            part_count: file.part_count,
+            size: file.size,
+        };
+
+        Ok((source, file.r#type))
+    })
+    .await?;
+
+    if !processing::is_decodable_image(&file_type) {
+        return Err(api::Error::ResourceNotFound);
+    }
+
+    let Some(thumbnail) = processing::thumbnail(&source, query.size, query.format).await? else {
+        return Err(api::Error::ResourceNotFound);
+    };
+
+    Ok((
+        StatusCode::OK,
+        [
+            (CONTENT_TYPE, thumbnail.r#type),
+            (CONTENT_LENGTH, thumbnail.size.to_string()),
+            // A file's content can change, so thumbnails can't be cached for long.
+            (CACHE_CONTROL, "private, max-age=60".into()),
+        ],
+        Body::from_stream(storage::read_parts(
+            thumbnail.content_id,
+            thumbnail.part_count,
+        )),
+    ))
+}
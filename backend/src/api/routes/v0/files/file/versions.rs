@@ -0,0 +1,215 @@
+//! The previous versions of a file's content, kept when it's replaced.
+
+use axum::http::StatusCode;
+use axum_macros::debug_handler;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::PgTransaction;
+
+use crate::{
+    api::{
+        self, Json,
+        db_helpers::{FolderRole, has_role, mark_content_maybe_unused},
+        extract::{AuthToken, Path},
+        response::{Response, body::Sha256Digest},
+    },
+    db::{self, TxError, TxResult},
+    id::Id,
+};
+
+pub(crate) mod version;
+
+/// A request path for this API route.
+type PathParams = Path<Id>;
+
+/// Lists the previous versions of a file's content, newest first.
+///
+/// # Errors
+///
+/// See [`crate::api::Error`].
+#[debug_handler]
+pub(crate) async fn get(
+    Path(file_id): PathParams,
+    AuthToken(token_hash): AuthToken,
+) -> impl Response<GetResponse> {
+    let versions = db::transaction!(async |tx| -> TxResult<_, api::Error> {
+        let Some(session) = sqlx::query!(
+            "SELECT user_id FROM sessions
+                WHERE token_hash = $1",
+            token_hash.as_ref(),
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        else {
+            return Err(TxError::Abort(api::Error::AuthFailed));
+        };
+
+        query_file_with_role(tx, &session.user_id, &file_id, FolderRole::Viewer).await?;
+
+        Ok(sqlx::query!(
+            r#"SELECT
+                file_versions.id,
+                file_versions.created_at,
+                file_versions.modified_at,
+                file_versions.size,
+                file_versions.type,
+                file_contents.hash AS "hash!"
+                FROM file_versions
+                INNER JOIN file_contents ON file_contents.id = file_versions.content_id
+                WHERE file_versions.file_id = $1
+                ORDER BY file_versions.created_at DESC"#,
+            file_id.as_slice(),
+        )
+        .fetch_all(tx.as_mut())
+        .await?)
+    })
+    .await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(GetResponse {
+            values: versions
+                .into_iter()
+                .map(|version| FileVersion {
+                    id: version.id.into(),
+                    created_at: version.created_at,
+                    modified_at: version.modified_at,
+                    size: version.size,
+                    r#type: version.r#type,
+                    sha256: version.hash.as_slice().into(),
+                })
+                .collect(),
+        }),
+    ))
+}
+
+/// A `GET` response body for this API route.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct GetResponse {
+    /// All of the file's previous versions.
+    values: Vec<FileVersion>,
+}
+
+/// A previous version of a file's content.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct FileVersion {
+    /// The version's ID.
+    id: Id,
+
+    /// When the file's content was replaced, making this a previous version.
+    created_at: DateTime<Utc>,
+
+    /// When the file was last modified before its content was replaced.
+    modified_at: DateTime<Utc>,
+
+    /// The size of the version's content in bytes.
+    size: i64,
+
+    /// The version's MIME type.
+    r#type: String,
+
+    /// The SHA-256 digest of the version's content.
+    sha256: Sha256Digest,
+}
+
+/// Deletes all previous versions of a file's content.
+///
+/// # Errors
+///
+/// See [`crate::api::Error`].
+#[debug_handler]
+pub(crate) async fn delete(
+    Path(file_id): PathParams,
+    AuthToken(token_hash): AuthToken,
+) -> impl Response<DeleteResponse> {
+    db::transaction!(async |tx| -> TxResult<_, api::Error> {
+        let Some(session) = sqlx::query!(
+            "SELECT user_id FROM sessions
+                WHERE token_hash = $1",
+            token_hash.as_ref(),
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        else {
+            return Err(TxError::Abort(api::Error::AuthFailed));
+        };
+
+        query_file_with_role(tx, &session.user_id, &file_id, FolderRole::Editor).await?;
+
+        let content_ids = sqlx::query_scalar!(
+            "DELETE FROM file_versions
+                WHERE file_id = $1
+                RETURNING content_id",
+            file_id.as_slice(),
+        )
+        .fetch_all(tx.as_mut())
+        .await?;
+
+        for content_id in content_ids {
+            mark_content_maybe_unused(tx, &content_id).await?;
+        }
+
+        Ok(())
+    })
+    .await?;
+
+    Ok((StatusCode::OK, Json(DeleteResponse {})))
+}
+
+/// A `DELETE` response body for this API route.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DeleteResponse {}
+
+/// A complete file whose versions a user has access to.
+pub(crate) struct VersionedFile {
+    /// The ID of the file's owner.
+    pub(crate) owner_id: Vec<u8>,
+
+    /// The file's name.
+    pub(crate) name: String,
+
+    /// The IDs of the file's ancestor folders, from the root down.
+    pub(crate) parent_id_path: Vec<Vec<u8>>,
+
+    /// The size of the file's current content in bytes.
+    pub(crate) size: i64,
+
+    /// The ID of the file's current content.
+    pub(crate) content_id: Vec<u8>,
+
+    /// The file's current revision.
+    pub(crate) revision: i64,
+}
+
+/// Queries a complete file, checking the user has at least the specified role in its parent folder.
+///
+/// # Errors
+///
+/// Returns an error if a database query fails or if the user doesn't have access to the file.
+pub(crate) async fn query_file_with_role(
+    tx: &mut PgTransaction<'static>,
+    user_id: &[u8],
+    file_id: &[u8],
+    role: FolderRole,
+) -> TxResult<VersionedFile, api::Error> {
+    let Some(file) = sqlx::query_as!(
+        VersionedFile,
+        "SELECT owner_id, name, parent_id_path, size, content_id, revision FROM files
+            WHERE id = $1 AND complete",
+        file_id,
+    )
+    .fetch_optional(tx.as_mut())
+    .await?
+    else {
+        return Err(TxError::Abort(api::Error::AccessDenied));
+    };
+
+    if !has_role(tx, user_id, &file.owner_id, &file.parent_id_path, role).await? {
+        return Err(TxError::Abort(api::Error::AccessDenied));
+    }
+
+    Ok(file)
+}
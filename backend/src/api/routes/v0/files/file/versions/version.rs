@@ -0,0 +1,74 @@
+//! A previous version of a file's content.
+
+use axum::http::StatusCode;
+use axum_macros::debug_handler;
+use serde::Serialize;
+
+use crate::{
+    api::{
+        self, Json,
+        db_helpers::{FolderRole, mark_content_maybe_unused},
+        extract::{AuthToken, Path},
+        response::Response,
+        routes::v0::files::file::versions::query_file_with_role,
+    },
+    db::{self, TxError, TxResult},
+    id::Id,
+};
+
+pub(crate) mod content;
+pub(crate) mod restore;
+
+/// A request path for this API route: the file's ID, then the version's ID.
+type PathParams = Path<(Id, Id)>;
+
+/// Deletes a previous version of a file's content.
+///
+/// # Errors
+///
+/// See [`crate::api::Error`].
+#[debug_handler]
+pub(crate) async fn delete(
+    Path((file_id, version_id)): PathParams,
+    AuthToken(token_hash): AuthToken,
+) -> impl Response<DeleteResponse> {
+    db::transaction!(async |tx| -> TxResult<_, api::Error> {
+        let Some(session) = sqlx::query!(
+            "SELECT user_id FROM sessions
+                WHERE token_hash = $1",
+            token_hash.as_ref(),
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        else {
+            return Err(TxError::Abort(api::Error::AuthFailed));
+        };
+
+        query_file_with_role(tx, &session.user_id, &file_id, FolderRole::Editor).await?;
+
+        let Some(content_id) = sqlx::query_scalar!(
+            "DELETE FROM file_versions
+                WHERE id = $1 AND file_id = $2
+                RETURNING content_id",
+            version_id.as_slice(),
+            file_id.as_slice(),
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        else {
+            return Err(TxError::Abort(api::Error::ResourceNotFound));
+        };
+
+        mark_content_maybe_unused(tx, &content_id).await?;
+
+        Ok(())
+    })
+    .await?;
+
+    Ok((StatusCode::OK, Json(DeleteResponse {})))
+}
+
+/// A `DELETE` response body for this API route.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DeleteResponse {}
@@ -0,0 +1,97 @@
+//! The content of a previous version of a file.
+
+use axum::{
+    body::Body,
+    http::{
+        StatusCode,
+        header::{
+            CONTENT_DISPOSITION, CONTENT_LENGTH, CONTENT_SECURITY_POLICY, CONTENT_TYPE, HeaderName,
+            X_CONTENT_TYPE_OPTIONS,
+        },
+    },
+};
+use axum_macros::debug_handler;
+use percent_encoding::{NON_ALPHANUMERIC, utf8_percent_encode};
+
+use crate::{
+    api::{
+        self,
+        db_helpers::FolderRole,
+        extract::{AuthToken, Path},
+        routes::v0::files::file::versions::query_file_with_role,
+    },
+    db::{self, TxError, TxResult},
+    id::Id,
+    storage,
+};
+
+/// A request path for this API route: the file's ID, then the version's ID.
+type PathParams = Path<(Id, Id)>;
+
+/// Downloads the content of a previous version of a file. Unlike other API routes, the response
+/// body is the content itself rather than JSON.
+///
+/// # Errors
+///
+/// See [`crate::api::Error`].
+#[debug_handler]
+pub(crate) async fn get(
+    Path((file_id, version_id)): PathParams,
+    AuthToken(token_hash): AuthToken,
+) -> Result<(StatusCode, [(HeaderName, String); 5], Body), api::Error> {
+    let (file_name, version) = db::transaction!(async |tx| -> TxResult<_, api::Error> {
+        let Some(session) = sqlx::query!(
+            "SELECT user_id FROM sessions
+                WHERE token_hash = $1",
+            token_hash.as_ref(),
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        else {
+            return Err(TxError::Abort(api::Error::AuthFailed));
+        };
+
+        let file = query_file_with_role(tx, &session.user_id, &file_id, FolderRole::Viewer).await?;
+
+        let Some(version) = sqlx::query!(
+            "SELECT
+                file_versions.size,
+                file_versions.type,
+                file_contents.id AS content_id,
+                file_contents.part_count
+                FROM file_versions
+                INNER JOIN file_contents ON file_contents.id = file_versions.content_id
+                WHERE file_versions.id = $1 AND file_versions.file_id = $2",
+            version_id.as_slice(),
+            file_id.as_slice(),
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        else {
+            return Err(TxError::Abort(api::Error::ResourceNotFound));
+        };
+
+        Ok((file.name, version))
+    })
+    .await?;
+
+    // This is served from the website's origin, so nothing can be allowed to render inline and run
+    // scripts there.
+    Ok((
+        StatusCode::OK,
+        [
+            (CONTENT_TYPE, version.r#type),
+            (CONTENT_LENGTH, version.size.to_string()),
+            (
+                CONTENT_DISPOSITION,
+                format!(
+                    "attachment; filename*=UTF-8''{}",
+                    utf8_percent_encode(&file_name, NON_ALPHANUMERIC),
+                ),
+            ),
+            (CONTENT_SECURITY_POLICY, "sandbox".into()),
+            (X_CONTENT_TYPE_OPTIONS, "nosniff".into()),
+        ],
+        Body::from_stream(storage::read_parts(version.content_id, version.part_count)),
+    ))
+}
@@ -0,0 +1,142 @@
+//! See [`post`].
+
+use axum::http::{StatusCode, header::ETAG};
+use axum_macros::debug_handler;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::{
+    api::{
+        self, Json,
+        db_helpers::{FolderRole, add_to_folder_sizes, record_file_version},
+        extract::{AuthToken, IfMatch, Path},
+        response::{Response, body::Sha256Digest, revision_etag},
+        routes::v0::files::file::versions::query_file_with_role,
+    },
+    db::{self, TxError, TxResult},
+    id::Id,
+    processing::{self, SourceContent},
+};
+
+/// A request path for this API route: the file's ID, then the version's ID.
+type PathParams = Path<(Id, Id)>;
+
+/// Restores a previous version of a file's content, making it the file's current content. The
+/// content being replaced is kept as a new version, so restoring can be undone. If the `If-Match`
+/// header is set, the file must not have changed since its `ETag` was retrieved.
+///
+/// # Errors
+///
+/// See [`crate::api::Error`].
+#[debug_handler]
+pub(crate) async fn post(
+    Path((file_id, version_id)): PathParams,
+    AuthToken(token_hash): AuthToken,
+    if_match: IfMatch,
+) -> impl Response<PostResponse> {
+    let (response, source, revision) = db::transaction!(async |tx| -> TxResult<_, api::Error> {
+        let Some(session) = sqlx::query!(
+            "SELECT user_id FROM sessions
+                WHERE token_hash = $1",
+            token_hash.as_ref(),
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        else {
+            return Err(TxError::Abort(api::Error::AuthFailed));
+        };
+
+        let file = query_file_with_role(tx, &session.user_id, &file_id, FolderRole::Editor).await?;
+
+        if_match.check(file.revision)?;
+
+        let Some(version) = sqlx::query!(
+            r#"SELECT
+                file_versions.size,
+                file_versions.type,
+                file_versions.dangerous,
+                file_contents.id AS content_id,
+                file_contents.hash AS "hash!",
+                file_contents.part_count
+                FROM file_versions
+                INNER JOIN file_contents ON file_contents.id = file_versions.content_id
+                WHERE file_versions.id = $1 AND file_versions.file_id = $2"#,
+            version_id.as_slice(),
+            file_id.as_slice(),
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        else {
+            return Err(TxError::Abort(api::Error::ResourceNotFound));
+        };
+
+        if version.content_id != file.content_id {
+            record_file_version(tx, &file_id).await?;
+        }
+
+        let modified_at = sqlx::query_scalar!(
+            "UPDATE files
+                SET modified_at = now(),
+                    revision = revision + 1,
+                    content_id = $1,
+                    size = $2,
+                    type = $3,
+                    dangerous = $4
+                WHERE id = $5 AND complete
+                RETURNING modified_at",
+            version.content_id,
+            version.size,
+            version.r#type,
+            version.dangerous,
+            file_id.as_slice(),
+        )
+        .fetch_one(tx.as_mut())
+        .await?;
+
+        add_to_folder_sizes(tx, &file.parent_id_path, version.size - file.size).await?;
+
+        let source = SourceContent {
+            id: version.content_id,
+            hash: version.hash.clone(),
+            part_count: version.part_count,
+            size: version.size,
+        };
+
+        let response = PostResponse {
+            modified_at,
+            size: version.size,
+            r#type: version.r#type,
+            sha256: version.hash.as_slice().into(),
+        };
+
+        Ok((response, source, file.revision + 1))
+    })
+    .await?;
+
+    if processing::is_decodable_image(&response.r#type) {
+        processing::generate_thumbnails(source);
+    }
+
+    Ok((
+        StatusCode::OK,
+        [(ETAG, revision_etag(revision))],
+        Json(response),
+    ))
+}
+
+/// A `POST` response body for this API route.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PostResponse {
+    /// When the file was modified by restoring the version.
+    modified_at: DateTime<Utc>,
+
+    /// The size of the file's restored content in bytes.
+    size: i64,
+
+    /// The file's restored MIME type.
+    r#type: String,
+
+    /// The SHA-256 digest of the file's restored content.
+    sha256: Sha256Digest,
+}
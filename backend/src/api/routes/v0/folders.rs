@@ -2,15 +2,21 @@
 
 pub(crate) mod folder;
 
-use axum::http::header::LOCATION;
+use axum::http::header::{ETAG, LOCATION};
 use axum_macros::debug_handler;
 use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
 
 use crate::{
     api::{
-        self, Json, db_helpers::query_folder_paths_to_modify_contents, extract::AuthToken,
-        response::Response, validation::FileName,
+        self, Json,
+        db_helpers::{
+            FolderRole, OnConflict, create_folder, query_folder_paths_to_modify_contents,
+            resolve_folder_name_conflict,
+        },
+        extract::AuthToken,
+        response::{Response, revision_etag},
+        validation::FileName,
     },
     db::{self, TxError, TxResult},
     id::{FolderBrowseKey, Id, NewFolderId},
@@ -25,6 +31,11 @@ pub(crate) struct PostRequest {
 
     /// The folder's name.
     name: FileName,
+
+    /// What to do if a folder with the same name already exists in the parent folder. Replacing a
+    /// folder deletes everything in it.
+    #[serde(default)]
+    on_conflict: OnConflict,
 }
 
 /// Creates a folder.
@@ -37,7 +48,7 @@ pub(crate) async fn post(
     AuthToken(token_hash): AuthToken,
     Json(body): Json<PostRequest>,
 ) -> impl Response<PostResponse> {
-    let (folder_id, browse_key, created_at) =
+    let (folder_id, browse_key, created_at, name) =
         db::transaction!(async |tx| -> TxResult<_, api::Error> {
             let Some(session) = sqlx::query!(
                 "SELECT user_id FROM sessions
@@ -50,54 +61,42 @@ pub(crate) async fn post(
                 return Err(TxError::Abort(api::Error::AuthFailed));
             };
 
-            let (parent_id_path, parent_name_path) = match &body.parent_id {
-                Some(parent_id) => {
-                    query_folder_paths_to_modify_contents(tx, &session.user_id, parent_id).await?
-                }
-                None => (vec![], vec![]),
-            };
+            let parent_id = body.parent_id.as_deref().map(Vec::as_slice);
 
-            let folder_id = NewFolderId::generate();
-            let browse_key = FolderBrowseKey::generate();
-
-            let folder = match sqlx::query!(
-                "INSERT INTO folders
-                    (id, name, owner_id, parent_id_path, parent_name_path, browse_key)
-                    VALUES ($1, $2, $3, $4, $5, $6)
-                    RETURNING created_at",
-                folder_id.as_slice(),
-                body.name.as_str(),
-                session.user_id,
-                parent_id_path.as_slice(),
-                parent_name_path.as_slice(),
-                browse_key.as_slice(),
+            let parent = query_folder_paths_to_modify_contents(
+                tx,
+                &session.user_id,
+                parent_id,
+                FolderRole::Uploader,
             )
-            .fetch_one(tx.as_mut())
-            .await
-            {
-                Err(sqlx::Error::Database(error))
-                    if error.constraint() == Some("folders_by_name_path") =>
-                {
-                    return Err(TxError::Abort(api::Error::AlreadyExists));
-                }
-
-                Err(sqlx::Error::Database(error)) if error.constraint() == Some("folders_pkey") => {
-                    return Err(TxError::Retry);
-                }
-
-                result => result?,
-            };
+            .await?;
+
+            let name = resolve_folder_name_conflict(
+                tx,
+                &session.user_id,
+                &parent,
+                &body.name,
+                None,
+                body.on_conflict,
+            )
+            .await?;
+
+            let (folder_id, browse_key, created_at) =
+                create_folder(tx, &session.user_id, parent_id, &name).await?;
 
-            Ok((folder_id, browse_key, folder.created_at))
+            Ok((folder_id, browse_key, created_at, name))
         })
         .await?;
 
     Ok((
         StatusCode::CREATED,
-        [(LOCATION, format!("/api/v0/folders/{folder_id}"))],
+        [
+            (LOCATION, format!("/api/v0/folders/{folder_id}")),
+            (ETAG, revision_etag(0)),
+        ],
         Json(PostResponse {
             id: folder_id,
-            name: body.name,
+            name,
             browse_key,
             created_at: created_at.timestamp_millis(),
         }),
@@ -111,7 +110,8 @@ pub(crate) struct PostResponse {
     /// The new folder's ID.
     id: NewFolderId,
 
-    /// The new folder's name.
+    /// The new folder's name, which has a number added if the requested name was taken and
+    /// `onConflict` is `"rename"`.
     name: FileName,
 
     /// The new folder's browse key.
@@ -1,5 +1,7 @@
 //! A folder.
 
+pub(crate) mod grants;
 pub(crate) mod r#move;
 pub(crate) mod name;
 pub(crate) mod share;
+pub(crate) mod upload_links;
@@ -0,0 +1,104 @@
+//! The set of other users granted access to a folder.
+
+use axum::http::StatusCode;
+use axum_macros::debug_handler;
+use serde::Serialize;
+
+use crate::{
+    api::{
+        self, Json,
+        db_helpers::FolderRole,
+        extract::{AuthToken, Path},
+        response::{
+            Response,
+            body::{FolderGrant, User},
+        },
+    },
+    db::{self, TxError, TxResult},
+    id::Id,
+};
+
+pub(crate) mod grant;
+
+/// A request path for this API route.
+type PathParams = Path<Id>;
+
+/// Lists the users granted access to one of the current authenticated user's folders. Grants on the
+/// folder's ancestors aren't included, even though they also apply to it.
+///
+/// # Errors
+///
+/// See [`crate::api::Error`].
+#[debug_handler]
+pub(crate) async fn get(
+    Path(folder_id): PathParams,
+    AuthToken(token_hash): AuthToken,
+) -> impl Response<GetResponse> {
+    let grants = db::transaction!(async |tx| -> TxResult<_, api::Error> {
+        let Some(session) = sqlx::query!(
+            "SELECT user_id FROM sessions
+                WHERE token_hash = $1",
+            token_hash.as_ref(),
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        else {
+            return Err(TxError::Abort(api::Error::AuthFailed));
+        };
+
+        let is_owner = sqlx::query!(
+            "SELECT TRUE AS exists FROM folders
+                WHERE id = $1 AND owner_id = $2",
+            folder_id.as_slice(),
+            session.user_id,
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        .is_some();
+
+        if !is_owner {
+            return Err(TxError::Abort(api::Error::AccessDenied));
+        }
+
+        Ok(sqlx::query!(
+            r#"SELECT
+                folder_grants.user_id,
+                users.name,
+                folder_grants.role AS "role: FolderRole",
+                folder_grants.created_at
+                FROM folder_grants
+                INNER JOIN users ON users.id = folder_grants.user_id
+                WHERE folder_grants.folder_id = $1
+                ORDER BY folder_grants.created_at"#,
+            folder_id.as_slice(),
+        )
+        .fetch_all(tx.as_mut())
+        .await?)
+    })
+    .await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(GetResponse {
+            values: grants
+                .into_iter()
+                .map(|grant| FolderGrant {
+                    user: User {
+                        id: grant.user_id.into(),
+                        name: grant.name,
+                    },
+                    role: grant.role,
+                    created_at: grant.created_at,
+                })
+                .collect(),
+        }),
+    ))
+}
+
+/// A `GET` response body for this API route.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct GetResponse {
+    /// All of the users granted access to the folder.
+    values: Vec<FolderGrant>,
+}
@@ -0,0 +1,169 @@
+//! Another user's access to a folder.
+
+use axum::http::StatusCode;
+use axum_macros::debug_handler;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::{
+        self, Json,
+        db_helpers::FolderRole,
+        extract::{AuthToken, Path},
+        response::{
+            Response,
+            body::{FolderGrant, User},
+        },
+    },
+    db::{self, TxError, TxResult},
+    id::Id,
+};
+
+/// A request path for this API route: the folder's ID, then the ID of the user granted access.
+type PathParams = Path<(Id, Id)>;
+
+/// A `PUT` request body for this API route.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub(crate) struct PutRequest {
+    /// The role to grant the user.
+    role: FolderRole,
+}
+
+/// Grants another user access to one of the current authenticated user's folders and everything
+/// inside it, or changes the role they were already granted.
+///
+/// # Errors
+///
+/// See [`crate::api::Error`].
+#[debug_handler]
+pub(crate) async fn put(
+    Path((folder_id, user_id)): PathParams,
+    AuthToken(token_hash): AuthToken,
+    Json(body): Json<PutRequest>,
+) -> impl Response<PutResponse> {
+    let grant = db::transaction!(async |tx| -> TxResult<_, api::Error> {
+        let Some(session) = sqlx::query!(
+            "SELECT user_id FROM sessions
+                WHERE token_hash = $1",
+            token_hash.as_ref(),
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        else {
+            return Err(TxError::Abort(api::Error::AuthFailed));
+        };
+
+        let is_owner = sqlx::query!(
+            "SELECT TRUE AS exists FROM folders
+                WHERE id = $1 AND owner_id = $2",
+            folder_id.as_slice(),
+            session.user_id,
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        .is_some();
+
+        if !is_owner {
+            return Err(TxError::Abort(api::Error::AccessDenied));
+        }
+
+        if *user_id == session.user_id {
+            return Err(TxError::Abort(api::Error::PathDataInvalid(
+                "you can't grant yourself access to your own folder".into(),
+            )));
+        }
+
+        let Some(user) = sqlx::query!(
+            "SELECT name FROM users
+                WHERE id = $1",
+            user_id.as_slice(),
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        else {
+            return Err(TxError::Abort(api::Error::ResourceNotFound));
+        };
+
+        let grant = sqlx::query!(
+            "INSERT INTO folder_grants (folder_id, user_id, role)
+                VALUES ($1, $2, $3)
+                ON CONFLICT (folder_id, user_id) DO UPDATE
+                    SET role = excluded.role
+                RETURNING created_at",
+            folder_id.as_slice(),
+            user_id.as_slice(),
+            body.role as FolderRole,
+        )
+        .fetch_one(tx.as_mut())
+        .await?;
+
+        Ok(FolderGrant {
+            user: User {
+                id: user_id.clone(),
+                name: user.name,
+            },
+            role: body.role,
+            created_at: grant.created_at,
+        })
+    })
+    .await?;
+
+    Ok((StatusCode::OK, Json(grant)))
+}
+
+/// A `PUT` response body for this API route.
+pub(crate) type PutResponse = FolderGrant;
+
+/// Revokes a user's access to a folder. Either the folder's owner or the user granted access can do
+/// this.
+///
+/// # Errors
+///
+/// See [`crate::api::Error`].
+#[debug_handler]
+pub(crate) async fn delete(
+    Path((folder_id, user_id)): PathParams,
+    AuthToken(token_hash): AuthToken,
+) -> impl Response<DeleteResponse> {
+    let is_grant_deleted = db::transaction!(async |tx| -> TxResult<_, api::Error> {
+        let Some(session) = sqlx::query!(
+            "SELECT user_id FROM sessions
+                WHERE token_hash = $1",
+            token_hash.as_ref(),
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        else {
+            return Err(TxError::Abort(api::Error::AuthFailed));
+        };
+
+        Ok(sqlx::query!(
+            "DELETE FROM folder_grants
+                WHERE folder_id = $1 AND user_id = $2 AND (
+                    user_id = $3 OR EXISTS (
+                        SELECT 1 FROM folders
+                            WHERE id = $1 AND owner_id = $3
+                    )
+                )",
+            folder_id.as_slice(),
+            user_id.as_slice(),
+            session.user_id,
+        )
+        .execute(tx.as_mut())
+        .await?
+        .rows_affected()
+            != 0)
+    })
+    .await?;
+
+    if !is_grant_deleted {
+        return Err(api::Error::ResourceNotFound);
+    }
+
+    Ok((StatusCode::OK, Json(DeleteResponse {})))
+}
+
+/// A `DELETE` response body for this API route.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DeleteResponse {}
@@ -1,5 +1,6 @@
 //! See [`post`].
 
+use axum::http::header::ETAG;
 use axum_macros::debug_handler;
 use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
@@ -7,9 +8,13 @@ use serde::{Deserialize, Serialize};
 use crate::{
     api::{
         self, Json,
-        db_helpers::query_folder_paths_to_modify_contents,
-        extract::{AuthToken, Path},
-        response::Response,
+        db_helpers::{
+            FolderRole, OnConflict, move_folder, query_folder_paths_to_modify_contents,
+            resolve_folder_name_conflict,
+        },
+        extract::{AuthToken, IfMatch, Path},
+        response::{Response, revision_etag},
+        validation::FileName,
     },
     db::{self, TxError, TxResult},
     id::Id,
@@ -24,9 +29,14 @@ type PathParams = Path<Id>;
 pub(crate) struct PostRequest {
     /// The new parent folder's ID, or [`None`] for the root directory.
     pub parent_id: Option<Id>,
+
+    /// What to do if a folder with the same name already exists in the new parent folder.
+    #[serde(default)]
+    pub on_conflict: OnConflict,
 }
 
-/// Changes a folder's parent folder.
+/// Changes a folder's parent folder. If the `If-Match` header is set, the folder must not have
+/// changed since its `ETag` was retrieved.
 ///
 /// # Errors
 ///
@@ -35,9 +45,10 @@ pub(crate) struct PostRequest {
 pub(crate) async fn post(
     Path(folder_id): PathParams,
     AuthToken(token_hash): AuthToken,
+    if_match: IfMatch,
     Json(body): Json<PostRequest>,
 ) -> impl Response<PostResponse> {
-    db::transaction!(async |tx| -> TxResult<_, api::Error> {
+    let (revision, name) = db::transaction!(async |tx| -> TxResult<_, api::Error> {
         let Some(session) = sqlx::query!(
             "SELECT user_id FROM sessions
                 WHERE token_hash = $1",
@@ -49,110 +60,69 @@ pub(crate) async fn post(
             return Err(TxError::Abort(api::Error::AuthFailed));
         };
 
-        let (new_parent_id_path, new_parent_name_path) = match &body.parent_id {
-            Some(parent_id) => {
-                query_folder_paths_to_modify_contents(tx, &session.user_id, parent_id).await?
-            }
-            None => (vec![], vec![]),
-        };
+        let new_parent_id = body.parent_id.as_deref().map(Vec::as_slice);
 
-        let folder = match sqlx::query!(
-            "UPDATE folders
-                SET parent_id_path = $1,
-                    parent_name_path = $2
-                WHERE owner_id = $3 AND id = $4
-                RETURNING
-                    name,
-                    size,
-                    OLD.parent_id_path AS old_parent_id_path",
-            new_parent_id_path.as_slice(),
-            new_parent_name_path.as_slice(),
-            session.user_id,
+        let Some(current_name) = sqlx::query_scalar!(
+            "SELECT name FROM folders
+                WHERE id = $1",
             folder_id.as_slice(),
         )
         .fetch_optional(tx.as_mut())
-        .await
-        {
-            Err(sqlx::Error::Database(error))
-                if error.constraint() == Some("folders_by_name_path") =>
-            {
-                return Err(TxError::Abort(api::Error::AlreadyExists));
-            }
-
-            Err(error) => return Err(error.into()),
-
-            Ok(None) => return Err(TxError::Abort(api::Error::AccessDenied)),
-
-            Ok(Some(folder)) => folder,
+        .await?
+        else {
+            return Err(TxError::Abort(api::Error::AccessDenied));
         };
 
-        if !folder.old_parent_id_path.is_empty() {
-            sqlx::query!(
-                "UPDATE folders
-                    SET size = size - $1
-                    WHERE id = ANY($2)",
-                folder.size,
-                folder.old_parent_id_path.as_slice(),
-            )
-            .execute(tx.as_mut())
-            .await?;
-        }
-
-        if !new_parent_id_path.is_empty() {
-            sqlx::query!(
-                "UPDATE folders
-                    SET size = size + $1
-                    WHERE id = ANY($2)",
-                folder.size,
-                new_parent_id_path.as_slice(),
-            )
-            .execute(tx.as_mut())
-            .await?;
-        }
-
-        let mut old_folder_id_path = folder.old_parent_id_path;
-        old_folder_id_path.push(folder_id.to_vec());
-
-        let mut new_folder_id_path = new_parent_id_path;
-        new_folder_id_path.push(folder_id.to_vec());
+        let Ok(current_name) = FileName::try_from(current_name) else {
+            return Err(TxError::Abort(api::Error::Internal(
+                "invalid folder name".into(),
+            )));
+        };
 
-        let mut new_folder_name_path = new_parent_name_path;
-        new_folder_name_path.push(folder.name);
+        let new_parent = query_folder_paths_to_modify_contents(
+            tx,
+            &session.user_id,
+            new_parent_id,
+            FolderRole::Editor,
+        )
+        .await?;
 
-        sqlx::query!(
-            "UPDATE folders
-                SET parent_id_path = $1 || parent_id_path[array_length($2::bytea[], 1) + 1:],
-                    parent_name_path = $3 || parent_name_path[array_length($2::text[], 1) + 1:]
-                WHERE owner_id = $4 AND parent_id_path >= $2 AND parent_id_path < $2 || NULL",
-            new_folder_id_path.as_slice(),
-            old_folder_id_path.as_slice(),
-            new_folder_name_path.as_slice(),
-            session.user_id,
+        let name = resolve_folder_name_conflict(
+            tx,
+            &session.user_id,
+            &new_parent,
+            &current_name,
+            Some(&folder_id),
+            body.on_conflict,
         )
-        .execute(tx.as_mut())
         .await?;
 
-        sqlx::query!(
-            "UPDATE files
-                SET parent_id_path = $1 || parent_id_path[array_length($2::bytea[], 1) + 1:],
-                    parent_name_path = $3 || parent_name_path[array_length($2::text[], 1) + 1:]
-                WHERE owner_id = $4 AND parent_id_path >= $2 AND parent_id_path < $2 || NULL",
-            new_folder_id_path.as_slice(),
-            old_folder_id_path.as_slice(),
-            new_folder_name_path.as_slice(),
-            session.user_id,
+        let revision = move_folder(
+            tx,
+            &session.user_id,
+            &folder_id,
+            new_parent_id,
+            Some(&name),
+            &if_match,
         )
-        .execute(tx.as_mut())
         .await?;
 
-        Ok(())
+        Ok((revision, name))
     })
     .await?;
 
-    Ok((StatusCode::OK, Json(PostResponse {})))
+    Ok((
+        StatusCode::OK,
+        [(ETAG, revision_etag(revision))],
+        Json(PostResponse { name }),
+    ))
 }
 
 /// A `POST` response body for this API route.
 #[derive(Serialize, Debug)]
 #[serde(rename_all = "camelCase")]
-pub(crate) struct PostResponse {}
+pub(crate) struct PostResponse {
+    /// The folder's name, which has a number added if its name was taken in the new parent folder
+    /// and `onConflict` is `"rename"`.
+    name: FileName,
+}
@@ -1,14 +1,15 @@
 //! A folder's name.
 
-use axum::http::StatusCode;
+use axum::http::{StatusCode, header::ETAG};
 use axum_macros::debug_handler;
 use serde::{Deserialize, Serialize};
 
 use crate::{
     api::{
         self, Json,
-        extract::{AuthToken, Path},
-        response::Response,
+        db_helpers::{FolderRole, check_file_name, has_role},
+        extract::{AuthToken, IfMatch, Path},
+        response::{Response, revision_etag},
         validation::FileName,
     },
     db::{self, TxError, TxResult},
@@ -26,7 +27,8 @@ pub(crate) struct PutRequest {
     name: FileName,
 }
 
-/// Renames a folder.
+/// Renames a folder. If the `If-Match` header is set, the folder must not have changed since its
+/// `ETag` was retrieved.
 ///
 /// # Errors
 ///
@@ -35,9 +37,10 @@ pub(crate) struct PutRequest {
 pub(crate) async fn put(
     Path(folder_id): PathParams,
     AuthToken(token_hash): AuthToken,
+    if_match: IfMatch,
     Json(body): Json<PutRequest>,
 ) -> impl Response<PutResponse> {
-    db::transaction!(async |tx| -> TxResult<_, api::Error> {
+    let revision = db::transaction!(async |tx| -> TxResult<_, api::Error> {
         let Some(session) = sqlx::query!(
             "SELECT user_id FROM sessions
                 WHERE token_hash = $1",
@@ -49,14 +52,43 @@ pub(crate) async fn put(
             return Err(TxError::Abort(api::Error::AuthFailed));
         };
 
+        let Some(current_folder) = sqlx::query!(
+            "SELECT owner_id, parent_id_path, revision FROM folders
+                WHERE id = $1",
+            folder_id.as_slice(),
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        else {
+            return Err(TxError::Abort(api::Error::AccessDenied));
+        };
+
+        if !has_role(
+            tx,
+            &session.user_id,
+            &current_folder.owner_id,
+            &current_folder.parent_id_path,
+            FolderRole::Editor,
+        )
+        .await?
+        {
+            return Err(TxError::Abort(api::Error::AccessDenied));
+        }
+
+        if_match.check(current_folder.revision)?;
+
+        check_file_name(tx, &current_folder.owner_id, &body.name).await?;
+
+        let owner_id = current_folder.owner_id;
+
         let folder = match sqlx::query!(
             "UPDATE folders
-                SET name = $1
-                WHERE id = $2 AND owner_id = $3
+                SET name = $1,
+                    revision = revision + 1
+                WHERE id = $2
                 RETURNING parent_name_path, OLD.name AS old_name",
             body.name.as_str(),
             folder_id.as_slice(),
-            session.user_id,
         )
         .fetch_optional(tx.as_mut())
         .await
@@ -85,7 +117,7 @@ pub(crate) async fn put(
                 SET parent_name_path = $1 || parent_name_path[array_length($1::text[], 1) + 1:]
                 WHERE owner_id = $2 AND parent_name_path >= $3 AND parent_name_path < $3 || NULL",
             new_folder_path.as_slice(),
-            session.user_id,
+            owner_id,
             old_folder_path.as_slice(),
         )
         .execute(tx.as_mut())
@@ -96,17 +128,21 @@ pub(crate) async fn put(
                 SET parent_name_path = $1 || parent_name_path[array_length($1::text[], 1) + 1:]
                 WHERE owner_id = $2 AND parent_name_path >= $3 AND parent_name_path < $3 || NULL",
             new_folder_path.as_slice(),
-            session.user_id,
+            owner_id,
             old_folder_path.as_slice(),
         )
         .execute(tx.as_mut())
         .await?;
 
-        Ok(())
+        Ok(current_folder.revision + 1)
     })
     .await?;
 
-    Ok((StatusCode::OK, Json(PutResponse { name: body.name })))
+    Ok((
+        StatusCode::OK,
+        [(ETAG, revision_etag(revision))],
+        Json(PutResponse { name: body.name }),
+    ))
 }
 
 /// A `PUT` response body for this API route.
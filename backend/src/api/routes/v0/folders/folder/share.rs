@@ -2,7 +2,8 @@
 
 use axum::http::StatusCode;
 use axum_macros::debug_handler;
-use serde::Serialize;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 
 use crate::{
     api::{
@@ -14,10 +15,78 @@ use crate::{
     id::Id,
 };
 
+pub(crate) mod password;
+
 /// A request path for this API route.
 type PathParams = Path<Id>;
 
-/// Shares a folder.
+/// Gets whether a folder is shared, when its share expires, and whether it needs a password.
+///
+/// # Errors
+///
+/// See [`crate::api::Error`].
+#[debug_handler]
+pub(crate) async fn get(
+    Path(folder_id): PathParams,
+    AuthToken(token_hash): AuthToken,
+) -> impl Response<GetResponse> {
+    let share = db::transaction!(async |tx| -> TxResult<_, api::Error> {
+        let Some(session) = sqlx::query!(
+            "SELECT user_id FROM sessions
+                WHERE token_hash = $1",
+            token_hash.as_ref(),
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        else {
+            return Err(TxError::Abort(api::Error::AuthFailed));
+        };
+
+        let Some(share) = sqlx::query!(
+            r#"SELECT
+                shared AND (share_expires_at IS NULL OR share_expires_at > now()) AS "shared!",
+                share_expires_at,
+                share_password_hash IS NOT NULL AS "password_protected!"
+                FROM folders
+                WHERE id = $1 AND owner_id = $2"#,
+            folder_id.as_slice(),
+            session.user_id,
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        else {
+            return Err(TxError::Abort(api::Error::AccessDenied));
+        };
+
+        Ok(share)
+    })
+    .await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(GetResponse {
+            shared: share.shared,
+            expires_at: share.share_expires_at.filter(|_| share.shared),
+            password_protected: share.password_protected && share.shared,
+        }),
+    ))
+}
+
+/// A `GET` response body for this API route.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct GetResponse {
+    /// Whether the folder is shared.
+    shared: bool,
+
+    /// When the folder's share expires, or [`None`] if it doesn't expire.
+    expires_at: Option<DateTime<Utc>>,
+
+    /// Whether viewing the folder through its share requires a password.
+    password_protected: bool,
+}
+
+/// Shares a folder without an expiry.
 ///
 /// # Errors
 ///
@@ -41,7 +110,7 @@ pub(crate) async fn post(
 
         let is_shared_updated = sqlx::query!(
             "UPDATE folders
-                SET shared = true
+                SET shared = true, share_expires_at = NULL
                 WHERE id = $1 AND owner_id = $2",
             folder_id.as_slice(),
             session.user_id,
@@ -67,7 +136,80 @@ pub(crate) async fn post(
 #[serde(rename_all = "camelCase")]
 pub(crate) struct PostResponse {}
 
-/// Unshares a folder.
+/// Shares a folder until an expiry time, or changes when its existing share expires. An expiry of
+/// `null` makes the share permanent.
+///
+/// # Errors
+///
+/// See [`crate::api::Error`].
+#[debug_handler]
+pub(crate) async fn patch(
+    Path(folder_id): PathParams,
+    AuthToken(token_hash): AuthToken,
+    Json(body): Json<PatchRequest>,
+) -> impl Response<PatchResponse> {
+    if body
+        .expires_at
+        .is_some_and(|expires_at| expires_at <= Utc::now())
+    {
+        return Err(api::Error::BodyDataInvalid(
+            "`expiresAt` must be in the future".into(),
+        ));
+    }
+
+    let password_protected = db::transaction!(async |tx| -> TxResult<_, api::Error> {
+        let Some(session) = sqlx::query!(
+            "SELECT user_id FROM sessions
+                WHERE token_hash = $1",
+            token_hash.as_ref(),
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        else {
+            return Err(TxError::Abort(api::Error::AuthFailed));
+        };
+
+        let Some(share) = sqlx::query!(
+            r#"UPDATE folders
+                SET shared = true, share_expires_at = $1
+                WHERE id = $2 AND owner_id = $3
+                RETURNING share_password_hash IS NOT NULL AS "password_protected!""#,
+            body.expires_at,
+            folder_id.as_slice(),
+            session.user_id,
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        else {
+            return Err(TxError::Abort(api::Error::AccessDenied));
+        };
+
+        Ok(share.password_protected)
+    })
+    .await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(PatchResponse {
+            shared: true,
+            expires_at: body.expires_at,
+            password_protected,
+        }),
+    ))
+}
+
+/// A `PATCH` request body for this API route.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PatchRequest {
+    /// When the share should expire, or [`None`] if it shouldn't expire.
+    expires_at: Option<DateTime<Utc>>,
+}
+
+/// A `PATCH` response body for this API route.
+pub(crate) type PatchResponse = GetResponse;
+
+/// Unshares a folder, also removing its share's password.
 ///
 /// # Errors
 ///
@@ -91,7 +233,7 @@ pub(crate) async fn delete(
 
         let is_shared_updated = sqlx::query!(
             "UPDATE folders
-                SET shared = false
+                SET shared = false, share_expires_at = NULL, share_password_hash = NULL
                 WHERE id = $1 AND owner_id = $2",
             folder_id.as_slice(),
             session.user_id,
@@ -0,0 +1,160 @@
+//! The password required to view a folder through its share.
+
+use axum::http::StatusCode;
+use axum_macros::debug_handler;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::{
+        self, Json,
+        extract::{AuthToken, Path},
+        response::Response,
+        validation::SharePassword,
+    },
+    crypto::hash_with_salt,
+    db::{self, TxError, TxResult},
+    id::Id,
+};
+
+/// A request path for this API route.
+type PathParams = Path<Id>;
+
+/// Sets or changes the password required to view a shared folder. Visitors who unlocked the share
+/// with a previous password must enter the new one.
+///
+/// # Errors
+///
+/// See [`crate::api::Error`].
+#[debug_handler]
+pub(crate) async fn put(
+    Path(folder_id): PathParams,
+    AuthToken(token_hash): AuthToken,
+    Json(body): Json<PutRequest>,
+) -> impl Response<PutResponse> {
+    let password_hash = hash_with_salt(&body.password);
+
+    db::transaction!(async |tx| -> TxResult<_, api::Error> {
+        let Some(session) = sqlx::query!(
+            "SELECT user_id FROM sessions
+                WHERE token_hash = $1",
+            token_hash.as_ref(),
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        else {
+            return Err(TxError::Abort(api::Error::AuthFailed));
+        };
+
+        let Some(share) = sqlx::query!(
+            r#"SELECT
+                shared AND (share_expires_at IS NULL OR share_expires_at > now()) AS "shared!"
+                FROM folders
+                WHERE id = $1 AND owner_id = $2"#,
+            folder_id.as_slice(),
+            session.user_id,
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        else {
+            return Err(TxError::Abort(api::Error::AccessDenied));
+        };
+
+        if !share.shared {
+            return Err(TxError::Abort(api::Error::ResourceNotFound));
+        }
+
+        sqlx::query!(
+            "UPDATE folders
+                SET share_password_hash = $1
+                WHERE id = $2",
+            password_hash,
+            folder_id.as_slice(),
+        )
+        .execute(tx.as_mut())
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM share_unlocks
+                WHERE share_id = $1",
+            folder_id.as_slice(),
+        )
+        .execute(tx.as_mut())
+        .await?;
+
+        Ok(())
+    })
+    .await?;
+
+    Ok((StatusCode::OK, Json(PutResponse {})))
+}
+
+/// A `PUT` request body for this API route.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PutRequest {
+    /// The password to require.
+    password: SharePassword,
+}
+
+/// A `PUT` response body for this API route.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PutResponse {}
+
+/// Removes the password required to view a shared folder, leaving it shared.
+///
+/// # Errors
+///
+/// See [`crate::api::Error`].
+#[debug_handler]
+pub(crate) async fn delete(
+    Path(folder_id): PathParams,
+    AuthToken(token_hash): AuthToken,
+) -> impl Response<DeleteResponse> {
+    db::transaction!(async |tx| -> TxResult<_, api::Error> {
+        let Some(session) = sqlx::query!(
+            "SELECT user_id FROM sessions
+                WHERE token_hash = $1",
+            token_hash.as_ref(),
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        else {
+            return Err(TxError::Abort(api::Error::AuthFailed));
+        };
+
+        let is_password_updated = sqlx::query!(
+            "UPDATE folders
+                SET share_password_hash = NULL
+                WHERE id = $1 AND owner_id = $2",
+            folder_id.as_slice(),
+            session.user_id,
+        )
+        .execute(tx.as_mut())
+        .await?
+        .rows_affected()
+            != 0;
+
+        if !is_password_updated {
+            return Err(TxError::Abort(api::Error::AccessDenied));
+        }
+
+        sqlx::query!(
+            "DELETE FROM share_unlocks
+                WHERE share_id = $1",
+            folder_id.as_slice(),
+        )
+        .execute(tx.as_mut())
+        .await?;
+
+        Ok(())
+    })
+    .await?;
+
+    Ok((StatusCode::OK, Json(DeleteResponse {})))
+}
+
+/// A `DELETE` response body for this API route.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DeleteResponse {}
@@ -0,0 +1,273 @@
+//! The set of links anyone can use to upload files to a folder.
+
+use axum::http::StatusCode;
+use axum_macros::debug_handler;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::{
+        self, Json,
+        extract::{AuthToken, Path},
+        response::{Response, body::UploadLink},
+        validation::MediaTypePattern,
+    },
+    crypto::hash_without_salt,
+    db::{self, TxError, TxResult},
+    id::{Id, NewUploadLinkId, Token},
+};
+
+pub(crate) mod upload_link;
+
+/// A request path for this API route.
+type PathParams = Path<Id>;
+
+/// Lists the upload links for one of the current authenticated user's folders.
+///
+/// # Errors
+///
+/// See [`crate::api::Error`].
+#[debug_handler]
+pub(crate) async fn get(
+    Path(folder_id): PathParams,
+    AuthToken(token_hash): AuthToken,
+) -> impl Response<GetResponse> {
+    let upload_links = db::transaction!(async |tx| -> TxResult<_, api::Error> {
+        let Some(session) = sqlx::query!(
+            "SELECT user_id FROM sessions
+                WHERE token_hash = $1",
+            token_hash.as_ref(),
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        else {
+            return Err(TxError::Abort(api::Error::AuthFailed));
+        };
+
+        let is_owner = sqlx::query!(
+            "SELECT TRUE AS exists FROM folders
+                WHERE id = $1 AND owner_id = $2",
+            folder_id.as_slice(),
+            session.user_id,
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        .is_some();
+
+        if !is_owner {
+            return Err(TxError::Abort(api::Error::AccessDenied));
+        }
+
+        Ok(sqlx::query!(
+            "SELECT
+                id,
+                created_at,
+                expires_at,
+                max_file_size,
+                max_file_count,
+                allowed_types,
+                file_count
+                FROM upload_links
+                WHERE folder_id = $1 AND (expires_at IS NULL OR expires_at > now())
+                ORDER BY created_at",
+            folder_id.as_slice(),
+        )
+        .fetch_all(tx.as_mut())
+        .await?)
+    })
+    .await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(GetResponse {
+            values: upload_links
+                .into_iter()
+                .map(|upload_link| UploadLink {
+                    id: upload_link.id.into(),
+                    created_at: upload_link.created_at,
+                    expires_at: upload_link.expires_at,
+                    max_file_size: upload_link.max_file_size,
+                    max_file_count: upload_link.max_file_count,
+                    allowed_types: upload_link.allowed_types,
+                    file_count: upload_link.file_count,
+                })
+                .collect(),
+        }),
+    ))
+}
+
+/// A `GET` response body for this API route.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct GetResponse {
+    /// All of the folder's unexpired upload links.
+    values: Vec<UploadLink>,
+}
+
+/// A `POST` request body for this API route.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub(crate) struct PostRequest {
+    /// When the upload link expires, or [`None`] if it doesn't expire.
+    #[serde(default)]
+    expires_at: Option<DateTime<Utc>>,
+
+    /// The maximum size in bytes of each file uploaded through the link, or [`None`] for no limit.
+    #[serde(default)]
+    max_file_size: Option<u64>,
+
+    /// The maximum number of files that can be uploaded through the link, or [`None`] for no limit.
+    #[serde(default)]
+    max_file_count: Option<u32>,
+
+    /// The types of files that can be uploaded through the link, or [`None`] to allow any type.
+    #[serde(default)]
+    allowed_types: Option<Vec<MediaTypePattern>>,
+}
+
+/// Creates a link anyone can use to upload files to one of the current authenticated user's
+/// folders, without letting them see what's in the folder. Files uploaded through the link belong
+/// to the folder's owner. The link's token is only ever returned in this response.
+///
+/// # Errors
+///
+/// See [`crate::api::Error`].
+#[debug_handler]
+pub(crate) async fn post(
+    Path(folder_id): PathParams,
+    AuthToken(token_hash): AuthToken,
+    Json(body): Json<PostRequest>,
+) -> impl Response<PostResponse> {
+    if body
+        .expires_at
+        .is_some_and(|expires_at| expires_at <= Utc::now())
+    {
+        return Err(api::Error::BodyDataInvalid(
+            "`expiresAt` must be in the future".into(),
+        ));
+    }
+
+    let max_file_size = match body.max_file_size.map(i64::try_from) {
+        None => None,
+        Some(Ok(max_file_size)) if max_file_size > 0 => Some(max_file_size),
+        Some(_) => {
+            return Err(api::Error::BodyDataInvalid(
+                "`maxFileSize` must be positive and fit in a 64-bit signed integer".into(),
+            ));
+        }
+    };
+
+    let max_file_count = match body.max_file_count.map(i32::try_from) {
+        None => None,
+        Some(Ok(max_file_count)) if max_file_count > 0 => Some(max_file_count),
+        Some(_) => {
+            return Err(api::Error::BodyDataInvalid(
+                "`maxFileCount` must be positive and fit in a 32-bit signed integer".into(),
+            ));
+        }
+    };
+
+    if body.allowed_types.as_ref().is_some_and(Vec::is_empty) {
+        return Err(api::Error::BodyDataInvalid(
+            "`allowedTypes` must be `null` or non-empty".into(),
+        ));
+    }
+
+    let allowed_types = body.allowed_types.map(|allowed_types| {
+        allowed_types
+            .into_iter()
+            .map(|allowed_type| allowed_type.to_string())
+            .collect::<Vec<_>>()
+    });
+
+    let response = db::transaction!(async |tx| -> TxResult<_, api::Error> {
+        let Some(session) = sqlx::query!(
+            "SELECT user_id FROM sessions
+                WHERE token_hash = $1",
+            token_hash.as_ref(),
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        else {
+            return Err(TxError::Abort(api::Error::AuthFailed));
+        };
+
+        let is_owner = sqlx::query!(
+            "SELECT TRUE AS exists FROM folders
+                WHERE id = $1 AND owner_id = $2",
+            folder_id.as_slice(),
+            session.user_id,
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        .is_some();
+
+        if !is_owner {
+            return Err(TxError::Abort(api::Error::AccessDenied));
+        }
+
+        let upload_link_id = NewUploadLinkId::generate();
+        let token = Token::generate();
+        let link_token_hash = hash_without_salt(&token);
+
+        let upload_link = match sqlx::query!(
+            "INSERT INTO upload_links (
+                id,
+                token_hash,
+                folder_id,
+                expires_at,
+                max_file_size,
+                max_file_count,
+                allowed_types
+            )
+                VALUES ($1, $2, $3, $4, $5, $6, $7)
+                RETURNING created_at",
+            upload_link_id.as_slice(),
+            link_token_hash.as_ref(),
+            folder_id.as_slice(),
+            body.expires_at,
+            max_file_size,
+            max_file_count,
+            allowed_types.as_deref() as Option<&[String]>,
+        )
+        .fetch_one(tx.as_mut())
+        .await
+        {
+            Err(sqlx::Error::Database(error))
+                if error.constraint() == Some("upload_links_pkey")
+                    || error.constraint() == Some("upload_links_token_hash_key") =>
+            {
+                return Err(TxError::Retry);
+            }
+            result => result?,
+        };
+
+        Ok(PostResponse {
+            upload_link: UploadLink {
+                id: upload_link_id.to_vec().into(),
+                created_at: upload_link.created_at,
+                expires_at: body.expires_at,
+                max_file_size,
+                max_file_count,
+                allowed_types: allowed_types.clone(),
+                file_count: 0,
+            },
+            token,
+        })
+    })
+    .await?;
+
+    Ok((StatusCode::OK, Json(response)))
+}
+
+/// A `POST` response body for this API route.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PostResponse {
+    /// The new upload link.
+    #[serde(flatten)]
+    upload_link: UploadLink,
+
+    /// The token anyone can use to upload files through the link.
+    token: Token,
+}
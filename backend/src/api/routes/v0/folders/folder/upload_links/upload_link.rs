@@ -0,0 +1,81 @@
+//! A link anyone can use to upload files to a folder.
+
+use axum::http::StatusCode;
+use axum_macros::debug_handler;
+use serde::Serialize;
+
+use crate::{
+    api::{
+        self, Json,
+        db_helpers::discard_link_uploads,
+        extract::{AuthToken, Path},
+        response::Response,
+    },
+    db::{self, TxError, TxResult},
+    id::Id,
+};
+
+/// A request path for this API route: the folder's ID, then the upload link's ID.
+type PathParams = Path<(Id, Id)>;
+
+/// Deletes one of the current authenticated user's upload links so nothing more can be uploaded
+/// through it. Files already uploaded through it are kept, but uploads still in progress are
+/// discarded.
+///
+/// # Errors
+///
+/// See [`crate::api::Error`].
+#[debug_handler]
+pub(crate) async fn delete(
+    Path((folder_id, upload_link_id)): PathParams,
+    AuthToken(token_hash): AuthToken,
+) -> impl Response<DeleteResponse> {
+    db::transaction!(async |tx| -> TxResult<_, api::Error> {
+        let Some(session) = sqlx::query!(
+            "SELECT user_id FROM sessions
+                WHERE token_hash = $1",
+            token_hash.as_ref(),
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        else {
+            return Err(TxError::Abort(api::Error::AuthFailed));
+        };
+
+        let is_owner = sqlx::query!(
+            "SELECT TRUE AS exists FROM upload_links
+                INNER JOIN folders ON folders.id = upload_links.folder_id
+                WHERE upload_links.id = $1 AND folders.id = $2 AND folders.owner_id = $3",
+            upload_link_id.as_slice(),
+            folder_id.as_slice(),
+            session.user_id,
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        .is_some();
+
+        if !is_owner {
+            return Err(TxError::Abort(api::Error::AccessDenied));
+        }
+
+        discard_link_uploads(tx, &[upload_link_id.to_vec()]).await?;
+
+        sqlx::query!(
+            "DELETE FROM upload_links
+                WHERE id = $1",
+            upload_link_id.as_slice(),
+        )
+        .execute(tx.as_mut())
+        .await?;
+
+        Ok(())
+    })
+    .await?;
+
+    Ok((StatusCode::OK, Json(DeleteResponse {})))
+}
+
+/// A `DELETE` response body for this API route.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DeleteResponse {}
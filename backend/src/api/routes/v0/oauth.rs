@@ -0,0 +1,194 @@
+//! OAuth 2.0 (RFC 6749) endpoints letting third-party apps act on users' behalf, using the
+//! authorization code grant with PKCE (RFC 7636).
+//!
+//! Access tokens are stored as sessions with scopes, the same as personal API tokens, so they're
+//! authenticated and limited to their scopes the same way.
+
+use axum::{
+    extract::rejection::FormRejection,
+    http::{
+        HeaderMap, HeaderValue, StatusCode,
+        header::{AUTHORIZATION, CACHE_CONTROL, WWW_AUTHENTICATE},
+    },
+    response::IntoResponse,
+};
+use base64::{Engine as _, engine::general_purpose::STANDARD};
+use percent_encoding::percent_decode_str;
+use serde::Serialize;
+use sqlx::PgTransaction;
+use strum_macros::IntoStaticStr;
+use thiserror::Error;
+
+use crate::{
+    api::Json,
+    crypto::hash_without_salt,
+    db::{TxError, TxResult},
+    id::{Id, Token},
+};
+
+pub(crate) mod authorizations;
+pub(crate) mod clients;
+pub(crate) mod revoke;
+pub(crate) mod token;
+
+/// An OAuth error, for endpoints OAuth clients call directly, which must respond in the format
+/// clients expect instead of with an [`crate::api::Error`]. Each variant's name is its OAuth error
+/// code.
+#[derive(Error, IntoStaticStr, Debug)]
+#[strum(serialize_all = "snake_case")]
+#[expect(
+    clippy::enum_variant_names,
+    reason = "OAuth error codes can't be renamed"
+)]
+pub(crate) enum Error {
+    /// The request is missing a required parameter or is otherwise malformed.
+    #[error("{0}")]
+    InvalidRequest(String),
+
+    /// The client doesn't exist, or its authentication failed.
+    #[error("Client authentication failed.")]
+    InvalidClient,
+
+    /// The authorization code or refresh token is invalid, expired, revoked, or was issued to
+    /// another client or for another redirect URI, or the PKCE code verifier is incorrect.
+    #[error("The provided authorization grant is invalid, expired, or revoked.")]
+    InvalidGrant,
+
+    /// The requested scope exceeds the scope the user granted.
+    #[error("The requested scope exceeds the granted scope.")]
+    InvalidScope,
+
+    /// The grant type isn't supported.
+    #[error("The grant type is not supported.")]
+    UnsupportedGrantType,
+
+    /// An internal error occurred on the server which is unknown or expected never to happen.
+    ///
+    /// For security, this must not expose error details to clients.
+    #[error("An unexpected internal server error occurred. Please try again.")]
+    ServerError(#[source] Box<dyn std::error::Error>),
+}
+
+impl Error {
+    /// Gets the HTTP response status code corresponding to the OAuth error.
+    const fn status(&self) -> StatusCode {
+        match self {
+            Self::InvalidRequest(_) => StatusCode::BAD_REQUEST,
+            Self::InvalidClient => StatusCode::UNAUTHORIZED,
+            Self::InvalidGrant => StatusCode::BAD_REQUEST,
+            Self::InvalidScope => StatusCode::BAD_REQUEST,
+            Self::UnsupportedGrantType => StatusCode::BAD_REQUEST,
+            Self::ServerError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl From<FormRejection> for Error {
+    fn from(error: FormRejection) -> Self {
+        Self::InvalidRequest(error.body_text())
+    }
+}
+
+impl From<sqlx::Error> for Error {
+    fn from(error: sqlx::Error) -> Self {
+        Self::ServerError(error.into())
+    }
+}
+
+/// An OAuth error's response body.
+#[derive(Serialize, Debug)]
+struct ErrorBody {
+    /// The OAuth error code.
+    error: &'static str,
+
+    /// The human-friendly error message.
+    error_description: String,
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> axum::response::Response {
+        let status = self.status();
+        let body = ErrorBody {
+            error: (&self).into(),
+            error_description: self.to_string(),
+        };
+
+        // Responses with tokens or errors about them mustn't be cached (RFC 6749, section 5.1).
+        let mut response = (status, [(CACHE_CONTROL, "no-store")], Json(body)).into_response();
+
+        if status == StatusCode::UNAUTHORIZED {
+            response.headers_mut().insert(
+                WWW_AUTHENTICATE,
+                HeaderValue::from_static("Basic realm=\"File Garden\""),
+            );
+        }
+
+        response
+    }
+}
+
+/// Authenticates an OAuth client by its ID and (if it's confidential) its secret, from either HTTP
+/// Basic authentication or request body parameters. Returns the client's ID.
+///
+/// # Errors
+///
+/// Returns [`Error::InvalidClient`] if authentication fails. Returns a database error if a database
+/// query fails.
+async fn authenticate_client(
+    tx: &mut PgTransaction<'static>,
+    headers: &HeaderMap,
+    client_id: Option<&str>,
+    client_secret: Option<&str>,
+) -> TxResult<Vec<u8>, Error> {
+    let basic_credentials = headers
+        .get(AUTHORIZATION)
+        .and_then(|header_value| header_value.to_str().ok())
+        .and_then(|header_value| header_value.split_once(' '))
+        .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("Basic"))
+        .and_then(|(_, credentials)| STANDARD.decode(credentials.trim()).ok())
+        .and_then(|credentials| String::from_utf8(credentials).ok());
+
+    // Basic authentication credentials are form-URL-encoded (RFC 6749, section 2.3.1).
+    let basic_credentials = basic_credentials.as_deref().and_then(|credentials| {
+        let (client_id, client_secret) = credentials.split_once(':')?;
+
+        Some((
+            percent_decode_str(client_id).decode_utf8().ok()?,
+            percent_decode_str(client_secret).decode_utf8().ok()?,
+        ))
+    });
+
+    let (client_id, client_secret) = match &basic_credentials {
+        Some((client_id, client_secret)) => (Some(&**client_id), Some(&**client_secret)),
+        None => (client_id, client_secret),
+    };
+
+    let Some(client_id) = client_id.and_then(|client_id| client_id.parse::<Id>().ok()) else {
+        return Err(TxError::Abort(Error::InvalidClient));
+    };
+
+    let Some(client) = sqlx::query!(
+        "SELECT secret_hash FROM oauth_clients
+            WHERE id = $1",
+        client_id.as_slice(),
+    )
+    .fetch_optional(tx.as_mut())
+    .await?
+    else {
+        return Err(TxError::Abort(Error::InvalidClient));
+    };
+
+    if let Some(secret_hash) = client.secret_hash {
+        let is_secret_correct = client_secret
+            .and_then(|client_secret| client_secret.parse::<Token>().ok())
+            .is_some_and(|client_secret| {
+                hash_without_salt(&client_secret).as_ref() == secret_hash.as_slice()
+            });
+
+        if !is_secret_correct {
+            return Err(TxError::Abort(Error::InvalidClient));
+        }
+    }
+
+    Ok(client_id.to_vec())
+}
@@ -0,0 +1,147 @@
+//! The set of authorizations users have given OAuth clients to act on their behalf.
+
+use axum::http::StatusCode;
+use axum_macros::debug_handler;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::{
+        self, Json,
+        db_helpers::ApiTokenScope,
+        extract::AuthToken,
+        response::Response,
+        validation::{OAuthScope, PkceCodeChallenge},
+    },
+    crypto::hash_without_salt,
+    db::{self, TxError, TxResult},
+    id::{Id, Token},
+};
+
+/// A `POST` request body for this API route.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub(crate) struct PostRequest {
+    /// The ID of the client to authorize.
+    pub client_id: Id,
+
+    /// One of the client's registered redirect URIs to send the authorization code to.
+    pub redirect_uri: String,
+
+    /// The scopes to grant the client access to.
+    pub scope: OAuthScope,
+
+    /// An opaque value from the client to pass back to it with the authorization code.
+    #[serde(default)]
+    pub state: Option<String>,
+
+    /// The client's PKCE code challenge.
+    pub code_challenge: PkceCodeChallenge,
+
+    /// The method the PKCE code challenge was created with. Must be `S256`.
+    pub code_challenge_method: String,
+}
+
+/// Records the current authenticated user's consent for an OAuth client to act on their behalf
+/// with the requested scopes, issuing an authorization code for the client to exchange for tokens.
+/// Returns the URI to redirect the user back to the client with.
+///
+/// # Errors
+///
+/// See [`crate::api::Error`].
+#[debug_handler]
+pub(crate) async fn post(
+    AuthToken(token_hash): AuthToken,
+    Json(body): Json<PostRequest>,
+) -> impl Response<PostResponse> {
+    if body.code_challenge_method != "S256" {
+        return Err(api::Error::BodyDataInvalid(
+            "`codeChallengeMethod` must be `S256`".into(),
+        ));
+    }
+
+    if body.scope.is_empty() {
+        return Err(api::Error::BodyDataInvalid(
+            "`scope` must be non-empty".into(),
+        ));
+    }
+
+    let scopes: Vec<_> = body.scope.iter().copied().collect();
+
+    let code = db::transaction!(async |tx| -> TxResult<_, api::Error> {
+        let Some(session) = sqlx::query!(
+            "SELECT user_id FROM sessions
+                WHERE token_hash = $1",
+            token_hash.as_ref(),
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        else {
+            return Err(TxError::Abort(api::Error::AuthFailed));
+        };
+
+        let Some(client) = sqlx::query!(
+            "SELECT redirect_uris FROM oauth_clients
+                WHERE id = $1",
+            body.client_id.as_slice(),
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        else {
+            return Err(TxError::Abort(api::Error::ResourceNotFound));
+        };
+
+        if !client.redirect_uris.contains(&body.redirect_uri) {
+            return Err(TxError::Abort(api::Error::BodyDataInvalid(
+                "`redirectUri` must be one of the client's registered redirect URIs".into(),
+            )));
+        }
+
+        let code = Token::generate();
+        let code_hash = hash_without_salt(&code);
+
+        sqlx::query!(
+            "INSERT INTO oauth_authorization_codes
+                (code_hash, client_id, user_id, redirect_uri, scopes, code_challenge)
+                VALUES ($1, $2, $3, $4, $5, $6)",
+            code_hash.as_ref(),
+            body.client_id.as_slice(),
+            session.user_id,
+            body.redirect_uri,
+            scopes.as_slice() as &[ApiTokenScope],
+            body.code_challenge.as_str(),
+        )
+        .execute(tx.as_mut())
+        .await?;
+
+        Ok(code)
+    })
+    .await?;
+
+    let mut redirect_uri =
+        Url::parse(&body.redirect_uri).expect("registered redirect URIs should be valid");
+
+    {
+        let mut query = redirect_uri.query_pairs_mut();
+        query.append_pair("code", &code.to_string());
+
+        if let Some(state) = &body.state {
+            query.append_pair("state", state);
+        }
+    }
+
+    Ok((
+        StatusCode::OK,
+        Json(PostResponse {
+            redirect_uri: redirect_uri.into(),
+        }),
+    ))
+}
+
+/// A `POST` response body for this API route.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PostResponse {
+    /// The client's redirect URI with the authorization code and state in its query.
+    redirect_uri: String,
+}
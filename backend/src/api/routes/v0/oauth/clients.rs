@@ -0,0 +1,3 @@
+//! The set of all OAuth clients.
+
+pub(crate) mod client;
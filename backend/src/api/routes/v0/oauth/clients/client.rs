@@ -0,0 +1,73 @@
+//! A third-party app registered to act on users' behalf through OAuth.
+
+use axum::http::StatusCode;
+use axum_macros::debug_handler;
+use serde::Serialize;
+
+use crate::{
+    api::{
+        self, Json,
+        extract::Path,
+        response::{Response, body::User},
+    },
+    db::{self, TxError, TxResult},
+    id::Id,
+};
+
+/// A request path for this API route.
+type PathParams = Path<Id>;
+
+/// Gets the public info of an OAuth client, for showing users when they're asked to authorize it.
+///
+/// # Errors
+///
+/// See [`crate::api::Error`].
+#[debug_handler]
+pub(crate) async fn get(Path(client_id): PathParams) -> impl Response<GetResponse> {
+    let client = db::transaction!(async |tx| -> TxResult<_, api::Error> {
+        let Some(client) = sqlx::query!(
+            "SELECT
+                oauth_clients.name,
+                users.id AS owner_id,
+                users.name AS owner_name
+                FROM oauth_clients
+                INNER JOIN users ON users.id = oauth_clients.user_id
+                WHERE oauth_clients.id = $1",
+            client_id.as_slice(),
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        else {
+            return Err(TxError::Abort(api::Error::ResourceNotFound));
+        };
+
+        Ok(client)
+    })
+    .await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(GetResponse {
+            id: client_id,
+            name: client.name,
+            owner: User {
+                id: client.owner_id.into(),
+                name: client.owner_name,
+            },
+        }),
+    ))
+}
+
+/// A `GET` response body for this API route.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct GetResponse {
+    /// The client's ID.
+    id: Id,
+
+    /// The client's name.
+    name: String,
+
+    /// The user who registered the client.
+    owner: User,
+}
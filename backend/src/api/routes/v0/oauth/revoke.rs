@@ -0,0 +1,99 @@
+//! The OAuth token revocation endpoint (RFC 7009).
+
+use axum::{
+    Form,
+    extract::rejection::FormRejection,
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+};
+use axum_macros::debug_handler;
+use serde::Deserialize;
+
+use crate::{
+    api::routes::v0::oauth::{Error, authenticate_client},
+    crypto::hash_without_salt,
+    db::{self, TxResult},
+    id::Token,
+};
+
+/// A `POST` request body for this API route, in `application/x-www-form-urlencoded` format as OAuth
+/// requires.
+#[derive(Deserialize, Debug)]
+pub(crate) struct PostRequest {
+    /// The access token or refresh token to revoke.
+    pub token: Option<String>,
+
+    /// The client's ID, if not sent via HTTP Basic authentication.
+    pub client_id: Option<String>,
+
+    /// The client's secret, if the client is confidential and it's not sent via HTTP Basic
+    /// authentication.
+    pub client_secret: Option<String>,
+}
+
+/// Revokes an access token or refresh token issued to an OAuth client. Revoking a refresh token
+/// revokes its whole grant, including all of its access tokens.
+///
+/// Succeeds even if the token is invalid, as RFC 7009 requires.
+///
+/// # Errors
+///
+/// See [`Error`].
+#[debug_handler]
+pub(crate) async fn post(
+    headers: HeaderMap,
+    body: Result<Form<PostRequest>, FormRejection>,
+) -> Result<impl IntoResponse, Error> {
+    let Form(body) = body?;
+
+    let Some(token) = body.token.as_deref() else {
+        return Err(Error::InvalidRequest("`token` is required.".into()));
+    };
+
+    let token_hash = token
+        .parse::<Token>()
+        .ok()
+        .map(|token| hash_without_salt(&token));
+
+    db::transaction!(async |tx| -> TxResult<_, Error> {
+        let client_id = authenticate_client(
+            tx,
+            &headers,
+            body.client_id.as_deref(),
+            body.client_secret.as_deref(),
+        )
+        .await?;
+
+        let Some(token_hash) = &token_hash else {
+            return Ok(());
+        };
+
+        let revoked_grant = sqlx::query!(
+            "DELETE FROM oauth_grants
+                WHERE refresh_token_hash = $1 AND client_id = $2",
+            token_hash.as_ref(),
+            client_id,
+        )
+        .execute(tx.as_mut())
+        .await?;
+
+        if revoked_grant.rows_affected() == 0 {
+            sqlx::query!(
+                "DELETE FROM sessions
+                    USING oauth_grants
+                    WHERE sessions.token_hash = $1
+                        AND oauth_grants.id = sessions.oauth_grant_id
+                        AND oauth_grants.client_id = $2",
+                token_hash.as_ref(),
+                client_id,
+            )
+            .execute(tx.as_mut())
+            .await?;
+        }
+
+        Ok(())
+    })
+    .await?;
+
+    Ok(StatusCode::OK)
+}
@@ -0,0 +1,282 @@
+//! The OAuth token endpoint, which exchanges authorization codes and refresh tokens for access
+//! tokens.
+
+use axum::{
+    Form,
+    extract::rejection::FormRejection,
+    http::{HeaderMap, StatusCode, header::CACHE_CONTROL},
+    response::IntoResponse,
+};
+use axum_macros::debug_handler;
+use chrono::{TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgTransaction;
+
+use crate::{
+    api::{
+        Json,
+        db_helpers::ApiTokenScope,
+        routes::v0::oauth::{Error, authenticate_client},
+        validation::OAuthScope,
+    },
+    crypto::{hash_without_salt, verify_pkce},
+    db::{self, TxError, TxResult},
+    id::{NewOAuthGrantId, Token},
+};
+
+/// How many seconds an access token is valid for.
+const ACCESS_TOKEN_LIFETIME_SECS: i64 = 60 * 60;
+
+/// A `POST` request body for this API route, in `application/x-www-form-urlencoded` format as OAuth
+/// requires. Every parameter is optional so missing ones can be reported as OAuth errors.
+#[derive(Deserialize, Debug)]
+pub(crate) struct PostRequest {
+    /// Either `authorization_code` or `refresh_token`.
+    pub grant_type: Option<String>,
+
+    /// For the `authorization_code` grant type, the authorization code.
+    pub code: Option<String>,
+
+    /// For the `authorization_code` grant type, the redirect URI the authorization code was sent
+    /// to.
+    pub redirect_uri: Option<String>,
+
+    /// For the `authorization_code` grant type, the PKCE code verifier.
+    pub code_verifier: Option<String>,
+
+    /// For the `refresh_token` grant type, the refresh token.
+    pub refresh_token: Option<String>,
+
+    /// For the `refresh_token` grant type, a subset of the granted scopes to limit the new access
+    /// token to.
+    pub scope: Option<String>,
+
+    /// The client's ID, if not sent via HTTP Basic authentication.
+    pub client_id: Option<String>,
+
+    /// The client's secret, if the client is confidential and it's not sent via HTTP Basic
+    /// authentication.
+    pub client_secret: Option<String>,
+}
+
+/// Issues an access token and a new refresh token to an OAuth client.
+///
+/// # Errors
+///
+/// See [`Error`].
+#[debug_handler]
+pub(crate) async fn post(
+    headers: HeaderMap,
+    body: Result<Form<PostRequest>, FormRejection>,
+) -> Result<impl IntoResponse, Error> {
+    let Form(body) = body?;
+
+    let grant_type = body
+        .grant_type
+        .as_deref()
+        .ok_or_else(|| Error::InvalidRequest("`grant_type` is required.".into()))?;
+
+    if !matches!(grant_type, "authorization_code" | "refresh_token") {
+        return Err(Error::UnsupportedGrantType);
+    }
+
+    let requested_scope = body
+        .scope
+        .as_deref()
+        .map(str::parse::<OAuthScope>)
+        .transpose()
+        .map_err(|_| Error::InvalidScope)?;
+
+    let response = db::transaction!(async |tx| -> TxResult<_, Error> {
+        let client_id = authenticate_client(
+            tx,
+            &headers,
+            body.client_id.as_deref(),
+            body.client_secret.as_deref(),
+        )
+        .await?;
+
+        let (grant_id, scopes, refresh_token) = if grant_type == "authorization_code" {
+            let (Some(code), Some(redirect_uri), Some(code_verifier)) = (
+                body.code.as_deref(),
+                body.redirect_uri.as_deref(),
+                body.code_verifier.as_deref(),
+            ) else {
+                return Err(TxError::Abort(Error::InvalidRequest(
+                    "`code`, `redirect_uri`, and `code_verifier` are required.".into(),
+                )));
+            };
+
+            let Ok(code) = code.parse::<Token>() else {
+                return Err(TxError::Abort(Error::InvalidGrant));
+            };
+            let code_hash = hash_without_salt(&code);
+
+            // Delete the code so it can only be exchanged once.
+            let Some(authorization_code) = sqlx::query!(
+                r#"DELETE FROM oauth_authorization_codes
+                    WHERE code_hash = $1
+                    RETURNING
+                        client_id,
+                        user_id,
+                        redirect_uri,
+                        scopes AS "scopes: Vec<ApiTokenScope>",
+                        code_challenge,
+                        created_at > now() - interval '10 minutes' AS "is_unexpired!""#,
+                code_hash.as_ref(),
+            )
+            .fetch_optional(tx.as_mut())
+            .await?
+            else {
+                return Err(TxError::Abort(Error::InvalidGrant));
+            };
+
+            if !authorization_code.is_unexpired
+                || authorization_code.client_id != client_id
+                || authorization_code.redirect_uri != redirect_uri
+                || !verify_pkce(code_verifier, &authorization_code.code_challenge)
+            {
+                return Err(TxError::Abort(Error::InvalidGrant));
+            }
+
+            let grant_id = NewOAuthGrantId::generate();
+            let refresh_token = Token::generate();
+            let refresh_token_hash = hash_without_salt(&refresh_token);
+
+            match sqlx::query!(
+                "INSERT INTO oauth_grants (id, client_id, user_id, scopes, refresh_token_hash)
+                    VALUES ($1, $2, $3, $4, $5)",
+                grant_id.as_slice(),
+                client_id,
+                authorization_code.user_id,
+                authorization_code.scopes.as_slice() as &[ApiTokenScope],
+                refresh_token_hash.as_ref(),
+            )
+            .execute(tx.as_mut())
+            .await
+            {
+                Err(sqlx::Error::Database(error))
+                    if error.constraint() == Some("oauth_grants_pkey") =>
+                {
+                    return Err(TxError::Retry);
+                }
+                result => result?,
+            };
+
+            (grant_id.to_vec(), authorization_code.scopes, refresh_token)
+        } else {
+            let Some(refresh_token) = body.refresh_token.as_deref() else {
+                return Err(TxError::Abort(Error::InvalidRequest(
+                    "`refresh_token` is required.".into(),
+                )));
+            };
+
+            let Ok(refresh_token) = refresh_token.parse::<Token>() else {
+                return Err(TxError::Abort(Error::InvalidGrant));
+            };
+            let refresh_token_hash = hash_without_salt(&refresh_token);
+
+            // Replace the refresh token so each one can only be used once.
+            let new_refresh_token = Token::generate();
+            let new_refresh_token_hash = hash_without_salt(&new_refresh_token);
+
+            let Some(grant) = sqlx::query!(
+                r#"UPDATE oauth_grants
+                    SET refresh_token_hash = $1
+                    WHERE refresh_token_hash = $2 AND client_id = $3
+                    RETURNING id, scopes AS "scopes: Vec<ApiTokenScope>""#,
+                new_refresh_token_hash.as_ref(),
+                refresh_token_hash.as_ref(),
+                client_id,
+            )
+            .fetch_optional(tx.as_mut())
+            .await?
+            else {
+                return Err(TxError::Abort(Error::InvalidGrant));
+            };
+
+            let scopes = match &requested_scope {
+                Some(requested_scope) => {
+                    if !requested_scope
+                        .iter()
+                        .all(|scope| grant.scopes.contains(scope))
+                    {
+                        return Err(TxError::Abort(Error::InvalidScope));
+                    }
+
+                    requested_scope.iter().copied().collect()
+                }
+                None => grant.scopes,
+            };
+
+            (grant.id, scopes, new_refresh_token)
+        };
+
+        let access_token = create_access_token(tx, &grant_id, &scopes).await?;
+
+        Ok(PostResponse {
+            access_token,
+            token_type: "Bearer",
+            expires_in: ACCESS_TOKEN_LIFETIME_SECS,
+            refresh_token,
+            scope: scopes.into_iter().collect(),
+        })
+    })
+    .await?;
+
+    Ok((
+        StatusCode::OK,
+        [(CACHE_CONTROL, "no-store")],
+        Json(response),
+    ))
+}
+
+/// Creates an access token for an OAuth grant, stored as a session limited to the specified scopes.
+///
+/// # Errors
+///
+/// Returns a database error if a database query fails.
+async fn create_access_token(
+    tx: &mut PgTransaction<'static>,
+    grant_id: &[u8],
+    scopes: &[ApiTokenScope],
+) -> TxResult<Token, Error> {
+    let access_token = Token::generate();
+    let access_token_hash = hash_without_salt(&access_token);
+    let expires_at = Utc::now() + TimeDelta::seconds(ACCESS_TOKEN_LIFETIME_SECS);
+
+    sqlx::query!(
+        "INSERT INTO sessions (token_hash, user_id, name, scopes, expires_at, oauth_grant_id)
+            SELECT $1, oauth_grants.user_id, oauth_clients.name, $2, $3, oauth_grants.id
+                FROM oauth_grants
+                JOIN oauth_clients ON oauth_clients.id = oauth_grants.client_id
+                WHERE oauth_grants.id = $4",
+        access_token_hash.as_ref(),
+        scopes as &[ApiTokenScope],
+        expires_at,
+        grant_id,
+    )
+    .execute(tx.as_mut())
+    .await?;
+
+    Ok(access_token)
+}
+
+/// A `POST` response body for this API route.
+#[derive(Serialize, Debug)]
+pub(crate) struct PostResponse {
+    /// The new access token, to send as a bearer token in the `Authorization` header.
+    access_token: Token,
+
+    /// The access token's type, which is always `Bearer`.
+    token_type: &'static str,
+
+    /// How many seconds until the access token expires.
+    expires_in: i64,
+
+    /// The new refresh token, which replaces any previous one for the same grant.
+    refresh_token: Token,
+
+    /// The scopes the access token is limited to.
+    scope: OAuthScope,
+}
@@ -8,8 +8,8 @@ use crate::{
     api::{
         self, Json,
         cookie::{CookieWrapper, SessionCookie},
-        db_helpers::create_session,
-        extract::Query,
+        db_helpers::{create_security_notice, create_session},
+        extract::{ClientIp, Query, UserAgent},
         response::{Response, body::User},
         validation::{
             NewUserPassword,
@@ -18,6 +18,7 @@ use crate::{
     },
     crypto::{hash_with_salt, hash_without_salt},
     db::{self, TxError, TxResult},
+    email::{MessageTemplate, PasswordChangedMessage},
     id::Token,
 };
 
@@ -48,13 +49,15 @@ pub(crate) struct PostRequest {
 #[debug_handler]
 pub(crate) async fn post(
     Query(query): Query<PostQuery>,
+    user_agent: UserAgent,
+    client_ip: ClientIp,
     Json(body): Json<PostRequest>,
 ) -> impl Response<PostResponse> {
     let token_hash = hash_without_salt(&query.token);
 
     let password_hash = hash_with_salt(&body.password);
 
-    let (user_id, user_name, session_token) =
+    let (user_id, user_name, session_token, notice) =
         db::transaction!(async |tx| -> TxResult<_, api::Error> {
             let Some(password_reset) = sqlx::query!(
                 "DELETE FROM password_resets
@@ -68,7 +71,9 @@ pub(crate) async fn post(
                 return Err(TxError::Abort(api::Error::ResourceNotFound));
             };
 
-            body.credentials.verify(tx, &password_reset.user_id).await?;
+            body.credentials
+                .verify_limited(tx, &password_reset.user_id, client_ip)
+                .await?;
 
             let user = sqlx::query!(
                 "UPDATE users
@@ -81,8 +86,8 @@ pub(crate) async fn post(
             .fetch_one(tx.as_mut())
             .await?;
 
-            // Expiring all sessions is a conventionally expected security feature whenever a user's
-            // password is changed.
+            // Expiring all sessions and OAuth grants is a conventionally expected security feature
+            // whenever a user's password is changed.
             sqlx::query!(
                 "DELETE FROM sessions
                     WHERE user_id = $1",
@@ -91,12 +96,27 @@ pub(crate) async fn post(
             .execute(tx.as_mut())
             .await?;
 
-            let session_token = create_session(tx, &password_reset.user_id).await?;
+            sqlx::query!(
+                "DELETE FROM oauth_grants
+                    WHERE user_id = $1",
+                password_reset.user_id,
+            )
+            .execute(tx.as_mut())
+            .await?;
 
-            Ok((password_reset.user_id, user.name, session_token))
+            let session_token = create_session(tx, &password_reset.user_id, &user_agent).await?;
+            let notice = create_security_notice(tx, &password_reset.user_id, &user_agent).await?;
+
+            Ok((password_reset.user_id, user.name, session_token, notice))
         })
         .await?;
 
+    if let Some(mailbox) = notice.mailbox() {
+        PasswordChangedMessage { notice: &notice }
+            .to(mailbox)
+            .send();
+    }
+
     Ok((
         StatusCode::OK,
         [SessionCookie::new(session_token.to_string()).to_header()],
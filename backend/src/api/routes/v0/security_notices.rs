@@ -0,0 +1,3 @@
+//! The set of notices emailed to users about security-sensitive changes to their accounts.
+
+pub(crate) mod security_notice;
@@ -0,0 +1,58 @@
+//! A notice emailed to a user about a security-sensitive change to their account.
+
+use axum::http::StatusCode;
+use axum_macros::debug_handler;
+use serde::Serialize;
+
+use crate::{
+    api::{self, Json, extract::Path, response::Response},
+    crypto::hash_without_salt,
+    db::{self, TxResult},
+    id::Token,
+};
+
+pub(crate) mod revoke_sessions;
+
+/// A request path for this API route.
+type PathParams = Path<Token>;
+
+/// Checks an existing unexpired security notice.
+///
+/// # Errors
+///
+/// See [`crate::api::Error`].
+#[debug_handler]
+pub(crate) async fn get(Path(token): PathParams) -> impl Response<GetResponse> {
+    let token_hash = hash_without_salt(&token);
+
+    let Some(security_notice) = db::transaction!(async |tx| -> TxResult<_, api::Error> {
+        Ok(sqlx::query!(
+            "SELECT users.email FROM users
+                INNER JOIN security_notices ON security_notices.user_id = users.id
+                WHERE security_notices.token_hash = $1
+                    AND security_notices.created_at > now() - interval '7 days'",
+            token_hash.as_ref(),
+        )
+        .fetch_optional(tx.as_mut())
+        .await?)
+    })
+    .await?
+    else {
+        return Err(api::Error::ResourceNotFound);
+    };
+
+    Ok((
+        StatusCode::OK,
+        Json(GetResponse {
+            email: security_notice.email,
+        }),
+    ))
+}
+
+/// A `GET` response body for this API route.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct GetResponse {
+    /// The current email of the user the notice was sent to.
+    pub email: String,
+}
@@ -0,0 +1,94 @@
+//! See [`post`].
+
+use axum::http::StatusCode;
+use axum_macros::debug_handler;
+use serde::Serialize;
+
+use crate::{
+    api::{
+        self, Json,
+        cookie::{CookieWrapper, SessionCookie},
+        extract::Path,
+        response::Response,
+    },
+    crypto::hash_without_salt,
+    db::{self, TxError, TxResult},
+    id::Token,
+};
+
+/// A request path for this API route.
+type PathParams = Path<Token>;
+
+/// Signs the user a security notice was sent to out everywhere, in case they didn't make the change
+/// it's about. This deletes all of their sessions (including personal API tokens), OAuth grants,
+/// WebDAV token, and S3 access keys, along with the notice itself.
+///
+/// # Errors
+///
+/// See [`crate::api::Error`].
+#[debug_handler]
+pub(crate) async fn post(Path(token): PathParams) -> impl Response<PostResponse> {
+    let token_hash = hash_without_salt(&token);
+
+    db::transaction!(async |tx| -> TxResult<_, api::Error> {
+        let Some(security_notice) = sqlx::query!(
+            "DELETE FROM security_notices
+                WHERE token_hash = $1 AND created_at > now() - interval '7 days'
+                RETURNING user_id",
+            token_hash.as_ref(),
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        else {
+            return Err(TxError::Abort(api::Error::ResourceNotFound));
+        };
+
+        sqlx::query!(
+            "DELETE FROM sessions
+                WHERE user_id = $1",
+            security_notice.user_id,
+        )
+        .execute(tx.as_mut())
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM oauth_grants
+                WHERE user_id = $1",
+            security_notice.user_id,
+        )
+        .execute(tx.as_mut())
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM webdav_tokens
+                WHERE user_id = $1",
+            security_notice.user_id,
+        )
+        .execute(tx.as_mut())
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM s3_access_keys
+                WHERE user_id = $1",
+            security_notice.user_id,
+        )
+        .execute(tx.as_mut())
+        .await?;
+
+        Ok(())
+    })
+    .await?;
+
+    // Whoever used the notice might be signed in to the account, in which case their session was
+    // just deleted too.
+    Ok((
+        StatusCode::OK,
+        [SessionCookie::expired().to_header()],
+        Json(PostResponse {}),
+    ))
+}
+
+/// A `POST` response body for this API route.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PostResponse {}
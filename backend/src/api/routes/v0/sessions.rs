@@ -8,7 +8,9 @@ use crate::{
     api::{
         self, Json,
         cookie::{CookieWrapper, SessionCookie},
-        db_helpers::create_session,
+        db_helpers::{create_security_notice, create_session, is_known_device},
+        extract::{ClientIp, UserAgent},
+        rate_limit,
         response::{Response, body::User},
         validation::{
             UserEmail,
@@ -16,14 +18,21 @@ use crate::{
         },
     },
     db::{self, TxError, TxResult},
+    email::{MessageTemplate, NewSignInMessage},
 };
 
+pub(crate) mod oidc;
+pub(crate) mod oidc_authorization;
+pub(crate) mod passkey_challenge;
+pub(crate) mod webauthn_challenge;
+
 /// A `POST` request body for this API route.
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase", deny_unknown_fields)]
 pub(crate) struct PostRequest {
-    /// The email address of the user signing in.
-    pub email: UserEmail,
+    /// The email address of the user signing in. Can be omitted when signing in with a passkey,
+    /// since passkeys identify their user.
+    pub email: Option<UserEmail>,
 
     /// The user's credentials.
     pub credentials: MultiFactorCredentials,
@@ -35,28 +44,55 @@ pub(crate) struct PostRequest {
 ///
 /// See [`crate::api::Error`].
 #[debug_handler]
-pub(crate) async fn post(Json(body): Json<PostRequest>) -> impl Response<PostResponse> {
-    let (token, user_id, user_name) = db::transaction!(async |tx| -> TxResult<_, api::Error> {
-        let Some(user) = sqlx::query!(
-            "SELECT users.id, users.name FROM users
-                WHERE users.email = $1",
-            body.email.as_str(),
-        )
-        .fetch_optional(tx.as_mut())
-        .await?
-        else {
-            // To mitigate user enumeration, send this same error response whether it was the email
-            // or the credentials that were incorrect.
-            return Err(TxError::Abort(api::Error::FirstFactorCredentialsWrong));
-        };
+pub(crate) async fn post(
+    user_agent: UserAgent,
+    client_ip: ClientIp,
+    Json(body): Json<PostRequest>,
+) -> impl Response<PostResponse> {
+    let (token, user_id, user_name, notice) =
+        db::transaction!(async |tx| -> TxResult<_, api::Error> {
+            let passkey_user_id = match body.email {
+                Some(_) => None,
+                None => body.credentials.find_user_id(tx).await?,
+            };
+
+            let Some(user) = sqlx::query!(
+                "SELECT users.id, users.name FROM users
+                WHERE users.email = $1 OR users.id = $2",
+                body.email.as_ref().map(UserEmail::as_str),
+                passkey_user_id,
+            )
+            .fetch_optional(tx.as_mut())
+            .await?
+            else {
+                rate_limit::record_unknown_user_failure(body.email.as_ref(), client_ip).await?;
+
+                // To mitigate user enumeration, send this same error response whether it was the
+                // email or the credentials that were incorrect.
+                return Err(TxError::Abort(api::Error::FirstFactorCredentialsWrong));
+            };
+
+            body.credentials
+                .verify_limited(tx, &user.id, client_ip)
+                .await?;
+
+            let notice = if is_known_device(tx, &user.id, &user_agent).await? {
+                None
+            } else {
+                Some(create_security_notice(tx, &user.id, &user_agent).await?)
+            };
 
-        body.credentials.verify(tx, &user.id).await?;
+            let token = create_session(tx, &user.id, &user_agent).await?;
 
-        let token = create_session(tx, &user.id).await?;
+            Ok((token, user.id, user.name, notice))
+        })
+        .await?;
 
-        Ok((token, user.id, user.name))
-    })
-    .await?;
+    if let Some(notice) = notice
+        && let Some(mailbox) = notice.mailbox()
+    {
+        NewSignInMessage { notice: &notice }.to(mailbox).send();
+    }
 
     // To reduce the session token's attack surface, it isn't included in the response. It's set as
     // an `HttpOnly` cookie instead so browser scripts can't access it.
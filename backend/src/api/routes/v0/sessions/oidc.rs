@@ -0,0 +1,196 @@
+//! Sign-in sessions created by signing in with the external OpenID Connect provider.
+
+use axum::{
+    http::{HeaderMap, StatusCode, header::COOKIE},
+    response::AppendHeaders,
+};
+use axum_macros::debug_handler;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::{
+        self, Json,
+        cookie::{CookieWrapper, OidcAuthorizationCookie, SessionCookie},
+        db_helpers::{create_security_notice, create_session, is_known_device},
+        extract::{ClientIp, UserAgent},
+        response::{Response, body::User},
+        validation::auth::{SecondFactorCredentials, VerifyCredentials},
+    },
+    crypto::hash_without_salt,
+    db::{self, TxError, TxResult},
+    email::{MessageTemplate, NewSignInMessage},
+    id::Token,
+    oidc::PROVIDER,
+};
+
+/// A `POST` request body for this API route.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub(crate) struct PostRequest {
+    /// The `state` query parameter the provider redirected the user back with.
+    pub state: Token,
+
+    /// The `code` query parameter the provider redirected the user back with. Can be omitted when
+    /// retrying with second-factor credentials after the provider already identified the user.
+    #[serde(default)]
+    pub code: Option<String>,
+
+    /// The user's second-factor credentials (if the user has 2FA enabled).
+    pub credentials: SecondFactorCredentials,
+}
+
+/// Signs a user in with the authorization response the external OpenID Connect provider redirected
+/// them back with, creating a sign-in session and returning a session cookie. The provider's
+/// account must already be linked to the user. The cookie returned by
+/// `POST /sessions/oidc-authorization` must be sent, so the authorization response can't be used
+/// from any other browser.
+///
+/// If the user has 2FA enabled, the provider only replaces their first factor. When second-factor
+/// credentials are missing or incorrect, this can be retried with the same `state` (and no `code`)
+/// until the authorization request expires.
+///
+/// # Errors
+///
+/// See [`crate::api::Error`].
+#[debug_handler]
+pub(crate) async fn post(
+    user_agent: UserAgent,
+    client_ip: ClientIp,
+    headers: HeaderMap,
+    Json(body): Json<PostRequest>,
+) -> impl Response<PostResponse> {
+    let Some(provider) = &*PROVIDER else {
+        return Err(api::Error::OidcUnavailable);
+    };
+
+    let cookie_state = headers
+        .get(COOKIE)
+        .and_then(|header_value| str::from_utf8(header_value.as_bytes()).ok())
+        .and_then(OidcAuthorizationCookie::from_header)
+        .and_then(|oidc_cookie| oidc_cookie.as_ref().value().parse::<Token>().ok());
+
+    if cookie_state.as_ref() != Some(&body.state) {
+        return Err(api::Error::OidcFailed);
+    }
+
+    let state_hash = hash_without_salt(&body.state);
+
+    let authorization = db::transaction!(async |tx| -> TxResult<_, api::Error> {
+        let Some(authorization) = sqlx::query!(
+            "SELECT nonce, code_verifier, identified_user_id FROM oidc_authorizations
+                WHERE state_hash = $1
+                    AND linking_user_id IS NULL
+                    AND created_at > now() - interval '10 minutes'",
+            state_hash.as_ref(),
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        else {
+            return Err(TxError::Abort(api::Error::OidcFailed));
+        };
+
+        Ok(authorization)
+    })
+    .await?;
+
+    if authorization.identified_user_id.is_none() {
+        let Some(code) = &body.code else {
+            return Err(api::Error::BodyDataInvalid(
+                "`code` is required until the provider identifies the user".into(),
+            ));
+        };
+
+        let identity = provider
+            .exchange_code(code, &authorization.code_verifier, &authorization.nonce)
+            .await?;
+
+        // The identified user is committed separately from signing in so the code (which the
+        // provider only accepts once) doesn't need to be exchanged again if 2FA fails.
+        db::transaction!(async |tx| -> TxResult<_, api::Error> {
+            let Some(user_id) = sqlx::query_scalar!(
+                "SELECT user_id FROM oidc_identities
+                    WHERE issuer = $1 AND subject = $2",
+                identity.issuer,
+                identity.subject,
+            )
+            .fetch_optional(tx.as_mut())
+            .await?
+            else {
+                return Err(TxError::Abort(api::Error::FirstFactorCredentialsWrong));
+            };
+
+            sqlx::query!(
+                "UPDATE oidc_authorizations
+                    SET identified_user_id = $1
+                    WHERE state_hash = $2",
+                user_id,
+                state_hash.as_ref(),
+            )
+            .execute(tx.as_mut())
+            .await?;
+
+            Ok(())
+        })
+        .await?;
+    }
+
+    let (token, user_id, user_name, notice) =
+        db::transaction!(async |tx| -> TxResult<_, api::Error> {
+            let Some(user) = sqlx::query!(
+                r#"DELETE FROM oidc_authorizations
+                USING users
+                WHERE oidc_authorizations.state_hash = $1
+                    AND users.id = oidc_authorizations.identified_user_id
+                RETURNING users.id, users.name"#,
+                state_hash.as_ref(),
+            )
+            .fetch_optional(tx.as_mut())
+            .await?
+            else {
+                return Err(TxError::Abort(api::Error::OidcFailed));
+            };
+
+            body.credentials
+                .verify_limited(tx, &user.id, client_ip)
+                .await?;
+
+            let notice = if is_known_device(tx, &user.id, &user_agent).await? {
+                None
+            } else {
+                Some(create_security_notice(tx, &user.id, &user_agent).await?)
+            };
+
+            let token = create_session(tx, &user.id, &user_agent).await?;
+
+            Ok((token, user.id, user.name, notice))
+        })
+        .await?;
+
+    if let Some(notice) = notice
+        && let Some(mailbox) = notice.mailbox()
+    {
+        NewSignInMessage { notice: &notice }.to(mailbox).send();
+    }
+
+    Ok((
+        StatusCode::OK,
+        AppendHeaders([
+            SessionCookie::new(token.to_string()).to_header(),
+            OidcAuthorizationCookie::expired().to_header(),
+        ]),
+        Json(PostResponse {
+            user: User {
+                id: user_id.into(),
+                name: user_name,
+            },
+        }),
+    ))
+}
+
+/// A `POST` response body for this API route.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PostResponse {
+    /// The newly authenticated user.
+    user: User,
+}
@@ -0,0 +1,54 @@
+//! An authorization request for a user to sign in with the external OpenID Connect provider.
+
+use axum::http::StatusCode;
+use axum_macros::debug_handler;
+use serde::Serialize;
+
+use crate::{
+    api::{
+        self, Json,
+        cookie::{CookieWrapper, OidcAuthorizationCookie},
+        db_helpers::create_oidc_authorization,
+        response::Response,
+    },
+    db::{self, TxResult},
+    oidc::PROVIDER,
+};
+
+/// Starts signing a user in with the external OpenID Connect provider, returning the provider's URL
+/// to redirect the user to. The provider redirects the user back to the website's `/oidc-callback`
+/// page, which should pass the `state` and `code` query parameters to `POST /sessions/oidc`. Also
+/// returns a cookie that must be sent along with them, which expires after 10 minutes.
+///
+/// # Errors
+///
+/// See [`crate::api::Error`].
+#[debug_handler]
+pub(crate) async fn post() -> impl Response<PostResponse> {
+    let Some(provider) = &*PROVIDER else {
+        return Err(api::Error::OidcUnavailable);
+    };
+
+    let authorization = provider.authorize().await?;
+
+    db::transaction!(async |tx| -> TxResult<_, api::Error> {
+        create_oidc_authorization(tx, &authorization, None).await
+    })
+    .await?;
+
+    Ok((
+        StatusCode::OK,
+        [OidcAuthorizationCookie::new(authorization.state.to_string()).to_header()],
+        Json(PostResponse {
+            authorization_url: authorization.url,
+        }),
+    ))
+}
+
+/// A `POST` response body for this API route.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PostResponse {
+    /// The provider's URL to redirect the user to. Expires after 10 minutes.
+    authorization_url: String,
+}
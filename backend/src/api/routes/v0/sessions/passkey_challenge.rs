@@ -0,0 +1,61 @@
+//! A challenge for a user to sign with one of their passkeys, either to sign in or to verify their
+//! credentials.
+
+use axum::http::StatusCode;
+use axum_macros::debug_handler;
+use serde::Serialize;
+
+use crate::{
+    api::{
+        self, Json,
+        db_helpers::create_webauthn_challenge,
+        response::{Response, body::WebAuthnCredentialDescriptor},
+    },
+    db::{self, TxResult},
+    id::WebAuthnChallenge,
+    webauthn::RELYING_PARTY_ID,
+};
+
+/// Issues a challenge for a user to sign with one of their passkeys, returning options to pass to
+/// `navigator.credentials.get`. The challenge isn't for any particular user, since passkeys
+/// identify their user.
+///
+/// # Errors
+///
+/// See [`crate::api::Error`].
+#[debug_handler]
+pub(crate) async fn post() -> impl Response<PostResponse> {
+    let challenge = db::transaction!(async |tx| -> TxResult<_, api::Error> {
+        create_webauthn_challenge(tx, None).await
+    })
+    .await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(PostResponse {
+            challenge,
+            rp_id: RELYING_PARTY_ID.clone(),
+            allow_credentials: Vec::new(),
+            user_verification: "required",
+        }),
+    ))
+}
+
+/// A `POST` response body for this API route, matching the WebAuthn API's
+/// `PublicKeyCredentialRequestOptionsJSON` so it can be passed to
+/// `PublicKeyCredential.parseRequestOptionsFromJSON`.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PostResponse {
+    /// The challenge for the passkey to sign. Expires after 5 minutes.
+    challenge: WebAuthnChallenge,
+
+    /// The relying party ID the passkey must be scoped to.
+    rp_id: String,
+
+    /// Always empty, so the user can choose any of their passkeys.
+    allow_credentials: Vec<WebAuthnCredentialDescriptor>,
+
+    /// Always `required`, since a passkey must verify the user.
+    user_verification: &'static str,
+}
@@ -0,0 +1,109 @@
+//! A challenge for a user signing in to sign with one of their security keys as a second factor.
+
+use axum::http::StatusCode;
+use axum_macros::debug_handler;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::{
+        self, Json,
+        db_helpers::create_webauthn_challenge,
+        extract::ClientIp,
+        rate_limit,
+        response::{Response, body::WebAuthnCredentialDescriptor},
+        validation::{
+            UserEmail,
+            auth::{FirstFactorCredentials, VerifyCredentials},
+        },
+    },
+    db::{self, TxError, TxResult},
+    id::WebAuthnChallenge,
+    webauthn::RELYING_PARTY_ID,
+};
+
+/// A `POST` request body for this API route.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub(crate) struct PostRequest {
+    /// The email address of the user signing in.
+    pub email: UserEmail,
+
+    /// The user's first-factor credentials. Required so the user's WebAuthn credential IDs aren't
+    /// exposed to anyone who knows their email.
+    pub credentials: FirstFactorCredentials,
+}
+
+/// Issues a challenge for a user signing in to sign with one of their security keys, returning
+/// options to pass to `navigator.credentials.get`.
+///
+/// # Errors
+///
+/// See [`crate::api::Error`].
+#[debug_handler]
+pub(crate) async fn post(
+    client_ip: ClientIp,
+    Json(body): Json<PostRequest>,
+) -> impl Response<PostResponse> {
+    let (challenge, credential_ids) = db::transaction!(async |tx| -> TxResult<_, api::Error> {
+        let Some(user) = sqlx::query!(
+            "SELECT id FROM users
+                WHERE email = $1",
+            body.email.as_str(),
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        else {
+            rate_limit::record_unknown_user_failure(Some(&body.email), client_ip).await?;
+
+            // To mitigate user enumeration, send this same error response whether it was the email
+            // or the credentials that were incorrect.
+            return Err(TxError::Abort(api::Error::FirstFactorCredentialsWrong));
+        };
+
+        body.credentials
+            .verify_limited(tx, &user.id, client_ip)
+            .await?;
+
+        let credential_ids = sqlx::query_scalar!(
+            "SELECT id FROM webauthn_credentials
+                WHERE user_id = $1 AND NOT passkey",
+            user.id,
+        )
+        .fetch_all(tx.as_mut())
+        .await?;
+
+        if credential_ids.is_empty() {
+            return Err(TxError::Abort(api::Error::ResourceNotFound));
+        }
+
+        let challenge = create_webauthn_challenge(tx, Some(&user.id)).await?;
+
+        Ok((challenge, credential_ids))
+    })
+    .await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(PostResponse {
+            challenge,
+            rp_id: RELYING_PARTY_ID.clone(),
+            allow_credentials: credential_ids.into_iter().map(Into::into).collect(),
+        }),
+    ))
+}
+
+/// A `POST` response body for this API route, matching the WebAuthn API's
+/// `PublicKeyCredentialRequestOptionsJSON` so it can be passed to
+/// `PublicKeyCredential.parseRequestOptionsFromJSON`.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PostResponse {
+    /// The challenge for the sign-in to sign. Expires after 5 minutes.
+    challenge: WebAuthnChallenge,
+
+    /// The relying party ID the credential must be scoped to.
+    rp_id: String,
+
+    /// The user's security keys, any of which can sign the challenge.
+    allow_credentials: Vec<WebAuthnCredentialDescriptor>,
+}
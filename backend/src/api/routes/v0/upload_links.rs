@@ -0,0 +1,3 @@
+//! The set of links anyone can use to upload files to a folder.
+
+pub(crate) mod upload_link;
@@ -0,0 +1,153 @@
+//! A link anyone can use to upload files to a folder.
+
+use axum::http::StatusCode;
+use axum_macros::debug_handler;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::PgTransaction;
+
+use crate::{
+    api::{
+        self, Json,
+        extract::Path,
+        response::{Response, body::User},
+    },
+    crypto::hash_without_salt,
+    db::{self, TxError, TxResult},
+    id::Token,
+};
+
+pub(crate) mod files;
+
+/// A request path for this API route.
+type PathParams = Path<Token>;
+
+/// An unexpired upload link.
+#[derive(Debug)]
+pub(crate) struct ActiveUploadLink {
+    /// The upload link's ID.
+    pub(crate) id: Vec<u8>,
+
+    /// The ID of the folder files are uploaded to.
+    pub(crate) folder_id: Vec<u8>,
+
+    /// When the upload link expires, or [`None`] if it doesn't expire.
+    pub(crate) expires_at: Option<DateTime<Utc>>,
+
+    /// The maximum size in bytes of each file uploaded through the link, or [`None`] for no limit.
+    pub(crate) max_file_size: Option<i64>,
+
+    /// The maximum number of files that can be uploaded through the link, or [`None`] for no limit.
+    pub(crate) max_file_count: Option<i32>,
+
+    /// The types of files that can be uploaded through the link, or [`None`] to allow any type.
+    pub(crate) allowed_types: Option<Vec<String>>,
+
+    /// The number of files uploaded through the link so far.
+    pub(crate) file_count: i32,
+}
+
+impl ActiveUploadLink {
+    /// Returns whether as many files have been uploaded through the link as it allows.
+    pub(crate) fn is_full(&self) -> bool {
+        self.max_file_count
+            .is_some_and(|max_file_count| self.file_count >= max_file_count)
+    }
+}
+
+/// `SELECT`s an unexpired upload link by its token.
+///
+/// # Errors
+///
+/// Returns an error if a database query fails, or if no unexpired upload link has the token.
+pub(crate) async fn query_upload_link(
+    tx: &mut PgTransaction<'static>,
+    token: &Token,
+) -> TxResult<ActiveUploadLink, api::Error> {
+    let token_hash = hash_without_salt(token);
+
+    let Some(upload_link) = sqlx::query_as!(
+        ActiveUploadLink,
+        "SELECT
+            id,
+            folder_id,
+            expires_at,
+            max_file_size,
+            max_file_count,
+            allowed_types,
+            file_count
+            FROM upload_links
+            WHERE token_hash = $1 AND (expires_at IS NULL OR expires_at > now())",
+        token_hash.as_ref(),
+    )
+    .fetch_optional(tx.as_mut())
+    .await?
+    else {
+        return Err(TxError::Abort(api::Error::ResourceNotFound));
+    };
+
+    Ok(upload_link)
+}
+
+/// Gets where an upload link uploads files to and what it allows to be uploaded. Doesn't require
+/// authentication.
+///
+/// # Errors
+///
+/// See [`crate::api::Error`].
+#[debug_handler]
+pub(crate) async fn get(Path(token): PathParams) -> impl Response<GetResponse> {
+    let response = db::transaction!(async |tx| -> TxResult<_, api::Error> {
+        let upload_link = query_upload_link(tx, &token).await?;
+
+        let folder = sqlx::query!(
+            "SELECT folders.name, users.id AS owner_id, users.name AS owner_name
+                FROM folders
+                INNER JOIN users ON users.id = folders.owner_id
+                WHERE folders.id = $1",
+            upload_link.folder_id,
+        )
+        .fetch_one(tx.as_mut())
+        .await?;
+
+        Ok(GetResponse {
+            folder_name: folder.name,
+            owner: User {
+                id: folder.owner_id.into(),
+                name: folder.owner_name,
+            },
+            expires_at: upload_link.expires_at,
+            max_file_size: upload_link.max_file_size,
+            remaining_file_count: upload_link
+                .max_file_count
+                .map(|max_file_count| max_file_count - upload_link.file_count),
+            allowed_types: upload_link.allowed_types,
+        })
+    })
+    .await?;
+
+    Ok((StatusCode::OK, Json(response)))
+}
+
+/// A `GET` response body for this API route.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct GetResponse {
+    /// The name of the folder files are uploaded to.
+    folder_name: String,
+
+    /// The folder's owner.
+    owner: User,
+
+    /// When the upload link expires, or [`None`] if it doesn't expire.
+    expires_at: Option<DateTime<Utc>>,
+
+    /// The maximum size in bytes of each file uploaded through the link, or [`None`] for no limit.
+    max_file_size: Option<i64>,
+
+    /// The number of files that can still be uploaded through the link, or [`None`] for no limit.
+    remaining_file_count: Option<i32>,
+
+    /// The types of files that can be uploaded through the link, or [`None`] to allow any type.
+    allowed_types: Option<Vec<String>>,
+}
@@ -0,0 +1,140 @@
+//! The set of files uploaded through an upload link.
+
+use axum_macros::debug_handler;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::{
+        self, Json, captcha,
+        db_helpers::{FolderPaths, OnConflict},
+        extract::Path,
+        response::Response,
+        routes::v0::{files::start_upload, upload_links::upload_link::query_upload_link},
+        validation::{CaptchaToken, FileName, Sha256DigestInput},
+    },
+    db::{self, TxError, TxResult},
+    id::{Id, Token},
+};
+
+pub(crate) mod file;
+
+/// A request path for this API route.
+type PathParams = Path<Token>;
+
+/// A `POST` request body for this API route.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub(crate) struct PostRequest {
+    /// The file's name.
+    name: FileName,
+
+    /// The size of the file's content in bytes.
+    size: u64,
+
+    /// The SHA-256 digest the file's content is expected to have. If specified, finalizing the
+    /// upload fails unless the uploaded content matches it.
+    sha256: Option<Sha256DigestInput>,
+
+    /// A token to verify this request was submitted manually.
+    captcha_token: CaptchaToken,
+}
+
+/// Starts uploading a new file through an upload link. Doesn't require authentication. If the name
+/// is already taken in the link's folder, a number is added to it, so uploaders can't tell what's
+/// in the folder.
+///
+/// # Errors
+///
+/// See [`crate::api::Error`].
+#[debug_handler]
+pub(crate) async fn post(
+    Path(token): PathParams,
+    Json(body): Json<PostRequest>,
+) -> impl Response<PostResponse> {
+    let Ok(size) = i64::try_from(body.size) else {
+        return Err(api::Error::BodyTooLarge);
+    };
+
+    // We don't want bots filling people's folders with junk.
+    if !captcha::verify(&body.captcha_token).await? {
+        return Err(api::Error::CaptchaFailed);
+    }
+
+    let file_id = db::transaction!(async |tx| -> TxResult<_, api::Error> {
+        let upload_link = query_upload_link(tx, &token).await?;
+
+        if upload_link
+            .max_file_size
+            .is_some_and(|max_file_size| size > max_file_size)
+        {
+            return Err(TxError::Abort(api::Error::BodyTooLarge));
+        }
+
+        if upload_link.is_full() {
+            return Err(TxError::Abort(api::Error::UploadLimitReached));
+        }
+
+        let folder = sqlx::query!(
+            "SELECT owner_id, name, parent_id_path, parent_name_path FROM folders
+                WHERE id = $1",
+            upload_link.folder_id,
+        )
+        .fetch_one(tx.as_mut())
+        .await?;
+
+        let mut id_path = folder.parent_id_path;
+        id_path.push(upload_link.folder_id.clone());
+
+        let mut name_path = folder.parent_name_path;
+        name_path.push(folder.name);
+
+        let parent = FolderPaths {
+            owner_id: folder.owner_id,
+            id_path,
+            name_path,
+        };
+
+        let (file_id, content_id, _) = start_upload(
+            tx,
+            &parent,
+            &body.name,
+            size,
+            body.sha256.as_ref().map(|digest| digest.as_slice()),
+            OnConflict::Rename,
+        )
+        .await?;
+
+        sqlx::query!(
+            "INSERT INTO upload_link_uploads (content_id, upload_link_id)
+                VALUES ($1, $2)",
+            content_id.as_slice(),
+            upload_link.id,
+        )
+        .execute(tx.as_mut())
+        .await?;
+
+        Ok(file_id)
+    })
+    .await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(PostResponse {
+            id: file_id,
+            size: body.size,
+        }),
+    ))
+}
+
+/// A `POST` response body for this API route. The new file's name isn't included, since a number
+/// added to it if the requested name was taken would reveal what else is in the link's folder.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PostResponse {
+    /// The new file's ID.
+    id: Id,
+
+    /// The size of the new file's content in bytes.
+    size: u64,
+}
@@ -0,0 +1,57 @@
+//! A file being uploaded through an upload link.
+
+use sqlx::PgTransaction;
+
+use crate::{
+    api::{self, routes::v0::upload_links::upload_link::ActiveUploadLink},
+    db::{TxError, TxResult},
+};
+
+pub(crate) mod finalize;
+pub(crate) mod parts;
+
+/// An incomplete file being uploaded through an upload link.
+#[derive(Debug)]
+pub(crate) struct LinkUpload {
+    /// The file's name.
+    pub(crate) name: String,
+
+    /// The ID of the file's content.
+    pub(crate) content_id: Vec<u8>,
+
+    /// The number of parts of the file's content uploaded so far.
+    pub(crate) part_count: i32,
+}
+
+/// `SELECT`s an incomplete file if its upload was started through a certain upload link.
+///
+/// # Errors
+///
+/// Returns an error if a database query fails, or if the file isn't an incomplete file started
+/// through the upload link.
+pub(crate) async fn query_link_upload(
+    tx: &mut PgTransaction<'static>,
+    upload_link: &ActiveUploadLink,
+    file_id: &[u8],
+) -> TxResult<LinkUpload, api::Error> {
+    let Some(upload) = sqlx::query_as!(
+        LinkUpload,
+        "SELECT files.name, files.content_id, file_contents.part_count
+            FROM files
+            INNER JOIN file_contents ON file_contents.id = files.content_id
+            INNER JOIN upload_link_uploads
+                ON upload_link_uploads.content_id = files.content_id
+            WHERE files.id = $1
+                AND NOT files.complete
+                AND upload_link_uploads.upload_link_id = $2",
+        file_id,
+        upload_link.id,
+    )
+    .fetch_optional(tx.as_mut())
+    .await?
+    else {
+        return Err(TxError::Abort(api::Error::AccessDenied));
+    };
+
+    Ok(upload)
+}
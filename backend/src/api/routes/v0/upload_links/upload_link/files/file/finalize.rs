@@ -0,0 +1,150 @@
+//! See [`post`].
+
+use axum::http::StatusCode;
+use axum_macros::debug_handler;
+use lettre::{Address, message::Mailbox};
+use serde::Serialize;
+
+use crate::{
+    api::{
+        self, Json,
+        db_helpers::mark_content_maybe_unused,
+        extract::Path,
+        response::{Response, body::Sha256Digest},
+        routes::v0::{
+            files::file::finalize::finalize_upload,
+            upload_links::upload_link::{files::file::query_link_upload, query_upload_link},
+        },
+        validation::MediaTypePattern,
+    },
+    db::{self, TxError, TxResult},
+    email::{MessageTemplate, UploadLinkFileMessage},
+    file_type::sniff_content,
+    id::{Id, Token},
+    processing,
+};
+
+/// A request path for this API route: the upload link's token, then the file's ID.
+type PathParams = Path<(Token, Id)>;
+
+/// Finalizes the upload of a file being uploaded through an upload link after all of its content is
+/// uploaded, adding the file to the link's folder and notifying the folder's owner by email.
+/// Doesn't require authentication.
+///
+/// If the file's type, as detected from its content, isn't allowed by the link, the upload is
+/// discarded instead.
+///
+/// # Errors
+///
+/// See [`crate::api::Error`].
+#[debug_handler]
+pub(crate) async fn post(Path((token, file_id)): PathParams) -> impl Response<PostResponse> {
+    let finalized = db::transaction!(async |tx| -> TxResult<_, api::Error> {
+        let upload_link = query_upload_link(tx, &token).await?;
+        let upload = query_link_upload(tx, &upload_link, &file_id).await?;
+
+        if upload_link.is_full() {
+            return Err(TxError::Abort(api::Error::UploadLimitReached));
+        }
+
+        if let Some(allowed_types) = &upload_link.allowed_types {
+            let file_type =
+                sniff_content(&upload.name, &upload.content_id, upload.part_count).await?;
+
+            let is_type_allowed = allowed_types.iter().any(|allowed_type| {
+                allowed_type
+                    .parse::<MediaTypePattern>()
+                    .is_ok_and(|pattern| pattern.matches(&file_type.mime_type))
+            });
+
+            if !is_type_allowed {
+                // Discard the upload so its content doesn't linger where nobody can see it.
+                sqlx::query!(
+                    "DELETE FROM files
+                        WHERE id = $1 AND NOT complete",
+                    file_id.as_slice(),
+                )
+                .execute(tx.as_mut())
+                .await?;
+
+                mark_content_maybe_unused(tx, &upload.content_id).await?;
+
+                return Ok(None);
+            }
+        }
+
+        let (response, source) = finalize_upload(tx, &file_id).await?;
+
+        sqlx::query!(
+            "DELETE FROM upload_link_uploads
+                WHERE content_id = $1",
+            upload.content_id,
+        )
+        .execute(tx.as_mut())
+        .await?;
+
+        sqlx::query!(
+            "UPDATE upload_links
+                SET file_count = file_count + 1
+                WHERE id = $1",
+            upload_link.id,
+        )
+        .execute(tx.as_mut())
+        .await?;
+
+        let owner = sqlx::query!(
+            "SELECT users.name, users.email, folders.name AS folder_name
+                FROM folders
+                INNER JOIN users ON users.id = folders.owner_id
+                WHERE folders.id = $1",
+            upload_link.folder_id,
+        )
+        .fetch_one(tx.as_mut())
+        .await?;
+
+        Ok(Some((response, source, owner)))
+    })
+    .await?;
+
+    let Some((response, source, owner)) = finalized else {
+        return Err(api::Error::FileTypeNotAllowed);
+    };
+
+    if processing::is_decodable_image(&response.r#type) {
+        processing::generate_thumbnails(source);
+    }
+
+    if let Ok(email) = owner.email.parse::<Address>() {
+        UploadLinkFileMessage {
+            user_name: &owner.name,
+            folder_name: &owner.folder_name,
+            file_name: &response.name,
+        }
+        .to(Mailbox::new(Some(owner.name.clone()), email))
+        .send();
+    }
+
+    Ok((
+        StatusCode::OK,
+        Json(PostResponse {
+            size: response.size,
+            r#type: response.r#type,
+            sha256: response.sha256,
+        }),
+    ))
+}
+
+/// A `POST` response body for this API route. Unlike when finalizing a file of one's own, the
+/// file's name isn't included, since it could reveal what else is in the link's folder.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PostResponse {
+    /// The size of the file's content in bytes.
+    size: i64,
+
+    /// The file's MIME type, as detected from its content.
+    r#type: String,
+
+    /// The SHA-256 digest of the file's content.
+    sha256: Sha256Digest,
+}
@@ -0,0 +1,52 @@
+//! The uploaded parts of the content of a file being uploaded through an upload link.
+
+use axum::http::StatusCode;
+use axum_macros::debug_handler;
+use serde::Serialize;
+
+use crate::{
+    api::{
+        self, Json,
+        extract::{Bytes, Path},
+        response::Response,
+        routes::v0::{
+            files::file::parts::append_part,
+            upload_links::upload_link::{files::file::query_link_upload, query_upload_link},
+        },
+    },
+    db::{self, TxResult},
+    id::{Id, Token},
+};
+
+/// A request path for this API route: the upload link's token, then the file's ID.
+type PathParams = Path<(Token, Id)>;
+
+/// Uploads the next part of the content of a file being uploaded through an upload link. The
+/// request body is the part's raw bytes. Doesn't require authentication.
+///
+/// # Errors
+///
+/// See [`crate::api::Error`].
+#[debug_handler]
+pub(crate) async fn post(
+    Path((token, file_id)): PathParams,
+    Bytes(part): Bytes,
+) -> impl Response<PostResponse> {
+    let uploaded_size = db::transaction!(async |tx| -> TxResult<_, api::Error> {
+        let upload_link = query_upload_link(tx, &token).await?;
+        query_link_upload(tx, &upload_link, &file_id).await?;
+
+        append_part(tx, &file_id, &part).await
+    })
+    .await?;
+
+    Ok((StatusCode::OK, Json(PostResponse { uploaded_size })))
+}
+
+/// A `POST` response body for this API route.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PostResponse {
+    /// The number of bytes of the file's content uploaded so far.
+    uploaded_size: i64,
+}
@@ -9,6 +9,7 @@ use crate::{
         self, Json,
         cookie::{CookieWrapper, SessionCookie},
         db_helpers::create_session,
+        extract::UserAgent,
         response::{Response, body::User},
         validation::{EmailVerificationCode, NewUserPassword, UserEmail, UserName},
     },
@@ -64,7 +65,10 @@ pub(crate) struct PostRequest {
 ///
 /// See [`crate::api::Error`].
 #[debug_handler]
-pub(crate) async fn post(Json(body): Json<PostRequest>) -> impl Response<PostResponse> {
+pub(crate) async fn post(
+    user_agent: UserAgent,
+    Json(body): Json<PostRequest>,
+) -> impl Response<PostResponse> {
     let password_hash = hash_with_salt(&body.password);
 
     let (user_id, session_token) = db::transaction!(async |tx| -> TxResult<_, api::Error> {
@@ -139,7 +143,7 @@ pub(crate) async fn post(Json(body): Json<PostRequest>) -> impl Response<PostRes
             result => result?,
         };
 
-        let session_token = create_session(tx, user_id.as_slice()).await?;
+        let session_token = create_session(tx, user_id.as_slice(), &user_agent).await?;
 
         Ok((user_id, session_token))
     })
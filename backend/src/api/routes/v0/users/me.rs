@@ -12,13 +12,24 @@ use crate::{
     db::{self, TxResult},
 };
 
+pub(crate) mod api_tokens;
+pub(crate) mod corrupt_files;
 pub(crate) mod email_change_request;
+pub(crate) mod folder_grants;
 pub(crate) mod name;
+pub(crate) mod oauth_clients;
+pub(crate) mod oauth_grants;
+pub(crate) mod oidc_authorization;
+pub(crate) mod oidc_identity;
 pub(crate) mod password;
+pub(crate) mod s3_access_keys;
 pub(crate) mod sessions;
 pub(crate) mod settings;
 pub(crate) mod totp;
 pub(crate) mod verify_credentials;
+pub(crate) mod webauthn_challenge;
+pub(crate) mod webauthn_credentials;
+pub(crate) mod webdav_token;
 
 /// Gets the current authenticated user's public profile info.
 ///
@@ -0,0 +1,183 @@
+//! The set of the current authenticated user's personal API tokens.
+
+use std::collections::BTreeSet;
+
+use axum::http::StatusCode;
+use axum_macros::debug_handler;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::{
+        self, Json,
+        db_helpers::ApiTokenScope,
+        extract::{AuthToken, ClientIp},
+        response::{Response, body::ApiToken},
+        validation::{
+            ApiTokenName,
+            auth::{MultiFactorCredentials, VerifyCredentials},
+        },
+    },
+    crypto::hash_without_salt,
+    db::{self, TxError, TxResult},
+    id::Token,
+};
+
+pub(crate) mod api_token;
+
+/// Lists all of the current authenticated user's unexpired API tokens.
+///
+/// # Errors
+///
+/// See [`crate::api::Error`].
+#[debug_handler]
+pub(crate) async fn get(AuthToken(token_hash): AuthToken) -> impl Response<GetResponse> {
+    let api_tokens = db::transaction!(async |tx| -> TxResult<_, api::Error> {
+        let Some(session) = sqlx::query!(
+            "SELECT user_id FROM sessions
+                WHERE token_hash = $1",
+            token_hash.as_ref(),
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        else {
+            return Err(TxError::Abort(api::Error::AuthFailed));
+        };
+
+        Ok(sqlx::query_as!(
+            ApiToken,
+            r#"SELECT
+                token_hash AS id,
+                name AS "name!",
+                scopes AS "scopes!: Vec<ApiTokenScope>",
+                created_at,
+                accessed_at,
+                expires_at
+                FROM sessions
+                WHERE user_id = $1
+                    AND scopes IS NOT NULL
+                    AND oauth_grant_id IS NULL
+                    AND (expires_at IS NULL OR expires_at > now())
+                ORDER BY created_at"#,
+            session.user_id,
+        )
+        .fetch_all(tx.as_mut())
+        .await?)
+    })
+    .await?;
+
+    Ok((StatusCode::OK, Json(GetResponse { values: api_tokens })))
+}
+
+/// A `GET` response body for this API route.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct GetResponse {
+    /// All of the user's unexpired API tokens.
+    values: Vec<ApiToken>,
+}
+
+/// A `POST` request body for this API route.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub(crate) struct PostRequest {
+    /// The user's credentials.
+    pub credentials: MultiFactorCredentials,
+
+    /// The user's name for the new API token.
+    pub name: ApiTokenName,
+
+    /// The scopes to grant the new API token access to.
+    pub scopes: BTreeSet<ApiTokenScope>,
+
+    /// When the new API token expires, or [`None`] if it doesn't expire.
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Creates a new personal API token for the current authenticated user, for scripts to send as a
+/// bearer token in the `Authorization` header. The token is only ever returned in this response.
+///
+/// # Errors
+///
+/// See [`crate::api::Error`].
+#[debug_handler]
+pub(crate) async fn post(
+    AuthToken(token_hash): AuthToken,
+    client_ip: ClientIp,
+    Json(body): Json<PostRequest>,
+) -> impl Response<PostResponse> {
+    if body.scopes.is_empty() {
+        return Err(api::Error::BodyDataInvalid(
+            "`scopes` must be non-empty".into(),
+        ));
+    }
+
+    if body
+        .expires_at
+        .is_some_and(|expires_at| expires_at <= Utc::now())
+    {
+        return Err(api::Error::BodyDataInvalid(
+            "`expiresAt` must be in the future".into(),
+        ));
+    }
+
+    let scopes: Vec<_> = body.scopes.iter().copied().collect();
+
+    let (token, api_token) = db::transaction!(async |tx| -> TxResult<_, api::Error> {
+        let Some(session) = sqlx::query!(
+            "SELECT user_id FROM sessions
+                WHERE token_hash = $1",
+            token_hash.as_ref(),
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        else {
+            return Err(TxError::Abort(api::Error::AuthFailed));
+        };
+
+        body.credentials
+            .verify_limited(tx, &session.user_id, client_ip)
+            .await?;
+
+        let token = Token::generate();
+        let new_token_hash = hash_without_salt(&token);
+
+        let api_token = sqlx::query_as!(
+            ApiToken,
+            r#"INSERT INTO sessions (token_hash, user_id, name, scopes, expires_at)
+                VALUES ($1, $2, $3, $4, $5)
+                RETURNING
+                    token_hash AS id,
+                    name AS "name!",
+                    scopes AS "scopes!: Vec<ApiTokenScope>",
+                    created_at,
+                    accessed_at,
+                    expires_at"#,
+            new_token_hash.as_ref(),
+            session.user_id,
+            body.name.as_str(),
+            scopes.as_slice() as &[ApiTokenScope],
+            body.expires_at,
+        )
+        .fetch_one(tx.as_mut())
+        .await?;
+
+        Ok((token, api_token))
+    })
+    .await?;
+
+    Ok((StatusCode::OK, Json(PostResponse { token, api_token })))
+}
+
+/// A `POST` response body for this API route.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PostResponse {
+    /// The new API token, to send as a bearer token in the `Authorization` header.
+    token: Token,
+
+    /// The new API token's info.
+    #[serde(flatten)]
+    api_token: ApiToken,
+}
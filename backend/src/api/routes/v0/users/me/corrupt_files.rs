@@ -0,0 +1,75 @@
+//! The set of the current authenticated user's files whose contents are corrupt in storage.
+
+use axum::http::StatusCode;
+use axum_macros::debug_handler;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::{
+    api::{self, Json, extract::AuthToken, response::Response},
+    db::{self, TxError, TxResult},
+    id::Id,
+};
+
+/// Lists all of the current authenticated user's files that the scrub job found to be corrupt, so
+/// the user can restore them from their own copies.
+///
+/// # Errors
+///
+/// See [`crate::api::Error`].
+#[debug_handler]
+pub(crate) async fn get(AuthToken(token_hash): AuthToken) -> impl Response<GetResponse> {
+    let files = db::transaction!(async |tx| -> TxResult<_, api::Error> {
+        let Some(user_id) = sqlx::query!(
+            "SELECT user_id FROM sessions
+                WHERE token_hash = $1",
+            token_hash.as_ref(),
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        .map(|session| session.user_id) else {
+            return Err(TxError::Abort(api::Error::AuthFailed));
+        };
+
+        Ok(sqlx::query_as!(
+            CorruptFile,
+            r#"SELECT
+                files.id,
+                array_to_string(files.parent_name_path || files.name, '/') AS "path!",
+                corrupt_file_contents.detected_at
+                FROM files
+                INNER JOIN corrupt_file_contents
+                    ON corrupt_file_contents.content_id = files.content_id
+                WHERE files.owner_id = $1 AND files.complete
+                ORDER BY corrupt_file_contents.detected_at"#,
+            user_id,
+        )
+        .fetch_all(tx.as_mut())
+        .await?)
+    })
+    .await?;
+
+    Ok((StatusCode::OK, Json(GetResponse { values: files })))
+}
+
+/// A file whose content is corrupt.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CorruptFile {
+    /// The file's ID.
+    id: Id,
+
+    /// The file's path relative to its owner's root folder.
+    path: String,
+
+    /// When the corruption was detected.
+    detected_at: DateTime<Utc>,
+}
+
+/// A `GET` response body for this API route.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct GetResponse {
+    /// All of the user's corrupt files, oldest detection first.
+    values: Vec<CorruptFile>,
+}
@@ -9,7 +9,7 @@ use crate::{
     WEBSITE_ORIGIN,
     api::{
         self, Json,
-        extract::AuthToken,
+        extract::{AuthToken, ClientIp},
         response::Response,
         validation::{
             UserEmail,
@@ -48,6 +48,7 @@ pub(crate) struct PostRequest {
 #[debug_handler]
 pub(crate) async fn post(
     AuthToken(token_hash): AuthToken,
+    client_ip: ClientIp,
     Json(body): Json<PostRequest>,
 ) -> impl Response<PostResponse> {
     enum SendMessage<F: FnOnce(), G: FnOnce()> {
@@ -70,7 +71,9 @@ pub(crate) async fn post(
             return Err(TxError::Abort(api::Error::AuthFailed));
         };
 
-        body.credentials.verify(tx, &user.id).await?;
+        body.credentials
+            .verify_limited(tx, &user.id, client_ip)
+            .await?;
 
         let taken_user = sqlx::query!(
             "SELECT name FROM users
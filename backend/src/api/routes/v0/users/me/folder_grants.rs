@@ -0,0 +1,104 @@
+//! The set of other users' folders the current authenticated user has been granted access to.
+
+use axum::http::StatusCode;
+use axum_macros::debug_handler;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::{
+    api::{
+        self, Json,
+        db_helpers::FolderRole,
+        extract::AuthToken,
+        response::{Response, body::User},
+    },
+    db::{self, TxError, TxResult},
+    id::Id,
+};
+
+/// Lists the folders the current authenticated user has been granted access to.
+///
+/// # Errors
+///
+/// See [`crate::api::Error`].
+#[debug_handler]
+pub(crate) async fn get(AuthToken(token_hash): AuthToken) -> impl Response<GetResponse> {
+    let grants = db::transaction!(async |tx| -> TxResult<_, api::Error> {
+        let Some(session) = sqlx::query!(
+            "SELECT user_id FROM sessions
+                WHERE token_hash = $1",
+            token_hash.as_ref(),
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        else {
+            return Err(TxError::Abort(api::Error::AuthFailed));
+        };
+
+        Ok(sqlx::query!(
+            r#"SELECT
+                folders.id,
+                folders.name,
+                users.id AS owner_id,
+                users.name AS owner_name,
+                folder_grants.role AS "role: FolderRole",
+                folder_grants.created_at
+                FROM folder_grants
+                INNER JOIN folders ON folders.id = folder_grants.folder_id
+                INNER JOIN users ON users.id = folders.owner_id
+                WHERE folder_grants.user_id = $1
+                ORDER BY folder_grants.created_at"#,
+            session.user_id,
+        )
+        .fetch_all(tx.as_mut())
+        .await?)
+    })
+    .await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(GetResponse {
+            values: grants
+                .into_iter()
+                .map(|grant| GrantedFolder {
+                    id: grant.id.into(),
+                    name: grant.name,
+                    owner: User {
+                        id: grant.owner_id.into(),
+                        name: grant.owner_name,
+                    },
+                    role: grant.role,
+                    granted_at: grant.created_at,
+                })
+                .collect(),
+        }),
+    ))
+}
+
+/// A `GET` response body for this API route.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct GetResponse {
+    /// All of the folders the user has been granted access to.
+    values: Vec<GrantedFolder>,
+}
+
+/// A folder the user has been granted access to.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct GrantedFolder {
+    /// The folder's ID.
+    id: Id,
+
+    /// The folder's name.
+    name: String,
+
+    /// The folder's owner.
+    owner: User,
+
+    /// The user's role on the folder.
+    role: FolderRole,
+
+    /// The timestamp the user was first granted access.
+    granted_at: DateTime<Utc>,
+}
@@ -0,0 +1,167 @@
+//! The set of OAuth clients the current authenticated user has registered.
+
+use axum::http::StatusCode;
+use axum_macros::debug_handler;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::{
+        self, Json,
+        extract::AuthToken,
+        response::{Response, body::OAuthClient},
+        validation::{OAuthClientName, OAuthRedirectUri},
+    },
+    crypto::hash_without_salt,
+    db::{self, TxError, TxResult},
+    id::{NewOAuthClientId, Token},
+};
+
+pub(crate) mod oauth_client;
+
+/// The maximum number of redirect URIs a client can have.
+const MAX_REDIRECT_URIS: usize = 10;
+
+/// Lists all of the OAuth clients the current authenticated user has registered.
+///
+/// # Errors
+///
+/// See [`crate::api::Error`].
+#[debug_handler]
+pub(crate) async fn get(AuthToken(token_hash): AuthToken) -> impl Response<GetResponse> {
+    let clients = db::transaction!(async |tx| -> TxResult<_, api::Error> {
+        let Some(session) = sqlx::query!(
+            "SELECT user_id FROM sessions
+                WHERE token_hash = $1",
+            token_hash.as_ref(),
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        else {
+            return Err(TxError::Abort(api::Error::AuthFailed));
+        };
+
+        Ok(sqlx::query_as!(
+            OAuthClient,
+            r#"SELECT
+                id,
+                name,
+                redirect_uris,
+                secret_hash IS NOT NULL AS "confidential!",
+                created_at
+                FROM oauth_clients
+                WHERE user_id = $1
+                ORDER BY created_at"#,
+            session.user_id,
+        )
+        .fetch_all(tx.as_mut())
+        .await?)
+    })
+    .await?;
+
+    Ok((StatusCode::OK, Json(GetResponse { values: clients })))
+}
+
+/// A `GET` response body for this API route.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct GetResponse {
+    /// All of the OAuth clients the user has registered.
+    values: Vec<OAuthClient>,
+}
+
+/// A `POST` request body for this API route.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub(crate) struct PostRequest {
+    /// The client's name, shown to users when they're asked to authorize it.
+    pub name: OAuthClientName,
+
+    /// The URIs the client can be redirected to with an authorization code.
+    pub redirect_uris: Vec<OAuthRedirectUri>,
+
+    /// Whether the client should have a secret. Apps that can't keep a secret, such as native or
+    /// browser apps, should be public clients relying on PKCE alone.
+    #[serde(default)]
+    pub confidential: bool,
+}
+
+/// Registers a new OAuth client owned by the current authenticated user. If the client is
+/// confidential, its secret is only ever returned in this response.
+///
+/// # Errors
+///
+/// See [`crate::api::Error`].
+#[debug_handler]
+pub(crate) async fn post(
+    AuthToken(token_hash): AuthToken,
+    Json(body): Json<PostRequest>,
+) -> impl Response<PostResponse> {
+    if !(1..=MAX_REDIRECT_URIS).contains(&body.redirect_uris.len()) {
+        return Err(api::Error::BodyDataInvalid(format!(
+            "`redirectUris` must have between 1 and {MAX_REDIRECT_URIS} items"
+        )));
+    }
+
+    let redirect_uris: Vec<String> = body.redirect_uris.iter().map(ToString::to_string).collect();
+
+    let (secret, client) = db::transaction!(async |tx| -> TxResult<_, api::Error> {
+        let Some(session) = sqlx::query!(
+            "SELECT user_id FROM sessions
+                WHERE token_hash = $1",
+            token_hash.as_ref(),
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        else {
+            return Err(TxError::Abort(api::Error::AuthFailed));
+        };
+
+        let id = NewOAuthClientId::generate();
+        let secret = body.confidential.then(Token::generate);
+        let secret_hash = secret.as_ref().map(hash_without_salt);
+
+        let client = match sqlx::query_as!(
+            OAuthClient,
+            r#"INSERT INTO oauth_clients (id, user_id, name, redirect_uris, secret_hash)
+                VALUES ($1, $2, $3, $4, $5)
+                RETURNING
+                    id,
+                    name,
+                    redirect_uris,
+                    secret_hash IS NOT NULL AS "confidential!",
+                    created_at"#,
+            id.as_slice(),
+            session.user_id,
+            body.name.as_str(),
+            &redirect_uris,
+            secret_hash.as_ref().map(AsRef::as_ref),
+        )
+        .fetch_one(tx.as_mut())
+        .await
+        {
+            Err(sqlx::Error::Database(error))
+                if error.constraint() == Some("oauth_clients_pkey") =>
+            {
+                return Err(TxError::Retry);
+            }
+            result => result?,
+        };
+
+        Ok((secret, client))
+    })
+    .await?;
+
+    Ok((StatusCode::OK, Json(PostResponse { secret, client })))
+}
+
+/// A `POST` response body for this API route.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PostResponse {
+    /// The new client's secret, or [`None`] if it's a public client.
+    secret: Option<Token>,
+
+    /// The new client's info.
+    #[serde(flatten)]
+    client: OAuthClient,
+}
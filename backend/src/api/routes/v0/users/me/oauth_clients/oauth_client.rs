@@ -0,0 +1,66 @@
+//! One of the OAuth clients the current authenticated user has registered.
+
+use axum::http::StatusCode;
+use axum_macros::debug_handler;
+use serde::Serialize;
+
+use crate::{
+    api::{
+        self, Json,
+        extract::{AuthToken, Path},
+        response::Response,
+    },
+    db::{self, TxError, TxResult},
+    id::Id,
+};
+
+/// A request path for this API route.
+type PathParams = Path<Id>;
+
+/// Deletes one of the OAuth clients the current authenticated user has registered, revoking every
+/// grant and token issued to it.
+///
+/// # Errors
+///
+/// See [`crate::api::Error`].
+#[debug_handler]
+pub(crate) async fn delete(
+    Path(client_id): PathParams,
+    AuthToken(token_hash): AuthToken,
+) -> impl Response<DeleteResponse> {
+    let is_client_deleted = db::transaction!(async |tx| -> TxResult<_, api::Error> {
+        let Some(session) = sqlx::query!(
+            "SELECT user_id FROM sessions
+                WHERE token_hash = $1",
+            token_hash.as_ref(),
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        else {
+            return Err(TxError::Abort(api::Error::AuthFailed));
+        };
+
+        Ok(sqlx::query!(
+            "DELETE FROM oauth_clients
+                WHERE id = $1 AND user_id = $2",
+            client_id.as_slice(),
+            session.user_id,
+        )
+        .execute(tx.as_mut())
+        .await?
+        .rows_affected()
+            != 0)
+    })
+    .await?;
+
+    if !is_client_deleted {
+        return Err(api::Error::ResourceNotFound);
+    }
+
+    Ok((StatusCode::OK, Json(DeleteResponse {})))
+}
+
+/// A `DELETE` response body for this API route.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DeleteResponse {}
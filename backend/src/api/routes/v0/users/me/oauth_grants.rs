@@ -0,0 +1,84 @@
+//! The set of OAuth clients the current authenticated user has authorized to act on their behalf.
+
+use axum::http::StatusCode;
+use axum_macros::debug_handler;
+use serde::Serialize;
+
+use crate::{
+    api::{
+        self, Json,
+        db_helpers::ApiTokenScope,
+        extract::AuthToken,
+        response::{
+            Response,
+            body::{OAuthGrant, OAuthGrantClient},
+        },
+    },
+    db::{self, TxError, TxResult},
+};
+
+pub(crate) mod oauth_grant;
+
+/// Lists all of the OAuth clients the current authenticated user has authorized.
+///
+/// # Errors
+///
+/// See [`crate::api::Error`].
+#[debug_handler]
+pub(crate) async fn get(AuthToken(token_hash): AuthToken) -> impl Response<GetResponse> {
+    let grants = db::transaction!(async |tx| -> TxResult<_, api::Error> {
+        let Some(session) = sqlx::query!(
+            "SELECT user_id FROM sessions
+                WHERE token_hash = $1",
+            token_hash.as_ref(),
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        else {
+            return Err(TxError::Abort(api::Error::AuthFailed));
+        };
+
+        Ok(sqlx::query!(
+            r#"SELECT
+                oauth_grants.id,
+                oauth_grants.client_id,
+                oauth_clients.name AS client_name,
+                oauth_grants.scopes AS "scopes: Vec<ApiTokenScope>",
+                oauth_grants.created_at
+                FROM oauth_grants
+                INNER JOIN oauth_clients ON oauth_clients.id = oauth_grants.client_id
+                WHERE oauth_grants.user_id = $1
+                ORDER BY oauth_grants.created_at"#,
+            session.user_id,
+        )
+        .fetch_all(tx.as_mut())
+        .await?)
+    })
+    .await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(GetResponse {
+            values: grants
+                .into_iter()
+                .map(|grant| OAuthGrant {
+                    id: grant.id.into(),
+                    client: OAuthGrantClient {
+                        id: grant.client_id.into(),
+                        name: grant.client_name,
+                    },
+                    scopes: grant.scopes,
+                    created_at: grant.created_at,
+                })
+                .collect(),
+        }),
+    ))
+}
+
+/// A `GET` response body for this API route.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct GetResponse {
+    /// All of the user's OAuth grants.
+    values: Vec<OAuthGrant>,
+}
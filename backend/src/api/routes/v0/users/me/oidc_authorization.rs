@@ -0,0 +1,82 @@
+//! An authorization request for the current authenticated user to link an account at the external
+//! OpenID Connect provider.
+
+use axum::http::StatusCode;
+use axum_macros::debug_handler;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::{
+        self, Json,
+        db_helpers::create_oidc_authorization,
+        extract::{AuthToken, ClientIp},
+        response::Response,
+        validation::auth::{MultiFactorCredentials, VerifyCredentials},
+    },
+    db::{self, TxError, TxResult},
+    oidc::PROVIDER,
+};
+
+/// A `POST` request body for this API route.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub(crate) struct PostRequest {
+    /// The user's credentials.
+    pub credentials: MultiFactorCredentials,
+}
+
+/// Starts linking an account at the external OpenID Connect provider to the current authenticated
+/// user, returning the provider's URL to redirect the user to. The provider redirects the user back
+/// to the website's `/oidc-callback` page, which should pass the `state` and `code` query
+/// parameters to `POST /users/me/oidc-identity`.
+///
+/// # Errors
+///
+/// See [`crate::api::Error`].
+#[debug_handler]
+pub(crate) async fn post(
+    AuthToken(token_hash): AuthToken,
+    client_ip: ClientIp,
+    Json(body): Json<PostRequest>,
+) -> impl Response<PostResponse> {
+    let Some(provider) = &*PROVIDER else {
+        return Err(api::Error::OidcUnavailable);
+    };
+
+    let authorization = provider.authorize().await?;
+
+    db::transaction!(async |tx| -> TxResult<_, api::Error> {
+        let Some(session) = sqlx::query!(
+            "SELECT user_id FROM sessions
+                WHERE token_hash = $1",
+            token_hash.as_ref(),
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        else {
+            return Err(TxError::Abort(api::Error::AuthFailed));
+        };
+
+        body.credentials
+            .verify_limited(tx, &session.user_id, client_ip)
+            .await?;
+
+        create_oidc_authorization(tx, &authorization, Some(&session.user_id)).await
+    })
+    .await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(PostResponse {
+            authorization_url: authorization.url,
+        }),
+    ))
+}
+
+/// A `POST` response body for this API route.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PostResponse {
+    /// The provider's URL to redirect the user to. Expires after 10 minutes.
+    authorization_url: String,
+}
@@ -0,0 +1,200 @@
+//! The current authenticated user's linked account at the external OpenID Connect provider, which
+//! they can sign in with instead of a password.
+
+use axum::http::StatusCode;
+use axum_macros::debug_handler;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::{
+        self, Json,
+        extract::AuthToken,
+        response::{Response, body::OidcIdentity},
+    },
+    crypto::hash_without_salt,
+    db::{self, TxError, TxResult},
+    id::Token,
+    oidc::PROVIDER,
+};
+
+/// Gets the current authenticated user's linked account at the external OpenID Connect provider.
+///
+/// # Errors
+///
+/// See [`crate::api::Error`].
+#[debug_handler]
+pub(crate) async fn get(AuthToken(token_hash): AuthToken) -> impl Response<OidcIdentity> {
+    let identity = db::transaction!(async |tx| -> TxResult<_, api::Error> {
+        let Some(session) = sqlx::query!(
+            "SELECT user_id FROM sessions
+                WHERE token_hash = $1",
+            token_hash.as_ref(),
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        else {
+            return Err(TxError::Abort(api::Error::AuthFailed));
+        };
+
+        let Some(identity) = sqlx::query_as!(
+            OidcIdentity,
+            "SELECT issuer, subject, created_at FROM oidc_identities
+                WHERE user_id = $1",
+            session.user_id,
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        else {
+            return Err(TxError::Abort(api::Error::ResourceNotFound));
+        };
+
+        Ok(identity)
+    })
+    .await?;
+
+    Ok((StatusCode::OK, Json(identity)))
+}
+
+/// A `POST` request body for this API route.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub(crate) struct PostRequest {
+    /// The `state` query parameter the provider redirected the user back with.
+    pub state: Token,
+
+    /// The `code` query parameter the provider redirected the user back with.
+    pub code: String,
+}
+
+/// Links an account at the external OpenID Connect provider to the current authenticated user,
+/// using the authorization response the provider redirected them back with. Fails if the user
+/// already has a linked account or the provider's account is linked to another user.
+///
+/// # Errors
+///
+/// See [`crate::api::Error`].
+#[debug_handler]
+pub(crate) async fn post(
+    AuthToken(token_hash): AuthToken,
+    Json(body): Json<PostRequest>,
+) -> impl Response<OidcIdentity> {
+    let Some(provider) = &*PROVIDER else {
+        return Err(api::Error::OidcUnavailable);
+    };
+
+    let state_hash = hash_without_salt(&body.state);
+
+    // The authorization request is used up even if linking fails, since the provider only accepts
+    // its code once anyway.
+    let (user_id, authorization) = db::transaction!(async |tx| -> TxResult<_, api::Error> {
+        let Some(session) = sqlx::query!(
+            "SELECT user_id FROM sessions
+                WHERE token_hash = $1",
+            token_hash.as_ref(),
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        else {
+            return Err(TxError::Abort(api::Error::AuthFailed));
+        };
+
+        let Some(authorization) = sqlx::query!(
+            "DELETE FROM oidc_authorizations
+                WHERE state_hash = $1
+                    AND linking_user_id = $2
+                    AND created_at > now() - interval '10 minutes'
+                RETURNING nonce, code_verifier",
+            state_hash.as_ref(),
+            session.user_id,
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        else {
+            return Err(TxError::Abort(api::Error::OidcFailed));
+        };
+
+        Ok((session.user_id, authorization))
+    })
+    .await?;
+
+    let identity = provider
+        .exchange_code(
+            &body.code,
+            &authorization.code_verifier,
+            &authorization.nonce,
+        )
+        .await?;
+
+    let identity = db::transaction!(async |tx| -> TxResult<_, api::Error> {
+        let identity = match sqlx::query_as!(
+            OidcIdentity,
+            "INSERT INTO oidc_identities (user_id, issuer, subject)
+                VALUES ($1, $2, $3)
+                RETURNING issuer, subject, created_at",
+            user_id,
+            identity.issuer,
+            identity.subject,
+        )
+        .fetch_one(tx.as_mut())
+        .await
+        {
+            Err(sqlx::Error::Database(error))
+                if matches!(
+                    error.constraint(),
+                    Some("oidc_identities_pkey" | "oidc_identities_issuer_subject_key"),
+                ) =>
+            {
+                return Err(TxError::Abort(api::Error::AlreadyExists));
+            }
+            result => result?,
+        };
+
+        Ok(identity)
+    })
+    .await?;
+
+    Ok((StatusCode::OK, Json(identity)))
+}
+
+/// Unlinks the current authenticated user's account at the external OpenID Connect provider.
+///
+/// # Errors
+///
+/// See [`crate::api::Error`].
+#[debug_handler]
+pub(crate) async fn delete(AuthToken(token_hash): AuthToken) -> impl Response<DeleteResponse> {
+    let is_identity_deleted = db::transaction!(async |tx| -> TxResult<_, api::Error> {
+        let Some(session) = sqlx::query!(
+            "SELECT user_id FROM sessions
+                WHERE token_hash = $1",
+            token_hash.as_ref(),
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        else {
+            return Err(TxError::Abort(api::Error::AuthFailed));
+        };
+
+        Ok(sqlx::query!(
+            "DELETE FROM oidc_identities
+                WHERE user_id = $1",
+            session.user_id,
+        )
+        .execute(tx.as_mut())
+        .await?
+        .rows_affected()
+            != 0)
+    })
+    .await?;
+
+    if !is_identity_deleted {
+        return Err(api::Error::ResourceNotFound);
+    }
+
+    Ok((StatusCode::OK, Json(DeleteResponse {})))
+}
+
+/// A `DELETE` response body for this API route.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DeleteResponse {}
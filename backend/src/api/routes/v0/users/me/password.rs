@@ -8,8 +8,8 @@ use crate::{
     api::{
         self, Json,
         cookie::{CookieWrapper, SessionCookie},
-        db_helpers::create_session,
-        extract::AuthToken,
+        db_helpers::{create_security_notice, create_session},
+        extract::{AuthToken, ClientIp, UserAgent},
         response::Response,
         validation::{
             NewUserPassword,
@@ -18,6 +18,7 @@ use crate::{
     },
     crypto::hash_with_salt,
     db::{self, TxError, TxResult},
+    email::{MessageTemplate, PasswordChangedMessage},
 };
 
 /// A `PATCH` request body for this API route.
@@ -39,11 +40,13 @@ pub(crate) struct PatchRequest {
 #[debug_handler]
 pub(crate) async fn patch(
     AuthToken(token_hash): AuthToken,
+    user_agent: UserAgent,
+    client_ip: ClientIp,
     Json(body): Json<PatchRequest>,
 ) -> impl Response<PatchResponse> {
     let password_hash = hash_with_salt(&body.password);
 
-    let session_token = db::transaction!(async |tx| -> TxResult<_, api::Error> {
+    let (session_token, notice) = db::transaction!(async |tx| -> TxResult<_, api::Error> {
         let Some(user_id) = sqlx::query!(
             "SELECT user_id FROM sessions
                 WHERE token_hash = $1",
@@ -55,10 +58,12 @@ pub(crate) async fn patch(
             return Err(TxError::Abort(api::Error::AuthFailed));
         };
 
-        body.credentials.verify(tx, &user_id).await?;
+        body.credentials
+            .verify_limited(tx, &user_id, client_ip)
+            .await?;
 
-        // Expiring all sessions is a conventionally expected security feature whenever a user's
-        // password is changed.
+        // Expiring all sessions and OAuth grants is a conventionally expected security feature
+        // whenever a user's password is changed.
         sqlx::query!(
             "DELETE FROM sessions
                 WHERE user_id = $1 AND token_hash != $2",
@@ -68,6 +73,14 @@ pub(crate) async fn patch(
         .execute(tx.as_mut())
         .await?;
 
+        sqlx::query!(
+            "DELETE FROM oauth_grants
+                WHERE user_id = $1",
+            user_id,
+        )
+        .execute(tx.as_mut())
+        .await?;
+
         sqlx::query!(
             "UPDATE users
                 SET password_hash = $1
@@ -78,12 +91,19 @@ pub(crate) async fn patch(
         .execute(tx.as_mut())
         .await?;
 
-        let session_token = create_session(tx, &user_id).await?;
+        let session_token = create_session(tx, &user_id, &user_agent).await?;
+        let notice = create_security_notice(tx, &user_id, &user_agent).await?;
 
-        Ok(session_token)
+        Ok((session_token, notice))
     })
     .await?;
 
+    if let Some(mailbox) = notice.mailbox() {
+        PasswordChangedMessage { notice: &notice }
+            .to(mailbox)
+            .send();
+    }
+
     Ok((
         StatusCode::OK,
         [SessionCookie::new(session_token.to_string()).to_header()],
@@ -0,0 +1,162 @@
+//! The set of the current authenticated user's access keys for the S3-compatible API.
+
+use axum::http::StatusCode;
+use axum_macros::debug_handler;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::{
+        self, Json,
+        extract::{AuthToken, ClientIp},
+        response::{Response, body::S3AccessKey},
+        validation::auth::{MultiFactorCredentials, VerifyCredentials},
+    },
+    db::{self, TxError, TxResult},
+    id::Id,
+};
+
+pub(crate) mod s3_access_key;
+
+/// The number of random bytes in an access key ID.
+const ACCESS_KEY_ID_LENGTH: usize = 11;
+
+/// The number of random bytes in an access key secret.
+const ACCESS_KEY_SECRET_LENGTH: usize = 30;
+
+/// Lists all of the current authenticated user's S3 access keys.
+///
+/// # Errors
+///
+/// See [`crate::api::Error`].
+#[debug_handler]
+pub(crate) async fn get(AuthToken(token_hash): AuthToken) -> impl Response<GetResponse> {
+    let access_keys = db::transaction!(async |tx| -> TxResult<_, api::Error> {
+        let Some(session) = sqlx::query!(
+            "SELECT user_id FROM sessions
+                WHERE token_hash = $1",
+            token_hash.as_ref(),
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        else {
+            return Err(TxError::Abort(api::Error::AuthFailed));
+        };
+
+        Ok(sqlx::query_as!(
+            S3AccessKey,
+            "SELECT id, created_at FROM s3_access_keys
+                WHERE user_id = $1
+                ORDER BY created_at",
+            session.user_id,
+        )
+        .fetch_all(tx.as_mut())
+        .await?)
+    })
+    .await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(GetResponse {
+            values: access_keys,
+        }),
+    ))
+}
+
+/// A `GET` response body for this API route.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct GetResponse {
+    /// All of the user's S3 access keys.
+    values: Vec<S3AccessKey>,
+}
+
+/// A `POST` request body for this API route.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub(crate) struct PostRequest {
+    /// The user's credentials.
+    pub credentials: MultiFactorCredentials,
+}
+
+/// Creates a new S3 access key for the current authenticated user. The key's secret is only ever
+/// returned in this response.
+///
+/// # Errors
+///
+/// See [`crate::api::Error`].
+#[debug_handler]
+pub(crate) async fn post(
+    AuthToken(token_hash): AuthToken,
+    client_ip: ClientIp,
+    Json(body): Json<PostRequest>,
+) -> impl Response<PostResponse> {
+    let response = db::transaction!(async |tx| -> TxResult<_, api::Error> {
+        let Some(session) = sqlx::query!(
+            "SELECT user_id FROM sessions
+                WHERE token_hash = $1",
+            token_hash.as_ref(),
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        else {
+            return Err(TxError::Abort(api::Error::AuthFailed));
+        };
+
+        body.credentials
+            .verify_limited(tx, &session.user_id, client_ip)
+            .await?;
+
+        // Access key IDs are conventionally uppercase and alphanumeric, which some clients expect.
+        let id = format!(
+            "FG{}",
+            base32::encode(
+                base32::Alphabet::Rfc4648 { padding: false },
+                Id::<[u8; ACCESS_KEY_ID_LENGTH]>::generate().as_slice(),
+            ),
+        );
+        let secret = Id::<[u8; ACCESS_KEY_SECRET_LENGTH]>::generate().to_string();
+
+        let access_key = match sqlx::query!(
+            "INSERT INTO s3_access_keys (id, user_id, secret)
+                VALUES ($1, $2, $3)
+                RETURNING created_at",
+            id,
+            session.user_id,
+            secret,
+        )
+        .fetch_one(tx.as_mut())
+        .await
+        {
+            Err(sqlx::Error::Database(error))
+                if error.constraint() == Some("s3_access_keys_pkey") =>
+            {
+                return Err(TxError::Retry);
+            }
+            result => result?,
+        };
+
+        Ok(PostResponse {
+            id,
+            secret,
+            created_at: access_key.created_at,
+        })
+    })
+    .await?;
+
+    Ok((StatusCode::OK, Json(response)))
+}
+
+/// A `POST` response body for this API route.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PostResponse {
+    /// The new access key's ID.
+    id: String,
+
+    /// The new access key's secret, used to sign requests.
+    secret: String,
+
+    /// The timestamp the access key was created.
+    created_at: DateTime<Utc>,
+}
@@ -7,8 +7,9 @@ use serde::Serialize;
 use crate::{
     api::{
         self, Json,
-        extract::AuthToken,
+        extract::{AuthToken, MAX_SESSION_IDLE_DAYS},
         response::{Response, body::Session},
+        user_agent::describe_device,
     },
     db::{self, TxError, TxResult},
     id::Id,
@@ -35,16 +36,27 @@ pub(crate) async fn get(AuthToken(token_hash): AuthToken) -> impl Response<GetRe
             return Err(TxError::Abort(api::Error::AuthFailed));
         };
 
-        Ok(sqlx::query_as!(
-            Session,
-            "SELECT token_hash AS id, created_at, accessed_at FROM sessions
-                WHERE user_id = $1",
+        Ok(sqlx::query!(
+            "SELECT token_hash, created_at, accessed_at, user_agent FROM sessions
+                WHERE user_id = $1
+                    AND scopes IS NULL
+                    AND accessed_at > now() - make_interval(days => $2)",
             user_id,
+            *MAX_SESSION_IDLE_DAYS,
         )
         .fetch_all(tx.as_mut())
         .await?)
     })
-    .await?;
+    .await?
+    .into_iter()
+    .map(|session| Session {
+        id: session.token_hash.into(),
+        created_at: session.created_at,
+        accessed_at: session.accessed_at,
+        device: session.user_agent.as_deref().and_then(describe_device),
+        user_agent: session.user_agent,
+    })
+    .collect();
 
     Ok((
         StatusCode::OK,
@@ -91,7 +91,7 @@ pub(crate) async fn delete(
 
         Ok(sqlx::query!(
             "DELETE FROM sessions
-                WHERE user_id = $1 AND token_hash = $2",
+                WHERE user_id = $1 AND token_hash = $2 AND scopes IS NULL",
             user_id,
             session_id,
         )
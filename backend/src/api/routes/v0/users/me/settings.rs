@@ -2,11 +2,11 @@
 
 use axum::http::StatusCode;
 use axum_macros::debug_handler;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use crate::{
     api::{self, Json, extract::AuthToken, response::Response},
-    db::{self, TxResult},
+    db::{self, TxError, TxResult},
 };
 
 /// Gets information about the current authenticated user's private settings.
@@ -18,7 +18,12 @@ use crate::{
 pub(crate) async fn get(AuthToken(token_hash): AuthToken) -> impl Response<GetResponse> {
     let Some(user) = db::transaction!(async |tx| -> TxResult<_, api::Error> {
         Ok(sqlx::query!(
-            r#"SELECT users.email, totp.secret IS NOT NULL AS "totp_enabled!" FROM users
+            r#"SELECT
+                users.email,
+                users.strict_file_names,
+                totp.secret IS NOT NULL AS "totp_enabled!",
+                cardinality(totp.unused_backup_code_hashes) AS backup_codes_remaining
+                FROM users
                 INNER JOIN sessions ON sessions.user_id = users.id
                 LEFT JOIN totp ON totp.user_id = users.id
                 WHERE sessions.token_hash = $1"#,
@@ -37,6 +42,8 @@ pub(crate) async fn get(AuthToken(token_hash): AuthToken) -> impl Response<GetRe
         Json(GetResponse {
             email: user.email,
             totp_enabled: user.totp_enabled,
+            backup_codes_remaining: user.backup_codes_remaining,
+            strict_file_names: user.strict_file_names,
         }),
     ))
 }
@@ -50,4 +57,69 @@ pub(crate) struct GetResponse {
 
     /// Whether the user has TOTP authentication enabled.
     totp_enabled: bool,
+
+    /// How many unused TOTP backup authentication codes the user has left, or [`None`] if TOTP is
+    /// disabled.
+    backup_codes_remaining: Option<i32>,
+
+    /// Whether new file and folder names in the user's folders must also be valid on Windows and
+    /// macOS.
+    strict_file_names: bool,
+}
+
+/// A `PATCH` request body for this API route.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub(crate) struct PatchRequest {
+    /// Whether new file and folder names in the user's folders must also be valid on Windows and
+    /// macOS. Existing names aren't affected.
+    pub strict_file_names: Option<bool>,
+}
+
+/// Changes the current authenticated user's private settings. Unspecified settings are left
+/// unchanged.
+///
+/// # Errors
+///
+/// See [`crate::api::Error`].
+#[debug_handler]
+pub(crate) async fn patch(
+    AuthToken(token_hash): AuthToken,
+    Json(body): Json<PatchRequest>,
+) -> impl Response<PatchResponse> {
+    let strict_file_names = db::transaction!(async |tx| -> TxResult<_, api::Error> {
+        let Some(user_id) = sqlx::query!(
+            "SELECT user_id FROM sessions
+                WHERE token_hash = $1",
+            token_hash.as_ref(),
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        .map(|session| session.user_id) else {
+            return Err(TxError::Abort(api::Error::AuthFailed));
+        };
+
+        Ok(sqlx::query_scalar!(
+            "UPDATE users
+                SET strict_file_names = coalesce($1, strict_file_names)
+                WHERE id = $2
+                RETURNING strict_file_names",
+            body.strict_file_names,
+            user_id.as_slice(),
+        )
+        .fetch_one(tx.as_mut())
+        .await?)
+    })
+    .await?;
+
+    Ok((StatusCode::OK, Json(PatchResponse { strict_file_names })))
+}
+
+/// A `PATCH` response body for this API route.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PatchResponse {
+    /// Whether new file and folder names in the user's folders must also be valid on Windows and
+    /// macOS.
+    strict_file_names: bool,
 }
@@ -9,22 +9,33 @@ use serde::{Deserialize, Serialize};
 use crate::{
     api::{
         self, Json,
-        extract::AuthToken,
+        db_helpers::create_security_notice,
+        extract::{AuthToken, ClientIp, UserAgent},
         response::Response,
         validation::{
-            Otp, TotpSecret,
+            BACKUP_CODE_LENGTH, Otp, TotpSecret,
             auth::{FirstFactorCredentials, VerifyCredentials},
         },
     },
-    crypto::{generate_short_code, verify_totp},
+    crypto::{encrypt_totp_secret, generate_short_code, hash_with_salt, verify_totp},
     db::{self, TxError, TxResult},
+    email::{MessageTemplate, TotpDisabledMessage, TotpEnabledMessage},
 };
 
-/// The length of a new backup authentication code.
-const BACKUP_CODE_LENGTH: usize = 8;
+pub(crate) mod backup_codes;
 
 /// The number of backup authentication codes to generate for a user.
-const BACKUP_CODE_COUNT: usize = 10;
+pub(crate) const BACKUP_CODE_COUNT: usize = 10;
+
+/// Generates a new set of backup authentication codes, returning the codes along with their hashes
+/// to store.
+pub(crate) fn generate_backup_codes() -> ([String; BACKUP_CODE_COUNT], Vec<String>) {
+    let backup_codes: [String; BACKUP_CODE_COUNT] =
+        array::from_fn(|_| generate_short_code(BACKUP_CODE_LENGTH));
+    let backup_code_hashes = backup_codes.iter().map(hash_with_salt).collect();
+
+    (backup_codes, backup_code_hashes)
+}
 
 /// A `DELETE` request body for this API route.
 #[derive(Deserialize, Debug)]
@@ -53,9 +64,11 @@ pub(crate) struct DeleteRequest {
 #[debug_handler]
 pub(crate) async fn delete(
     AuthToken(token_hash): AuthToken,
+    user_agent: UserAgent,
+    client_ip: ClientIp,
     Json(body): Json<DeleteRequest>,
 ) -> impl Response<DeleteResponse> {
-    let is_totp_deleted = db::transaction!(async |tx| -> TxResult<_, api::Error> {
+    let notice = db::transaction!(async |tx| -> TxResult<_, api::Error> {
         let Some(session) = sqlx::query!(
             "SELECT user_id FROM sessions
                 WHERE token_hash = $1",
@@ -67,9 +80,11 @@ pub(crate) async fn delete(
             return Err(TxError::Abort(api::Error::AuthFailed));
         };
 
-        body.credentials.verify(tx, &session.user_id).await?;
+        body.credentials
+            .verify_limited(tx, &session.user_id, client_ip)
+            .await?;
 
-        Ok(sqlx::query!(
+        let is_totp_deleted = sqlx::query!(
             "DELETE FROM totp
                 WHERE user_id = $1",
             session.user_id,
@@ -77,12 +92,18 @@ pub(crate) async fn delete(
         .execute(tx.as_mut())
         .await?
         .rows_affected()
-            != 0)
+            != 0;
+
+        if !is_totp_deleted {
+            return Err(TxError::Abort(api::Error::ResourceNotFound));
+        }
+
+        create_security_notice(tx, &session.user_id, &user_agent).await
     })
     .await?;
 
-    if !is_totp_deleted {
-        return Err(api::Error::ResourceNotFound);
+    if let Some(mailbox) = notice.mailbox() {
+        TotpDisabledMessage { notice: &notice }.to(mailbox).send();
     }
 
     Ok((StatusCode::OK, Json(DeleteResponse {})))
@@ -116,9 +137,11 @@ pub(crate) struct PostRequest {
 #[debug_handler]
 pub(crate) async fn post(
     AuthToken(token_hash): AuthToken,
+    user_agent: UserAgent,
+    client_ip: ClientIp,
     Json(body): Json<PostRequest>,
 ) -> impl Response<PostResponse> {
-    let backup_codes = db::transaction!(async |tx| -> TxResult<_, api::Error> {
+    let (backup_codes, notice) = db::transaction!(async |tx| -> TxResult<_, api::Error> {
         let Some(session) = sqlx::query!(
             "SELECT user_id FROM sessions
                 WHERE token_hash = $1",
@@ -130,22 +153,32 @@ pub(crate) async fn post(
             return Err(TxError::Abort(api::Error::AuthFailed));
         };
 
-        body.credentials.verify(tx, &session.user_id).await?;
+        body.credentials
+            .verify_limited(tx, &session.user_id, client_ip)
+            .await?;
 
         if !verify_totp(&body.otp, body.secret.as_ref()) {
             return Err(TxError::Abort(api::Error::TotpSetupWrong));
         }
 
-        let backup_codes: [String; BACKUP_CODE_COUNT] =
-            array::from_fn(|_| generate_short_code(BACKUP_CODE_LENGTH));
+        let (secret_key_id, encrypted_secret) =
+            encrypt_totp_secret(body.secret.as_ref(), &session.user_id);
+        let (backup_codes, backup_code_hashes) = generate_backup_codes();
 
         match sqlx::query!(
-            "INSERT INTO totp (user_id, secret, otp_used_last, unused_backup_codes)
-                VALUES ($1, $2, $3, $4)",
+            "INSERT INTO totp (
+                user_id,
+                secret,
+                secret_key_id,
+                otp_used_last,
+                unused_backup_code_hashes
+            )
+                VALUES ($1, $2, $3, $4, $5)",
             session.user_id,
-            body.secret.as_ref(),
+            encrypted_secret,
+            secret_key_id,
             *body.otp,
-            &backup_codes,
+            &backup_code_hashes,
         )
         .execute(tx.as_mut())
         .await
@@ -157,10 +190,16 @@ pub(crate) async fn post(
             result => result?,
         };
 
-        Ok(backup_codes)
+        let notice = create_security_notice(tx, &session.user_id, &user_agent).await?;
+
+        Ok((backup_codes, notice))
     })
     .await?;
 
+    if let Some(mailbox) = notice.mailbox() {
+        TotpEnabledMessage { notice: &notice }.to(mailbox).send();
+    }
+
     Ok((StatusCode::CREATED, Json(PostResponse { backup_codes })))
 }
 
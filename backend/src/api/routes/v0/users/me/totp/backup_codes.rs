@@ -0,0 +1,97 @@
+//! The current authenticated user's TOTP backup authentication codes.
+
+use axum::http::StatusCode;
+use axum_macros::debug_handler;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::{
+        self, Json,
+        db_helpers::create_security_notice,
+        extract::{AuthToken, ClientIp, UserAgent},
+        response::Response,
+        routes::v0::users::me::totp::{BACKUP_CODE_COUNT, generate_backup_codes},
+        validation::auth::{FirstFactorCredentials, VerifyCredentials},
+    },
+    db::{self, TxError, TxResult},
+    email::{BackupCodesRegeneratedMessage, MessageTemplate},
+};
+
+/// A `POST` request body for this API route.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub(crate) struct PostRequest {
+    /// The user's credentials. These are single-factor for the same reason as when disabling TOTP:
+    /// a user who can disable and re-enable TOTP can get new backup codes that way anyway.
+    pub credentials: FirstFactorCredentials,
+}
+
+/// Replaces the current authenticated user's TOTP backup codes with a new set, so the old ones no
+/// longer work.
+///
+/// # Errors
+///
+/// See [`crate::api::Error`].
+#[debug_handler]
+pub(crate) async fn post(
+    AuthToken(token_hash): AuthToken,
+    user_agent: UserAgent,
+    client_ip: ClientIp,
+    Json(body): Json<PostRequest>,
+) -> impl Response<PostResponse> {
+    let (backup_codes, notice) = db::transaction!(async |tx| -> TxResult<_, api::Error> {
+        let Some(session) = sqlx::query!(
+            "SELECT user_id FROM sessions
+                WHERE token_hash = $1",
+            token_hash.as_ref(),
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        else {
+            return Err(TxError::Abort(api::Error::AuthFailed));
+        };
+
+        body.credentials
+            .verify_limited(tx, &session.user_id, client_ip)
+            .await?;
+
+        let (backup_codes, backup_code_hashes) = generate_backup_codes();
+
+        let is_totp_updated = sqlx::query!(
+            "UPDATE totp
+                SET unused_backup_code_hashes = $1
+                WHERE user_id = $2",
+            &backup_code_hashes,
+            session.user_id,
+        )
+        .execute(tx.as_mut())
+        .await?
+        .rows_affected()
+            != 0;
+
+        if !is_totp_updated {
+            return Err(TxError::Abort(api::Error::ResourceNotFound));
+        }
+
+        let notice = create_security_notice(tx, &session.user_id, &user_agent).await?;
+
+        Ok((backup_codes, notice))
+    })
+    .await?;
+
+    if let Some(mailbox) = notice.mailbox() {
+        BackupCodesRegeneratedMessage { notice: &notice }
+            .to(mailbox)
+            .send();
+    }
+
+    Ok((StatusCode::OK, Json(PostResponse { backup_codes })))
+}
+
+/// A `POST` response body for this API route.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PostResponse {
+    /// The new TOTP backup authentication codes.
+    backup_codes: [String; BACKUP_CODE_COUNT],
+}
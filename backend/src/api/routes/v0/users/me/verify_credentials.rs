@@ -7,7 +7,7 @@ use serde::{Deserialize, Serialize};
 use crate::{
     api::{
         self, Json,
-        extract::AuthToken,
+        extract::{AuthToken, ClientIp},
         response::Response,
         validation::auth::{MultiFactorCredentials, VerifyCredentials},
     },
@@ -31,6 +31,7 @@ pub(crate) struct PostRequest {
 #[debug_handler]
 pub(crate) async fn post(
     AuthToken(token_hash): AuthToken,
+    client_ip: ClientIp,
     Json(body): Json<PostRequest>,
 ) -> impl Response<PostResponse> {
     db::transaction!(async |tx| -> TxResult<_, api::Error> {
@@ -45,7 +46,9 @@ pub(crate) async fn post(
             return Err(TxError::Abort(api::Error::AuthFailed));
         };
 
-        body.credentials.verify(tx, &session.user_id).await?;
+        body.credentials
+            .verify_limited(tx, &session.user_id, client_ip)
+            .await?;
 
         Ok(())
     })
@@ -0,0 +1,170 @@
+//! A challenge for the current authenticated user to register a new WebAuthn credential with.
+
+use axum::http::StatusCode;
+use axum_macros::debug_handler;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::{
+        self, Json,
+        db_helpers::create_webauthn_challenge,
+        extract::AuthToken,
+        response::{Response, body::WebAuthnCredentialDescriptor},
+    },
+    db::{self, TxError, TxResult},
+    id::{Id, WebAuthnChallenge},
+    webauthn::{RELYING_PARTY_ID, RELYING_PARTY_NAME, SUPPORTED_ALGORITHMS},
+};
+
+/// A `POST` request body for this API route.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub(crate) struct PostRequest {
+    /// Whether the credential to register is a passkey rather than a security key.
+    #[serde(default)]
+    pub passkey: bool,
+}
+
+/// Issues a challenge for the current authenticated user to register a new WebAuthn credential
+/// with, returning options to pass to `navigator.credentials.create`.
+///
+/// # Errors
+///
+/// See [`crate::api::Error`].
+#[debug_handler]
+pub(crate) async fn post(
+    AuthToken(token_hash): AuthToken,
+    Json(body): Json<PostRequest>,
+) -> impl Response<PostResponse> {
+    let response = db::transaction!(async |tx| -> TxResult<_, api::Error> {
+        let Some(user) = sqlx::query!(
+            "SELECT users.id, users.email, users.name FROM users
+                INNER JOIN sessions ON sessions.user_id = users.id
+                WHERE sessions.token_hash = $1",
+            token_hash.as_ref(),
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        else {
+            return Err(TxError::Abort(api::Error::AuthFailed));
+        };
+
+        let challenge = create_webauthn_challenge(tx, Some(&user.id)).await?;
+
+        let credential_ids = sqlx::query_scalar!(
+            "SELECT id FROM webauthn_credentials
+                WHERE user_id = $1",
+            user.id,
+        )
+        .fetch_all(tx.as_mut())
+        .await?;
+
+        Ok(PostResponse {
+            challenge,
+            rp: RelyingParty {
+                id: RELYING_PARTY_ID.clone(),
+                name: RELYING_PARTY_NAME,
+            },
+            user: WebAuthnUser {
+                id: user.id.into(),
+                name: user.email,
+                display_name: user.name,
+            },
+            pub_key_cred_params: SUPPORTED_ALGORITHMS
+                .into_iter()
+                .map(|alg| PublicKeyCredentialParameters {
+                    r#type: "public-key",
+                    alg,
+                })
+                .collect(),
+            exclude_credentials: credential_ids.into_iter().map(Into::into).collect(),
+            authenticator_selection: if body.passkey {
+                AuthenticatorSelection {
+                    resident_key: "required",
+                    user_verification: "required",
+                }
+            } else {
+                AuthenticatorSelection {
+                    resident_key: "discouraged",
+                    user_verification: "discouraged",
+                }
+            },
+        })
+    })
+    .await?;
+
+    Ok((StatusCode::OK, Json(response)))
+}
+
+/// A `POST` response body for this API route, matching the WebAuthn API's
+/// `PublicKeyCredentialCreationOptionsJSON` so it can be passed to
+/// `PublicKeyCredential.parseCreationOptionsFromJSON`.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PostResponse {
+    /// The challenge for the registration to sign. Expires after 5 minutes.
+    challenge: WebAuthnChallenge,
+
+    /// The relying party to register the credential with.
+    rp: RelyingParty,
+
+    /// The user to register the credential for.
+    user: WebAuthnUser,
+
+    /// The supported public key algorithms, in order of preference.
+    pub_key_cred_params: Vec<PublicKeyCredentialParameters>,
+
+    /// The user's existing WebAuthn credentials, so the same authenticator isn't registered twice.
+    exclude_credentials: Vec<WebAuthnCredentialDescriptor>,
+
+    /// Requirements for the authenticator, which differ between passkeys and security keys.
+    authenticator_selection: AuthenticatorSelection,
+}
+
+/// A WebAuthn relying party.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct RelyingParty {
+    /// The relying party's ID.
+    id: String,
+
+    /// The relying party's name.
+    name: &'static str,
+}
+
+/// A user in WebAuthn options.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct WebAuthnUser {
+    /// The user's ID.
+    id: Id,
+
+    /// The user's email, which authenticators use to tell accounts apart.
+    name: String,
+
+    /// The user's display name.
+    display_name: String,
+}
+
+/// A supported public key algorithm in WebAuthn options.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PublicKeyCredentialParameters {
+    /// The credential type, which is always `public-key`.
+    r#type: &'static str,
+
+    /// The algorithm's COSE identifier.
+    alg: i64,
+}
+
+/// Requirements for the authenticator in WebAuthn options.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AuthenticatorSelection {
+    /// Whether the credential must be discoverable, letting the user sign in without entering their
+    /// email.
+    resident_key: &'static str,
+
+    /// Whether the authenticator must verify the user (e.g., by PIN or biometrics).
+    user_verification: &'static str,
+}
@@ -0,0 +1,169 @@
+//! The set of the current authenticated user's WebAuthn credentials, such as security keys.
+
+use axum::http::StatusCode;
+use axum_macros::debug_handler;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::{
+        self, Json,
+        db_helpers::consume_webauthn_challenge,
+        extract::{AuthToken, ClientIp},
+        response::{Response, body::WebAuthnCredential},
+        validation::{
+            Base64UrlBytes, WebAuthnCredentialName,
+            auth::{FirstFactorCredentials, VerifyCredentials},
+        },
+    },
+    db::{self, TxError, TxResult},
+    webauthn::{self, Ceremony, RELYING_PARTY_ID},
+};
+
+pub(crate) mod webauthn_credential;
+
+/// Lists all of the current authenticated user's WebAuthn credentials.
+///
+/// # Errors
+///
+/// See [`crate::api::Error`].
+#[debug_handler]
+pub(crate) async fn get(AuthToken(token_hash): AuthToken) -> impl Response<GetResponse> {
+    let credentials = db::transaction!(async |tx| -> TxResult<_, api::Error> {
+        let Some(session) = sqlx::query!(
+            "SELECT user_id FROM sessions
+                WHERE token_hash = $1",
+            token_hash.as_ref(),
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        else {
+            return Err(TxError::Abort(api::Error::AuthFailed));
+        };
+
+        Ok(sqlx::query_as!(
+            WebAuthnCredential,
+            "SELECT id, name, passkey, created_at, last_used_at FROM webauthn_credentials
+                WHERE user_id = $1
+                ORDER BY created_at",
+            session.user_id,
+        )
+        .fetch_all(tx.as_mut())
+        .await?)
+    })
+    .await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(GetResponse {
+            values: credentials,
+        }),
+    ))
+}
+
+/// A `GET` response body for this API route.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct GetResponse {
+    /// All of the user's WebAuthn credentials.
+    values: Vec<WebAuthnCredential>,
+}
+
+/// A `POST` request body for this API route.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub(crate) struct PostRequest {
+    /// The user's credentials.
+    pub credentials: FirstFactorCredentials,
+
+    /// The user's name for the new WebAuthn credential.
+    pub name: WebAuthnCredentialName,
+
+    /// Whether the new WebAuthn credential is a passkey rather than a security key. Passkeys can be
+    /// used to sign in on their own, so they must verify the user.
+    #[serde(default)]
+    pub passkey: bool,
+
+    /// The client data returned by `navigator.credentials.create`, as JSON. Must sign a challenge
+    /// from `POST /users/me/webauthn-challenge`.
+    #[serde(rename = "clientDataJSON")]
+    pub client_data_json: Base64UrlBytes,
+
+    /// The attestation object returned by `navigator.credentials.create`.
+    pub attestation_object: Base64UrlBytes,
+}
+
+/// Registers a new WebAuthn credential for the current authenticated user. A security key enables
+/// WebAuthn-based 2FA, whereas a passkey enables signing in without a password.
+///
+/// # Errors
+///
+/// See [`crate::api::Error`].
+#[debug_handler]
+pub(crate) async fn post(
+    AuthToken(token_hash): AuthToken,
+    client_ip: ClientIp,
+    Json(body): Json<PostRequest>,
+) -> impl Response<PostResponse> {
+    let credential = db::transaction!(async |tx| -> TxResult<_, api::Error> {
+        let Some(session) = sqlx::query!(
+            "SELECT user_id FROM sessions
+                WHERE token_hash = $1",
+            token_hash.as_ref(),
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        else {
+            return Err(TxError::Abort(api::Error::AuthFailed));
+        };
+
+        body.credentials
+            .verify_limited(tx, &session.user_id, client_ip)
+            .await?;
+
+        let Ok(challenge) = webauthn::verify_client_data(&body.client_data_json, Ceremony::Create)
+        else {
+            return Err(TxError::Abort(api::Error::WebAuthnSetupWrong));
+        };
+
+        if !consume_webauthn_challenge(tx, &challenge, Some(&session.user_id)).await? {
+            return Err(TxError::Abort(api::Error::WebAuthnSetupWrong));
+        }
+
+        let Ok(registration) = webauthn::verify_registration(
+            &RELYING_PARTY_ID,
+            &body.attestation_object,
+            body.passkey,
+        ) else {
+            return Err(TxError::Abort(api::Error::WebAuthnSetupWrong));
+        };
+
+        match sqlx::query_as!(
+            WebAuthnCredential,
+            "INSERT INTO webauthn_credentials (id, user_id, name, passkey, public_key, sign_count)
+                VALUES ($1, $2, $3, $4, $5, $6)
+                RETURNING id, name, passkey, created_at, last_used_at",
+            registration.credential_id,
+            session.user_id,
+            body.name.as_str(),
+            body.passkey,
+            registration.public_key,
+            i64::from(registration.sign_count),
+        )
+        .fetch_one(tx.as_mut())
+        .await
+        {
+            Err(sqlx::Error::Database(error))
+                if error.constraint() == Some("webauthn_credentials_pkey") =>
+            {
+                Err(TxError::Abort(api::Error::AlreadyExists))
+            }
+            result => Ok(result?),
+        }
+    })
+    .await?;
+
+    Ok((StatusCode::OK, Json(credential)))
+}
+
+/// A `POST` response body for this API route.
+pub(crate) type PostResponse = WebAuthnCredential;
@@ -0,0 +1,80 @@
+//! One of the current authenticated user's WebAuthn credentials.
+
+use axum::http::StatusCode;
+use axum_macros::debug_handler;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::{
+        self, Json,
+        extract::{AuthToken, ClientIp, Path},
+        response::Response,
+        validation::auth::{FirstFactorCredentials, VerifyCredentials},
+    },
+    db::{self, TxError, TxResult},
+    id::Id,
+};
+
+/// A request path for this API route.
+type PathParams = Path<Id>;
+
+/// A `DELETE` request body for this API route.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub(crate) struct DeleteRequest {
+    /// The user's credentials. Single-factor for the same reasons as in `DELETE /users/me/totp`.
+    pub credentials: FirstFactorCredentials,
+}
+
+/// Removes one of the current authenticated user's WebAuthn credentials.
+///
+/// # Errors
+///
+/// See [`crate::api::Error`].
+#[debug_handler]
+pub(crate) async fn delete(
+    Path(credential_id): PathParams,
+    AuthToken(token_hash): AuthToken,
+    client_ip: ClientIp,
+    Json(body): Json<DeleteRequest>,
+) -> impl Response<DeleteResponse> {
+    let is_credential_deleted = db::transaction!(async |tx| -> TxResult<_, api::Error> {
+        let Some(session) = sqlx::query!(
+            "SELECT user_id FROM sessions
+                WHERE token_hash = $1",
+            token_hash.as_ref(),
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        else {
+            return Err(TxError::Abort(api::Error::AuthFailed));
+        };
+
+        body.credentials
+            .verify_limited(tx, &session.user_id, client_ip)
+            .await?;
+
+        Ok(sqlx::query!(
+            "DELETE FROM webauthn_credentials
+                WHERE id = $1 AND user_id = $2",
+            credential_id.as_slice(),
+            session.user_id,
+        )
+        .execute(tx.as_mut())
+        .await?
+        .rows_affected()
+            != 0)
+    })
+    .await?;
+
+    if !is_credential_deleted {
+        return Err(api::Error::ResourceNotFound);
+    }
+
+    Ok((StatusCode::OK, Json(DeleteResponse {})))
+}
+
+/// A `DELETE` response body for this API route.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DeleteResponse {}
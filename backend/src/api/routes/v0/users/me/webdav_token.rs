@@ -0,0 +1,140 @@
+//! The current authenticated user's token for signing into the WebDAV server.
+
+use axum::http::StatusCode;
+use axum_macros::debug_handler;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::{
+        self, Json,
+        extract::{AuthToken, ClientIp},
+        response::Response,
+        validation::auth::{MultiFactorCredentials, VerifyCredentials},
+    },
+    crypto::hash_without_salt,
+    db::{self, TxError, TxResult},
+    id::Token,
+};
+
+/// A `POST` request body for this API route.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub(crate) struct PostRequest {
+    /// The user's credentials.
+    pub credentials: MultiFactorCredentials,
+}
+
+/// Generates a new WebDAV token for the current authenticated user, replacing any existing one. The
+/// token is only ever returned in this response.
+///
+/// # Errors
+///
+/// See [`crate::api::Error`].
+#[debug_handler]
+pub(crate) async fn post(
+    AuthToken(token_hash): AuthToken,
+    client_ip: ClientIp,
+    Json(body): Json<PostRequest>,
+) -> impl Response<PostResponse> {
+    let webdav_token = db::transaction!(async |tx| -> TxResult<_, api::Error> {
+        let Some(session) = sqlx::query!(
+            "SELECT user_id FROM sessions
+                WHERE token_hash = $1",
+            token_hash.as_ref(),
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        else {
+            return Err(TxError::Abort(api::Error::AuthFailed));
+        };
+
+        body.credentials
+            .verify_limited(tx, &session.user_id, client_ip)
+            .await?;
+
+        let webdav_token = Token::generate();
+        let token_hash = hash_without_salt(&webdav_token);
+
+        match sqlx::query!(
+            "INSERT INTO webdav_tokens (user_id, token_hash)
+                VALUES ($1, $2)
+                ON CONFLICT (user_id) DO UPDATE
+                    SET created_at = now(),
+                        token_hash = excluded.token_hash",
+            session.user_id,
+            token_hash.as_ref(),
+        )
+        .execute(tx.as_mut())
+        .await
+        {
+            Err(sqlx::Error::Database(error))
+                if error.constraint() == Some("webdav_tokens_token_hash_key") =>
+            {
+                return Err(TxError::Retry);
+            }
+            result => result?,
+        };
+
+        Ok(webdav_token)
+    })
+    .await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(PostResponse {
+            token: webdav_token,
+        }),
+    ))
+}
+
+/// A `POST` response body for this API route.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PostResponse {
+    /// The new WebDAV token, to use as the password for HTTP Basic authentication (with the user's
+    /// email as the username).
+    token: Token,
+}
+
+/// Revokes the current authenticated user's WebDAV token.
+///
+/// # Errors
+///
+/// See [`crate::api::Error`].
+#[debug_handler]
+pub(crate) async fn delete(AuthToken(token_hash): AuthToken) -> impl Response<DeleteResponse> {
+    let is_token_deleted = db::transaction!(async |tx| -> TxResult<_, api::Error> {
+        let Some(session) = sqlx::query!(
+            "SELECT user_id FROM sessions
+                WHERE token_hash = $1",
+            token_hash.as_ref(),
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        else {
+            return Err(TxError::Abort(api::Error::AuthFailed));
+        };
+
+        Ok(sqlx::query!(
+            "DELETE FROM webdav_tokens
+                WHERE user_id = $1",
+            session.user_id,
+        )
+        .execute(tx.as_mut())
+        .await?
+        .rows_affected()
+            != 0)
+    })
+    .await?;
+
+    if !is_token_deleted {
+        return Err(api::Error::ResourceNotFound);
+    }
+
+    Ok((StatusCode::OK, Json(DeleteResponse {})))
+}
+
+/// A `DELETE` response body for this API route.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DeleteResponse {}
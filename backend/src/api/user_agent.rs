@@ -0,0 +1,84 @@
+//! Approximate descriptions of the devices behind user agents, to help users recognize their
+//! sessions.
+//!
+//! User agent strings are unreliable and full of tokens copied from other browsers, so this only
+//! looks for the few most telling tokens, checked in an order where the more specific ones win.
+
+/// Browser names, each paired with a token that browser's user agents include.
+const BROWSERS: &[(&str, &str)] = &[
+    ("Edg/", "Edge"),
+    ("EdgA/", "Edge"),
+    ("EdgiOS/", "Edge"),
+    ("OPR/", "Opera"),
+    ("SamsungBrowser/", "Samsung Internet"),
+    ("FxiOS/", "Firefox"),
+    ("Firefox/", "Firefox"),
+    ("CriOS/", "Chrome"),
+    ("Chrome/", "Chrome"),
+    ("Safari/", "Safari"),
+];
+
+/// Operating system names, each paired with a token that user agents on that operating system
+/// include.
+const OPERATING_SYSTEMS: &[(&str, &str)] = &[
+    ("iPhone", "iOS"),
+    ("iPad", "iPadOS"),
+    ("Android", "Android"),
+    ("CrOS", "ChromeOS"),
+    ("Windows", "Windows"),
+    ("Macintosh", "macOS"),
+    ("Linux", "Linux"),
+];
+
+/// Describes the browser and operating system a user agent string likely belongs to, like "Firefox
+/// on Windows". Returns [`None`] if neither is recognized.
+pub(crate) fn describe_device(user_agent: &str) -> Option<String> {
+    let find = |names: &[(&str, &'static str)]| {
+        names
+            .iter()
+            .find(|(token, _)| user_agent.contains(token))
+            .map(|(_, name)| *name)
+    };
+
+    match (find(BROWSERS), find(OPERATING_SYSTEMS)) {
+        (Some(browser), Some(os)) => Some(format!("{browser} on {os}")),
+        (Some(name), None) | (None, Some(name)) => Some(name.to_owned()),
+        (None, None) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn describes_devices() {
+        let cases = [
+            (
+                "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:131.0) Gecko/20100101 Firefox/131.0",
+                Some("Firefox on Windows"),
+            ),
+            (
+                "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/130.0.0.0 Safari/537.36 Edg/130.0.0.0",
+                Some("Edge on macOS"),
+            ),
+            (
+                "Mozilla/5.0 (iPhone; CPU iPhone OS 18_0 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/18.0 Mobile/15E148 Safari/604.1",
+                Some("Safari on iOS"),
+            ),
+            (
+                "Mozilla/5.0 (Linux; Android 10; K) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/130.0.0.0 Mobile Safari/537.36",
+                Some("Chrome on Android"),
+            ),
+            ("curl/8.10.1", None),
+        ];
+
+        for (user_agent, description) in cases {
+            assert_eq!(
+                describe_device(user_agent).as_deref(),
+                description,
+                "{user_agent}",
+            );
+        }
+    }
+}
@@ -1,14 +1,27 @@
 //! Utilities to help with API request validation.
 
-use std::{borrow::Cow, str::FromStr};
-
+use std::{
+    borrow::Cow,
+    collections::BTreeSet,
+    fmt::{self, Formatter},
+    str::FromStr,
+};
+
+use base64::{
+    Engine as _,
+    engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD},
+};
 use derive_more::derive::{AsRef, Deref, Display};
 use idna::uts46::{self, Uts46};
 use lettre::Address;
 use regex_macro::regex;
+use reqwest::Url;
 use serde::{Deserialize, Serialize};
 use serde_with::{DeserializeFromStr, SerializeDisplay};
 use thiserror::Error;
+use unicode_normalization::{UnicodeNormalization, is_nfc};
+
+use crate::api::db_helpers::ApiTokenScope;
 
 pub(crate) mod auth;
 
@@ -21,8 +34,14 @@ pub(crate) type NewUserPassword = BoundedString<8, 256>;
 /// A user's password in plain text.
 pub(crate) type UserPassword = BoundedString<0, 256>;
 
+/// A password for viewing a share, in plain text.
+pub(crate) type SharePassword = BoundedString<1, 256>;
+
+/// The length of a TOTP backup authentication code.
+pub(crate) const BACKUP_CODE_LENGTH: usize = 8;
+
 /// A user's TOTP or backup authentication code.
-pub(crate) type Otp = BoundedString<6, 8>;
+pub(crate) type Otp = BoundedString<6, BACKUP_CODE_LENGTH>;
 
 /// An unverified email's verification code.
 pub(crate) type EmailVerificationCode = BoundedString<6, 6>;
@@ -30,6 +49,15 @@ pub(crate) type EmailVerificationCode = BoundedString<6, 6>;
 /// A CAPTCHA token.
 pub(crate) type CaptchaToken = BoundedString<1, 2048>;
 
+/// A user's name for one of their WebAuthn credentials.
+pub(crate) type WebAuthnCredentialName = BoundedString<1, 64>;
+
+/// A user's name for one of their personal API tokens.
+pub(crate) type ApiTokenName = BoundedString<1, 64>;
+
+/// A user's name for one of their OAuth clients.
+pub(crate) type OAuthClientName = BoundedString<1, 64>;
+
 /// A [`String`] newtype that guarantees its length is within a certain range.
 #[derive(
     Deref,
@@ -135,6 +163,56 @@ impl FileName {
     /// Forbidding these simplifies some implementation details when handling file paths, and it
     /// improves interoperability since these are the illegal characters for POSIX pathnames.
     const ILLEGAL_CHARS: [u8; 2] = [b'\0', b'/'];
+
+    /// The additional characters Windows doesn't allow in file names, checked in strict mode.
+    const STRICT_ILLEGAL_CHARS: [char; 8] = ['<', '>', ':', '"', '\\', '|', '?', '*'];
+
+    /// The names Windows reserves for devices, checked case-insensitively in strict mode. Windows
+    /// reserves these even with an extension (e.g. `NUL.txt`).
+    const RESERVED_NAMES: [&str; 22] = [
+        "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+        "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+    ];
+
+    /// Checks that the name is also valid on Windows and macOS, for users who opt into strict file
+    /// names because they sync their files to those systems.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the name contains a control character or a character Windows doesn't
+    /// allow, ends with a dot or space, or is a name Windows reserves.
+    pub(crate) fn check_strict(&self) -> Result<(), FileNameError> {
+        for (i, char) in self.char_indices() {
+            if char.is_control() {
+                return Err(FileNameError::ControlChar(i, char));
+            }
+
+            if Self::STRICT_ILLEGAL_CHARS.contains(&char) {
+                return Err(FileNameError::IllegalChar(i, char));
+            }
+        }
+
+        if let Some(char) = self.chars().next_back()
+            && (char == '.' || char == ' ')
+        {
+            return Err(FileNameError::TrailingChar(char));
+        }
+
+        let stem = self
+            .split('.')
+            .next()
+            .unwrap_or_default()
+            .trim_end_matches(' ');
+
+        if Self::RESERVED_NAMES
+            .iter()
+            .any(|reserved_name| stem.eq_ignore_ascii_case(reserved_name))
+        {
+            return Err(FileNameError::ReservedName);
+        }
+
+        Ok(())
+    }
 }
 
 /// An error constructing a [`FileName`].
@@ -144,15 +222,34 @@ pub(crate) enum FileNameError {
     #[error(transparent)]
     Bounds(#[from] BoundedStringError<{ FileName::MIN_LENGTH }, { FileName::MAX_LENGTH }>),
 
+    /// The value contains a control character, which strict mode doesn't allow.
+    #[error("control character {1:?} at position {0}")]
+    ControlChar(usize, char),
+
     /// The value contains an illegal character.
     #[error("illegal character {1:?} at position {0}")]
     IllegalChar(usize, char),
+
+    /// The value is a name Windows reserves for devices, which strict mode doesn't allow.
+    #[error("name reserved by Windows")]
+    ReservedName,
+
+    /// The value ends with a dot or space, which strict mode doesn't allow.
+    #[error("trailing {0:?} not allowed")]
+    TrailingChar(char),
 }
 
 impl TryFrom<String> for FileName {
     type Error = FileNameError;
 
     fn try_from(value: String) -> Result<Self, Self::Error> {
+        // Normalize to NFC so names that look the same can't coexist as different strings.
+        let value = if is_nfc(&value) {
+            value
+        } else {
+            value.nfc().collect()
+        };
+
         let value: BoundedString<_, _> = value.try_into()?;
 
         if let Some((i, byte)) = value
@@ -337,6 +434,269 @@ impl FromStr for TotpSecret {
     }
 }
 
+/// Binary data decoded from `base64url` (without padding), such as the data WebAuthn clients
+/// return.
+#[derive(Deref, AsRef, DeserializeFromStr, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+#[as_ref(forward)]
+pub(crate) struct Base64UrlBytes(Vec<u8>);
+
+impl FromStr for Base64UrlBytes {
+    type Err = base64::DecodeError;
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        Ok(Self(URL_SAFE_NO_PAD.decode(str)?))
+    }
+}
+
+/// A SHA-256 digest, encoded in either hexadecimal or RFC-4648-compliant Base64.
+#[derive(Deref, AsRef, DeserializeFromStr, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub(crate) struct Sha256DigestInput([u8; 32]);
+
+impl Sha256DigestInput {
+    /// The number of hexadecimal characters needed to encode a `Sha256DigestInput`.
+    const HEX_LENGTH: usize = 64;
+
+    /// The number of Base64 characters (including padding) needed to encode a `Sha256DigestInput`.
+    const BASE64_LENGTH: usize = 44;
+}
+
+/// An error constructing a [`Sha256DigestInput`].
+#[derive(Error, Copy, Clone, Debug)]
+#[non_exhaustive]
+pub(crate) enum Sha256DigestInputError {
+    /// The hexadecimal encoding was invalid.
+    #[error("invalid hexadecimal encoding")]
+    Hex,
+
+    /// The Base64 encoding was invalid.
+    #[error("invalid Base64 encoding (according to RFC 4648)")]
+    Base64,
+
+    /// The encoded string length is incorrect.
+    #[error(
+        "invalid encoded string length {}, expected {} (hexadecimal) or {} (Base64)",
+        .0,
+        Sha256DigestInput::HEX_LENGTH,
+        Sha256DigestInput::BASE64_LENGTH,
+    )]
+    EncodedLength(usize),
+}
+
+impl FromStr for Sha256DigestInput {
+    type Err = Sha256DigestInputError;
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        let mut digest = [0; 32];
+
+        match str.len() {
+            Self::HEX_LENGTH => {
+                // `u8::from_str_radix` allows a leading `+`, so validate the digits beforehand.
+                if !str.bytes().all(|char| char.is_ascii_hexdigit()) {
+                    return Err(Sha256DigestInputError::Hex);
+                }
+
+                for (byte, pair) in digest.iter_mut().zip(str.as_bytes().chunks_exact(2)) {
+                    let pair =
+                        std::str::from_utf8(pair).map_err(|_| Sha256DigestInputError::Hex)?;
+                    *byte =
+                        u8::from_str_radix(pair, 16).map_err(|_| Sha256DigestInputError::Hex)?;
+                }
+            }
+            Self::BASE64_LENGTH => {
+                let length = STANDARD
+                    .decode_slice(str, &mut digest)
+                    .map_err(|_| Sha256DigestInputError::Base64)?;
+
+                if length != digest.len() {
+                    return Err(Sha256DigestInputError::Base64);
+                }
+            }
+            length => return Err(Sha256DigestInputError::EncodedLength(length)),
+        }
+
+        Ok(Self(digest))
+    }
+}
+
+/// A pattern matching MIME types by their essence (without parameters), either exactly (e.g.,
+/// `image/png`) or by their top-level type (e.g., `image/*`). Normalized to lowercase.
+#[derive(
+    Deref,
+    AsRef,
+    Display,
+    DeserializeFromStr,
+    SerializeDisplay,
+    Clone,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Debug,
+)]
+#[as_ref(forward)]
+pub(crate) struct MediaTypePattern(String);
+
+impl MediaTypePattern {
+    /// A `MediaTypePattern`'s maximum length.
+    const MAX_LENGTH: usize = 255;
+
+    /// Returns whether a MIME type matches the pattern. Any parameters on the MIME type (e.g.,
+    /// `charset`) are ignored.
+    pub(crate) fn matches(&self, mime_type: &str) -> bool {
+        let essence = mime_type
+            .split_once(';')
+            .map_or(mime_type, |(essence, _)| essence)
+            .trim()
+            .to_ascii_lowercase();
+
+        match self.0.strip_suffix('*') {
+            Some(type_prefix) => essence.starts_with(type_prefix),
+            None => essence == self.0,
+        }
+    }
+}
+
+/// An error constructing a [`MediaTypePattern`].
+#[derive(Error, Clone, Copy, Debug)]
+#[error("invalid media type pattern, expected `type/subtype` or `type/*`")]
+pub(crate) struct MediaTypePatternError;
+
+impl FromStr for MediaTypePattern {
+    type Err = MediaTypePatternError;
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        if str.len() > Self::MAX_LENGTH
+            || !regex!(r"^[A-Za-z0-9!#$&^_.+-]+/(?:[A-Za-z0-9!#$&^_.+-]+|\*)$").is_match(str)
+        {
+            return Err(MediaTypePatternError);
+        }
+
+        Ok(Self(str.to_ascii_lowercase()))
+    }
+}
+
+/// A URI an OAuth client can be redirected to with an authorization code. Must be HTTPS, HTTP on a
+/// loopback address, or a private-use scheme for native apps (RFC 8252), and can't have a
+/// fragment.
+#[derive(
+    Deref,
+    AsRef,
+    Display,
+    DeserializeFromStr,
+    SerializeDisplay,
+    Clone,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Debug,
+)]
+#[as_ref(forward)]
+pub(crate) struct OAuthRedirectUri(String);
+
+impl OAuthRedirectUri {
+    /// An `OAuthRedirectUri`'s maximum length.
+    const MAX_LENGTH: usize = 2048;
+}
+
+/// An error constructing an [`OAuthRedirectUri`].
+#[derive(Error, Clone, Copy, Debug)]
+#[error(
+    "invalid redirect URI, expected an absolute URI without a fragment using HTTPS, HTTP on a \
+    loopback address, or a private-use scheme"
+)]
+pub(crate) struct OAuthRedirectUriError;
+
+impl FromStr for OAuthRedirectUri {
+    type Err = OAuthRedirectUriError;
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        if str.len() > Self::MAX_LENGTH {
+            return Err(OAuthRedirectUriError);
+        }
+
+        let url = Url::parse(str).map_err(|_| OAuthRedirectUriError)?;
+
+        let is_scheme_allowed = match url.scheme() {
+            "https" => true,
+            "http" => matches!(url.host_str(), Some("localhost" | "127.0.0.1" | "[::1]")),
+            // Private-use schemes must be in reverse domain name notation (e.g.,
+            // `com.example.app`).
+            scheme => scheme.contains('.'),
+        };
+
+        if !is_scheme_allowed || url.fragment().is_some() {
+            return Err(OAuthRedirectUriError);
+        }
+
+        Ok(Self(str.to_owned()))
+    }
+}
+
+/// A set of scopes for an OAuth access token, encoded as space-separated scope names.
+#[derive(
+    Deref, DeserializeFromStr, SerializeDisplay, Clone, PartialEq, Eq, PartialOrd, Ord, Debug,
+)]
+pub(crate) struct OAuthScope(BTreeSet<ApiTokenScope>);
+
+impl FromIterator<ApiTokenScope> for OAuthScope {
+    fn from_iter<T: IntoIterator<Item = ApiTokenScope>>(iter: T) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+impl fmt::Display for OAuthScope {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let scope_names: Vec<&str> = self.0.iter().map(|&scope| scope.into()).collect();
+        f.write_str(&scope_names.join(" "))
+    }
+}
+
+/// An error constructing an [`OAuthScope`].
+#[derive(Error, Clone, Copy, Debug)]
+#[error("invalid scope, expected space-separated scope names")]
+pub(crate) struct OAuthScopeError;
+
+impl FromStr for OAuthScope {
+    type Err = OAuthScopeError;
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        let scopes = str
+            .split(' ')
+            .map(ApiTokenScope::from_str)
+            .collect::<Result<BTreeSet<_>, _>>()
+            .map_err(|_| OAuthScopeError)?;
+
+        Ok(Self(scopes))
+    }
+}
+
+/// A PKCE code challenge (RFC 7636) created with the `S256` method, which is a SHA-256 digest
+/// encoded in `base64url` (without padding).
+#[derive(Deref, AsRef, DeserializeFromStr, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+#[as_ref(forward)]
+pub(crate) struct PkceCodeChallenge(String);
+
+/// An error constructing a [`PkceCodeChallenge`].
+#[derive(Error, Clone, Copy, Debug)]
+#[error("invalid code challenge, expected a SHA-256 digest in `base64url`")]
+pub(crate) struct PkceCodeChallengeError;
+
+impl FromStr for PkceCodeChallenge {
+    type Err = PkceCodeChallengeError;
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        let mut digest = [0; 32];
+
+        match URL_SAFE_NO_PAD.decode_slice(str, &mut digest) {
+            Ok(32) if str.len() == 43 => Ok(Self(str.to_owned())),
+            _ => Err(PkceCodeChallengeError),
+        }
+    }
+}
+
 #[cfg(test)]
 #[expect(clippy::missing_errors_doc, reason = "See rust-lang/rust-clippy#13391")]
 mod tests {
@@ -462,4 +822,170 @@ mod tests {
 
         Ok(())
     }
+
+    /// Ensures hexadecimal and Base64 encodings of the same SHA-256 digest are equivalent.
+    #[test]
+    fn sha256_digest_encodings() -> anyhow::Result<()> {
+        let hex = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+        let uppercase_hex = hex.to_uppercase();
+        let base64 = "47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU=";
+
+        assert_eq!(
+            hex.parse::<Sha256DigestInput>()?,
+            base64.parse::<Sha256DigestInput>()?
+        );
+        assert_eq!(
+            hex.parse::<Sha256DigestInput>()?,
+            uppercase_hex.parse::<Sha256DigestInput>()?
+        );
+
+        let invalid_digests = [
+            "",
+            &hex[1..],
+            "g3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+            "+3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+            "47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU",
+            "47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuF==",
+        ];
+
+        for digest in invalid_digests {
+            assert!(
+                digest.parse::<Sha256DigestInput>().is_err(),
+                "parsing {digest:?}"
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn media_type_patterns() -> anyhow::Result<()> {
+        let png = "image/png".parse::<MediaTypePattern>()?;
+        assert!(png.matches("image/png"));
+        assert!(png.matches("IMAGE/PNG"));
+        assert!(!png.matches("image/pngx"));
+        assert!(!png.matches("image/jpeg"));
+
+        let image = "Image/*".parse::<MediaTypePattern>()?;
+        assert!(image.matches("image/png"));
+        assert!(image.matches("image/svg+xml"));
+        assert!(!image.matches("imagex/png"));
+        assert!(!image.matches("text/plain"));
+
+        let text = "text/plain".parse::<MediaTypePattern>()?;
+        assert!(text.matches("text/plain; charset=utf-8"));
+
+        for invalid in [
+            "",
+            "image",
+            "image/",
+            "*/*",
+            "image/png; charset=utf-8",
+            "a/b/c",
+        ] {
+            assert!(
+                invalid.parse::<MediaTypePattern>().is_err(),
+                "media type pattern {invalid:?} should be invalid",
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn file_names_normalized() -> Result<(), FileNameError> {
+        let decomposed = FileName::try_from("cafe\u{301}.txt".to_owned())?;
+        let composed = FileName::try_from("caf\u{e9}.txt".to_owned())?;
+
+        assert_eq!(decomposed, composed);
+        assert_eq!(decomposed.as_str(), "caf\u{e9}.txt");
+
+        Ok(())
+    }
+
+    #[test]
+    fn strict_file_names() -> Result<(), FileNameError> {
+        for name in [
+            "file.txt",
+            "CONSOLE",
+            "nul-device.txt",
+            ".hidden",
+            "caf\u{e9}",
+        ] {
+            assert!(
+                FileName::try_from(name.to_owned())?.check_strict().is_ok(),
+                "file name {name:?} should be allowed in strict mode",
+            );
+        }
+
+        for name in [
+            "CON",
+            "nul.txt",
+            "Com1 .tar.gz",
+            "trailing-dot.",
+            "trailing-space ",
+            "line\nbreak",
+            "tab\t",
+            "what?",
+            "a:b",
+            "back\\slash",
+        ] {
+            assert!(
+                FileName::try_from(name.to_owned())?.check_strict().is_err(),
+                "file name {name:?} should be invalid in strict mode",
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn oauth_redirect_uris() {
+        for uri in [
+            "https://example.com/callback",
+            "https://example.com/callback?query=1",
+            "http://localhost:8080/callback",
+            "http://127.0.0.1/callback",
+            "http://[::1]:8080/callback",
+            "com.example.app:/callback",
+        ] {
+            assert!(
+                uri.parse::<OAuthRedirectUri>().is_ok(),
+                "redirect URI {uri:?} should be valid",
+            );
+        }
+
+        for uri in [
+            "",
+            "/callback",
+            "http://example.com/callback",
+            "https://example.com/callback#fragment",
+            "javascript:alert(1)",
+            "data:text/html,hi",
+        ] {
+            assert!(
+                uri.parse::<OAuthRedirectUri>().is_err(),
+                "redirect URI {uri:?} should be invalid",
+            );
+        }
+    }
+
+    #[test]
+    fn oauth_scopes() -> Result<(), OAuthScopeError> {
+        let scope = "writeFiles readFiles writeFiles".parse::<OAuthScope>()?;
+        assert_eq!(
+            scope.iter().copied().collect::<Vec<_>>(),
+            [ApiTokenScope::ReadFiles, ApiTokenScope::WriteFiles],
+        );
+        assert_eq!(scope.to_string(), "readFiles writeFiles");
+
+        for invalid in ["", "readFiles  writeFiles", "read_files", "unknown"] {
+            assert!(
+                invalid.parse::<OAuthScope>().is_err(),
+                "scope {invalid:?} should be invalid",
+            );
+        }
+
+        Ok(())
+    }
 }
@@ -4,14 +4,19 @@ use serde::Deserialize;
 use sqlx::PgTransaction;
 use strum::IntoEnumIterator;
 use strum_macros::{EnumDiscriminants, EnumIter};
+use tokio::task;
 
 use crate::{
     api::{
         self,
-        validation::{Otp, UserPassword},
+        db_helpers::consume_webauthn_challenge,
+        extract::ClientIp,
+        rate_limit::{self, Subject},
+        validation::{BACKUP_CODE_LENGTH, Base64UrlBytes, Otp, UserPassword},
     },
-    crypto::{verify_hash, verify_totp},
+    crypto::{decrypt_totp_secret, find_matching_hash, verify_hash, verify_totp},
     db::{TxError, TxResult},
+    webauthn::{self, Ceremony, RELYING_PARTY_ID},
 };
 
 /// User credentials exclusively for first-factor authentication.
@@ -35,13 +40,45 @@ pub(crate) enum ExclusiveSecondFactorCredentials {
         /// The one-time password.
         otp: Otp,
     },
+
+    /// Credentials for WebAuthn-based 2FA using a security key.
+    WebAuthn {
+        /// The security key's assertion.
+        webauthn: WebAuthnAssertion,
+    },
+}
+
+/// A WebAuthn assertion returned by `navigator.credentials.get`, signing a challenge issued by the
+/// server.
+#[derive(Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub(crate) struct WebAuthnAssertion {
+    /// The ID of the credential that made the assertion.
+    pub credential_id: Base64UrlBytes,
+
+    /// The client data the assertion signs, as JSON.
+    #[serde(rename = "clientDataJSON")]
+    pub client_data_json: Base64UrlBytes,
+
+    /// The authenticator data the assertion signs.
+    pub authenticator_data: Base64UrlBytes,
+
+    /// The assertion's signature.
+    pub signature: Base64UrlBytes,
 }
 
 /// User credentials for multi-factor authentication that can't be divided into separate first
 /// and second authentication factors.
 #[derive(Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
 #[serde(rename_all = "camelCase", untagged)]
-pub(crate) enum ExclusiveMultiFactorCredentials {}
+pub(crate) enum ExclusiveMultiFactorCredentials {
+    /// Credentials for WebAuthn-based MFA using a passkey, which is something the user has and
+    /// verifies the user itself (e.g., by PIN or biometrics).
+    Passkey {
+        /// The passkey's assertion.
+        passkey: WebAuthnAssertion,
+    },
+}
 
 /// User credentials which are required only if the user has them enabled.
 #[derive(Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
@@ -104,6 +141,32 @@ pub(crate) enum MultiFactorCredentials {
     Multi(ExclusiveMultiFactorCredentials),
 }
 
+impl MultiFactorCredentials {
+    /// Finds the ID of the user the credentials belong to if they identify the user on their own,
+    /// which passkeys do. Doesn't verify the credentials.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a database query fails.
+    pub(crate) async fn find_user_id(
+        &self,
+        tx: &mut PgTransaction<'static>,
+    ) -> sqlx::Result<Option<Vec<u8>>> {
+        match self {
+            Self::FirstAndSecond { .. } => Ok(None),
+            Self::Multi(ExclusiveMultiFactorCredentials::Passkey { passkey }) => {
+                sqlx::query_scalar!(
+                    "SELECT user_id FROM webauthn_credentials
+                        WHERE id = $1 AND passkey",
+                    passkey.credential_id.as_slice(),
+                )
+                .fetch_optional(tx.as_mut())
+                .await
+            }
+        }
+    }
+}
+
 impl SecondFactorAuthMethod {
     /// Checks if the 2FA method is enabled for a user.
     ///
@@ -124,6 +187,15 @@ impl SecondFactorAuthMethod {
             .fetch_optional(tx.as_mut())
             .await?
             .is_some()),
+            Self::WebAuthn => Ok(sqlx::query!(
+                "SELECT TRUE AS exists FROM webauthn_credentials
+                    WHERE user_id = $1 AND NOT passkey
+                    LIMIT 1",
+                user_id.as_ref(),
+            )
+            .fetch_optional(tx.as_mut())
+            .await?
+            .is_some()),
         }
     }
 }
@@ -141,6 +213,45 @@ pub(crate) trait VerifyCredentials {
         tx: &mut PgTransaction<'static>,
         user_id: &UserId,
     ) -> TxResult<(), api::Error>;
+
+    /// Verifies the credentials like [`Self::verify`], but only if neither the user nor the client
+    /// has failed to authenticate too many times recently. Incorrect credentials count as a failure
+    /// for both.
+    ///
+    /// Routes should always call this rather than [`Self::verify`], so credentials can't be
+    /// guessed by brute force.
+    ///
+    /// # Errors
+    ///
+    /// Returns an API error if verification fails or either the user or the client is locked out.
+    /// Returns a database error if a database query fails.
+    async fn verify_limited<UserId: AsRef<[u8]>>(
+        &self,
+        tx: &mut PgTransaction<'static>,
+        user_id: &UserId,
+        ClientIp(client_ip): ClientIp,
+    ) -> TxResult<(), api::Error> {
+        let subjects = [Subject::User(user_id.as_ref()), Subject::Client(client_ip)];
+
+        // The attempt is counted before it's verified, so concurrent attempts can't all get past
+        // the limit. Returning without failing it drops it, which uncounts it.
+        let attempt = rate_limit::begin_attempt(&subjects).await?;
+
+        // The error isn't kept across the `await` below since API errors aren't `Send`.
+        let is_second_factor_wrong = match self.verify(tx, user_id).await {
+            Err(TxError::Abort(api::Error::FirstFactorCredentialsWrong)) => false,
+            Err(TxError::Abort(api::Error::SecondFactorCredentialsWrong)) => true,
+            result => return result,
+        };
+
+        attempt.fail().await?;
+
+        Err(TxError::Abort(if is_second_factor_wrong {
+            api::Error::SecondFactorCredentialsWrong
+        } else {
+            api::Error::FirstFactorCredentialsWrong
+        }))
+    }
 }
 
 impl VerifyCredentials for ExclusiveFirstFactorCredentials {
@@ -176,22 +287,42 @@ impl VerifyCredentials for ExclusiveSecondFactorCredentials {
         user_id: &UserId,
     ) -> TxResult<(), api::Error> {
         match self {
-            Self::Totp { otp } => {
-                if sqlx::query!(
-                    "UPDATE totp
-                        SET unused_backup_codes = array_remove(unused_backup_codes, $1)
-                        WHERE user_id = $2 AND $1 = ANY (unused_backup_codes)",
-                    otp.as_str(),
+            // Only codes the length of a backup code are checked against backup codes, since that
+            // means checking every one of the user's backup code hashes, which is slow.
+            Self::Totp { otp } if otp.len() == BACKUP_CODE_LENGTH => {
+                if let Some(totp) = sqlx::query!(
+                    "SELECT unused_backup_code_hashes FROM totp
+                        WHERE user_id = $1",
                     user_id.as_ref(),
                 )
-                .execute(tx.as_mut())
+                .fetch_optional(tx.as_mut())
                 .await?
-                .rows_affected()
-                    > 0
                 {
-                    return Ok(());
+                    // Hashing with Argon2 once per backup code would stall the async runtime's
+                    // worker thread for too long.
+                    let matching_index = task::spawn_blocking({
+                        let otp = otp.clone();
+                        let hashes = totp.unused_backup_code_hashes.clone();
+                        move || find_matching_hash(&otp, &hashes)
+                    })
+                    .await?;
+
+                    if let Some(i) = matching_index {
+                        sqlx::query!(
+                            "UPDATE totp
+                                SET unused_backup_code_hashes = array_remove(unused_backup_code_hashes, $1)
+                                WHERE user_id = $2",
+                            totp.unused_backup_code_hashes[i],
+                            user_id.as_ref(),
+                        )
+                        .execute(tx.as_mut())
+                        .await?;
+
+                        return Ok(());
+                    }
                 }
-
+            }
+            Self::Totp { otp } => {
                 if let Some(totp) = sqlx::query!(
                     "UPDATE totp
                         SET otp_used_2nd_to_last = otp_used_last,
@@ -199,23 +330,104 @@ impl VerifyCredentials for ExclusiveSecondFactorCredentials {
                         WHERE user_id = $2
                             AND $1 IS DISTINCT FROM otp_used_last
                             AND $1 IS DISTINCT FROM otp_used_2nd_to_last
-                        RETURNING secret",
+                        RETURNING secret, secret_key_id",
                     otp.as_str(),
                     user_id.as_ref(),
                 )
                 .fetch_optional(tx.as_mut())
                 .await?
-                    && verify_totp(otp, &totp.secret)
+                    && verify_totp(
+                        otp,
+                        &decrypt_totp_secret(&totp.secret_key_id, &totp.secret, user_id)?,
+                    )
                 {
                     return Ok(());
                 }
             }
+            Self::WebAuthn { webauthn } => {
+                if webauthn.verify(tx, user_id, false).await? {
+                    return Ok(());
+                }
+            }
         }
 
         Err(TxError::Abort(api::Error::SecondFactorCredentialsWrong))
     }
 }
 
+impl WebAuthnAssertion {
+    /// Checks whether the assertion is valid for one of a user's WebAuthn credentials (either
+    /// passkeys or security keys) and signs an issued challenge, using up the challenge.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a database query fails.
+    async fn verify<UserId: AsRef<[u8]>>(
+        &self,
+        tx: &mut PgTransaction<'static>,
+        user_id: &UserId,
+        passkey: bool,
+    ) -> TxResult<bool, api::Error> {
+        let Ok(challenge) = webauthn::verify_client_data(&self.client_data_json, Ceremony::Get)
+        else {
+            return Ok(false);
+        };
+
+        // Passkey challenges are issued before the user is known, so they aren't for any user.
+        let challenge_user_id = (!passkey).then_some(user_id.as_ref());
+
+        if !consume_webauthn_challenge(tx, &challenge, challenge_user_id).await? {
+            return Ok(false);
+        }
+
+        let Some(credential) = sqlx::query!(
+            "SELECT public_key, sign_count FROM webauthn_credentials
+                WHERE id = $1 AND user_id = $2 AND passkey = $3",
+            self.credential_id.as_slice(),
+            user_id.as_ref(),
+            passkey,
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        else {
+            return Ok(false);
+        };
+
+        let Ok(sign_count) = webauthn::verify_assertion(
+            &RELYING_PARTY_ID,
+            &credential.public_key,
+            &self.client_data_json,
+            &self.authenticator_data,
+            &self.signature,
+            // A passkey must verify the user, since that's what makes it multi-factor.
+            passkey,
+        ) else {
+            return Ok(false);
+        };
+
+        // A signature counter that didn't increase suggests the authenticator was cloned, unless
+        // the authenticator doesn't have a counter, in which case it's always 0.
+        let sign_count = i64::from(sign_count);
+
+        if (sign_count != 0 || credential.sign_count != 0) && sign_count <= credential.sign_count {
+            return Ok(false);
+        }
+
+        sqlx::query!(
+            "UPDATE webauthn_credentials
+                SET sign_count = $1,
+                    last_used_at = now()
+                WHERE id = $2",
+            sign_count,
+            self.credential_id.as_slice(),
+        )
+        .execute(tx.as_mut())
+        .await?;
+
+        Ok(true)
+    }
+}
+
 impl VerifyCredentials for IfEnabled<ExclusiveSecondFactorCredentials> {
     async fn verify<UserId: AsRef<[u8]>>(
         &self,
@@ -239,10 +451,18 @@ impl VerifyCredentials for IfEnabled<ExclusiveSecondFactorCredentials> {
 impl VerifyCredentials for ExclusiveMultiFactorCredentials {
     async fn verify<UserId: AsRef<[u8]>>(
         &self,
-        _tx: &mut PgTransaction<'static>,
-        _user_id: &UserId,
+        tx: &mut PgTransaction<'static>,
+        user_id: &UserId,
     ) -> TxResult<(), api::Error> {
-        match *self {}
+        match self {
+            Self::Passkey { passkey } => {
+                if passkey.verify(tx, user_id, true).await? {
+                    return Ok(());
+                }
+            }
+        }
+
+        Err(TxError::Abort(api::Error::FirstFactorCredentialsWrong))
     }
 }
 
@@ -1,24 +1,112 @@
 //! A web server for user-uploaded content. File Garden exposes this via `https://file.garden/`.
 
-use std::borrow::Cow;
+use std::{borrow::Cow, net::IpAddr};
 
+use askama::Template;
 use axum::{
-    extract::Request,
+    body::{Body, to_bytes},
+    extract::{FromRequestParts, Request},
     http::{
-        Method, StatusCode,
-        header::{ACCESS_CONTROL_ALLOW_ORIGIN, ALLOW, CONTENT_SECURITY_POLICY},
+        HeaderName, HeaderValue, Method, StatusCode,
+        header::{
+            ACCESS_CONTROL_ALLOW_ORIGIN, ALLOW, CACHE_CONTROL, CONTENT_DISPOSITION, CONTENT_LENGTH,
+            CONTENT_SECURITY_POLICY, CONTENT_TYPE, COOKIE, LAST_MODIFIED, X_CONTENT_TYPE_OPTIONS,
+        },
     },
 };
-use percent_encoding::{percent_decode_str, utf8_percent_encode};
+use base64::{Engine as _, engine::general_purpose::STANDARD};
+use chrono::{DateTime, Utc};
+use percent_encoding::{NON_ALPHANUMERIC, percent_decode_str, utf8_percent_encode};
+use unicode_normalization::UnicodeNormalization;
 
-use crate::{WEBSITE_ORIGIN, percent_encoding::COMPONENT_IGNORING_SLASH, response::Response};
+use crate::{
+    WEBSITE_ORIGIN,
+    api::{
+        cookie::{CookieWrapper, ShareUnlockCookie},
+        extract::ClientIp,
+        rate_limit::Subject,
+    },
+    crypto::{hash_without_salt, verify_hash},
+    db::{self, TxResult},
+    id::{Id, Token},
+    percent_encoding::COMPONENT_IGNORING_SLASH,
+    processing::{self, Fit, ImageOutputFormat, MAX_RESIZE_DIMENSION, Resize, SourceContent},
+    response::{Response, format_http_date},
+    storage,
+};
+
+/// The `Repr-Digest` header name (RFC 9530).
+const REPR_DIGEST: HeaderName = HeaderName::from_static("repr-digest");
+
+/// The `Want-Repr-Digest` header name (RFC 9530).
+const WANT_REPR_DIGEST: HeaderName = HeaderName::from_static("want-repr-digest");
+
+/// How many incorrect passwords a client can enter for a share within 15 minutes before its further
+/// attempts are refused.
+const MAX_UNLOCK_ATTEMPTS: i64 = 10;
+
+/// The maximum size of an unlock form submission's body in bytes.
+const MAX_UNLOCK_BODY_SIZE: usize = 4096;
+
+/// A file to respond with.
+struct FileToServe {
+    /// The file's ID.
+    id: Vec<u8>,
+
+    /// The IDs of the file's ancestor folders.
+    parent_id_path: Vec<Vec<u8>>,
+
+    /// The file's content ID.
+    content_id: Vec<u8>,
+
+    /// The SHA-256 hash of the file's content.
+    hash: Vec<u8>,
+
+    /// The number of parts the file's content is stored in.
+    part_count: i32,
 
-/// The start of a file ID query parameter.
-const FILE_ID_QUERY_PREFIX: &str = "_id=";
+    /// The size of the file's content in bytes.
+    size: i64,
+
+    /// The file's MIME type.
+    r#type: String,
+
+    /// Whether the file's type is dangerous to render inline.
+    dangerous: bool,
+
+    /// When the file was last modified.
+    modified_at: DateTime<Utc>,
+}
+
+/// An unexpired share that a file can be accessed through.
+struct Share {
+    /// The ID of the shared file or folder.
+    id: Vec<u8>,
+
+    /// Whether the share requires a password.
+    password_protected: bool,
+
+    /// Whether the visitor has entered the share's password.
+    unlocked: bool,
+}
+
+/// A page asking for a password-protected share's password.
+#[derive(Template, Debug)]
+#[template(path = "content/share_unlock.html")]
+struct ShareUnlockPage<'a> {
+    /// An error message about the previous attempt, if any.
+    error: Option<&'a str>,
+}
 
 /// The service function to handle incoming requests for user-uploaded content.
-pub(super) fn handle(request: Request) -> Response {
-    let (request, _body) = request.into_parts();
+pub(super) async fn handle(request: Request) -> Response {
+    let (mut request, body) = request.into_parts();
+
+    // This is only needed to count failed attempts to unlock shares.
+    let client_ip = ClientIp::from_request_parts(&mut request, &())
+        .await
+        .ok()
+        .map(|ClientIp(client_ip)| client_ip);
     let mut response = Response::new();
 
     response
@@ -28,7 +116,11 @@ pub(super) fn handle(request: Request) -> Response {
             "default-src 'self' 'unsafe-eval' 'unsafe-inline' blob: data: mediastream:",
         );
 
-    if !(request.method == Method::GET || request.method == Method::HEAD) {
+    // `POST` is only used to unlock password-protected shares.
+    if !(request.method == Method::GET
+        || request.method == Method::HEAD
+        || request.method == Method::POST)
+    {
         let status = if request.method == Method::OPTIONS {
             StatusCode::NO_CONTENT
         } else {
@@ -37,7 +129,7 @@ pub(super) fn handle(request: Request) -> Response {
 
         response
             .status(status)
-            .header_valid(ALLOW, "GET, HEAD, OPTIONS");
+            .header_valid(ALLOW, "GET, HEAD, OPTIONS, POST");
 
         return response;
     }
@@ -57,17 +149,28 @@ pub(super) fn handle(request: Request) -> Response {
         return response.plain_error(StatusCode::BAD_REQUEST);
     }
 
+    // File names are stored normalized to NFC, so the path must be too. A path that isn't is
+    // redirected to its normalized form below.
+    let path: String = path.nfc().collect();
+
     let normalized_encoded_path: Cow<str> =
         utf8_percent_encode(&path, COMPONENT_IGNORING_SLASH).into();
 
     let query = request.uri.query();
 
-    if encoded_path != normalized_encoded_path {
-        // Redirect to the same URI with normalized path encoding. This reduces how many URLs must
-        // be purged from the CDN's cache when a file changes. It's impossible to purge every
-        // possible variation of encoding for a URL.
+    let Some(content_query) = ContentQuery::parse(query) else {
+        return response.plain_error(StatusCode::BAD_REQUEST);
+    };
+
+    let normalized_query = content_query.to_normalized();
+    let normalized_query = (!normalized_query.is_empty()).then_some(normalized_query.as_str());
 
-        let normalized_uri = concat_path_and_query(&normalized_encoded_path, query);
+    if encoded_path != normalized_encoded_path || query != normalized_query {
+        // Redirect to the same URI with normalized path encoding and query parameter order. This
+        // reduces how many URLs must be purged from the CDN's cache when a file changes. It's
+        // impossible to purge every possible variation of encoding for a URL.
+
+        let normalized_uri = concat_path_and_query(&normalized_encoded_path, normalized_query);
 
         return response.permanent_redirect(&normalized_uri);
     }
@@ -80,26 +183,532 @@ pub(super) fn handle(request: Request) -> Response {
         return response.plain_error(StatusCode::BAD_REQUEST);
     };
 
-    let file_id = match query {
-        Some(query) => query
-            .split('&')
-            .find_map(|param| param.strip_prefix(FILE_ID_QUERY_PREFIX)),
+    let Ok(owner_id) = user_identifier.parse::<Id>() else {
+        return response.plain_error(StatusCode::NOT_FOUND);
+    };
+
+    let file_id = match content_query.file_id.map(str::parse::<Id>) {
+        Some(Ok(file_id)) => Some(file_id),
+        Some(Err(_)) => return response.plain_error(StatusCode::NOT_FOUND),
         None => None,
     };
 
-    // response
-    //     .header_valid(CONTENT_LENGTH, 0)
-    //     .header_valid(CONTENT_TYPE, "")
-    //     .header_valid(LAST_MODIFIED, "");
+    let mut parent_name_path: Vec<String> = file_path.split('/').map(String::from).collect();
+    let mut file_name = parent_name_path
+        .pop()
+        .expect("split string should have at least one segment");
+
+    let unlock_token = request
+        .headers
+        .get(COOKIE)
+        .and_then(|header_value| str::from_utf8(header_value.as_bytes()).ok())
+        .and_then(ShareUnlockCookie::from_header)
+        .and_then(|unlock_cookie| unlock_cookie.as_ref().value().parse::<Token>().ok());
+    let unlock_token_hash = unlock_token.as_ref().map(hash_without_salt);
+
+    let (mut file, shares) = match db::transaction!(async |tx| -> TxResult<_> {
+        let file = match &file_id {
+            Some(file_id) => {
+                sqlx::query_as!(
+                    FileToServe,
+                    r#"SELECT
+                        files.id,
+                        files.parent_id_path,
+                        files.content_id,
+                        file_contents.hash AS "hash!",
+                        file_contents.part_count,
+                        files.size,
+                        files.type,
+                        files.dangerous,
+                        files.modified_at
+                        FROM files
+                        INNER JOIN file_contents ON file_contents.id = files.content_id
+                        WHERE files.owner_id = $1 AND files.id = $2 AND files.complete"#,
+                    owner_id.as_slice(),
+                    file_id.as_slice(),
+                )
+                .fetch_optional(tx.as_mut())
+                .await?
+            }
+            None => {
+                sqlx::query_as!(
+                    FileToServe,
+                    r#"SELECT
+                        files.id,
+                        files.parent_id_path,
+                        files.content_id,
+                        file_contents.hash AS "hash!",
+                        file_contents.part_count,
+                        files.size,
+                        files.type,
+                        files.dangerous,
+                        files.modified_at
+                        FROM files
+                        INNER JOIN file_contents ON file_contents.id = files.content_id
+                        WHERE files.owner_id = $1 AND files.parent_name_path = $2
+                            AND files.name = $3 AND files.complete"#,
+                    owner_id.as_slice(),
+                    parent_name_path.as_slice(),
+                    file_name,
+                )
+                .fetch_optional(tx.as_mut())
+                .await?
+            }
+        };
+
+        let Some(file) = file else {
+            return Ok(None);
+        };
+
+        // A file is only accessible through the shares of it or its ancestor folders that haven't
+        // expired. They're ordered from nearest to farthest.
+        let shares = sqlx::query_as!(
+            Share,
+            r#"SELECT
+                shares.id AS "id!",
+                shares.share_password_hash IS NOT NULL AS "password_protected!",
+                EXISTS (
+                    SELECT 1 FROM share_unlocks
+                        WHERE share_unlocks.token_hash = $3
+                            AND share_unlocks.share_id = shares.id
+                            AND share_unlocks.expires_at > now()
+                ) AS "unlocked!"
+                FROM (
+                    SELECT id, parent_id_path, shared, share_expires_at, share_password_hash
+                        FROM files
+                        WHERE id = $1 AND complete
+                    UNION ALL
+                    SELECT id, parent_id_path, shared, share_expires_at, share_password_hash
+                        FROM folders
+                        WHERE id = ANY($2)
+                ) AS shares
+                WHERE shares.shared
+                    AND (shares.share_expires_at IS NULL OR shares.share_expires_at > now())
+                ORDER BY cardinality(shares.parent_id_path) DESC"#,
+            file.id,
+            file.parent_id_path.as_slice(),
+            unlock_token_hash.as_ref().map(AsRef::as_ref),
+        )
+        .fetch_all(tx.as_mut())
+        .await?;
+
+        Ok(Some((file, shares)))
+    })
+    .await
+    {
+        Ok(Some((file, shares))) if !shares.is_empty() => (file, shares),
+        Ok(_) => return response.plain_error(StatusCode::NOT_FOUND),
+        Err(_) => return response.plain_error(StatusCode::INTERNAL_SERVER_ERROR),
+    };
+
+    // Any share without a password, or with one the visitor has entered, grants access. Otherwise,
+    // the visitor has to unlock the nearest share.
+    let is_password_protected = shares.iter().any(|share| share.password_protected);
+    let locked_share = if shares
+        .iter()
+        .any(|share| !share.password_protected || share.unlocked)
+    {
+        None
+    } else {
+        shares.into_iter().next()
+    };
+
+    let uri = concat_path_and_query(encoded_path, query);
+
+    if request.method == Method::POST {
+        let Some(share) = locked_share else {
+            return response.see_other(&uri);
+        };
+
+        let Some(client_ip) = client_ip else {
+            return response.plain_error(StatusCode::INTERNAL_SERVER_ERROR);
+        };
+
+        return unlock(response, &uri, body, &share.id, client_ip, unlock_token).await;
+    }
+
+    if locked_share.is_some() {
+        return unlock_page(response, &request.method, StatusCode::FORBIDDEN, None);
+    }
+
+    // Password-protected content must not be cached by the CDN, or anyone could get it from there.
+    if is_password_protected {
+        response.header_valid(CACHE_CONTROL, "private");
+    }
+
+    if let Some(resize) = content_query.resize()
+        && processing::is_decodable_image(&file.r#type)
+        && !file.dangerous
+    {
+        let source = SourceContent {
+            id: file.content_id.clone(),
+            hash: file.hash.clone(),
+            part_count: file.part_count,
+            size: file.size,
+        };
+
+        // If the image can't be resized, the original file is served instead.
+        match processing::resize(&source, resize).await {
+            Ok(Some(derived)) => {
+                file.content_id = derived.content_id;
+                file.hash = derived.hash;
+                file.part_count = derived.part_count;
+                file.size = derived.size;
+                file.r#type = derived.r#type;
+
+                file_name = match file_name.rsplit_once('.') {
+                    Some((stem, _)) if !stem.is_empty() => stem.to_owned(),
+                    _ => file_name,
+                };
+                file_name.push('.');
+                file_name.push_str(resize.format.extension());
+            }
+            Ok(None) => {}
+            Err(_) => return response.plain_error(StatusCode::INTERNAL_SERVER_ERROR),
+        }
+    }
+
+    // Files that could run scripts on the content origin are downloaded rather than rendered, so
+    // viewers of shared files are protected from malicious scripts.
+    let disposition = if file.dangerous {
+        "attachment"
+    } else {
+        "inline"
+    };
+
+    response
+        .header_valid(CONTENT_TYPE, file.r#type)
+        .header_valid(CONTENT_LENGTH, file.size)
+        .header_valid(
+            CONTENT_DISPOSITION,
+            format!(
+                "{disposition}; filename*=UTF-8''{}",
+                utf8_percent_encode(&file_name, NON_ALPHANUMERIC),
+            ),
+        )
+        .header_valid(X_CONTENT_TYPE_OPTIONS, "nosniff")
+        .header_valid(LAST_MODIFIED, format_http_date(file.modified_at));
+
+    if wants_sha256_digest(request.headers.get(WANT_REPR_DIGEST)) {
+        response.header_valid(
+            REPR_DIGEST,
+            format!("sha-256=:{}:", STANDARD.encode(&file.hash)),
+        );
+    }
 
     if request.method == Method::HEAD {
         return response;
     }
 
-    response.body(format!(
-        "{user_identifier} - {file_path} - {}",
-        file_id.unwrap_or("None")
-    ))
+    response.body(Body::from_stream(storage::read_parts(
+        file.content_id,
+        file.part_count,
+    )))
+}
+
+/// The result of trying to unlock a password-protected share.
+enum UnlockOutcome {
+    /// The password was correct.
+    Unlocked,
+
+    /// The password was incorrect.
+    PasswordWrong,
+
+    /// Too many incorrect passwords were entered recently, so the password wasn't checked.
+    TooManyAttempts,
+}
+
+/// Handles a submission of the form to unlock a password-protected share. If the password is
+/// correct, the visitor gets a cookie that unlocks the share and is redirected back to the file.
+async fn unlock(
+    mut response: Response,
+    uri: &str,
+    body: Body,
+    share_id: &[u8],
+    client_ip: IpAddr,
+    token: Option<Token>,
+) -> Response {
+    let Ok(body) = to_bytes(body, MAX_UNLOCK_BODY_SIZE).await else {
+        return response.plain_error(StatusCode::PAYLOAD_TOO_LARGE);
+    };
+
+    let Some(password) = form_password(&body) else {
+        return response.plain_error(StatusCode::BAD_REQUEST);
+    };
+
+    // A visitor's existing token is reused so the other shares they've unlocked stay unlocked.
+    let token = token.unwrap_or_else(Token::generate);
+    let token_hash = hash_without_salt(&token);
+
+    // Attempts are counted per client so one client can't lock others out of the share.
+    let client = Subject::Client(client_ip).key();
+
+    let Ok(outcome) = db::transaction!(async |tx| -> TxResult<_> {
+        let attempt_count = sqlx::query_scalar!(
+            r#"SELECT count(*) AS "count!" FROM share_unlock_attempts
+                WHERE share_id = $1
+                    AND client = $2
+                    AND attempted_at > now() - interval '15 minutes'"#,
+            share_id,
+            client,
+        )
+        .fetch_one(tx.as_mut())
+        .await?;
+
+        if attempt_count >= MAX_UNLOCK_ATTEMPTS {
+            return Ok(UnlockOutcome::TooManyAttempts);
+        }
+
+        // The password is read again in case it changed since the share was found.
+        let Some(password_hash) = sqlx::query_scalar!(
+            "SELECT share_password_hash FROM files
+                WHERE id = $1 AND complete
+            UNION ALL
+            SELECT share_password_hash FROM folders
+                WHERE id = $1",
+            share_id,
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        .flatten() else {
+            return Ok(UnlockOutcome::Unlocked);
+        };
+
+        if !verify_hash(&password, &password_hash) {
+            sqlx::query!(
+                "INSERT INTO share_unlock_attempts (share_id, client)
+                    VALUES ($1, $2)",
+                share_id,
+                client,
+            )
+            .execute(tx.as_mut())
+            .await?;
+
+            return Ok(UnlockOutcome::PasswordWrong);
+        }
+
+        sqlx::query!(
+            "INSERT INTO share_unlocks (expires_at, token_hash, share_id)
+                VALUES (now() + make_interval(secs => $1), $2, $3)
+                ON CONFLICT (token_hash, share_id) DO UPDATE
+                    SET expires_at = excluded.expires_at",
+            ShareUnlockCookie::MAX_AGE.as_seconds_f64(),
+            token_hash.as_ref(),
+            share_id,
+        )
+        .execute(tx.as_mut())
+        .await?;
+
+        Ok(UnlockOutcome::Unlocked)
+    })
+    .await
+    else {
+        return response.plain_error(StatusCode::INTERNAL_SERVER_ERROR);
+    };
+
+    match outcome {
+        UnlockOutcome::Unlocked => {
+            let (name, value) = ShareUnlockCookie::new(token.to_string()).to_header();
+            response.header_valid(name, value);
+
+            response.see_other(uri)
+        }
+        UnlockOutcome::PasswordWrong => unlock_page(
+            response,
+            &Method::POST,
+            StatusCode::FORBIDDEN,
+            Some("Incorrect password."),
+        ),
+        UnlockOutcome::TooManyAttempts => unlock_page(
+            response,
+            &Method::POST,
+            StatusCode::TOO_MANY_REQUESTS,
+            Some("Too many incorrect passwords were entered. Please try again later."),
+        ),
+    }
+}
+
+/// Gets the password from an unlock form submission's `application/x-www-form-urlencoded` body.
+fn form_password(body: &[u8]) -> Option<String> {
+    let body = str::from_utf8(body).ok()?;
+    let password = body
+        .split('&')
+        .find_map(|param| param.strip_prefix("password="))?;
+
+    // Form encoding uses `+` for spaces, and a literal `+` is percent-encoded, so it's replaced
+    // before decoding.
+    percent_decode_str(&password.replace('+', " "))
+        .decode_utf8()
+        .ok()
+        .map(Cow::into_owned)
+}
+
+/// Responds with a page asking for a password-protected share's password.
+fn unlock_page(
+    mut response: Response,
+    method: &Method,
+    status: StatusCode,
+    error: Option<&str>,
+) -> Response {
+    response
+        .status(status)
+        .header_valid(CONTENT_TYPE, "text/html; charset=utf-8")
+        .header_valid(CACHE_CONTROL, "no-store");
+
+    if *method == Method::HEAD {
+        return response;
+    }
+
+    let Ok(page) = (ShareUnlockPage { error }).render() else {
+        return response.plain_error(StatusCode::INTERNAL_SERVER_ERROR);
+    };
+
+    response.body(page)
+}
+
+/// Checks whether a request's `Want-Repr-Digest` header (RFC 9530) accepts a SHA-256 digest of the
+/// response's representation. SHA-256 is the only supported algorithm. Without a valid header, the
+/// digest is always sent.
+fn wants_sha256_digest(want_repr_digest: Option<&HeaderValue>) -> bool {
+    let Some(Ok(want_repr_digest)) = want_repr_digest.map(HeaderValue::to_str) else {
+        return true;
+    };
+
+    let mut preferences = Vec::new();
+
+    for preference in want_repr_digest.split(',') {
+        let Some((algorithm, weight)) = preference.trim().split_once('=') else {
+            return true;
+        };
+
+        let Ok(weight) = weight.parse::<u8>() else {
+            return true;
+        };
+
+        preferences.push((algorithm, weight));
+    }
+
+    // A weight of 0 means the algorithm isn't acceptable.
+    preferences
+        .iter()
+        .any(|&(algorithm, weight)| algorithm == "sha-256" && weight > 0)
+}
+
+/// A request's query parameters.
+#[derive(Default, Debug)]
+struct ContentQuery<'a> {
+    /// The unparsed ID of the file to respond with, overriding the file path.
+    file_id: Option<&'a str>,
+
+    /// The width to resize an image to.
+    width: Option<u32>,
+
+    /// The height to resize an image to.
+    height: Option<u32>,
+
+    /// How to fit a resized image within its width and height.
+    fit: Option<Fit>,
+
+    /// The image format to convert an image to.
+    format: Option<ImageOutputFormat>,
+
+    /// Any other query parameters, which are ignored but kept for cache busting.
+    other: Vec<&'a str>,
+}
+
+impl<'a> ContentQuery<'a> {
+    /// Parses a request URI's query. Returns [`None`] if any recognized parameter is invalid or
+    /// repeated.
+    fn parse(query: Option<&'a str>) -> Option<Self> {
+        /// Sets a parameter's value, failing if it's already set.
+        fn set<T>(param: &mut Option<T>, value: Option<T>) -> Option<()> {
+            if param.is_some() {
+                return None;
+            }
+
+            *param = Some(value?);
+            Some(())
+        }
+
+        /// Parses an image dimension parameter's value.
+        fn dimension(value: &str) -> Option<u32> {
+            value
+                .parse()
+                .ok()
+                .filter(|dimension| (1..=MAX_RESIZE_DIMENSION).contains(dimension))
+        }
+
+        let mut content_query = Self::default();
+
+        for param in query.unwrap_or_default().split('&') {
+            if param.is_empty() {
+                continue;
+            }
+
+            let (key, value) = param.split_once('=').unwrap_or((param, ""));
+
+            match key {
+                "_id" => set(&mut content_query.file_id, Some(value))?,
+                "width" => set(&mut content_query.width, dimension(value))?,
+                "height" => set(&mut content_query.height, dimension(value))?,
+                "fit" => set(&mut content_query.fit, value.parse().ok())?,
+                "format" => set(&mut content_query.format, value.parse().ok())?,
+                _ => content_query.other.push(param),
+            }
+        }
+
+        Some(content_query)
+    }
+
+    /// Gets the parameters to resize or convert an image with, if any were specified.
+    fn resize(&self) -> Option<Resize> {
+        if self.width.is_none() && self.height.is_none() && self.format.is_none() {
+            return None;
+        }
+
+        Some(Resize {
+            width: self.width,
+            height: self.height,
+            fit: self.fit.unwrap_or_default(),
+            format: self.format.unwrap_or_default(),
+        })
+    }
+
+    /// Serializes the query in a normalized form, so equivalent URLs are cached by the CDN as one.
+    /// Recognized parameters come first in a fixed order, and parameters with no effect are
+    /// omitted.
+    fn to_normalized(&self) -> String {
+        let mut params = Vec::new();
+
+        if let Some(file_id) = self.file_id {
+            params.push(format!("_id={file_id}"));
+        }
+
+        if let Some(width) = self.width {
+            params.push(format!("width={width}"));
+        }
+
+        if let Some(height) = self.height {
+            params.push(format!("height={height}"));
+        }
+
+        // Fitting only matters when both dimensions are constrained.
+        if let Some(fit) = self.fit
+            && fit != Fit::default()
+            && self.width.is_some()
+            && self.height.is_some()
+        {
+            params.push(format!("fit={}", <&str>::from(fit)));
+        }
+
+        if let Some(format) = self.format {
+            params.push(format!("format={}", <&str>::from(format)));
+        }
+
+        params.extend(self.other.iter().map(|&param| param.to_owned()));
+
+        params.join("&")
+    }
 }
 
 /// Joins a path and a query into one string, separated by a `?` if there exists a query.
@@ -113,3 +722,77 @@ fn concat_path_and_query<'a>(path: &'a str, query: Option<&'a str>) -> Cow<'a, s
 
     path_and_query
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses a query and serializes it in normalized form, or returns [`None`] if it's invalid.
+    fn normalized(query: &str) -> Option<String> {
+        ContentQuery::parse(Some(query)).map(|query| query.to_normalized())
+    }
+
+    #[test]
+    fn parses_resize_params() {
+        let query = ContentQuery::parse(Some("width=100&fit=cover&format=png&v=2"))
+            .expect("query should be valid");
+
+        assert_eq!(
+            query.resize(),
+            Some(Resize {
+                width: Some(100),
+                height: None,
+                fit: Fit::Cover,
+                format: ImageOutputFormat::Png,
+            }),
+        );
+        assert_eq!(query.other, ["v=2"]);
+
+        assert_eq!(
+            ContentQuery::parse(None).map(|query| query.resize()),
+            Some(None),
+        );
+        assert_eq!(
+            ContentQuery::parse(Some("v=2")).map(|query| query.resize()),
+            Some(None),
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_params() {
+        assert_eq!(normalized("width=0"), None);
+        assert_eq!(normalized("width=4097"), None);
+        assert_eq!(normalized("height=-1"), None);
+        assert_eq!(normalized("width="), None);
+        assert_eq!(normalized("fit=squash"), None);
+        assert_eq!(normalized("format=gif"), None);
+        assert_eq!(normalized("width=1&width=1"), None);
+        assert_eq!(normalized("_id=a&_id=b"), None);
+    }
+
+    #[test]
+    fn normalizes_params() {
+        assert_eq!(normalized("").as_deref(), Some(""));
+        assert_eq!(
+            normalized("format=png&v=2&height=50&width=100&_id=abc").as_deref(),
+            Some("_id=abc&width=100&height=50&format=png&v=2"),
+        );
+
+        // Fitting only matters when both dimensions are constrained and it isn't the default.
+        assert_eq!(
+            normalized("fit=cover&width=100").as_deref(),
+            Some("width=100"),
+        );
+        assert_eq!(
+            normalized("fit=contain&width=100&height=50").as_deref(),
+            Some("width=100&height=50"),
+        );
+        assert_eq!(
+            normalized("fit=fill&width=100&height=50").as_deref(),
+            Some("width=100&height=50&fit=fill"),
+        );
+
+        // Unrecognized params are kept in order for cache busting.
+        assert_eq!(normalized("b&&a=1").as_deref(), Some("b&a=1"));
+    }
+}
@@ -1,18 +1,95 @@
 //! Utilities for cryptographic operations.
 
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::{
+    str::FromStr,
+    sync::LazyLock,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 use argon2::{
     Argon2, PasswordHash, PasswordHasher, PasswordVerifier,
     password_hash::{Salt, SaltString},
 };
+use base64::{
+    Engine as _,
+    engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD},
+};
 use rand::{
     Rng,
     distr::{Distribution, Uniform},
 };
-use ring::digest::{Digest, SHA256, digest};
+use ring::{
+    aead::{AES_256_GCM, Aad, LessSafeKey, NONCE_LEN, Nonce, UnboundKey},
+    digest::{Digest, SHA256, digest},
+};
+use sha2::{Sha256, digest::common::hazmat::SerializableState};
+use thiserror::Error;
 use totp_lite::{Sha1, totp_custom};
 
+/// The keys TOTP secrets are encrypted with at rest, parsed from the `TOTP_SECRET_KEYS` environment
+/// variable.
+static TOTP_SECRET_KEYS: LazyLock<TotpSecretKeys> = LazyLock::new(|| {
+    dotenvy::var("TOTP_SECRET_KEYS")
+        .expect("environment variable `TOTP_SECRET_KEYS` should be a valid string")
+        .parse()
+        .unwrap_or_else(|error| panic!("environment variable `TOTP_SECRET_KEYS` {error}"))
+});
+
+/// Keys TOTP secrets are encrypted with, each paired with its key ID. The first key encrypts new
+/// secrets. Any others are older keys, kept only to decrypt secrets that haven't been re-encrypted
+/// with the first key yet.
+struct TotpSecretKeys(Vec<(String, LessSafeKey)>);
+
+impl FromStr for TotpSecretKeys {
+    type Err = TotpSecretKeysError;
+
+    /// Parses a comma-separated list of keys in the format `id:key`, where each key is 32 bytes
+    /// encoded in base64.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let mut keys = Vec::<(String, LessSafeKey)>::new();
+
+        for key in value.split(',') {
+            let (key_id, key) = key
+                .trim()
+                .split_once(':')
+                .filter(|(key_id, _)| !key_id.is_empty())
+                .ok_or(TotpSecretKeysError::Format)?;
+
+            // Otherwise, only the first key with an ID would ever be tried, so secrets encrypted
+            // with the others couldn't be decrypted.
+            if keys.iter().any(|(id, _)| id == key_id) {
+                return Err(TotpSecretKeysError::DuplicateKeyId(key_id.to_owned()));
+            }
+
+            let key = STANDARD
+                .decode(key)
+                .ok()
+                .and_then(|key| UnboundKey::new(&AES_256_GCM, &key).ok())
+                .ok_or(TotpSecretKeysError::Key)?;
+
+            keys.push((key_id.to_owned(), LessSafeKey::new(key)));
+        }
+
+        Ok(Self(keys))
+    }
+}
+
+/// An error parsing [`TotpSecretKeys`].
+#[derive(Error, Debug)]
+enum TotpSecretKeysError {
+    /// A key isn't in the format `id:key`.
+    #[error("should have each key in the format `id:key`")]
+    Format,
+
+    /// A key isn't 32 bytes encoded in base64.
+    #[error("should have each key be 32 bytes encoded in base64")]
+    Key,
+
+    /// More than one key has the same ID.
+    #[error("shouldn't have more than one key with the ID {0:?}")]
+    DuplicateKeyId(String),
+}
+
 /// Hashes the input using SHA-256.
 ///
 /// Salt is necessary for secrets that may be short or guessable, so use [`hash_with_salt`] instead
@@ -21,6 +98,21 @@ pub(crate) fn hash_without_salt<T: AsRef<[u8]>>(bytes: &T) -> Digest {
     digest(&SHA256, bytes.as_ref())
 }
 
+/// Serializes the state of an unfinished SHA-256 hash, so hashing can be resumed later (e.g., by a
+/// later request uploading the next part of a file).
+pub(crate) fn serialize_hash_state(hasher: &Sha256) -> Vec<u8> {
+    hasher.serialize().to_vec()
+}
+
+/// Deserializes the state of an unfinished SHA-256 hash serialized by [`serialize_hash_state`].
+///
+/// If the serialized state is invalid, returns [`None`].
+pub(crate) fn deserialize_hash_state(state: &[u8]) -> Option<Sha256> {
+    let state = state.try_into().ok()?;
+
+    Sha256::deserialize(state).ok()
+}
+
 /// Salts and hashes the input using Argon2, returning a hash in PHC string format.
 ///
 /// Salt is necessary for secrets that may be short or guessable, but it has a drawback: a database
@@ -53,6 +145,24 @@ pub(crate) fn verify_hash<T: AsRef<[u8]>>(bytes: &T, hash_phc_format: &str) -> b
         .is_ok()
 }
 
+/// Finds which of the Argon2 hashes specified in PHC string format (as outputted by
+/// [`hash_with_salt`]) the input bytes match, returning the matching hash's index.
+///
+/// Every hash is checked even after a match is found, so the time this takes doesn't reveal which
+/// hash (if any) matched.
+pub(crate) fn find_matching_hash<T: AsRef<[u8]>, H: AsRef<str>>(
+    bytes: &T,
+    hashes_phc_format: &[H],
+) -> Option<usize> {
+    hashes_phc_format
+        .iter()
+        .enumerate()
+        .fold(None, |found, (i, hash)| {
+            let is_match = verify_hash(bytes, hash.as_ref());
+            found.or(is_match.then_some(i))
+        })
+}
+
 /// Checks if a TOTP code matches a TOTP secret.
 ///
 /// # Panics
@@ -71,6 +181,111 @@ pub(crate) fn verify_totp<T: AsRef<[u8]>>(totp: &str, secret: &T) -> bool {
         || totp == totp_custom::<Sha1>(STEP, DIGITS, secret.as_ref(), time - STEP)
 }
 
+impl TotpSecretKeys {
+    /// Gets the ID of the key new TOTP secrets are encrypted with.
+    fn current_key_id(&self) -> &str {
+        &self.0[0].0
+    }
+
+    /// See [`encrypt_totp_secret`].
+    fn encrypt(&self, secret: &[u8], user_id: &[u8]) -> (&str, Vec<u8>) {
+        let (key_id, key) = &self.0[0];
+
+        let mut nonce = [0; NONCE_LEN];
+        rand::rng().fill_bytes(&mut nonce);
+
+        let mut encrypted_secret = secret.to_vec();
+        key.seal_in_place_append_tag(
+            Nonce::assume_unique_for_key(nonce),
+            Aad::from(user_id),
+            &mut encrypted_secret,
+        )
+        .expect("TOTP secret should be short enough to encrypt");
+
+        // The nonce is stored in front of the ciphertext so it's available for decryption.
+        encrypted_secret.splice(0..0, nonce);
+
+        (key_id, encrypted_secret)
+    }
+
+    /// See [`decrypt_totp_secret`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no key has the specified ID, or if the encrypted secret is invalid or
+    /// wasn't encrypted for the specified user.
+    fn decrypt(
+        &self,
+        key_id: &str,
+        encrypted_secret: &[u8],
+        user_id: &[u8],
+    ) -> Result<Vec<u8>, TotpSecretDecryptionError> {
+        let Some((_, key)) = self.0.iter().find(|(id, _)| id == key_id) else {
+            return Err(TotpSecretDecryptionError::UnknownKey(key_id.to_owned()));
+        };
+
+        let Some((nonce, ciphertext)) = encrypted_secret.split_first_chunk::<NONCE_LEN>() else {
+            return Err(TotpSecretDecryptionError::Invalid);
+        };
+
+        let mut secret = ciphertext.to_vec();
+        let secret_len = key
+            .open_in_place(
+                Nonce::assume_unique_for_key(*nonce),
+                Aad::from(user_id),
+                &mut secret,
+            )
+            .map_err(|_| TotpSecretDecryptionError::Invalid)?
+            .len();
+        secret.truncate(secret_len);
+
+        Ok(secret)
+    }
+}
+
+/// Gets the ID of the key new TOTP secrets are encrypted with.
+pub(crate) fn current_totp_secret_key_id() -> &'static str {
+    TOTP_SECRET_KEYS.current_key_id()
+}
+
+/// Encrypts a TOTP secret with the current TOTP secret key using AES-256-GCM, returning the key's
+/// ID and the encrypted secret.
+///
+/// The user ID is authenticated along with the secret, so an encrypted secret can't be moved to a
+/// different user.
+pub(crate) fn encrypt_totp_secret<T: AsRef<[u8]>>(
+    secret: &[u8],
+    user_id: &T,
+) -> (&'static str, Vec<u8>) {
+    TOTP_SECRET_KEYS.encrypt(secret, user_id.as_ref())
+}
+
+/// Decrypts a TOTP secret encrypted by [`encrypt_totp_secret`] for the specified user.
+///
+/// # Errors
+///
+/// Returns an error if no configured TOTP secret key has the specified ID, or if the encrypted
+/// secret is invalid or wasn't encrypted for the specified user.
+pub(crate) fn decrypt_totp_secret<T: AsRef<[u8]>>(
+    key_id: &str,
+    encrypted_secret: &[u8],
+    user_id: &T,
+) -> Result<Vec<u8>, TotpSecretDecryptionError> {
+    TOTP_SECRET_KEYS.decrypt(key_id, encrypted_secret, user_id.as_ref())
+}
+
+/// An error decrypting a TOTP secret.
+#[derive(Error, Debug)]
+pub(crate) enum TotpSecretDecryptionError {
+    /// No configured TOTP secret key has the ID the secret was encrypted with.
+    #[error("no key in `TOTP_SECRET_KEYS` has the ID {0:?}")]
+    UnknownKey(String),
+
+    /// The encrypted secret is invalid or wasn't encrypted for the specified user.
+    #[error("TOTP secret couldn't be decrypted")]
+    Invalid,
+}
+
 /// Generates a cryptographically secure pseudorandom string that should be short and easy to type.
 pub(crate) fn generate_short_code(length: usize) -> String {
     // `O` is excluded because it's often mistaken for `0`.
@@ -86,3 +301,220 @@ pub(crate) fn generate_short_code(length: usize) -> String {
         .map(|i| CHARS[i])
         .collect()
 }
+
+/// Verifies a PKCE code verifier (RFC 7636) matches a code challenge created from it with the
+/// `S256` method.
+pub(crate) fn verify_pkce(code_verifier: &str, code_challenge: &str) -> bool {
+    (43..=128).contains(&code_verifier.len())
+        && code_verifier
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || b"-._~".contains(&byte))
+        && URL_SAFE_NO_PAD.encode(hash_without_salt(&code_verifier)) == code_challenge
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates a PKCE code challenge from a code verifier with the `S256` method.
+    fn code_challenge(code_verifier: &str) -> String {
+        URL_SAFE_NO_PAD.encode(hash_without_salt(&code_verifier))
+    }
+
+    const NEW_KEY: &str = "new:AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8=";
+    const OLD_KEY: &str = "old:Hx4dHBsaGRgXFhUUExIREA8ODQwLCgkIBwYFBAMCAQA=";
+
+    #[test]
+    fn totp_secrets_round_trip() {
+        let keys: TotpSecretKeys = format!("{NEW_KEY}, {OLD_KEY}")
+            .parse()
+            .expect("keys should be valid");
+        let secret = b"12345678901234567890";
+
+        let (key_id, encrypted_secret) = keys.encrypt(secret, b"user");
+        assert_eq!(key_id, "new");
+        assert_ne!(&encrypted_secret[NONCE_LEN..][..secret.len()], secret);
+
+        assert_eq!(
+            keys.decrypt(key_id, &encrypted_secret, b"user")
+                .expect("secret should decrypt"),
+            secret,
+        );
+    }
+
+    #[test]
+    fn totp_secrets_decrypt_with_old_keys() {
+        let old_keys: TotpSecretKeys = OLD_KEY.parse().expect("keys should be valid");
+        let keys: TotpSecretKeys = format!("{NEW_KEY},{OLD_KEY}")
+            .parse()
+            .expect("keys should be valid");
+        let secret = b"12345678901234567890";
+
+        let (key_id, encrypted_secret) = old_keys.encrypt(secret, b"user");
+        assert_eq!(key_id, "old");
+
+        assert_eq!(
+            keys.decrypt(key_id, &encrypted_secret, b"user")
+                .expect("secret should decrypt"),
+            secret,
+        );
+        assert!(matches!(
+            keys.decrypt("new", &encrypted_secret, b"user"),
+            Err(TotpSecretDecryptionError::Invalid),
+        ));
+    }
+
+    #[test]
+    fn totp_secrets_are_bound_to_users() {
+        let keys: TotpSecretKeys = NEW_KEY.parse().expect("keys should be valid");
+
+        let (key_id, encrypted_secret) = keys.encrypt(b"12345678901234567890", b"user");
+
+        assert!(matches!(
+            keys.decrypt(key_id, &encrypted_secret, b"other user"),
+            Err(TotpSecretDecryptionError::Invalid),
+        ));
+    }
+
+    #[test]
+    fn invalid_encrypted_totp_secrets_fail() {
+        let keys: TotpSecretKeys = NEW_KEY.parse().expect("keys should be valid");
+
+        let (key_id, mut encrypted_secret) = keys.encrypt(b"12345678901234567890", b"user");
+        assert!(matches!(
+            keys.decrypt("unknown", &encrypted_secret, b"user"),
+            Err(TotpSecretDecryptionError::UnknownKey(key_id)) if key_id == "unknown",
+        ));
+
+        *encrypted_secret
+            .last_mut()
+            .expect("encrypted secret should be nonempty") ^= 1;
+        assert!(matches!(
+            keys.decrypt(key_id, &encrypted_secret, b"user"),
+            Err(TotpSecretDecryptionError::Invalid),
+        ));
+
+        assert!(matches!(
+            keys.decrypt(key_id, &encrypted_secret[..NONCE_LEN - 1], b"user"),
+            Err(TotpSecretDecryptionError::Invalid),
+        ));
+    }
+
+    #[test]
+    fn parses_totp_secret_keys() {
+        let keys: TotpSecretKeys = format!(" {NEW_KEY} ,{OLD_KEY}")
+            .parse()
+            .expect("keys should be valid");
+        let key_ids: Vec<_> = keys.0.iter().map(|(key_id, _)| key_id.as_str()).collect();
+
+        assert_eq!(key_ids, ["new", "old"]);
+        assert_eq!(keys.current_key_id(), "new");
+    }
+
+    #[test]
+    fn rejects_invalid_totp_secret_keys() {
+        for value in [
+            "",
+            "AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8=",
+            ":AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8=",
+            &format!("{NEW_KEY},"),
+        ] {
+            assert!(
+                matches!(
+                    value.parse::<TotpSecretKeys>(),
+                    Err(TotpSecretKeysError::Format),
+                ),
+                "{value:?} should be invalid",
+            );
+        }
+
+        for value in [
+            "key:AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxw=",
+            "key:not base64",
+        ] {
+            assert!(
+                matches!(
+                    value.parse::<TotpSecretKeys>(),
+                    Err(TotpSecretKeysError::Key)
+                ),
+                "{value:?} should be invalid",
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_duplicate_totp_secret_key_ids() {
+        assert!(matches!(
+            format!("{NEW_KEY},new:Hx4dHBsaGRgXFhUUExIREA8ODQwLCgkIBwYFBAMCAQA=")
+                .parse::<TotpSecretKeys>(),
+            Err(TotpSecretKeysError::DuplicateKeyId(key_id)) if key_id == "new",
+        ));
+    }
+
+    #[test]
+    fn finds_matching_hash() {
+        let hashes = ["first", "second", "third"].map(|code| hash_with_salt(&code));
+
+        assert_eq!(find_matching_hash(&"first", &hashes), Some(0));
+        assert_eq!(find_matching_hash(&"third", &hashes), Some(2));
+        assert_eq!(find_matching_hash(&"fourth", &hashes), None);
+        assert_eq!(find_matching_hash::<_, String>(&"first", &[]), None);
+    }
+
+    #[test]
+    fn finds_first_matching_hash() {
+        let hashes = [
+            "not a hash".to_owned(),
+            hash_with_salt(&"code"),
+            hash_with_salt(&"code"),
+        ];
+
+        assert_eq!(find_matching_hash(&"code", &hashes), Some(1));
+    }
+
+    #[test]
+    fn pkce_matches() {
+        assert!(verify_pkce(
+            "M25iVXpKU3puUjFaYWg3T1NDTDQtcW1ROUY5YXlwalNoc0hhakxifmZHag",
+            "qjrzSW9gMiUgpUvqgEPE4_-8swvyCtfOVvg55o5S_es",
+        ));
+
+        let code_verifier = "0123456789-._~abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        assert!(verify_pkce(code_verifier, &code_challenge(code_verifier)));
+    }
+
+    #[test]
+    fn pkce_rejects_wrong_challenges() {
+        let code_verifier = "a".repeat(43);
+
+        assert!(!verify_pkce(
+            &code_verifier,
+            &code_challenge(&"b".repeat(43))
+        ));
+        assert!(!verify_pkce(&code_verifier, ""));
+
+        // The plain method isn't supported.
+        assert!(!verify_pkce(&code_verifier, &code_verifier));
+    }
+
+    #[test]
+    fn pkce_rejects_invalid_verifiers() {
+        for code_verifier in [
+            "a".repeat(42),
+            "a".repeat(129),
+            format!("{}+", "a".repeat(42)),
+            format!("{}=", "a".repeat(42)),
+            format!("{} ", "a".repeat(42)),
+            format!("{}é", "a".repeat(41)),
+        ] {
+            assert!(
+                !verify_pkce(&code_verifier, &code_challenge(&code_verifier)),
+                "{code_verifier:?} should be invalid",
+            );
+        }
+
+        for code_verifier in ["a".repeat(43), "a".repeat(128)] {
+            assert!(verify_pkce(&code_verifier, &code_challenge(&code_verifier)));
+        }
+    }
+}
@@ -6,6 +6,10 @@ use castaway::cast;
 use sha2::{Digest, Sha256};
 use sqlx::{Executor, PgPool, postgres::PgPoolOptions};
 
+use crate::crypto::{
+    current_totp_secret_key_id, decrypt_totp_secret, encrypt_totp_secret, hash_with_salt,
+};
+
 /// The SQLx database pool.
 static DB_POOL: OnceLock<PgPool> = OnceLock::new();
 
@@ -19,7 +23,7 @@ static DB_POOL: OnceLock<PgPool> = OnceLock::new();
 /// # Panics
 ///
 /// May panic if the database is already initialized.
-pub(super) async fn initialize(db_url: &str) -> sqlx::Result<()> {
+pub(super) async fn initialize(db_url: &str) -> anyhow::Result<()> {
     let pool = PgPoolOptions::new()
         .after_connect(|conn, _| {
             Box::pin(async move {
@@ -39,6 +43,10 @@ pub(super) async fn initialize(db_url: &str) -> sqlx::Result<()> {
         .expect("database pool shouldn't already be initialized");
 
     sync_terms_version_to_db().await?;
+    // TOTP secrets are encrypted first, since that moves them back into `totp` from where backup
+    // codes aren't hashed.
+    encrypt_totp_secrets(false).await?;
+    hash_plain_text_backup_codes().await?;
 
     Ok(())
 }
@@ -109,6 +117,143 @@ async fn sync_terms_version_to_db() -> sqlx::Result<()> {
     Ok(())
 }
 
+/// Hashes any TOTP backup codes still stored in plain text from before backup codes were hashed.
+///
+/// # Errors
+///
+/// Returns an error if a database query fails.
+async fn hash_plain_text_backup_codes() -> sqlx::Result<()> {
+    /// The prefix of an Argon2 hash in PHC string format.
+    const HASH_PREFIX: &str = "$argon2";
+
+    transaction!(async |tx| -> TxResult<_> {
+        let totps = sqlx::query!(
+            "SELECT user_id, unused_backup_code_hashes FROM totp
+                WHERE EXISTS (
+                    SELECT FROM unnest(unused_backup_code_hashes) AS hash
+                        WHERE NOT starts_with(hash, $1)
+                )",
+            HASH_PREFIX,
+        )
+        .fetch_all(tx.as_mut())
+        .await?;
+
+        for totp in totps {
+            let backup_code_hashes: Vec<_> = totp
+                .unused_backup_code_hashes
+                .iter()
+                .map(|code| {
+                    if code.starts_with(HASH_PREFIX) {
+                        code.clone()
+                    } else {
+                        hash_with_salt(code)
+                    }
+                })
+                .collect();
+
+            sqlx::query!(
+                "UPDATE totp
+                    SET unused_backup_code_hashes = $1
+                    WHERE user_id = $2",
+                &backup_code_hashes,
+                totp.user_id,
+            )
+            .execute(tx.as_mut())
+            .await?;
+        }
+
+        Ok(())
+    })
+    .await?;
+
+    Ok(())
+}
+
+/// Encrypts TOTP secrets with the current TOTP secret key, returning how many were encrypted.
+///
+/// Secrets still stored in plain text from before TOTP secrets were encrypted are always encrypted
+/// and moved from `plain_text_totp` back into `totp`. If `include_old_keys` is set, secrets
+/// encrypted with older keys are also re-encrypted, so those keys can be removed from the
+/// configuration afterward.
+///
+/// # Errors
+///
+/// Returns an error if a database query fails or a secret can't be decrypted.
+pub(crate) async fn encrypt_totp_secrets(include_old_keys: bool) -> anyhow::Result<u64> {
+    let current_key_id = current_totp_secret_key_id();
+
+    transaction!(async |tx| -> TxResult<_, anyhow::Error> {
+        let plain_text_totps = sqlx::query!(
+            "DELETE FROM plain_text_totp
+                RETURNING
+                    created_at,
+                    user_id,
+                    secret,
+                    otp_used_last,
+                    otp_used_2nd_to_last,
+                    unused_backup_code_hashes",
+        )
+        .fetch_all(tx.as_mut())
+        .await?;
+
+        for totp in &plain_text_totps {
+            let (secret_key_id, encrypted_secret) =
+                encrypt_totp_secret(&totp.secret, &totp.user_id);
+
+            sqlx::query!(
+                "INSERT INTO totp (
+                    created_at,
+                    user_id,
+                    secret,
+                    secret_key_id,
+                    otp_used_last,
+                    otp_used_2nd_to_last,
+                    unused_backup_code_hashes
+                )
+                    VALUES ($1, $2, $3, $4, $5, $6, $7)",
+                totp.created_at,
+                totp.user_id,
+                encrypted_secret,
+                secret_key_id,
+                totp.otp_used_last,
+                totp.otp_used_2nd_to_last,
+                &totp.unused_backup_code_hashes,
+            )
+            .execute(tx.as_mut())
+            .await?;
+        }
+
+        let totps = sqlx::query!(
+            "SELECT user_id, secret, secret_key_id FROM totp
+                WHERE $1 AND secret_key_id != $2",
+            include_old_keys,
+            current_key_id,
+        )
+        .fetch_all(tx.as_mut())
+        .await?;
+
+        for totp in &totps {
+            let secret = decrypt_totp_secret(&totp.secret_key_id, &totp.secret, &totp.user_id)?;
+            let (secret_key_id, encrypted_secret) = encrypt_totp_secret(&secret, &totp.user_id);
+
+            sqlx::query!(
+                "UPDATE totp
+                    SET secret = $1,
+                        secret_key_id = $2
+                    WHERE user_id = $3",
+                encrypted_secret,
+                secret_key_id,
+                totp.user_id,
+            )
+            .execute(tx.as_mut())
+            .await?;
+        }
+
+        Ok((plain_text_totps.len() + totps.len()) as u64)
+    })
+    .await
+}
+
 /// Gets the SQLx database pool.
 ///
 /// # Panics
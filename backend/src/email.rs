@@ -114,6 +114,159 @@ impl MessageTemplate for PasswordResetFailedMessage<'_> {
     }
 }
 
+/// An email template informing a user that someone uploaded a file to one of their folders through
+/// an upload link.
+#[derive(Template, Debug)]
+#[template(path = "email/upload_link_file.html")]
+pub(crate) struct UploadLinkFileMessage<'a> {
+    /// The name of the user who owns the folder.
+    pub(crate) user_name: &'a str,
+
+    /// The name of the folder the file was uploaded to.
+    pub(crate) folder_name: &'a str,
+
+    /// The name of the uploaded file.
+    pub(crate) file_name: &'a str,
+}
+
+impl MessageTemplate for UploadLinkFileMessage<'_> {
+    fn subject(&self) -> String {
+        "New file uploaded to your folder".into()
+    }
+}
+
+/// An email template warning a user that authentication attempts for their account are temporarily
+/// paused after too many failed attempts.
+#[derive(Template, Debug)]
+#[template(path = "email/auth_failures.html")]
+pub(crate) struct AuthFailuresMessage<'a> {
+    /// The user's name.
+    pub(crate) user_name: &'a str,
+}
+
+impl MessageTemplate for AuthFailuresMessage<'_> {
+    fn subject(&self) -> String {
+        "Failed sign-in attempts".into()
+    }
+}
+
+/// Details about a security-sensitive change to a user's account, included in the email notifying
+/// them of it.
+#[derive(Debug)]
+pub(crate) struct SecurityNotice {
+    /// The user's name.
+    pub(crate) user_name: String,
+
+    /// The user's email address, which the notice is sent to.
+    pub(crate) email: String,
+
+    /// When the change was made, formatted for display.
+    pub(crate) time: String,
+
+    /// An approximate description of the device the change was made from, if it's known.
+    pub(crate) device: Option<String>,
+
+    /// The URL the user can visit to sign out everywhere if they didn't make the change.
+    pub(crate) revoke_sessions_url: String,
+}
+
+impl SecurityNotice {
+    /// Gets the mailbox to send the notice to, if the user's email address is valid.
+    pub(crate) fn mailbox(&self) -> Option<Mailbox> {
+        let email = self.email.parse().ok()?;
+
+        Some(Mailbox::new(Some(self.user_name.clone()), email))
+    }
+}
+
+/// An email template informing a user that their password was changed.
+#[derive(Template, Debug)]
+#[template(path = "email/password_changed.html")]
+pub(crate) struct PasswordChangedMessage<'a> {
+    /// Details about the change.
+    pub(crate) notice: &'a SecurityNotice,
+}
+
+impl MessageTemplate for PasswordChangedMessage<'_> {
+    fn subject(&self) -> String {
+        "Your password was changed".into()
+    }
+}
+
+/// An email template informing a user at their previous email address that their email was
+/// changed.
+#[derive(Template, Debug)]
+#[template(path = "email/email_changed.html")]
+pub(crate) struct EmailChangedMessage<'a> {
+    /// Details about the change.
+    pub(crate) notice: &'a SecurityNotice,
+
+    /// The user's new email address.
+    pub(crate) new_email: &'a str,
+}
+
+impl MessageTemplate for EmailChangedMessage<'_> {
+    fn subject(&self) -> String {
+        "Your email was changed".into()
+    }
+}
+
+/// An email template informing a user that TOTP was enabled for their account.
+#[derive(Template, Debug)]
+#[template(path = "email/totp_enabled.html")]
+pub(crate) struct TotpEnabledMessage<'a> {
+    /// Details about the change.
+    pub(crate) notice: &'a SecurityNotice,
+}
+
+impl MessageTemplate for TotpEnabledMessage<'_> {
+    fn subject(&self) -> String {
+        "Two-factor authentication enabled".into()
+    }
+}
+
+/// An email template informing a user that their TOTP backup codes were regenerated.
+#[derive(Template, Debug)]
+#[template(path = "email/backup_codes_regenerated.html")]
+pub(crate) struct BackupCodesRegeneratedMessage<'a> {
+    /// Details about the change.
+    pub(crate) notice: &'a SecurityNotice,
+}
+
+impl MessageTemplate for BackupCodesRegeneratedMessage<'_> {
+    fn subject(&self) -> String {
+        "Backup codes regenerated".into()
+    }
+}
+
+/// An email template informing a user that TOTP was disabled for their account.
+#[derive(Template, Debug)]
+#[template(path = "email/totp_disabled.html")]
+pub(crate) struct TotpDisabledMessage<'a> {
+    /// Details about the change.
+    pub(crate) notice: &'a SecurityNotice,
+}
+
+impl MessageTemplate for TotpDisabledMessage<'_> {
+    fn subject(&self) -> String {
+        "Two-factor authentication disabled".into()
+    }
+}
+
+/// An email template informing a user that their account was signed in to from a new device.
+#[derive(Template, Debug)]
+#[template(path = "email/new_sign_in.html")]
+pub(crate) struct NewSignInMessage<'a> {
+    /// Details about the sign-in.
+    pub(crate) notice: &'a SecurityNotice,
+}
+
+impl MessageTemplate for NewSignInMessage<'_> {
+    fn subject(&self) -> String {
+        "New sign-in to your account".into()
+    }
+}
+
 /// The mailbox automated emails are sent from.
 static FROM_MAILBOX: LazyLock<Mailbox> = LazyLock::new(|| {
     dotenvy::var("FROM_MAILBOX")
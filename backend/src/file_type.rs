@@ -0,0 +1,500 @@
+//! Detection of file types from file content. See [`sniff`].
+
+use std::{io, str};
+
+use crate::storage;
+
+/// The number of bytes from the start of a file's content that [`sniff`] needs to detect its type.
+pub(crate) const SNIFF_LENGTH: usize = 4096;
+
+/// The most bytes of an SVG that [`sniff_content`] checks for scripts. Larger SVGs are always
+/// treated as dangerous.
+const MAX_SVG_SCAN_LENGTH: usize = 8 * 1024 * 1024;
+
+/// The local names of SVG elements that can run scripts or embed content that can.
+const SCRIPTABLE_ELEMENTS: [&str; 6] = [
+    "script",
+    "foreignobject",
+    "handler",
+    "iframe",
+    "embed",
+    "object",
+];
+
+/// The file type of any content that can't be identified more specifically.
+pub(crate) const UNKNOWN_TYPE: &str = "application/octet-stream";
+
+/// A file type detected by [`sniff`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub(crate) struct SniffedType {
+    /// The file's MIME type, including a `charset` parameter for text.
+    pub(crate) mime_type: String,
+
+    /// Whether the file is dangerous to render inline from the content origin (e.g., because it can
+    /// run scripts).
+    pub(crate) dangerous: bool,
+}
+
+impl SniffedType {
+    /// Constructs a `SniffedType` that isn't dangerous.
+    fn safe(mime_type: &str) -> Self {
+        Self {
+            mime_type: mime_type.into(),
+            dangerous: false,
+        }
+    }
+}
+
+/// Detects a file's type from the start of its content (at least [`SNIFF_LENGTH`] bytes when
+/// available) and its name.
+///
+/// The content's magic bytes take priority over the name's extension, since a name can claim
+/// anything. The extension is only used to tell apart types the content alone can't distinguish,
+/// such as the various text formats or ZIP-based formats.
+pub(crate) fn sniff(name: &str, head: &[u8]) -> SniffedType {
+    let extension = name
+        .rsplit_once('.')
+        .map(|(_, extension)| extension.to_ascii_lowercase());
+    let extension = extension.as_deref().unwrap_or_default();
+
+    if let Some(mime_type) = sniff_magic_bytes(extension, head) {
+        return SniffedType::safe(mime_type);
+    }
+
+    let Some((charset, text)) = decode_text_head(head) else {
+        return SniffedType::safe(UNKNOWN_TYPE);
+    };
+
+    let trimmed_text = text.trim_start_matches('\u{feff}').trim_start();
+    let looks_like_svg = extension == "svg"
+        || (starts_with_ignore_ascii_case(trimmed_text, "<?xml")
+            || starts_with_ignore_ascii_case(trimmed_text, "<svg"))
+            && contains_ignore_ascii_case(&text, "<svg");
+
+    let (mime_type, dangerous) = if looks_like_svg {
+        ("image/svg+xml", svg_can_run_scripts(&text))
+    } else {
+        match extension {
+            "htm" | "html" | "shtml" => ("text/html", true),
+            "xht" | "xhtml" => ("application/xhtml+xml", true),
+            // XML can embed XHTML, which can run scripts.
+            "xml" | "xsl" | "xslt" => ("application/xml", true),
+            "css" => ("text/css", false),
+            "csv" => ("text/csv", false),
+            "ics" => ("text/calendar", false),
+            "js" | "cjs" | "mjs" => ("text/javascript", false),
+            "json" | "map" => ("application/json", false),
+            "md" | "markdown" => ("text/markdown", false),
+            "srt" => ("application/x-subrip", false),
+            "tsv" => ("text/tab-separated-values", false),
+            "vtt" => ("text/vtt", false),
+            "yaml" | "yml" => ("application/yaml", false),
+            _ => ("text/plain", false),
+        }
+    };
+
+    SniffedType {
+        mime_type: format!("{mime_type}; charset={charset}"),
+        dangerous,
+    }
+}
+
+/// Detects a stored file content's type using [`sniff`], reading as much of the content as needed.
+///
+/// Scripts can appear anywhere in an SVG, so an SVG longer than [`SNIFF_LENGTH`] is checked in
+/// full rather than by its head alone.
+///
+/// # Errors
+///
+/// Returns an error if reading from the file system fails.
+pub(crate) async fn sniff_content(
+    name: &str,
+    content_id: &[u8],
+    part_count: i32,
+) -> io::Result<SniffedType> {
+    let head = storage::read_head(content_id, part_count, SNIFF_LENGTH).await?;
+    let file_type = sniff(name, &head);
+
+    let is_svg_cut_off = head.len() == SNIFF_LENGTH
+        && !file_type.dangerous
+        && file_type.mime_type.starts_with("image/svg+xml");
+
+    if !is_svg_cut_off {
+        return Ok(file_type);
+    }
+
+    let svg = storage::read_head(content_id, part_count, MAX_SVG_SCAN_LENGTH + 1).await?;
+
+    if svg.len() > MAX_SVG_SCAN_LENGTH {
+        return Ok(SniffedType {
+            dangerous: true,
+            ..file_type
+        });
+    }
+
+    Ok(sniff(name, &svg))
+}
+
+/// Detects a binary file type from magic bytes at the start of the content.
+fn sniff_magic_bytes(extension: &str, head: &[u8]) -> Option<&'static str> {
+    /// File signatures that only need a prefix match, paired with their MIME types.
+    const PREFIXES: &[(&[u8], &str)] = &[
+        (b"\x89PNG\r\n\x1a\n", "image/png"),
+        (b"\xff\xd8\xff", "image/jpeg"),
+        (b"GIF87a", "image/gif"),
+        (b"GIF89a", "image/gif"),
+        (b"BM", "image/bmp"),
+        (b"\x00\x00\x01\x00", "image/x-icon"),
+        (b"II*\x00", "image/tiff"),
+        (b"MM\x00*", "image/tiff"),
+        (b"\xff\x0a", "image/jxl"),
+        (b"\x00\x00\x00\x0cJXL \x0d\x0a\x87\x0a", "image/jxl"),
+        (b"%PDF-", "application/pdf"),
+        (b"ID3", "audio/mpeg"),
+        (b"fLaC", "audio/flac"),
+        (b"OggS", "application/ogg"),
+        (b"MThd", "audio/midi"),
+        (b"#!AMR", "audio/amr"),
+        (b"\x1f\x8b", "application/gzip"),
+        (b"BZh", "application/x-bzip2"),
+        (b"\xfd7zXZ\x00", "application/x-xz"),
+        (b"(\xb5/\xfd", "application/zstd"),
+        (b"7z\xbc\xaf\x27\x1c", "application/x-7z-compressed"),
+        (b"Rar!\x1a\x07", "application/vnd.rar"),
+        (b"wOFF", "font/woff"),
+        (b"wOF2", "font/woff2"),
+        (b"OTTO", "font/otf"),
+        (b"\x00\x01\x00\x00\x00", "font/ttf"),
+        (b"\x00asm", "application/wasm"),
+    ];
+
+    for (prefix, mime_type) in PREFIXES {
+        if head.starts_with(prefix) {
+            return Some(mime_type);
+        }
+    }
+
+    if head.len() >= 12 && head.starts_with(b"RIFF") {
+        return match &head[8..12] {
+            b"WEBP" => Some("image/webp"),
+            b"WAVE" => Some("audio/wav"),
+            b"AVI " => Some("video/x-msvideo"),
+            _ => None,
+        };
+    }
+
+    if head.len() >= 12 && &head[4..8] == b"ftyp" {
+        return Some(match &head[8..12] {
+            b"avif" | b"avis" => "image/avif",
+            b"heic" | b"heix" | b"heim" | b"heis" => "image/heic",
+            b"mif1" | b"msf1" => "image/heif",
+            b"qt  " => "video/quicktime",
+            b"M4A " | b"M4B " => "audio/mp4",
+            b"3gp4" | b"3gp5" | b"3gp6" => "video/3gpp",
+            _ => "video/mp4",
+        });
+    }
+
+    if head.starts_with(b"\x1a\x45\xdf\xa3") {
+        return Some(if contains_bytes(head, b"webm") {
+            "video/webm"
+        } else if extension == "mka" {
+            "audio/matroska"
+        } else {
+            "video/matroska"
+        });
+    }
+
+    if head.starts_with(b"PK\x03\x04") || head.starts_with(b"PK\x05\x06") {
+        return Some(match extension {
+            "apk" => "application/vnd.android.package-archive",
+            "docx" => "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+            "epub" => "application/epub+zip",
+            "jar" => "application/java-archive",
+            "odp" => "application/vnd.oasis.opendocument.presentation",
+            "ods" => "application/vnd.oasis.opendocument.spreadsheet",
+            "odt" => "application/vnd.oasis.opendocument.text",
+            "pptx" => "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+            "xlsx" => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+            _ => "application/zip",
+        });
+    }
+
+    if head.len() >= 262 && &head[257..262] == b"ustar" {
+        return Some("application/x-tar");
+    }
+
+    // MPEG audio frames without an ID3 tag start with an 11-bit frame sync.
+    if head.len() >= 2 && head[0] == 0xff && head[1] & 0xe0 == 0xe0 && extension == "mp3" {
+        return Some("audio/mpeg");
+    }
+
+    None
+}
+
+/// Decodes the start of a file's content as text, returning its charset and decoded text, or
+/// [`None`] if the content seems to be binary.
+fn decode_text_head(head: &[u8]) -> Option<(&'static str, String)> {
+    if let Some(utf16) = head.strip_prefix(b"\xff\xfe") {
+        return Some(("utf-16le", decode_utf16(utf16, u16::from_le_bytes)));
+    }
+
+    if let Some(utf16) = head.strip_prefix(b"\xfe\xff") {
+        return Some(("utf-16be", decode_utf16(utf16, u16::from_be_bytes)));
+    }
+
+    if head.iter().any(|&byte| is_binary_byte(byte)) {
+        return None;
+    }
+
+    let head_without_bom = head.strip_prefix(b"\xef\xbb\xbf").unwrap_or(head);
+
+    match str::from_utf8(head_without_bom) {
+        Ok(text) => Some(("utf-8", text.into())),
+
+        // The head may have been cut off in the middle of a UTF-8 sequence.
+        Err(error) if error.error_len().is_none() => Some((
+            "utf-8",
+            String::from_utf8_lossy(&head_without_bom[..error.valid_up_to()]).into_owned(),
+        )),
+
+        // Text that isn't UTF-8 is most likely in this legacy encoding, which browsers also treat
+        // ISO-8859-1 as.
+        Err(_) => Some((
+            "windows-1252",
+            head.iter().map(|&byte| char::from(byte)).collect(),
+        )),
+    }
+}
+
+/// Decodes UTF-16 bytes lossily using the specified byte order, ignoring any odd trailing byte.
+fn decode_utf16(bytes: &[u8], from_bytes: fn([u8; 2]) -> u16) -> String {
+    let units = bytes
+        .chunks_exact(2)
+        .map(|pair| from_bytes([pair[0], pair[1]]));
+
+    char::decode_utf16(units)
+        .map(|char| char.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect()
+}
+
+/// Checks if a byte is a control character that shouldn't appear in text.
+const fn is_binary_byte(byte: u8) -> bool {
+    matches!(byte, 0x00..=0x08 | 0x0e..=0x1a | 0x1c..=0x1f)
+}
+
+/// Checks if SVG markup contains anything that could run scripts when rendered as a document.
+fn svg_can_run_scripts(svg: &str) -> bool {
+    let svg = svg.to_ascii_lowercase();
+
+    if svg.contains("<!entity") {
+        return true;
+    }
+
+    let has_scriptable_element = svg.split('<').skip(1).any(|tag| {
+        let name = tag
+            .split(|char: char| char.is_ascii_whitespace() || matches!(char, '/' | '>'))
+            .next()
+            .unwrap_or_default();
+
+        // Elements can be given any namespace prefix, like `<svg:script>`.
+        let local_name = name.rsplit(':').next().unwrap_or(name);
+
+        SCRIPTABLE_ELEMENTS.contains(&local_name)
+    });
+
+    let has_event_handler = svg.match_indices("on").any(|(i, _)| {
+        let is_attribute_start = svg[..i]
+            .chars()
+            .next_back()
+            .is_some_and(|char| char.is_ascii_whitespace() || matches!(char, '/' | '"' | '\''));
+
+        // Match event handler attributes like `onload=`.
+        is_attribute_start
+            && svg[i + 2..]
+                .trim_start_matches(|char: char| char.is_ascii_alphabetic())
+                .trim_start()
+                .starts_with('=')
+    });
+
+    // Browsers ignore whitespace and control characters in URL schemes, and attribute values can
+    // spell them with character references.
+    let has_javascript_url = decode_character_references(&svg)
+        .chars()
+        .filter(|char| !char.is_ascii_whitespace() && !char.is_control())
+        .collect::<String>()
+        .contains("javascript:");
+
+    has_scriptable_element || has_event_handler || has_javascript_url
+}
+
+/// Decodes numeric character references (like `&#58;` and `&#x3a;`) and the named character
+/// references that matter for detecting URL schemes. Others are left as is.
+fn decode_character_references(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(i) = rest.find('&') {
+        decoded.push_str(&rest[..i]);
+        rest = &rest[i + 1..];
+
+        let reference_end = rest
+            .find(|char: char| !char.is_ascii_alphanumeric() && char != '#')
+            .unwrap_or(rest.len());
+        let reference = &rest[..reference_end];
+
+        let char = match reference.strip_prefix('#') {
+            Some(number) => match number.strip_prefix('x') {
+                Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                None => number.parse().ok(),
+            }
+            .and_then(char::from_u32),
+            None => match reference {
+                "colon" => Some(':'),
+                "tab" => Some('\t'),
+                "newline" => Some('\n'),
+                "amp" => Some('&'),
+                _ => None,
+            },
+        };
+
+        match char {
+            Some(char) => {
+                decoded.push(char);
+                rest = &rest[reference_end..];
+                rest = rest.strip_prefix(';').unwrap_or(rest);
+            }
+            None => decoded.push('&'),
+        }
+    }
+
+    decoded.push_str(rest);
+    decoded
+}
+
+/// Checks if a string starts with a prefix, ignoring ASCII case.
+fn starts_with_ignore_ascii_case(string: &str, prefix: &str) -> bool {
+    string
+        .as_bytes()
+        .get(..prefix.len())
+        .is_some_and(|start| start.eq_ignore_ascii_case(prefix.as_bytes()))
+}
+
+/// Checks if a string contains a substring, ignoring ASCII case.
+fn contains_ignore_ascii_case(string: &str, substring: &str) -> bool {
+    string
+        .as_bytes()
+        .windows(substring.len())
+        .any(|window| window.eq_ignore_ascii_case(substring.as_bytes()))
+}
+
+/// Checks if a byte slice contains a subslice.
+fn contains_bytes(bytes: &[u8], subslice: &[u8]) -> bool {
+    bytes
+        .windows(subslice.len())
+        .any(|window| window == subslice)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn magic_bytes_override_extension() {
+        let png = b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR";
+
+        assert_eq!(sniff("image.png", png), SniffedType::safe("image/png"));
+        assert_eq!(sniff("page.html", png), SniffedType::safe("image/png"));
+        assert_eq!(sniff("no-extension", png), SniffedType::safe("image/png"));
+    }
+
+    #[test]
+    fn zip_based_formats_use_extension() {
+        let zip = b"PK\x03\x04\x14\x00\x00\x00";
+
+        assert_eq!(
+            sniff("book.epub", zip),
+            SniffedType::safe("application/epub+zip"),
+        );
+        assert_eq!(
+            sniff("archive.zip", zip),
+            SniffedType::safe("application/zip")
+        );
+        assert_eq!(
+            sniff("archive.txt", zip),
+            SniffedType::safe("application/zip")
+        );
+    }
+
+    #[test]
+    fn text_encodings() {
+        assert_eq!(
+            sniff("notes.txt", "héllo".as_bytes()),
+            SniffedType::safe("text/plain; charset=utf-8"),
+        );
+        assert_eq!(
+            sniff("notes.txt", b"h\xe9llo"),
+            SniffedType::safe("text/plain; charset=windows-1252"),
+        );
+        assert_eq!(
+            sniff("notes.txt", b"\xff\xfeh\x00i\x00"),
+            SniffedType::safe("text/plain; charset=utf-16le"),
+        );
+
+        // The head can be cut off in the middle of a UTF-8 sequence.
+        assert_eq!(
+            sniff("notes.txt", &"🌱".as_bytes()[..3]),
+            SniffedType::safe("text/plain; charset=utf-8"),
+        );
+    }
+
+    #[test]
+    fn binary_without_magic_bytes() {
+        assert_eq!(
+            sniff("data.txt", b"\x00\x01\x02\x03"),
+            SniffedType::safe(UNKNOWN_TYPE),
+        );
+    }
+
+    #[test]
+    fn dangerous_types() {
+        assert!(sniff("page.html", b"<!DOCTYPE html>").dangerous);
+
+        // HTML content can't be dangerous without a type that browsers render as HTML.
+        assert_eq!(
+            sniff("page.txt", b"<!DOCTYPE html><script></script>"),
+            SniffedType::safe("text/plain; charset=utf-8"),
+        );
+
+        let safe_svg =
+            br#"<svg xmlns="http://www.w3.org/2000/svg"><text>Bonjour &amp; ton=1</text></svg>"#;
+        assert_eq!(
+            sniff("image.svg", safe_svg),
+            SniffedType::safe("image/svg+xml; charset=utf-8"),
+        );
+        assert_eq!(
+            sniff("image.txt", safe_svg),
+            SniffedType::safe("image/svg+xml; charset=utf-8"),
+        );
+
+        let unsafe_svgs: [&[u8]; 9] = [
+            br#"<svg xmlns="http://www.w3.org/2000/svg"><script>alert(1)</script></svg>"#,
+            br#"<svg xmlns="http://www.w3.org/2000/svg" onload = "alert(1)"></svg>"#,
+            br#"<?xml version="1.0"?><svg><a href="javascript:alert(1)"/></svg>"#,
+            b"<svg/onload=alert(1)>",
+            b"<svg xmlns=\"http://www.w3.org/2000/svg\"\ronload=\"alert(1)\"></svg>",
+            b"<svg xmlns=\"http://www.w3.org/2000/svg\"\x0conload=\"alert(1)\"></svg>",
+            br#"<svg><a href="javascript&colon;alert(1)"/></svg>"#,
+            br#"<svg><a href="java&#x09;script&#58;alert(1)"/></svg>"#,
+            br#"<svg xmlns:s="http://www.w3.org/2000/svg"><s:script>alert(1)</s:script></svg>"#,
+        ];
+
+        for svg in unsafe_svgs {
+            assert!(
+                sniff("image.svg", svg).dangerous,
+                "SVG {:?} should be dangerous",
+                String::from_utf8_lossy(svg),
+            );
+        }
+    }
+}
@@ -20,6 +20,28 @@ pub(crate) type Token = Id<[u8; 128]>;
 /// The type to create new folder IDs with. Note that existing folder IDs may not fit this type.
 pub(crate) type NewFolderId = Id<[u8; 9]>;
 
+/// The type to create new file IDs with. Note that existing file IDs may not fit this type.
+pub(crate) type NewFileId = Id<[u8; 9]>;
+
+/// The type to create new file content IDs with. Note that existing file content IDs may not fit
+/// this type.
+pub(crate) type NewFileContentId = Id<[u8; 16]>;
+
+/// The type to create new upload link IDs with.
+pub(crate) type NewUploadLinkId = Id<[u8; 9]>;
+
+/// The type to create new file version IDs with.
+pub(crate) type NewFileVersionId = Id<[u8; 9]>;
+
+/// A challenge for a WebAuthn registration or assertion to sign.
+pub(crate) type WebAuthnChallenge = Id<[u8; 32]>;
+
+/// The type to create new OAuth client IDs with.
+pub(crate) type NewOAuthClientId = Id<[u8; 16]>;
+
+/// The type to create new OAuth grant IDs with.
+pub(crate) type NewOAuthGrantId = Id<[u8; 16]>;
+
 /// A folder's browse key.
 pub(crate) type FolderBrowseKey = Id<[u8; 24]>;
 
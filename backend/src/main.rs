@@ -1,6 +1,6 @@
 //! File Garden's backend web server.
 
-use std::sync::LazyLock;
+use std::{env, net::SocketAddr, sync::LazyLock};
 
 use axum::handler::HandlerWithoutStateExt;
 use tokio::net::TcpListener;
@@ -10,11 +10,21 @@ mod content;
 mod crypto;
 mod db;
 mod email;
+mod file_type;
 mod id;
+mod oidc;
 mod percent_encoding;
+mod processing;
 mod response;
 mod router;
+mod s3;
+mod scrub;
+mod storage;
+mod sweep;
+mod webauthn;
+mod webdav;
 mod website;
+mod xml;
 
 /// The URI origin for user-uploaded content.
 pub(crate) static CONTENT_ORIGIN: LazyLock<String> = LazyLock::new(|| {
@@ -42,13 +52,45 @@ async fn main() -> anyhow::Result<()> {
 
     db::initialize(&db_url).await?;
 
+    // Administrative commands are run instead of the server if passed as an argument.
+    if let Some(command) = env::args().nth(1) {
+        return run_command(&command).await;
+    }
+
+    tokio::spawn(scrub::run());
+    tokio::spawn(sweep::run());
+
     println!("Listening to {address}...");
 
     let listener = TcpListener::bind(address).await?;
 
     println!("Ready!");
 
-    axum::serve(listener, router::handle.into_make_service()).await?;
+    axum::serve(
+        listener,
+        router::handle.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Runs an administrative command.
+///
+/// # Errors
+///
+/// Returns an error if the command is unknown or fails.
+async fn run_command(command: &str) -> anyhow::Result<()> {
+    match command {
+        // Re-encrypts all TOTP secrets with the current key, so older keys can be removed from
+        // `TOTP_SECRET_KEYS` afterward.
+        "reencrypt-totp-secrets" => {
+            let count = db::encrypt_totp_secrets(true).await?;
+
+            println!("Re-encrypted {count} TOTP secrets.");
+        }
+        _ => anyhow::bail!("unknown command `{command}`"),
+    }
 
     Ok(())
 }
@@ -0,0 +1,447 @@
+//! A client for signing users in with an external OpenID Connect provider, using the authorization
+//! code flow with PKCE.
+//!
+//! ID tokens are received directly from the provider's token endpoint rather than through the
+//! browser, so TLS authenticates them instead of their signatures, as OpenID Connect Core 1.0
+//! (section 3.1.3.7) allows. That's why the issuer must use HTTPS, except on loopback addresses so
+//! a mock provider can be used for local testing.
+
+use std::{env::VarError, sync::LazyLock};
+
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
+use chrono::Utc;
+use reqwest::{StatusCode, Url};
+use serde::Deserialize;
+use thiserror::Error;
+use tokio::sync::OnceCell;
+
+use crate::{
+    WEBSITE_ORIGIN,
+    crypto::hash_without_salt,
+    id::{Id, Token},
+};
+
+/// The configured OpenID Connect provider, or [`None`] if signing in with one isn't enabled.
+pub(crate) static PROVIDER: LazyLock<Option<Provider>> = LazyLock::new(|| {
+    let issuer = match dotenvy::var("OIDC_ISSUER") {
+        Err(dotenvy::Error::EnvVar(VarError::NotPresent)) => return None,
+
+        issuer => {
+            issuer.expect("environment variable `OIDC_ISSUER` should be a valid string if set")
+        }
+    };
+
+    let is_issuer_secure = Url::parse(&issuer).is_ok_and(|url| match url.scheme() {
+        "https" => true,
+        "http" => matches!(url.host_str(), Some("localhost" | "127.0.0.1" | "[::1]")),
+        _ => false,
+    });
+
+    assert!(
+        is_issuer_secure,
+        "environment variable `OIDC_ISSUER` should be an HTTPS URL, or HTTP on a loopback address",
+    );
+
+    let client_id = dotenvy::var("OIDC_CLIENT_ID").expect(
+        "environment variable `OIDC_CLIENT_ID` should be a valid string if `OIDC_ISSUER` is set",
+    );
+
+    let client_secret =
+        match dotenvy::var("OIDC_CLIENT_SECRET") {
+            // If the environment variable is unset, authenticate as a public client with PKCE
+            // alone.
+            Err(dotenvy::Error::EnvVar(VarError::NotPresent)) => None,
+
+            client_secret => Some(client_secret.expect(
+                "environment variable `OIDC_CLIENT_SECRET` should be a valid string if set",
+            )),
+        };
+
+    Some(Provider {
+        issuer,
+        client_id,
+        client_secret,
+        metadata: OnceCell::new(),
+    })
+});
+
+/// The client for connecting to the OpenID Connect provider.
+static OIDC_CLIENT: LazyLock<reqwest::Client> = LazyLock::new(reqwest::Client::new);
+
+/// The URI the provider redirects users back to, which is a website page that passes the
+/// authorization response to the API.
+static REDIRECT_URI: LazyLock<String> =
+    LazyLock::new(|| format!("{}/oidc-callback", *WEBSITE_ORIGIN));
+
+/// How many seconds of clock skew with the provider to allow when checking ID tokens' expiration.
+const CLOCK_SKEW_SECS: i64 = 60;
+
+/// An external OpenID Connect provider.
+#[derive(Debug)]
+pub(crate) struct Provider {
+    /// The provider's issuer identifier, which is the URL its discovery document is under.
+    issuer: String,
+
+    /// This server's client ID registered with the provider.
+    client_id: String,
+
+    /// This server's client secret registered with the provider, or [`None`] if it's a public
+    /// client.
+    client_secret: Option<String>,
+
+    /// The provider's metadata, fetched from its discovery document the first time it's needed.
+    metadata: OnceCell<Metadata>,
+}
+
+/// The members of an OpenID Connect provider's discovery document that are needed.
+#[derive(Deserialize, Debug)]
+struct Metadata {
+    /// The provider's issuer identifier, which must match the configured one.
+    issuer: String,
+
+    /// The URL to redirect users to for authorization.
+    authorization_endpoint: String,
+
+    /// The URL to exchange authorization codes for tokens at.
+    token_endpoint: String,
+}
+
+/// A new authorization request to redirect a user to the provider with. Its state, nonce, and code
+/// verifier must be stored until the user is redirected back.
+#[derive(Debug)]
+pub(crate) struct Authorization {
+    /// The state, which identifies the authorization request when the user is redirected back.
+    pub(crate) state: Token,
+
+    /// The nonce the ID token must contain.
+    pub(crate) nonce: String,
+
+    /// The PKCE code verifier.
+    pub(crate) code_verifier: String,
+
+    /// The provider's authorization URL to redirect the user to.
+    pub(crate) url: String,
+}
+
+/// An account at the provider, identified by a verified ID token.
+#[derive(Debug)]
+pub(crate) struct Identity {
+    /// The provider's issuer identifier.
+    pub(crate) issuer: String,
+
+    /// The account's subject identifier, which the provider guarantees is unique and never
+    /// reassigned.
+    pub(crate) subject: String,
+}
+
+/// A `POST` response body from the provider's token endpoint.
+#[derive(Deserialize, Debug)]
+struct TokenResponse {
+    /// The ID token, as a JWT.
+    id_token: String,
+}
+
+/// An ID token's audience, which can be one client ID or an array of them.
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum Audience {
+    /// A single client ID.
+    One(String),
+
+    /// An array of client IDs.
+    Many(Vec<String>),
+}
+
+/// The claims of an ID token that need verifying.
+#[derive(Deserialize, Debug)]
+struct IdTokenClaims {
+    /// The issuer identifier.
+    iss: String,
+
+    /// The subject identifier.
+    sub: String,
+
+    /// The client IDs the ID token is intended for.
+    aud: Audience,
+
+    /// The client ID the ID token was issued to, if it has multiple audiences.
+    #[serde(default)]
+    azp: Option<String>,
+
+    /// The Unix timestamp the ID token expires at.
+    exp: i64,
+
+    /// The nonce from the authorization request.
+    #[serde(default)]
+    nonce: Option<String>,
+}
+
+/// An error signing in with the OpenID Connect provider.
+#[derive(Error, Debug)]
+pub(crate) enum Error {
+    /// A request to the provider failed.
+    #[error("OpenID Connect provider request failed: {0}")]
+    Request(#[from] reqwest::Error),
+
+    /// The provider's discovery document is invalid or for a different issuer than the configured
+    /// one.
+    #[error("OpenID Connect provider's discovery document is invalid")]
+    Metadata,
+
+    /// The provider's token endpoint rejected the authorization code.
+    #[error("OpenID Connect provider rejected the authorization code")]
+    CodeRejected,
+
+    /// The ID token is malformed, or it isn't valid for this client and authorization request.
+    #[error("invalid ID token")]
+    IdToken,
+}
+
+impl Provider {
+    /// Gets the provider's metadata, fetching its discovery document if it hasn't been yet.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the discovery document can't be fetched or is invalid.
+    async fn metadata(&self) -> Result<&Metadata, Error> {
+        self.metadata
+            .get_or_try_init(|| async {
+                let metadata: Metadata = OIDC_CLIENT
+                    .get(format!(
+                        "{}/.well-known/openid-configuration",
+                        self.issuer.trim_end_matches('/'),
+                    ))
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .json()
+                    .await?;
+
+                if metadata.issuer != self.issuer {
+                    return Err(Error::Metadata);
+                }
+
+                Ok(metadata)
+            })
+            .await
+    }
+
+    /// Creates a new authorization request to redirect a user to the provider with.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the provider's metadata can't be fetched or is invalid.
+    pub(crate) async fn authorize(&self) -> Result<Authorization, Error> {
+        let metadata = self.metadata().await?;
+
+        let state = Token::generate();
+        let nonce = Id::<[u8; 32]>::generate().to_string();
+        let code_verifier = Id::<[u8; 32]>::generate().to_string();
+        let code_challenge = URL_SAFE_NO_PAD.encode(hash_without_salt(&code_verifier));
+
+        let mut url = Url::parse(&metadata.authorization_endpoint).map_err(|_| Error::Metadata)?;
+
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &self.client_id)
+            .append_pair("redirect_uri", &REDIRECT_URI)
+            .append_pair("scope", "openid")
+            .append_pair("state", &state.to_string())
+            .append_pair("nonce", &nonce)
+            .append_pair("code_challenge", &code_challenge)
+            .append_pair("code_challenge_method", "S256");
+
+        Ok(Authorization {
+            state,
+            nonce,
+            code_verifier,
+            url: url.into(),
+        })
+    }
+
+    /// Exchanges an authorization code from the provider for an ID token, returning the identity
+    /// it verifies.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the provider can't be reached, rejects the code, or returns an ID token
+    /// that's invalid for the authorization request.
+    pub(crate) async fn exchange_code(
+        &self,
+        code: &str,
+        code_verifier: &str,
+        nonce: &str,
+    ) -> Result<Identity, Error> {
+        let metadata = self.metadata().await?;
+
+        let mut params = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", &REDIRECT_URI),
+            ("code_verifier", code_verifier),
+            ("client_id", &self.client_id),
+        ];
+
+        if let Some(client_secret) = &self.client_secret {
+            params.push(("client_secret", client_secret));
+        }
+
+        let response = OIDC_CLIENT
+            .post(&metadata.token_endpoint)
+            .form(&params)
+            .send()
+            .await?;
+
+        if response.status() == StatusCode::BAD_REQUEST
+            || response.status() == StatusCode::UNAUTHORIZED
+        {
+            return Err(Error::CodeRejected);
+        }
+
+        let response: TokenResponse = response.error_for_status()?.json().await?;
+
+        let subject = verify_id_token(
+            &response.id_token,
+            &self.issuer,
+            &self.client_id,
+            nonce,
+            Utc::now().timestamp(),
+        )?;
+
+        Ok(Identity {
+            issuer: self.issuer.clone(),
+            subject,
+        })
+    }
+}
+
+/// Verifies an ID token's claims are valid for an authorization request at a Unix timestamp,
+/// returning its subject identifier. The signature isn't verified (see the module documentation).
+///
+/// # Errors
+///
+/// Returns [`Error::IdToken`] if the ID token is malformed or any claim is invalid.
+fn verify_id_token(
+    id_token: &str,
+    issuer: &str,
+    client_id: &str,
+    nonce: &str,
+    now: i64,
+) -> Result<String, Error> {
+    let mut parts = id_token.split('.');
+
+    let (Some(_header), Some(payload), Some(_signature), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err(Error::IdToken);
+    };
+
+    let payload = URL_SAFE_NO_PAD
+        .decode(payload)
+        .map_err(|_| Error::IdToken)?;
+    let claims: IdTokenClaims = serde_json::from_slice(&payload).map_err(|_| Error::IdToken)?;
+
+    let is_audience_valid = match &claims.aud {
+        Audience::One(audience) => audience == client_id,
+        Audience::Many(audiences) => {
+            audiences.iter().any(|audience| audience == client_id)
+                && (audiences.len() == 1 || claims.azp.as_deref() == Some(client_id))
+        }
+    };
+
+    if claims.iss != issuer
+        || !is_audience_valid
+        || claims.exp + CLOCK_SKEW_SECS <= now
+        || claims.nonce.as_deref() != Some(nonce)
+        || claims.sub.is_empty()
+    {
+        return Err(Error::IdToken);
+    }
+
+    Ok(claims.sub)
+}
+
+#[cfg(test)]
+#[expect(clippy::missing_errors_doc, reason = "See rust-lang/rust-clippy#13391")]
+mod tests {
+    use serde_json::{Value, json};
+
+    use super::*;
+
+    /// Encodes claims as an unsigned JWT.
+    fn id_token(claims: &Value) -> String {
+        format!(
+            "{}.{}.",
+            URL_SAFE_NO_PAD.encode(r#"{"alg":"RS256"}"#),
+            URL_SAFE_NO_PAD.encode(claims.to_string()),
+        )
+    }
+
+    #[test]
+    fn verifies_id_token_claims() -> Result<(), Error> {
+        let claims = json!({
+            "iss": "https://idp.example",
+            "sub": "user-1",
+            "aud": ["client", "other"],
+            "azp": "client",
+            "exp": 1000,
+            "nonce": "nonce",
+        });
+
+        assert_eq!(
+            verify_id_token(
+                &id_token(&claims),
+                "https://idp.example",
+                "client",
+                "nonce",
+                900
+            )?,
+            "user-1",
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_invalid_id_tokens() {
+        let valid = json!({
+            "iss": "https://idp.example",
+            "sub": "user-1",
+            "aud": "client",
+            "exp": 1000,
+            "nonce": "nonce",
+        });
+
+        let invalid_changes = [
+            ("iss", json!("https://evil.example")),
+            ("aud", json!("other")),
+            ("aud", json!(["client", "other"])),
+            ("exp", json!(800)),
+            ("nonce", json!("replayed")),
+            ("nonce", Value::Null),
+            ("sub", json!("")),
+        ];
+
+        for (claim, value) in invalid_changes {
+            let mut claims = valid.clone();
+            claims[claim] = value;
+
+            assert!(
+                verify_id_token(
+                    &id_token(&claims),
+                    "https://idp.example",
+                    "client",
+                    "nonce",
+                    900
+                )
+                .is_err(),
+                "ID token with {claim} changed should be invalid",
+            );
+        }
+
+        assert!(
+            verify_id_token("not-a-jwt", "https://idp.example", "client", "nonce", 900).is_err(),
+            "malformed ID token should be invalid",
+        );
+    }
+}
@@ -24,3 +24,13 @@ pub(crate) const COMPONENT: &AsciiSet = &NON_ALPHANUMERIC
 /// [`encodeURIComponent`](https://developer.mozilla.org/docs/Web/JavaScript/Reference/Global_Objects/encodeURIComponent),
 /// with the exception that `/` characters are left alone rather than percent-encoded.
 pub(crate) const COMPONENT_IGNORING_SLASH: &AsciiSet = &COMPONENT.remove(b'/');
+
+/// All ASCII characters except the [unreserved characters of RFC
+/// 3986](https://datatracker.ietf.org/doc/html/rfc3986#section-2.3).
+///
+/// This is the set AWS Signature Version 4 uses to encode canonical query strings.
+pub(crate) const UNRESERVED: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'~');
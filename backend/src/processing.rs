@@ -0,0 +1,647 @@
+//! Processing of file contents into derived file contents, such as thumbnails.
+
+use std::io::{self, Cursor};
+
+use image::{DynamicImage, ImageFormat, ImageReader, Limits, imageops::FilterType};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use sqlx::PgTransaction;
+use strum_macros::{EnumString, IntoStaticStr};
+use thiserror::Error;
+use tokio::sync::Semaphore;
+
+use crate::{
+    db::{self, TxResult},
+    id::NewFileContentId,
+    storage,
+};
+
+/// The maximum size in bytes of a file content that can be processed. Processing reads the whole
+/// content into memory, so larger contents are skipped.
+const MAX_SOURCE_SIZE: i64 = 64 * 1024 * 1024;
+
+/// The maximum width or height (in pixels) of an image that can be decoded for processing.
+const MAX_DECODE_DIMENSION: u32 = 16384;
+
+/// The maximum number of bytes a decoder can allocate to decode an image for processing.
+const MAX_DECODE_ALLOCATION: u64 = 256 * 1024 * 1024;
+
+/// The maximum number of file contents that can be processed at once. Processing is CPU- and
+/// memory-intensive, so further requests wait their turn.
+static PROCESSING_PERMITS: Semaphore = Semaphore::const_new(4);
+
+/// The MIME types of images that can be decoded for processing. Contents of other types (e.g.,
+/// SVGs) are never read for processing, since they could never be decoded.
+const DECODABLE_IMAGE_TYPES: [&str; 7] = [
+    "image/bmp",
+    "image/gif",
+    "image/jpeg",
+    "image/png",
+    "image/tiff",
+    "image/webp",
+    "image/x-icon",
+];
+
+/// The widths and heights (in pixels) that thumbnails can be generated to fit within.
+pub(crate) const THUMBNAIL_SIZES: [u32; 3] = [128, 256, 512];
+
+/// The prefix of every resized image's variant string.
+const RESIZE_VARIANT_PREFIX: &str = "resize-";
+
+/// The maximum number of resized variants that can be derived from one file content. Beyond this,
+/// the original file content is served instead, so one shared image can't be used to fill storage
+/// with endless variants.
+const MAX_RESIZE_VARIANTS: i64 = 16;
+
+/// A complete file content to derive other file contents from.
+#[derive(Clone, Debug)]
+pub(crate) struct SourceContent {
+    /// The file content's ID.
+    pub(crate) id: Vec<u8>,
+
+    /// The file content's SHA-256 hash.
+    pub(crate) hash: Vec<u8>,
+
+    /// The number of parts the file content is stored in.
+    pub(crate) part_count: i32,
+
+    /// The size of the file content in bytes.
+    pub(crate) size: i64,
+}
+
+/// A file content derived from another file content.
+#[derive(Clone, Debug)]
+pub(crate) struct DerivedContent {
+    /// The derived file content's ID.
+    pub(crate) content_id: Vec<u8>,
+
+    /// The derived file content's SHA-256 hash.
+    pub(crate) hash: Vec<u8>,
+
+    /// The number of parts the derived file content is stored in.
+    pub(crate) part_count: i32,
+
+    /// The size of the derived file content in bytes.
+    pub(crate) size: i64,
+
+    /// The derived file content's MIME type.
+    pub(crate) r#type: String,
+}
+
+/// The maximum width or height (in pixels) that images can be resized to.
+pub(crate) const MAX_RESIZE_DIMENSION: u32 = 4096;
+
+/// An image format that derived images can be encoded in.
+#[derive(
+    Deserialize, EnumString, IntoStaticStr, Clone, Copy, PartialEq, Eq, Hash, Default, Debug,
+)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub(crate) enum ImageOutputFormat {
+    /// Lossless WebP.
+    #[default]
+    Webp,
+
+    /// AVIF.
+    Avif,
+
+    /// PNG.
+    Png,
+}
+
+impl ImageOutputFormat {
+    /// Gets the [`ImageFormat`] to encode images in.
+    const fn image_format(self) -> ImageFormat {
+        match self {
+            Self::Webp => ImageFormat::WebP,
+            Self::Avif => ImageFormat::Avif,
+            Self::Png => ImageFormat::Png,
+        }
+    }
+
+    /// Gets the format's MIME type.
+    const fn mime_type(self) -> &'static str {
+        match self {
+            Self::Webp => "image/webp",
+            Self::Avif => "image/avif",
+            Self::Png => "image/png",
+        }
+    }
+
+    /// Gets the format's file extension.
+    pub(crate) const fn extension(self) -> &'static str {
+        match self {
+            Self::Webp => "webp",
+            Self::Avif => "avif",
+            Self::Png => "png",
+        }
+    }
+}
+
+/// How a resized image fits within the specified width and height when both are specified.
+#[derive(EnumString, IntoStaticStr, Clone, Copy, PartialEq, Eq, Hash, Default, Debug)]
+#[strum(serialize_all = "lowercase")]
+pub(crate) enum Fit {
+    /// Scale the image to fit within the width and height, preserving its aspect ratio.
+    #[default]
+    Contain,
+
+    /// Scale the image to cover the width and height, preserving its aspect ratio and cropping
+    /// whatever's outside them.
+    Cover,
+
+    /// Stretch the image to exactly the width and height, or to the largest size with their aspect
+    /// ratio that the image can be scaled down to.
+    Fill,
+}
+
+/// Parameters to resize and convert an image with.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default, Debug)]
+pub(crate) struct Resize {
+    /// The maximum width in pixels, or [`None`] to only constrain the height. Must be at most
+    /// [`MAX_RESIZE_DIMENSION`].
+    pub(crate) width: Option<u32>,
+
+    /// The maximum height in pixels, or [`None`] to only constrain the width. Must be at most
+    /// [`MAX_RESIZE_DIMENSION`].
+    pub(crate) height: Option<u32>,
+
+    /// How the image fits within the width and height. Ignored unless both are specified.
+    pub(crate) fit: Fit,
+
+    /// The image format to convert to.
+    pub(crate) format: ImageOutputFormat,
+}
+
+impl Resize {
+    /// Gets a string unique to these parameters, for identifying the derived image.
+    fn variant(self) -> String {
+        let dimension = |dimension: Option<u32>| match dimension {
+            Some(dimension) => dimension.to_string(),
+            None => "auto".into(),
+        };
+
+        format!(
+            "{RESIZE_VARIANT_PREFIX}{}x{}-{}.{}",
+            dimension(self.width),
+            dimension(self.height),
+            <&str>::from(self.fit),
+            self.format.extension(),
+        )
+    }
+
+    /// Resizes an image according to these parameters. Images are never scaled up.
+    fn apply(self, image: DynamicImage) -> DynamicImage {
+        let (source_width, source_height) = (image.width(), image.height());
+
+        match (self.width, self.height, self.fit) {
+            (None, None, _) => image,
+            (Some(width), Some(height), fit @ (Fit::Cover | Fit::Fill)) => {
+                // Shrink the requested area proportionally until it's no larger than the source, so
+                // its aspect ratio is kept.
+                let scale = f64::max(
+                    f64::from(width) / f64::from(source_width),
+                    f64::from(height) / f64::from(source_height),
+                )
+                .max(1.0);
+                let width = scale_dimension(width, scale);
+                let height = scale_dimension(height, scale);
+
+                if fit == Fit::Cover {
+                    image.resize_to_fill(width, height, FilterType::Lanczos3)
+                } else {
+                    image.resize_exact(width, height, FilterType::Lanczos3)
+                }
+            }
+            (width, height, _) => {
+                let width = width.unwrap_or(u32::MAX);
+                let height = height.unwrap_or(u32::MAX);
+
+                if width >= source_width && height >= source_height {
+                    image
+                } else {
+                    image.resize(width, height, FilterType::Lanczos3)
+                }
+            }
+        }
+    }
+}
+
+/// Checks whether a file content of a MIME type is an image that can be processed.
+pub(crate) fn is_decodable_image(mime_type: &str) -> bool {
+    DECODABLE_IMAGE_TYPES.contains(&mime_type)
+}
+
+/// Divides an image dimension by a scale factor, rounding to at least one pixel.
+fn scale_dimension(dimension: u32, scale: f64) -> u32 {
+    #[expect(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        reason = "the result is positive and no larger than the original dimension"
+    )]
+    let scaled = (f64::from(dimension) / scale).round() as u32;
+
+    scaled.max(1)
+}
+
+/// An error processing a file content.
+#[derive(Error, Debug)]
+pub(crate) enum Error {
+    /// A database query failed.
+    #[error(transparent)]
+    Database(#[from] sqlx::Error),
+
+    /// Reading from or writing to storage failed.
+    #[error(transparent)]
+    Storage(#[from] io::Error),
+
+    /// The processing task panicked or was cancelled.
+    #[error(transparent)]
+    Task(#[from] tokio::task::JoinError),
+}
+
+/// Gets a thumbnail of an image file content that fits within the specified size (which should be
+/// one of [`THUMBNAIL_SIZES`]), generating it if it doesn't exist yet.
+///
+/// Returns [`None`] if the file content isn't an image that can be decoded.
+///
+/// # Errors
+///
+/// Returns an error if a database query, storage operation, or processing task fails.
+pub(crate) async fn thumbnail(
+    source: &SourceContent,
+    size: u32,
+    format: ImageOutputFormat,
+) -> Result<Option<DerivedContent>, Error> {
+    let variant = format!("thumbnail-{size}.{}", format.extension());
+
+    derive(source, &variant, None, move |bytes| {
+        let image = decode(bytes)?;
+
+        // Thumbnails should never be larger than the source image.
+        let image = if image.width() > size || image.height() > size {
+            image.thumbnail(size, size)
+        } else {
+            image
+        };
+
+        encode(image, format)
+    })
+    .await
+}
+
+/// Gets a resized and converted variant of an image file content, generating it if it doesn't
+/// exist yet.
+///
+/// Returns [`None`] if the file content isn't an image that can be decoded, or if the maximum
+/// number of resized variants of it already exist.
+///
+/// # Errors
+///
+/// Returns an error if a database query, storage operation, or processing task fails.
+pub(crate) async fn resize(
+    source: &SourceContent,
+    resize: Resize,
+) -> Result<Option<DerivedContent>, Error> {
+    let limit = VariantLimit {
+        prefix: RESIZE_VARIANT_PREFIX,
+        max: MAX_RESIZE_VARIANTS,
+    };
+
+    derive(source, &resize.variant(), Some(limit), move |bytes| {
+        let image = decode(bytes)?;
+
+        encode(resize.apply(image), resize.format)
+    })
+    .await
+}
+
+/// Decodes an image within limits on its dimensions and memory use, returning [`None`] if it can't
+/// be decoded or exceeds the limits.
+fn decode(bytes: Vec<u8>) -> Option<DynamicImage> {
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_DECODE_DIMENSION);
+    limits.max_image_height = Some(MAX_DECODE_DIMENSION);
+    limits.max_alloc = Some(MAX_DECODE_ALLOCATION);
+
+    let mut reader = ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()
+        .ok()?;
+    reader.limits(limits);
+
+    reader.decode().ok()
+}
+
+/// Encodes an image, returning its bytes and MIME type, or [`None`] if encoding fails.
+fn encode(image: DynamicImage, format: ImageOutputFormat) -> Option<(Vec<u8>, String)> {
+    // The WebP encoder only supports 8-bit RGB(A) images.
+    let image = DynamicImage::ImageRgba8(image.into_rgba8());
+
+    let mut output = Cursor::new(Vec::new());
+    image.write_to(&mut output, format.image_format()).ok()?;
+
+    Some((output.into_inner(), format.mime_type().into()))
+}
+
+/// Generates thumbnails of an image file content in the background at every size in
+/// [`THUMBNAIL_SIZES`], so they're ready by the time they're requested.
+///
+/// Errors are ignored, since any missing thumbnail is generated again when it's requested.
+pub(crate) fn generate_thumbnails(source: SourceContent) {
+    tokio::spawn(async move {
+        for size in THUMBNAIL_SIZES {
+            if !matches!(
+                thumbnail(&source, size, ImageOutputFormat::default()).await,
+                Ok(Some(_)),
+            ) {
+                break;
+            }
+        }
+    });
+}
+
+/// A limit on how many variants of one kind can be derived from the same source file content.
+#[derive(Clone, Copy, Debug)]
+struct VariantLimit {
+    /// The prefix of the variant strings of this kind.
+    prefix: &'static str,
+
+    /// The maximum number of variants of this kind.
+    max: i64,
+}
+
+/// The result of storing a newly derived file content.
+enum Insertion {
+    /// The derived file content was stored.
+    Inserted,
+
+    /// Another task derived the same file content concurrently.
+    Existing(DerivedContent),
+
+    /// The limit on variants of the derived file content's kind was reached concurrently.
+    LimitReached,
+}
+
+/// Gets a file content derived from a source file content, identified by a variant string unique to
+/// the derivation and its parameters. If the derived file content doesn't exist yet, it's
+/// generated by calling `process` on a blocking thread with the source file content's bytes, unless
+/// `limit` is reached.
+///
+/// `process` returns the derived file content's bytes and MIME type, or [`None`] if the source
+/// file content can't be processed. Source file contents that can't be processed are recorded, so
+/// they're never read again to try.
+///
+/// # Errors
+///
+/// Returns an error if a database query, storage operation, or processing task fails.
+async fn derive<F>(
+    source: &SourceContent,
+    variant: &str,
+    limit: Option<VariantLimit>,
+    process: F,
+) -> Result<Option<DerivedContent>, Error>
+where
+    F: FnOnce(Vec<u8>) -> Option<(Vec<u8>, String)> + Send + 'static,
+{
+    let (derived, can_derive) = db::transaction!(async |tx| -> TxResult<_> {
+        let derived = query_derived(tx, source, variant).await?;
+
+        if derived.is_some() {
+            return Ok((derived, false));
+        }
+
+        let is_unprocessable = sqlx::query_scalar!(
+            r#"SELECT EXISTS(
+                SELECT FROM unprocessable_file_contents
+                    WHERE source_content_hash = $1
+            ) AS "exists!""#,
+            source.hash,
+        )
+        .fetch_one(tx.as_mut())
+        .await?;
+
+        let can_derive = !is_unprocessable && !is_limit_reached(tx, source, limit).await?;
+
+        Ok((None, can_derive))
+    })
+    .await?;
+
+    if derived.is_some() {
+        return Ok(derived);
+    }
+
+    if !can_derive || source.size > MAX_SOURCE_SIZE {
+        return Ok(None);
+    }
+
+    let _permit = PROCESSING_PERMITS
+        .acquire()
+        .await
+        .expect("processing semaphore should never be closed");
+
+    let mut bytes = Vec::new();
+    for part_index in 0..source.part_count {
+        bytes.extend(storage::read_part(&source.id, part_index).await?);
+    }
+
+    let Some((output, r#type)) = tokio::task::spawn_blocking(|| process(bytes)).await? else {
+        db::transaction!(async |tx| -> TxResult<_> {
+            sqlx::query!(
+                "INSERT INTO unprocessable_file_contents (source_content_hash)
+                    VALUES ($1)
+                    ON CONFLICT (source_content_hash) DO NOTHING",
+                source.hash,
+            )
+            .execute(tx.as_mut())
+            .await?;
+
+            Ok(())
+        })
+        .await?;
+
+        return Ok(None);
+    };
+
+    let content_id = NewFileContentId::generate();
+    let size = i64::try_from(output.len()).expect("derived content size should fit in `i64`");
+    let part_size = i32::try_from(output.len()).expect("derived content should fit in one part");
+    let hash = Sha256::digest(&output);
+
+    storage::write_part(content_id.as_slice(), 0, &output).await?;
+
+    let result = db::transaction!(async |tx| -> TxResult<_> {
+        // Another task may have derived the same content or other variants concurrently.
+        if let Some(derived) = query_derived(tx, source, variant).await? {
+            return Ok(Insertion::Existing(derived));
+        }
+
+        if is_limit_reached(tx, source, limit).await? {
+            return Ok(Insertion::LimitReached);
+        }
+
+        sqlx::query!(
+            "INSERT INTO file_contents (
+                id,
+                complete,
+                hash,
+                original_size,
+                encoded_size,
+                part_count,
+                decoded_part_sizes
+            )
+                VALUES ($1, TRUE, $2, $3, $3, 1, ARRAY[$4::integer])",
+            content_id.as_slice(),
+            hash.as_slice(),
+            size,
+            part_size,
+        )
+        .execute(tx.as_mut())
+        .await?;
+
+        sqlx::query!(
+            "INSERT INTO derived_file_contents (source_content_hash, variant, content_id, type)
+                VALUES ($1, $2, $3, $4)",
+            source.hash,
+            variant,
+            content_id.as_slice(),
+            r#type,
+        )
+        .execute(tx.as_mut())
+        .await?;
+
+        Ok(Insertion::Inserted)
+    })
+    .await;
+
+    let derived = match result {
+        Ok(Insertion::Inserted) => None,
+        Ok(Insertion::Existing(derived)) => Some(derived),
+        Ok(Insertion::LimitReached) => {
+            storage::delete_content(content_id.as_slice()).await?;
+            return Ok(None);
+        }
+        Err(error) => {
+            let _ = storage::delete_content(content_id.as_slice()).await;
+            return Err(error.into());
+        }
+    };
+
+    if let Some(derived) = derived {
+        storage::delete_content(content_id.as_slice()).await?;
+        return Ok(Some(derived));
+    }
+
+    Ok(Some(DerivedContent {
+        content_id: content_id.to_vec(),
+        hash: hash.to_vec(),
+        part_count: 1,
+        size,
+        r#type,
+    }))
+}
+
+/// Checks whether the maximum number of variants of a kind have already been derived from a source
+/// file content. Always `false` if there's no limit.
+///
+/// # Errors
+///
+/// Returns an error if a database query fails.
+async fn is_limit_reached(
+    tx: &mut PgTransaction<'static>,
+    source: &SourceContent,
+    limit: Option<VariantLimit>,
+) -> TxResult<bool> {
+    let Some(limit) = limit else {
+        return Ok(false);
+    };
+
+    let variant_count = sqlx::query_scalar!(
+        r#"SELECT count(*) AS "count!" FROM derived_file_contents
+            WHERE source_content_hash = $1 AND starts_with(variant, $2)"#,
+        source.hash,
+        limit.prefix,
+    )
+    .fetch_one(tx.as_mut())
+    .await?;
+
+    Ok(variant_count >= limit.max)
+}
+
+/// Gets an existing file content derived from a source file content.
+///
+/// # Errors
+///
+/// Returns an error if a database query fails.
+async fn query_derived(
+    tx: &mut PgTransaction<'static>,
+    source: &SourceContent,
+    variant: &str,
+) -> TxResult<Option<DerivedContent>> {
+    Ok(sqlx::query_as!(
+        DerivedContent,
+        r#"SELECT
+            derived_file_contents.content_id,
+            file_contents.hash AS "hash!",
+            file_contents.part_count,
+            file_contents.original_size AS size,
+            derived_file_contents.type
+            FROM derived_file_contents
+            INNER JOIN file_contents ON file_contents.id = derived_file_contents.content_id
+            WHERE derived_file_contents.source_content_hash = $1
+                AND derived_file_contents.variant = $2"#,
+        source.hash,
+        variant,
+    )
+    .fetch_optional(tx.as_mut())
+    .await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Gets the dimensions of a 400×200 image after resizing it.
+    fn resized(width: Option<u32>, height: Option<u32>, fit: Fit) -> (u32, u32) {
+        let resize = Resize {
+            width,
+            height,
+            fit,
+            format: ImageOutputFormat::default(),
+        };
+        let image = resize.apply(DynamicImage::new_rgba8(400, 200));
+
+        (image.width(), image.height())
+    }
+
+    #[test]
+    fn resizes_within_dimensions() {
+        assert_eq!(resized(None, None, Fit::Contain), (400, 200));
+        assert_eq!(resized(Some(100), None, Fit::Contain), (100, 50));
+        assert_eq!(resized(None, Some(100), Fit::Contain), (200, 100));
+        assert_eq!(resized(Some(100), Some(100), Fit::Contain), (100, 50));
+        assert_eq!(resized(Some(100), Some(100), Fit::Cover), (100, 100));
+        assert_eq!(resized(Some(100), Some(100), Fit::Fill), (100, 100));
+    }
+
+    #[test]
+    fn never_scales_up() {
+        assert_eq!(resized(Some(800), None, Fit::Contain), (400, 200));
+        assert_eq!(resized(Some(800), Some(800), Fit::Contain), (400, 200));
+        assert_eq!(resized(Some(800), Some(800), Fit::Cover), (200, 200));
+    }
+
+    #[test]
+    fn fill_keeps_requested_aspect_ratio() {
+        assert_eq!(resized(Some(800), Some(100), Fit::Fill), (400, 50));
+        assert_eq!(resized(Some(100), Some(800), Fit::Fill), (25, 200));
+    }
+
+    #[test]
+    fn only_raster_images_are_decodable() {
+        assert!(is_decodable_image("image/png"));
+        assert!(is_decodable_image("image/jpeg"));
+        assert!(!is_decodable_image("image/svg+xml; charset=utf-8"));
+        assert!(!is_decodable_image("image/avif"));
+        assert!(!is_decodable_image("text/plain; charset=utf-8"));
+    }
+}
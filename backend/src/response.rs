@@ -7,6 +7,7 @@ use axum::{
         header::{CONTENT_TYPE, LOCATION},
     },
 };
+use chrono::{DateTime, Utc};
 
 /// A wrapper for [`axum::response::Response`] with a simpler API.
 pub(crate) struct Response {
@@ -75,6 +76,20 @@ impl Response {
         self
     }
 
+    /// Sets the response to a [`303 See
+    /// Other`](https://developer.mozilla.org/docs/Web/HTTP/Status/303) redirect.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the location isn't a valid header value. See "Panics" section of
+    /// [`Response::header_valid`].
+    pub(crate) fn see_other(mut self, location: &str) -> Self {
+        self.status(StatusCode::SEE_OTHER)
+            .header_valid(LOCATION, location);
+
+        self
+    }
+
     /// Sets a [`StatusCode`], and sets it along with its canonical reason text (e.g. `404 Not
     /// Found`) as a `text/plain` body on the response.
     pub(crate) fn plain_error(mut self, status: StatusCode) -> Self {
@@ -89,3 +104,15 @@ impl axum::response::IntoResponse for Response {
         self.inner
     }
 }
+
+/// Formats a timestamp as an HTTP date, e.g. for the `Last-Modified` header.
+pub(crate) fn format_http_date(date: DateTime<Utc>) -> String {
+    date.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+/// Formats a file content's SHA-256 hash as a strong entity tag, e.g. for the `ETag` header.
+pub(crate) fn etag(hash: &[u8]) -> String {
+    let hex: String = hash.iter().map(|byte| format!("{byte:02x}")).collect();
+
+    format!("\"{hex}\"")
+}
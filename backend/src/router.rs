@@ -9,7 +9,7 @@ use axum::{
 };
 use axum_macros::debug_handler;
 
-use crate::{CONTENT_ORIGIN, WEBSITE_ORIGIN, api, content, website};
+use crate::{CONTENT_ORIGIN, WEBSITE_ORIGIN, api, content, s3, webdav, website};
 
 /// The URI authority for user-uploaded content.
 static CONTENT_AUTHORITY: LazyLock<&str> = LazyLock::new(|| authority_from_origin(&CONTENT_ORIGIN));
@@ -31,7 +31,7 @@ pub(super) async fn handle(request: Request) -> Response {
     };
 
     if authority == Some(*CONTENT_AUTHORITY) {
-        return content::handle(request).into_response();
+        return content::handle(request).await.into_response();
     }
 
     if authority == Some(*WEBSITE_AUTHORITY) {
@@ -39,6 +39,15 @@ pub(super) async fn handle(request: Request) -> Response {
             return api::handle(request).await;
         }
 
+        let path = request.uri().path();
+        if path == webdav::PATH_PREFIX || path.starts_with("/dav/") {
+            return webdav::handle(request).await.into_response();
+        }
+
+        if path == s3::PATH_PREFIX || path.starts_with("/s3/") {
+            return s3::handle(request).await.into_response();
+        }
+
         return website::handle(request).await;
     }
 
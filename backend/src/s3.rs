@@ -0,0 +1,343 @@
+//! An S3-compatible API over each user's files and folders, so tools that speak Amazon S3 (like
+//! `rclone` and backup software) can use File Garden. File Garden exposes this via path-style
+//! requests under `https://filegarden.com/s3`.
+//!
+//! Each bucket maps to a folder: the bucket named `root` is the user's root folder, and any other
+//! bucket name is the ID of one of the user's folders. Object keys map to name paths relative to
+//! the bucket's folder, with `/` separating folder names. Folders are created as needed when
+//! objects are written, and a key ending in `/` refers to a folder itself, so empty folders are
+//! listed as objects with such keys.
+//!
+//! Requests are authenticated with AWS Signature Version 4, signed with one of the user's S3 access
+//! keys. Only a subset of S3 is supported: listing buckets and objects, getting, putting, copying,
+//! and deleting objects, and multipart uploads.
+
+use std::io;
+
+use axum::{
+    body::Body,
+    extract::Request,
+    http::{Method, StatusCode, header::CONTENT_TYPE},
+};
+use percent_encoding::percent_decode_str;
+use strum_macros::IntoStaticStr;
+use thiserror::Error;
+
+use crate::{api, db::TxError, response::Response, xml};
+
+mod auth;
+mod bucket;
+mod multipart;
+mod objects;
+
+use auth::Payload;
+
+/// The URI path the S3-compatible API is exposed under.
+pub(crate) const PATH_PREFIX: &str = "/s3";
+
+/// The XML namespace of S3 response bodies.
+const XML_NAMESPACE: &str = "http://s3.amazonaws.com/doc/2006-03-01/";
+
+/// An S3 error. Each variant's name is its S3 error code.
+#[derive(Error, IntoStaticStr, Debug)]
+#[expect(clippy::enum_variant_names, reason = "S3 error codes can't be renamed")]
+enum Error {
+    /// The request isn't allowed, either because it isn't authenticated or because the user can't
+    /// access the requested resource.
+    #[error("Access Denied")]
+    AccessDenied,
+
+    /// The `Authorization` header can't be parsed.
+    #[error("The authorization header is malformed.")]
+    AuthorizationHeaderMalformed,
+
+    /// The request body is larger than allowed for the operation.
+    #[error("Your proposed upload exceeds the maximum allowed size.")]
+    EntityTooLarge,
+
+    /// The request body ended early or isn't correctly framed.
+    #[error("The request body is incomplete or malformed.")]
+    IncompleteBody,
+
+    /// An internal error occurred on the server which is unknown or expected never to happen.
+    #[error("We encountered an internal error. Please try again.")]
+    InternalError,
+
+    /// The access key ID the request was signed with doesn't exist.
+    #[error("The AWS access key ID you provided does not exist in our records.")]
+    InvalidAccessKeyId,
+
+    /// A request parameter is invalid.
+    #[error("Invalid argument: {0}")]
+    InvalidArgument(&'static str),
+
+    /// A part listed to complete a multipart upload wasn't uploaded, or its entity tag doesn't
+    /// match.
+    #[error(
+        "One or more of the specified parts could not be found, or the specified entity tag might \
+        not have matched the part's entity tag."
+    )]
+    InvalidPart,
+
+    /// The parts listed to complete a multipart upload aren't in ascending order.
+    #[error("The list of parts was not in ascending order.")]
+    InvalidPartOrder,
+
+    /// The requested range can't be satisfied.
+    #[error("The requested range is not satisfiable.")]
+    InvalidRange,
+
+    /// An XML request body can't be parsed.
+    #[error("The XML you provided was not well-formed or did not validate against our schema.")]
+    MalformedXML,
+
+    /// The HTTP method isn't allowed for the requested resource.
+    #[error("The specified method is not allowed against this resource.")]
+    MethodNotAllowed,
+
+    /// The requested bucket doesn't exist.
+    #[error("The specified bucket does not exist.")]
+    NoSuchBucket,
+
+    /// The requested object doesn't exist.
+    #[error("The specified key does not exist.")]
+    NoSuchKey,
+
+    /// The requested multipart upload doesn't exist.
+    #[error("The specified multipart upload does not exist.")]
+    NoSuchUpload,
+
+    /// The request uses an S3 feature that isn't supported.
+    #[error("A header or query you provided implies functionality that is not implemented.")]
+    NotImplemented,
+
+    /// A conflicting operation is in progress, such as another multipart upload to the same key.
+    #[error("A conflicting operation is currently in progress against this resource.")]
+    OperationAborted,
+
+    /// The request's timestamp is too far from the server's time.
+    #[error("The difference between the request time and the server's time is too large.")]
+    RequestTimeTooSkewed,
+
+    /// The request's signature is incorrect.
+    #[error("The request signature we calculated does not match the signature you provided.")]
+    SignatureDoesNotMatch,
+
+    /// The request body doesn't match the SHA-256 hash it was signed with.
+    #[error("The provided `x-amz-content-sha256` header does not match what was computed.")]
+    XAmzContentSHA256Mismatch,
+}
+
+impl Error {
+    /// Gets the HTTP response status code corresponding to the S3 error.
+    const fn status(&self) -> StatusCode {
+        match self {
+            Self::AccessDenied => StatusCode::FORBIDDEN,
+            Self::AuthorizationHeaderMalformed => StatusCode::BAD_REQUEST,
+            Self::EntityTooLarge => StatusCode::BAD_REQUEST,
+            Self::IncompleteBody => StatusCode::BAD_REQUEST,
+            Self::InternalError => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::InvalidAccessKeyId => StatusCode::FORBIDDEN,
+            Self::InvalidArgument(_) => StatusCode::BAD_REQUEST,
+            Self::InvalidPart => StatusCode::BAD_REQUEST,
+            Self::InvalidPartOrder => StatusCode::BAD_REQUEST,
+            Self::InvalidRange => StatusCode::RANGE_NOT_SATISFIABLE,
+            Self::MalformedXML => StatusCode::BAD_REQUEST,
+            Self::MethodNotAllowed => StatusCode::METHOD_NOT_ALLOWED,
+            Self::NoSuchBucket => StatusCode::NOT_FOUND,
+            Self::NoSuchKey => StatusCode::NOT_FOUND,
+            Self::NoSuchUpload => StatusCode::NOT_FOUND,
+            Self::NotImplemented => StatusCode::NOT_IMPLEMENTED,
+            Self::OperationAborted => StatusCode::CONFLICT,
+            Self::RequestTimeTooSkewed => StatusCode::FORBIDDEN,
+            Self::SignatureDoesNotMatch => StatusCode::FORBIDDEN,
+            Self::XAmzContentSHA256Mismatch => StatusCode::BAD_REQUEST,
+        }
+    }
+
+    /// Converts the error into an S3 XML error response.
+    fn into_response(self) -> Response {
+        let code: &'static str = (&self).into();
+        let mut response = Response::new();
+
+        response
+            .status(self.status())
+            .header_valid(CONTENT_TYPE, "application/xml");
+
+        response.body(format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<Error><Code>{code}</Code><Message>{}</Message></Error>"#,
+            xml::escape(&self.to_string()),
+        ))
+    }
+}
+
+impl From<api::Error> for Error {
+    fn from(error: api::Error) -> Self {
+        match error {
+            api::Error::AccessDenied => Self::AccessDenied,
+            api::Error::AlreadyExists => Self::OperationAborted,
+            api::Error::FileNameNotAllowed(_) => Self::InvalidArgument(
+                "keys must consist of file names allowed by the user's settings",
+            ),
+            _ => Self::InternalError,
+        }
+    }
+}
+
+impl From<sqlx::Error> for Error {
+    fn from(_: sqlx::Error) -> Self {
+        Self::InternalError
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(_: io::Error) -> Self {
+        Self::InternalError
+    }
+}
+
+impl From<TxError<api::Error>> for TxError<Error> {
+    fn from(error: TxError<api::Error>) -> Self {
+        match error {
+            TxError::Abort(error) => Self::Abort(error.into()),
+            TxError::Retry => Self::Retry,
+        }
+    }
+}
+
+/// A request's decoded URI query parameters, in their original order.
+#[derive(Debug)]
+struct Query(Vec<(String, String)>);
+
+impl Query {
+    /// Parses a percent-encoded URI query. A `+` is kept as is rather than decoded as a space, as
+    /// in S3.
+    fn parse(query: Option<&str>) -> Self {
+        let decode = |component: &str| {
+            percent_decode_str(component)
+                .decode_utf8_lossy()
+                .into_owned()
+        };
+
+        Self(
+            query
+                .unwrap_or_default()
+                .split('&')
+                .filter(|pair| !pair.is_empty())
+                .map(|pair| {
+                    let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+                    (decode(name), decode(value))
+                })
+                .collect(),
+        )
+    }
+
+    /// Gets the value of the first parameter with the specified name.
+    fn get(&self, name: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(param_name, _)| param_name == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Checks whether a parameter with the specified name exists.
+    fn contains(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+}
+
+/// The service function to handle incoming requests for the S3-compatible API.
+pub(super) async fn handle(request: Request) -> Response {
+    match handle_request(request).await {
+        Ok(response) => response,
+        Err(error) => error.into_response(),
+    }
+}
+
+/// Authenticates a request and dispatches it to the handler for its S3 operation.
+///
+/// # Errors
+///
+/// Returns an error if the request isn't authenticated, doesn't correspond to a supported S3
+/// operation, or fails.
+async fn handle_request(request: Request) -> Result<Response, Error> {
+    let (request, body) = request.into_parts();
+    let query = Query::parse(request.uri.query());
+
+    let auth = auth::authenticate(&request, &query).await?;
+    let payload = Payload::new(body, &auth)?;
+    let user_id = auth.user_id.as_slice();
+
+    let path = request
+        .uri
+        .path()
+        .strip_prefix(PATH_PREFIX)
+        .unwrap_or_default();
+    let path = path.strip_prefix('/').unwrap_or(path);
+    let (bucket, key) = path.split_once('/').unwrap_or((path, ""));
+
+    let bucket = percent_decode_str(bucket)
+        .decode_utf8()
+        .map_err(|_| Error::NoSuchBucket)?;
+    let key = percent_decode_str(key)
+        .decode_utf8()
+        .map_err(|_| Error::InvalidArgument("keys must be valid UTF-8"))?;
+
+    let method = &request.method;
+    let headers = &request.headers;
+
+    if bucket.is_empty() {
+        return match *method {
+            Method::GET => bucket::list_buckets(user_id).await,
+            _ => Err(Error::MethodNotAllowed),
+        };
+    }
+
+    if key.is_empty() {
+        return match *method {
+            Method::GET if query.contains("location") => {
+                bucket::get_location(user_id, &bucket).await
+            }
+            Method::GET => objects::list(user_id, &bucket, &query).await,
+            Method::HEAD | Method::PUT => bucket::head(user_id, &bucket).await,
+            _ => Err(Error::NotImplemented),
+        };
+    }
+
+    let upload_id = query.get("uploadId");
+
+    match (method, upload_id) {
+        (&Method::GET, None) => objects::get(user_id, &bucket, &key, headers, true).await,
+        (&Method::HEAD, None) => objects::get(user_id, &bucket, &key, headers, false).await,
+        (&Method::PUT, None) if headers.contains_key(objects::COPY_SOURCE) => {
+            objects::copy(user_id, &bucket, &key, headers).await
+        }
+        (&Method::PUT, None) => objects::put(user_id, &bucket, &key, payload).await,
+        (&Method::DELETE, None) => objects::delete(user_id, &bucket, &key).await,
+        (&Method::POST, None) if query.contains("uploads") => {
+            multipart::create(user_id, &bucket, &key).await
+        }
+        (&Method::PUT, Some(upload_id)) if !headers.contains_key(objects::COPY_SOURCE) => {
+            multipart::upload_part(user_id, upload_id, query.get("partNumber"), payload).await
+        }
+        (&Method::POST, Some(upload_id)) => {
+            multipart::complete(user_id, &bucket, &key, upload_id, payload).await
+        }
+        (&Method::DELETE, Some(upload_id)) => multipart::abort(user_id, upload_id).await,
+        _ => Err(Error::NotImplemented),
+    }
+}
+
+/// Constructs a `200 OK` response with an S3 XML body. The body's root element has the specified
+/// name and content.
+fn xml_response(root_name: &str, content: &str) -> Response {
+    let mut response = Response::new();
+
+    response.header_valid(CONTENT_TYPE, "application/xml");
+
+    response.body(Body::from(format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<{root_name} xmlns="{XML_NAMESPACE}">{content}</{root_name}>"#,
+    )))
+}
@@ -0,0 +1,292 @@
+//! Mapping S3 buckets and keys to folders and name paths, and handlers for bucket-level operations.
+
+use std::fmt::{self, Display, Formatter};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use sqlx::PgTransaction;
+
+use crate::{
+    api::{db_helpers::create_folder, validation::FileName},
+    db::{self, TxError, TxResult},
+    id::Id,
+    response::Response,
+    xml,
+};
+
+use super::{Error, xml_response};
+
+/// The name of the bucket for a user's root folder.
+pub(super) const ROOT_BUCKET: &str = "root";
+
+/// A bucket's folder.
+#[derive(Debug)]
+pub(super) struct Bucket {
+    /// The folder's ID, or [`None`] for the user's root folder.
+    pub(super) folder_id: Option<Vec<u8>>,
+
+    /// The folder's name path, including its own name.
+    pub(super) name_path: Vec<String>,
+}
+
+/// Gets the folder a bucket name maps to.
+///
+/// # Errors
+///
+/// Returns an error if the bucket name isn't `root` or the ID of one of the user's folders, or if a
+/// database query fails.
+pub(super) async fn resolve(
+    tx: &mut PgTransaction<'static>,
+    user_id: &[u8],
+    name: &str,
+) -> TxResult<Bucket, Error> {
+    if name == ROOT_BUCKET {
+        return Ok(Bucket {
+            folder_id: None,
+            name_path: vec![],
+        });
+    }
+
+    let Ok(folder_id) = name.parse::<Id>() else {
+        return Err(TxError::Abort(Error::NoSuchBucket));
+    };
+
+    let Some(folder) = sqlx::query!(
+        "SELECT name, parent_name_path FROM folders
+            WHERE id = $1 AND owner_id = $2",
+        folder_id.as_slice(),
+        user_id,
+    )
+    .fetch_optional(tx.as_mut())
+    .await?
+    else {
+        return Err(TxError::Abort(Error::NoSuchBucket));
+    };
+
+    let mut name_path = folder.parent_name_path;
+    name_path.push(folder.name);
+
+    Ok(Bucket {
+        folder_id: Some(folder_id.to_vec()),
+        name_path,
+    })
+}
+
+/// An object key parsed into file names.
+#[derive(Debug)]
+pub(super) struct Key {
+    /// The names along the key's path, relative to the bucket's folder.
+    pub(super) segments: Vec<FileName>,
+
+    /// Whether the key ends with `/`, so it refers to a folder rather than a file.
+    pub(super) is_folder: bool,
+}
+
+impl Key {
+    /// Parses an object key.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any name in the key is empty, `.`, `..`, or otherwise invalid.
+    pub(super) fn parse(key: &str) -> Result<Self, Error> {
+        let (key, is_folder) = match key.strip_suffix('/') {
+            Some(key) => (key, true),
+            None => (key, false),
+        };
+
+        let segments = key
+            .split('/')
+            .map(|segment| {
+                if segment == "." || segment == ".." {
+                    return Err(Error::InvalidArgument(
+                        "keys can't contain `.` or `..` names",
+                    ));
+                }
+
+                FileName::try_from(segment.to_owned())
+                    .map_err(|_| Error::InvalidArgument("keys must consist of valid file names"))
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            segments,
+            is_folder,
+        })
+    }
+
+    /// Splits the key into the names of its parent folders and its own name.
+    pub(super) fn split_last(&self) -> (&[FileName], &FileName) {
+        let (name, parent_segments) = self
+            .segments
+            .split_last()
+            .expect("keys should have at least one segment");
+
+        (parent_segments, name)
+    }
+}
+
+impl Display for Key {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (index, segment) in self.segments.iter().enumerate() {
+            if index != 0 {
+                f.write_str("/")?;
+            }
+
+            f.write_str(segment)?;
+        }
+
+        if self.is_folder {
+            f.write_str("/")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Gets the full name path of something in a bucket's folder.
+pub(super) fn name_path(bucket: &Bucket, segments: &[FileName]) -> Vec<String> {
+    let mut name_path = bucket.name_path.clone();
+    name_path.extend(segments.iter().map(ToString::to_string));
+    name_path
+}
+
+/// A folder found by [`find_folder`].
+#[derive(Debug)]
+pub(super) struct Folder {
+    /// The folder's ID.
+    pub(super) id: Vec<u8>,
+
+    /// The folder's ID path, including its own ID.
+    pub(super) id_path: Vec<Vec<u8>>,
+
+    /// When the folder was created.
+    pub(super) created_at: DateTime<Utc>,
+}
+
+/// Finds the folder at a path in a bucket, if it exists.
+///
+/// # Errors
+///
+/// Returns an error if a database query fails.
+pub(super) async fn find_folder(
+    tx: &mut PgTransaction<'static>,
+    user_id: &[u8],
+    bucket: &Bucket,
+    segments: &[FileName],
+) -> TxResult<Option<Folder>, Error> {
+    let Some((name, parent_segments)) = segments.split_last() else {
+        return Ok(None);
+    };
+
+    let parent_name_path = name_path(bucket, parent_segments);
+
+    let folder = sqlx::query!(
+        "SELECT id, parent_id_path, created_at FROM folders
+            WHERE owner_id = $1 AND parent_name_path = $2 AND name = $3",
+        user_id,
+        parent_name_path.as_slice(),
+        name.as_str(),
+    )
+    .fetch_optional(tx.as_mut())
+    .await?;
+
+    Ok(folder.map(|folder| {
+        let mut id_path = folder.parent_id_path;
+        id_path.push(folder.id.clone());
+
+        Folder {
+            id: folder.id,
+            id_path,
+            created_at: folder.created_at,
+        }
+    }))
+}
+
+/// Gets the ID of the folder at a path in a bucket, creating it and any missing folders above it.
+/// Returns [`None`] for the user's root folder.
+///
+/// # Errors
+///
+/// Returns an error if a database query fails.
+pub(super) async fn ensure_folders(
+    tx: &mut PgTransaction<'static>,
+    user_id: &[u8],
+    bucket: &Bucket,
+    segments: &[FileName],
+) -> TxResult<Option<Vec<u8>>, Error> {
+    let mut folder_id = bucket.folder_id.clone();
+
+    for depth in 1..=segments.len() {
+        folder_id = Some(
+            if let Some(folder) = find_folder(tx, user_id, bucket, &segments[..depth]).await? {
+                folder.id
+            } else {
+                let (new_folder_id, _, _) =
+                    create_folder(tx, user_id, folder_id.as_deref(), &segments[depth - 1]).await?;
+
+                new_folder_id.to_vec()
+            },
+        );
+    }
+
+    Ok(folder_id)
+}
+
+/// Formats a timestamp the way S3 response bodies do.
+pub(super) fn format_timestamp(date: DateTime<Utc>) -> String {
+    date.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+/// Handles a `ListBuckets` request. Only the `root` bucket is listed, since listing every folder as
+/// a bucket would be unhelpful.
+///
+/// # Errors
+///
+/// Returns an error if a database query fails.
+pub(super) async fn list_buckets(user_id: &[u8]) -> Result<Response, Error> {
+    let user = db::transaction!(async |tx| -> TxResult<_, Error> {
+        Ok(sqlx::query!(
+            "SELECT name, created_at FROM users
+                WHERE id = $1",
+            user_id,
+        )
+        .fetch_one(tx.as_mut())
+        .await?)
+    })
+    .await?;
+
+    Ok(xml_response(
+        "ListAllMyBucketsResult",
+        &format!(
+            "<Owner><ID>{}</ID><DisplayName>{}</DisplayName></Owner>\
+            <Buckets><Bucket><Name>{ROOT_BUCKET}</Name>\
+            <CreationDate>{}</CreationDate></Bucket></Buckets>",
+            Id::from(user_id),
+            xml::escape(&user.name),
+            format_timestamp(user.created_at),
+        ),
+    ))
+}
+
+/// Handles a `GetBucketLocation` request. Buckets have no region.
+///
+/// # Errors
+///
+/// Returns an error if the bucket doesn't exist or a database query fails.
+pub(super) async fn get_location(user_id: &[u8], bucket_name: &str) -> Result<Response, Error> {
+    db::transaction!(async |tx| -> TxResult<_, Error> { resolve(tx, user_id, bucket_name).await })
+        .await?;
+
+    Ok(xml_response("LocationConstraint", ""))
+}
+
+/// Handles a `HeadBucket` request, or a `CreateBucket` request for a bucket that already exists.
+///
+/// # Errors
+///
+/// Returns an error if the bucket doesn't exist or a database query fails.
+pub(super) async fn head(user_id: &[u8], bucket_name: &str) -> Result<Response, Error> {
+    db::transaction!(async |tx| -> TxResult<_, Error> { resolve(tx, user_id, bucket_name).await })
+        .await?;
+
+    Ok(Response::new())
+}
@@ -0,0 +1,562 @@
+//! Handlers for S3 multipart uploads.
+//!
+//! A multipart upload is an incomplete file at the upload's key, whose content's parts are stored
+//! at index `part_number - 1` as they're uploaded. Completing the upload renumbers the listed parts
+//! to be contiguous, and then the content is completed and put at the key like any other upload.
+
+use axum::http::{StatusCode, header::ETAG};
+use sha2::{Digest, Sha256};
+use sqlx::PgTransaction;
+
+use crate::{
+    api::db_helpers::{
+        FolderPaths, FolderRole, check_file_name, put_file, query_folder_paths_to_modify_contents,
+    },
+    crypto::serialize_hash_state,
+    db::{self, TxError, TxResult},
+    file_type::{UNKNOWN_TYPE, sniff_content},
+    id::{Id, NewFileContentId, NewFileId},
+    response::{Response, etag},
+    storage::{self, WrittenContent},
+    xml,
+};
+
+use super::{
+    Error,
+    auth::Payload,
+    bucket::{self, Key, ensure_folders},
+    objects::process_content,
+    xml_response,
+};
+
+/// The maximum size of an uploaded part in bytes.
+pub(super) const MAX_PART_SIZE: usize = 16 * 1024 * 1024;
+
+/// The maximum size of a `CompleteMultipartUpload` request body in bytes.
+const MAX_COMPLETE_BODY_SIZE: usize = 1024 * 1024;
+
+/// The range of valid part numbers.
+const PART_NUMBERS: std::ops::RangeInclusive<i32> = 1..=10_000;
+
+/// An in-progress multipart upload found by [`find_upload`].
+struct Upload {
+    /// The ID of the upload's incomplete file.
+    file_id: Vec<u8>,
+
+    /// The name path of the upload's key, including the file's name.
+    name_path: Vec<String>,
+}
+
+/// Finds one of the user's in-progress multipart uploads by its upload ID.
+///
+/// # Errors
+///
+/// Returns an error if the upload doesn't exist or a database query fails.
+async fn find_upload(
+    tx: &mut PgTransaction<'static>,
+    user_id: &[u8],
+    content_id: &[u8],
+) -> TxResult<Upload, Error> {
+    let Some(upload) = sqlx::query!(
+        "SELECT files.id, files.parent_name_path, files.name FROM s3_multipart_uploads
+            INNER JOIN files ON files.content_id = s3_multipart_uploads.content_id
+            WHERE s3_multipart_uploads.content_id = $1 AND files.owner_id = $2
+                AND NOT files.complete",
+        content_id,
+        user_id,
+    )
+    .fetch_optional(tx.as_mut())
+    .await?
+    else {
+        return Err(TxError::Abort(Error::NoSuchUpload));
+    };
+
+    let mut name_path = upload.parent_name_path;
+    name_path.push(upload.name);
+
+    Ok(Upload {
+        file_id: upload.id,
+        name_path,
+    })
+}
+
+/// Parses an upload ID into the ID of the upload's file content.
+///
+/// # Errors
+///
+/// Returns an error if the upload ID is invalid.
+fn parse_upload_id(upload_id: &str) -> Result<Id, Error> {
+    upload_id.parse().map_err(|_| Error::NoSuchUpload)
+}
+
+/// Handles a `CreateMultipartUpload` request, creating an incomplete file at the key. Missing
+/// folders in the key are created.
+///
+/// # Errors
+///
+/// Returns an error if the bucket doesn't exist, the key is invalid, another upload to the key is
+/// in progress, or a database query fails.
+pub(super) async fn create(
+    user_id: &[u8],
+    bucket_name: &str,
+    key: &str,
+) -> Result<Response, Error> {
+    let key = Key::parse(key)?;
+
+    if key.is_folder {
+        return Err(Error::InvalidArgument("folders can't be uploaded in parts"));
+    }
+
+    let (parent_segments, name) = key.split_last();
+
+    let content_id = db::transaction!(async |tx| -> TxResult<_, Error> {
+        let bucket = bucket::resolve(tx, user_id, bucket_name).await?;
+        let parent_id = ensure_folders(tx, user_id, &bucket, parent_segments).await?;
+
+        let FolderPaths {
+            id_path: parent_id_path,
+            name_path: parent_name_path,
+            ..
+        } = query_folder_paths_to_modify_contents(
+            tx,
+            user_id,
+            parent_id.as_deref(),
+            FolderRole::Editor,
+        )
+        .await?;
+
+        let content_id = NewFileContentId::generate();
+        let partial_hash = serialize_hash_state(&Sha256::new());
+
+        match sqlx::query!(
+            "INSERT INTO file_contents
+                (id, complete, partial_hash, original_size, decoded_part_sizes)
+                VALUES ($1, FALSE, $2, 0, '{}')",
+            content_id.as_slice(),
+            partial_hash,
+        )
+        .execute(tx.as_mut())
+        .await
+        {
+            Err(sqlx::Error::Database(error))
+                if error.constraint() == Some("file_contents_pkey") =>
+            {
+                return Err(TxError::Retry);
+            }
+            result => result?,
+        };
+
+        sqlx::query!(
+            "INSERT INTO s3_multipart_uploads (content_id)
+                VALUES ($1)",
+            content_id.as_slice(),
+        )
+        .execute(tx.as_mut())
+        .await?;
+
+        check_file_name(tx, user_id, name).await?;
+
+        let file_id = NewFileId::generate();
+
+        match sqlx::query!(
+            "INSERT INTO files (
+                created_at,
+                id,
+                name,
+                owner_id,
+                parent_id_path,
+                parent_name_path,
+                size,
+                content_id,
+                type
+            )
+                VALUES (now(), $1, $2, $3, $4, $5, 0, $6, $7)",
+            file_id.as_slice(),
+            name.as_str(),
+            user_id,
+            parent_id_path.as_slice(),
+            parent_name_path.as_slice(),
+            content_id.as_slice(),
+            UNKNOWN_TYPE,
+        )
+        .execute(tx.as_mut())
+        .await
+        {
+            Err(sqlx::Error::Database(error))
+                if error.constraint() == Some("files_by_name_path") =>
+            {
+                return Err(TxError::Abort(Error::OperationAborted));
+            }
+
+            Err(sqlx::Error::Database(error)) if error.constraint() == Some("files_pkey") => {
+                return Err(TxError::Retry);
+            }
+
+            result => result?,
+        };
+
+        Ok(content_id)
+    })
+    .await?;
+
+    Ok(xml_response(
+        "InitiateMultipartUploadResult",
+        &format!(
+            "<Bucket>{}</Bucket><Key>{}</Key><UploadId>{content_id}</UploadId>",
+            xml::escape(bucket_name),
+            xml::escape(&key.to_string()),
+        ),
+    ))
+}
+
+/// Handles an `UploadPart` request, storing or replacing one part of a multipart upload.
+///
+/// # Errors
+///
+/// Returns an error if the upload doesn't exist, the part number is invalid, the payload is too
+/// large, can't be read, or doesn't match its signature, or storage or a database query fails.
+pub(super) async fn upload_part(
+    user_id: &[u8],
+    upload_id: &str,
+    part_number: Option<&str>,
+    payload: Payload,
+) -> Result<Response, Error> {
+    let content_id = parse_upload_id(upload_id)?;
+
+    let Some(part_number) = part_number
+        .and_then(|part_number| part_number.parse::<i32>().ok())
+        .filter(|part_number| PART_NUMBERS.contains(part_number))
+    else {
+        return Err(Error::InvalidArgument(
+            "`partNumber` must be an integer from 1 to 10000",
+        ));
+    };
+
+    db::transaction!(async |tx| -> TxResult<_, Error> {
+        find_upload(tx, user_id, &content_id).await?;
+        Ok(())
+    })
+    .await?;
+
+    let part = payload.read_to_end(MAX_PART_SIZE).await?;
+    let hash = Sha256::digest(&part).to_vec();
+
+    storage::write_part(&content_id, part_number - 1, &part).await?;
+
+    db::transaction!(async |tx| -> TxResult<_, Error> {
+        find_upload(tx, user_id, &content_id).await?;
+
+        sqlx::query!(
+            "INSERT INTO s3_multipart_parts (content_id, part_number, size, hash)
+                VALUES ($1, $2, $3, $4)
+                ON CONFLICT (content_id, part_number) DO UPDATE
+                    SET modified_at = now(),
+                        size = excluded.size,
+                        hash = excluded.hash",
+            content_id.as_slice(),
+            part_number,
+            part.len() as i32,
+            hash,
+        )
+        .execute(tx.as_mut())
+        .await?;
+
+        Ok(())
+    })
+    .await?;
+
+    let mut response = Response::new();
+    response.header_valid(ETAG, etag(&hash));
+
+    Ok(response)
+}
+
+/// Gets the text content of each element with the specified name in an XML document, without
+/// unescaping it. Nested elements with the same name aren't supported.
+fn element_texts<'a>(xml: &'a str, name: &str) -> Vec<&'a str> {
+    let start_tag = format!("<{name}>");
+    let end_tag = format!("</{name}>");
+
+    let mut texts = Vec::new();
+    let mut rest = xml;
+
+    while let Some(start) = rest.find(&start_tag) {
+        rest = &rest[start + start_tag.len()..];
+
+        let Some(end) = rest.find(&end_tag) else {
+            break;
+        };
+
+        texts.push(&rest[..end]);
+        rest = &rest[end + end_tag.len()..];
+    }
+
+    texts
+}
+
+/// Parses a `CompleteMultipartUpload` request body into the listed part numbers and the
+/// hexadecimal hashes in their entity tags.
+///
+/// # Errors
+///
+/// Returns an error if the body is malformed or the parts aren't in ascending order.
+fn parse_completed_parts(body: &[u8]) -> Result<Vec<(i32, String)>, Error> {
+    let body = str::from_utf8(body).map_err(|_| Error::MalformedXML)?;
+
+    let parts = element_texts(body, "Part")
+        .into_iter()
+        .map(|part| {
+            let part_number = element_texts(part, "PartNumber")
+                .first()
+                .and_then(|part_number| part_number.trim().parse::<i32>().ok())
+                .ok_or(Error::MalformedXML)?;
+
+            let etag = element_texts(part, "ETag")
+                .first()
+                .ok_or(Error::MalformedXML)?
+                .replace("&quot;", "")
+                .replace('"', "")
+                .trim()
+                .to_ascii_lowercase();
+
+            Ok::<_, Error>((part_number, etag))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if parts.is_empty() {
+        return Err(Error::MalformedXML);
+    }
+
+    if parts.windows(2).any(|pair| pair[0].0 >= pair[1].0) {
+        return Err(Error::InvalidPartOrder);
+    }
+
+    Ok(parts)
+}
+
+/// Handles a `CompleteMultipartUpload` request, assembling the listed parts into the file's content
+/// and putting it at the key, replacing any existing file there. Unlisted parts are discarded.
+///
+/// # Errors
+///
+/// Returns an error if the upload doesn't exist at the key, the body is malformed, a listed part
+/// doesn't match what was uploaded, or storage or a database query fails.
+pub(super) async fn complete(
+    user_id: &[u8],
+    bucket_name: &str,
+    key: &str,
+    upload_id: &str,
+    payload: Payload,
+) -> Result<Response, Error> {
+    let key = Key::parse(key)?;
+    let content_id = parse_upload_id(upload_id)?;
+
+    if key.is_folder {
+        return Err(Error::NoSuchUpload);
+    }
+
+    let completed_parts =
+        parse_completed_parts(&payload.read_to_end(MAX_COMPLETE_BODY_SIZE).await?)?;
+
+    let uploaded_parts = db::transaction!(async |tx| -> TxResult<_, Error> {
+        let bucket = bucket::resolve(tx, user_id, bucket_name).await?;
+        let upload = find_upload(tx, user_id, &content_id).await?;
+
+        if upload.name_path != bucket::name_path(&bucket, &key.segments) {
+            return Err(TxError::Abort(Error::NoSuchUpload));
+        }
+
+        Ok(sqlx::query!(
+            "SELECT part_number, size, hash FROM s3_multipart_parts
+                WHERE content_id = $1",
+            content_id.as_slice(),
+        )
+        .fetch_all(tx.as_mut())
+        .await?)
+    })
+    .await?;
+
+    let mut part_sizes = Vec::with_capacity(completed_parts.len());
+
+    for (part_number, part_etag) in &completed_parts {
+        let Some(part) = uploaded_parts
+            .iter()
+            .find(|part| part.part_number == *part_number)
+            .filter(|part| etag(&part.hash).trim_matches('"') == part_etag)
+        else {
+            return Err(Error::InvalidPart);
+        };
+
+        part_sizes.push(part.size);
+    }
+
+    let result = async {
+        // Moving parts in ascending order can't overwrite a listed part that hasn't moved yet,
+        // since each part's new index is at most its old index.
+        for (part_index, (part_number, _)) in completed_parts.iter().enumerate() {
+            let part_index = part_index as i32;
+
+            if part_number - 1 != part_index {
+                storage::move_part(&content_id, part_number - 1, part_index).await?;
+            }
+        }
+
+        let part_count = completed_parts.len() as i32;
+
+        for part in &uploaded_parts {
+            if part.part_number > part_count {
+                storage::delete_part(&content_id, part.part_number - 1).await?;
+            }
+        }
+
+        let mut hasher = Sha256::new();
+        let mut size = 0;
+
+        for part_index in 0..part_count {
+            let part = storage::read_part(&content_id, part_index).await?;
+
+            hasher.update(&part);
+            size += part.len() as i64;
+        }
+
+        let content = WrittenContent {
+            hash: hasher.finalize().to_vec(),
+            size,
+            part_sizes,
+        };
+
+        let (parent_segments, name) = key.split_last();
+        let file_type = sniff_content(name, &content_id, part_count).await?;
+
+        db::transaction!(async |tx| -> TxResult<_, Error> {
+            let upload = find_upload(tx, user_id, &content_id).await?;
+
+            sqlx::query!(
+                "DELETE FROM files
+                    WHERE id = $1 AND NOT complete",
+                upload.file_id,
+            )
+            .execute(tx.as_mut())
+            .await?;
+
+            sqlx::query!(
+                "DELETE FROM s3_multipart_uploads
+                    WHERE content_id = $1",
+                content_id.as_slice(),
+            )
+            .execute(tx.as_mut())
+            .await?;
+
+            sqlx::query!(
+                "UPDATE file_contents
+                    SET modified_at = now(),
+                        complete = TRUE,
+                        hash = $1,
+                        partial_hash = NULL,
+                        original_size = $2,
+                        encoded_size = $2,
+                        part_count = $3,
+                        decoded_part_sizes = $4
+                    WHERE id = $5",
+                content.hash,
+                content.size,
+                content.part_count(),
+                content.part_sizes.as_slice(),
+                content_id.as_slice(),
+            )
+            .execute(tx.as_mut())
+            .await?;
+
+            let bucket = bucket::resolve(tx, user_id, bucket_name).await?;
+            let parent_id = ensure_folders(tx, user_id, &bucket, parent_segments).await?;
+
+            put_file(
+                tx,
+                user_id,
+                parent_id.as_deref(),
+                name,
+                &content_id,
+                content.size,
+                &file_type,
+            )
+            .await?;
+
+            Ok(())
+        })
+        .await?;
+
+        process_content(&file_type, &content_id, &content);
+
+        Ok(content)
+    }
+    .await;
+
+    // The parts may have been renumbered, so the upload can't be completed again.
+    let content = match result {
+        Ok(content) => content,
+        Err(error) => {
+            let _ = delete_upload(user_id, &content_id).await;
+            return Err(error);
+        }
+    };
+
+    Ok(xml_response(
+        "CompleteMultipartUploadResult",
+        &format!(
+            "<Bucket>{}</Bucket><Key>{}</Key><ETag>{}</ETag>",
+            xml::escape(bucket_name),
+            xml::escape(&key.to_string()),
+            xml::escape(&etag(&content.hash)),
+        ),
+    ))
+}
+
+/// Deletes a multipart upload's incomplete file and everything stored for it.
+///
+/// # Errors
+///
+/// Returns an error if the upload doesn't exist, or if storage or a database query fails.
+async fn delete_upload(user_id: &[u8], content_id: &[u8]) -> Result<(), Error> {
+    db::transaction!(async |tx| -> TxResult<_, Error> {
+        let upload = find_upload(tx, user_id, content_id).await?;
+
+        sqlx::query!(
+            "DELETE FROM files
+                WHERE id = $1 AND NOT complete",
+            upload.file_id,
+        )
+        .execute(tx.as_mut())
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM file_contents
+                WHERE id = $1",
+            content_id,
+        )
+        .execute(tx.as_mut())
+        .await?;
+
+        Ok(())
+    })
+    .await?;
+
+    storage::delete_content(content_id).await?;
+
+    Ok(())
+}
+
+/// Handles an `AbortMultipartUpload` request.
+///
+/// # Errors
+///
+/// Returns an error if the upload doesn't exist, or if storage or a database query fails.
+pub(super) async fn abort(user_id: &[u8], upload_id: &str) -> Result<Response, Error> {
+    let content_id = parse_upload_id(upload_id)?;
+
+    delete_upload(user_id, &content_id).await?;
+
+    let mut response = Response::new();
+    response.status(StatusCode::NO_CONTENT);
+
+    Ok(response)
+}
@@ -0,0 +1,851 @@
+//! Handlers for S3 operations on objects.
+
+use std::{fmt::Write as _, io};
+
+use axum::{
+    body::Body,
+    http::{
+        HeaderMap, HeaderName, StatusCode,
+        header::{
+            ACCEPT_RANGES, CONTENT_DISPOSITION, CONTENT_LENGTH, CONTENT_RANGE,
+            CONTENT_SECURITY_POLICY, CONTENT_TYPE, ETAG, LAST_MODIFIED, RANGE,
+            X_CONTENT_TYPE_OPTIONS,
+        },
+    },
+};
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
+use chrono::{DateTime, Utc};
+use futures_util::{Stream, stream};
+use percent_encoding::{AsciiSet, percent_decode_str, utf8_percent_encode};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    api::{
+        db_helpers::{delete_file, delete_folder, insert_written_content, put_file},
+        extract::IfMatch,
+    },
+    db::{self, TxError, TxResult},
+    file_type::{SniffedType, sniff_content},
+    id::NewFileContentId,
+    percent_encoding::UNRESERVED,
+    processing::{self, SourceContent},
+    response::{Response, etag, format_http_date},
+    storage, xml,
+};
+
+use super::{
+    Error, Query,
+    auth::Payload,
+    bucket::{self, Bucket, Key, ensure_folders, find_folder, format_timestamp},
+    xml_response,
+};
+
+/// The `x-amz-copy-source` header name.
+pub(super) const COPY_SOURCE: HeaderName = HeaderName::from_static("x-amz-copy-source");
+
+/// The most keys a single list request can return.
+const MAX_KEYS: usize = 1000;
+
+/// The characters to percent-encode in keys for list requests with `encoding-type=url`.
+const KEY_ENCODE_SET: &AsciiSet = &UNRESERVED.remove(b'/');
+
+/// An object to list in a list request.
+struct Object {
+    /// The object's key.
+    key: String,
+
+    /// When the object was last modified.
+    modified_at: DateTime<Utc>,
+
+    /// The size of the object in bytes.
+    size: i64,
+
+    /// The SHA-256 hash of the object's content.
+    hash: Vec<u8>,
+}
+
+/// An entry in a list response.
+enum ListEntry<'a> {
+    /// An object.
+    Object(&'a Object),
+
+    /// A common prefix that keys are grouped under.
+    CommonPrefix(&'a str),
+}
+
+impl ListEntry<'_> {
+    /// Gets the entry's key or common prefix.
+    fn key(&self) -> &str {
+        match self {
+            Self::Object(object) => &object.key,
+            Self::CommonPrefix(prefix) => prefix,
+        }
+    }
+}
+
+/// Handles a `ListObjects` or `ListObjectsV2` request. Empty folders are listed as objects with
+/// keys ending in `/`.
+///
+/// # Errors
+///
+/// Returns an error if the bucket doesn't exist, a parameter is invalid, or a database query fails.
+pub(super) async fn list(
+    user_id: &[u8],
+    bucket_name: &str,
+    query: &Query,
+) -> Result<Response, Error> {
+    let is_v2 = query.get("list-type") == Some("2");
+    let prefix = query.get("prefix").unwrap_or_default();
+    let delimiter = query
+        .get("delimiter")
+        .filter(|delimiter| !delimiter.is_empty());
+
+    let max_keys = match query.get("max-keys") {
+        Some(max_keys) => max_keys
+            .parse::<usize>()
+            .map_err(|_| Error::InvalidArgument("`max-keys` must be a non-negative integer"))?
+            .min(MAX_KEYS),
+        None => MAX_KEYS,
+    };
+
+    let url_encoded = match query.get("encoding-type") {
+        Some("url") => true,
+        Some(_) => return Err(Error::InvalidArgument("`encoding-type` must be `url`")),
+        None => false,
+    };
+
+    let continuation_token = query.get("continuation-token").filter(|_| is_v2);
+    let start_after = match continuation_token {
+        Some(token) => Some(
+            URL_SAFE_NO_PAD
+                .decode(token)
+                .ok()
+                .and_then(|key| String::from_utf8(key).ok())
+                .ok_or(Error::InvalidArgument("the continuation token is invalid"))?,
+        ),
+        None if is_v2 => query.get("start-after").map(str::to_owned),
+        None => query.get("marker").map(str::to_owned),
+    };
+
+    let mut objects = db::transaction!(async |tx| -> TxResult<_, Error> {
+        let bucket = bucket::resolve(tx, user_id, bucket_name).await?;
+        let depth = bucket.name_path.len();
+
+        // Only folders and files under the folders named in the prefix can match. The prefix's last
+        // folder name is left out of the search, since it may be incomplete or name an empty folder
+        // that itself needs listing.
+        let mut search_name_path = bucket.name_path.clone();
+        let mut prefix_folders: Vec<&str> = prefix.split('/').collect();
+        prefix_folders.pop();
+        prefix_folders.pop();
+        search_name_path.extend(prefix_folders.iter().map(|name| (*name).to_owned()));
+
+        let relative_key = |parent_name_path: &[String], name: &str| {
+            let mut key = String::new();
+
+            for folder_name in &parent_name_path[depth..] {
+                key.push_str(folder_name);
+                key.push('/');
+            }
+
+            key.push_str(name);
+            key
+        };
+
+        // The `NULL` must be typed so it's appended as an element, making an upper bound for paths
+        // that start with the search path.
+        let folders = sqlx::query!(
+            "SELECT name, parent_name_path, created_at FROM folders
+                WHERE owner_id = $1
+                    AND parent_name_path >= $2 AND parent_name_path < $2 || NULL::text",
+            user_id,
+            search_name_path.as_slice(),
+        )
+        .fetch_all(tx.as_mut())
+        .await?;
+
+        let empty_hash = Sha256::digest([]).to_vec();
+
+        let mut objects: Vec<Object> = folders
+            .into_iter()
+            .map(|folder| Object {
+                key: format!("{}/", relative_key(&folder.parent_name_path, &folder.name)),
+                modified_at: folder.created_at,
+                size: 0,
+                hash: empty_hash.clone(),
+            })
+            .collect();
+
+        let files = sqlx::query!(
+            r#"SELECT
+                files.name,
+                files.parent_name_path,
+                files.modified_at,
+                files.size,
+                file_contents.hash AS "hash!"
+                FROM files
+                INNER JOIN file_contents ON file_contents.id = files.content_id
+                WHERE files.owner_id = $1
+                    AND files.parent_name_path >= $2 AND files.parent_name_path < $2 || NULL::text
+                    AND files.complete"#,
+            user_id,
+            search_name_path.as_slice(),
+        )
+        .fetch_all(tx.as_mut())
+        .await?;
+
+        objects.extend(files.into_iter().map(|file| Object {
+            key: relative_key(&file.parent_name_path, &file.name),
+            modified_at: file.modified_at,
+            size: file.size,
+            hash: file.hash,
+        }));
+
+        Ok(objects)
+    })
+    .await?;
+
+    objects.sort_unstable_by(|a, b| a.key.cmp(&b.key));
+
+    // A folder only needs to be listed as an object if it's empty, since otherwise its path is
+    // implied by the keys inside it, which sort right after it.
+    let objects: Vec<&Object> = objects
+        .iter()
+        .enumerate()
+        .filter(|(index, object)| {
+            !object.key.ends_with('/')
+                || objects
+                    .get(index + 1)
+                    .is_none_or(|next| !next.key.starts_with(&object.key))
+        })
+        .map(|(_, object)| object)
+        .filter(|object| object.key.starts_with(prefix))
+        .collect();
+
+    let mut entries = Vec::new();
+    let mut is_truncated = false;
+
+    for object in objects {
+        if let Some(start_after) = &start_after {
+            // Keys grouped under a common prefix that was already listed must be skipped too.
+            if object.key.as_str() <= start_after.as_str()
+                || (delimiter.is_some_and(|delimiter| start_after.ends_with(delimiter))
+                    && object.key.starts_with(start_after.as_str()))
+            {
+                continue;
+            }
+        }
+
+        let entry = match delimiter.and_then(|delimiter| {
+            object.key[prefix.len()..]
+                .find(delimiter)
+                .map(|index| &object.key[..prefix.len() + index + delimiter.len()])
+        }) {
+            Some(common_prefix) => ListEntry::CommonPrefix(common_prefix),
+            None => ListEntry::Object(object),
+        };
+
+        if let Some(ListEntry::CommonPrefix(last_prefix)) = entries.last()
+            && entry.key() == *last_prefix
+        {
+            continue;
+        }
+
+        if entries.len() == max_keys {
+            is_truncated = true;
+            break;
+        }
+
+        entries.push(entry);
+    }
+
+    let encode = |text: &str| {
+        if url_encoded {
+            utf8_percent_encode(text, KEY_ENCODE_SET).to_string()
+        } else {
+            xml::escape(text)
+        }
+    };
+
+    let mut content = String::new();
+
+    // Writing to a `String` can't fail.
+    let _ = write!(
+        content,
+        "<Name>{}</Name><Prefix>{}</Prefix><MaxKeys>{max_keys}</MaxKeys>\
+        <IsTruncated>{is_truncated}</IsTruncated>",
+        xml::escape(bucket_name),
+        encode(prefix),
+    );
+
+    if let Some(delimiter) = delimiter {
+        let _ = write!(content, "<Delimiter>{}</Delimiter>", encode(delimiter));
+    }
+
+    if url_encoded {
+        content.push_str("<EncodingType>url</EncodingType>");
+    }
+
+    let last_key = entries.last().map(ListEntry::key);
+
+    if is_v2 {
+        let _ = write!(content, "<KeyCount>{}</KeyCount>", entries.len());
+
+        if let Some(token) = continuation_token {
+            let _ = write!(
+                content,
+                "<ContinuationToken>{}</ContinuationToken>",
+                xml::escape(token),
+            );
+        }
+
+        if let Some(start_after) = query.get("start-after") {
+            let _ = write!(content, "<StartAfter>{}</StartAfter>", encode(start_after));
+        }
+
+        if is_truncated && let Some(last_key) = last_key {
+            let _ = write!(
+                content,
+                "<NextContinuationToken>{}</NextContinuationToken>",
+                URL_SAFE_NO_PAD.encode(last_key),
+            );
+        }
+    } else {
+        let _ = write!(
+            content,
+            "<Marker>{}</Marker>",
+            encode(query.get("marker").unwrap_or_default()),
+        );
+
+        if is_truncated && let Some(last_key) = last_key {
+            let _ = write!(content, "<NextMarker>{}</NextMarker>", encode(last_key));
+        }
+    }
+
+    for entry in &entries {
+        match entry {
+            ListEntry::Object(object) => {
+                let _ = write!(
+                    content,
+                    "<Contents><Key>{}</Key><LastModified>{}</LastModified>\
+                    <ETag>{}</ETag><Size>{}</Size><StorageClass>STANDARD</StorageClass></Contents>",
+                    encode(&object.key),
+                    format_timestamp(object.modified_at),
+                    xml::escape(&etag(&object.hash)),
+                    object.size,
+                );
+            }
+            ListEntry::CommonPrefix(prefix) => {
+                let _ = write!(
+                    content,
+                    "<CommonPrefixes><Prefix>{}</Prefix></CommonPrefixes>",
+                    encode(prefix),
+                );
+            }
+        }
+    }
+
+    Ok(xml_response("ListBucketResult", &content))
+}
+
+/// A complete file found by [`find_file`].
+struct File {
+    /// The file's ID.
+    id: Vec<u8>,
+
+    /// When the file was last modified.
+    modified_at: DateTime<Utc>,
+
+    /// The size of the file's content in bytes.
+    size: i64,
+
+    /// The file's MIME type.
+    r#type: String,
+
+    /// Whether the file's type is dangerous to serve.
+    dangerous: bool,
+
+    /// The ID of the file's content.
+    content_id: Vec<u8>,
+
+    /// The SHA-256 hash of the file's content.
+    hash: Vec<u8>,
+
+    /// The number of parts the file's content is stored in.
+    part_count: i32,
+
+    /// The size of each of the content's parts, if known.
+    part_sizes: Option<Vec<i64>>,
+}
+
+/// Finds the complete file at a key in a bucket, if it exists.
+///
+/// # Errors
+///
+/// Returns an error if a database query fails.
+async fn find_file(
+    tx: &mut sqlx::PgTransaction<'static>,
+    user_id: &[u8],
+    bucket: &Bucket,
+    key: &Key,
+) -> TxResult<Option<File>, Error> {
+    let (parent_segments, name) = key.split_last();
+    let parent_name_path = bucket::name_path(bucket, parent_segments);
+
+    let file = sqlx::query!(
+        r#"SELECT
+            files.id,
+            files.modified_at,
+            files.size,
+            files.type,
+            files.dangerous,
+            files.content_id,
+            file_contents.hash AS "hash!",
+            file_contents.part_count,
+            file_contents.decoded_part_size,
+            file_contents.decoded_part_sizes
+            FROM files
+            INNER JOIN file_contents ON file_contents.id = files.content_id
+            WHERE files.owner_id = $1 AND files.parent_name_path = $2 AND files.name = $3
+                AND files.complete"#,
+        user_id,
+        parent_name_path.as_slice(),
+        name.as_str(),
+    )
+    .fetch_optional(tx.as_mut())
+    .await?;
+
+    Ok(file.map(|file| {
+        let part_sizes = match (file.decoded_part_sizes, file.decoded_part_size) {
+            (Some(part_sizes), _) => Some(part_sizes.into_iter().map(i64::from).collect()),
+            (None, Some(part_size)) if file.part_count > 0 => {
+                let part_size = i64::from(part_size);
+                let mut part_sizes = vec![part_size; file.part_count as usize];
+
+                // Any remainder is in the last part.
+                if let Some(last_part_size) = part_sizes.last_mut() {
+                    *last_part_size = file.size - part_size * i64::from(file.part_count - 1);
+                }
+
+                Some(part_sizes)
+            }
+            _ => None,
+        };
+
+        File {
+            id: file.id,
+            modified_at: file.modified_at,
+            size: file.size,
+            r#type: file.r#type,
+            dangerous: file.dangerous,
+            content_id: file.content_id,
+            hash: file.hash,
+            part_count: file.part_count,
+            part_sizes,
+        }
+    }))
+}
+
+/// Parses a `Range` header value with a single byte range into a start and an exclusive end.
+/// Returns [`None`] if the range isn't in a supported form, in which case the header is ignored.
+///
+/// # Errors
+///
+/// Returns an error if the range can't be satisfied for the content's size.
+fn parse_range(range: &str, size: i64) -> Result<Option<(i64, i64)>, Error> {
+    let Some((start, end)) = range
+        .strip_prefix("bytes=")
+        .filter(|range| !range.contains(','))
+        .and_then(|range| range.split_once('-'))
+    else {
+        return Ok(None);
+    };
+
+    let (start, end) = match (start.trim(), end.trim()) {
+        ("", suffix_length) => {
+            let Ok(suffix_length) = suffix_length.parse::<i64>() else {
+                return Ok(None);
+            };
+
+            ((size - suffix_length).max(0), size)
+        }
+        (start, end) => {
+            let Ok(start) = start.parse::<i64>() else {
+                return Ok(None);
+            };
+
+            let end = match end {
+                "" => size,
+                end => match end.parse::<i64>() {
+                    Ok(end) if end >= start => (end + 1).min(size),
+                    _ => return Ok(None),
+                },
+            };
+
+            (start, end)
+        }
+    };
+
+    if start >= end {
+        return Err(Error::InvalidRange);
+    }
+
+    Ok(Some((start, end)))
+}
+
+/// Reads a byte range of a file's content as a stream. Parts before the range aren't read if the
+/// content's part sizes are known.
+fn read_range(file: File, start: i64, end: i64) -> impl Stream<Item = io::Result<Vec<u8>>> + Send {
+    stream::try_unfold(
+        (file, 0, 0_i64),
+        move |(file, mut part_index, mut part_start)| async move {
+            loop {
+                if part_index >= file.part_count || part_start >= end {
+                    return Ok(None);
+                }
+
+                if let Some(part_size) = file
+                    .part_sizes
+                    .as_ref()
+                    .and_then(|part_sizes| part_sizes.get(part_index as usize))
+                    && part_start + part_size <= start
+                {
+                    part_index += 1;
+                    part_start += part_size;
+                    continue;
+                }
+
+                let part = storage::read_part(&file.content_id, part_index).await?;
+                let part_end = part_start + part.len() as i64;
+
+                let slice_start = (start - part_start).clamp(0, part.len() as i64) as usize;
+                let slice_end = (end - part_start).clamp(0, part.len() as i64) as usize;
+                let bytes = part[slice_start..slice_end].to_vec();
+
+                return Ok(Some((bytes, (file, part_index + 1, part_end))));
+            }
+        },
+    )
+}
+
+/// Handles a `GetObject` or `HeadObject` request. Supports a single byte range.
+///
+/// # Errors
+///
+/// Returns an error if nothing exists at the key, the range can't be satisfied, or a database query
+/// fails.
+pub(super) async fn get(
+    user_id: &[u8],
+    bucket_name: &str,
+    key: &str,
+    headers: &HeaderMap,
+    include_body: bool,
+) -> Result<Response, Error> {
+    let key = Key::parse(key)?;
+
+    let result = db::transaction!(async |tx| -> TxResult<_, Error> {
+        let bucket = bucket::resolve(tx, user_id, bucket_name).await?;
+
+        if key.is_folder {
+            return Ok(find_folder(tx, user_id, &bucket, &key.segments)
+                .await?
+                .map(Err));
+        }
+
+        Ok(find_file(tx, user_id, &bucket, &key).await?.map(Ok))
+    })
+    .await?;
+
+    let mut response = Response::new();
+
+    let file = match result {
+        Some(Ok(file)) => file,
+        Some(Err(folder)) => {
+            response
+                .header_valid(CONTENT_LENGTH, 0)
+                .header_valid(ETAG, etag(&Sha256::digest([])))
+                .header_valid(LAST_MODIFIED, format_http_date(folder.created_at));
+
+            return Ok(response);
+        }
+        None => return Err(Error::NoSuchKey),
+    };
+
+    let range = match headers.get(RANGE).and_then(|range| range.to_str().ok()) {
+        Some(range) => parse_range(range, file.size)?,
+        None => None,
+    };
+
+    // This is served from the website's origin, so nothing can be allowed to render inline and run
+    // scripts there.
+    response
+        .header_valid(CONTENT_TYPE, file.r#type.as_str())
+        .header_valid(CONTENT_DISPOSITION, "attachment")
+        .header_valid(CONTENT_SECURITY_POLICY, "sandbox")
+        .header_valid(X_CONTENT_TYPE_OPTIONS, "nosniff")
+        .header_valid(ACCEPT_RANGES, "bytes")
+        .header_valid(ETAG, etag(&file.hash))
+        .header_valid(LAST_MODIFIED, format_http_date(file.modified_at));
+
+    let (start, end) = match range {
+        Some((start, end)) => {
+            response.status(StatusCode::PARTIAL_CONTENT).header_valid(
+                CONTENT_RANGE,
+                format!("bytes {start}-{}/{}", end - 1, file.size),
+            );
+
+            (start, end)
+        }
+        None => (0, file.size),
+    };
+
+    response.header_valid(CONTENT_LENGTH, end - start);
+
+    if !include_body {
+        return Ok(response);
+    }
+
+    Ok(response.body(Body::from_stream(read_range(file, start, end))))
+}
+
+/// Generates thumbnails for a newly stored file content if it's an image that can be decoded.
+pub(super) fn process_content(
+    file_type: &SniffedType,
+    content_id: &[u8],
+    content: &storage::WrittenContent,
+) {
+    if processing::is_decodable_image(&file_type.mime_type) {
+        processing::generate_thumbnails(SourceContent {
+            id: content_id.to_vec(),
+            part_count: content.part_count(),
+            size: content.size,
+            hash: content.hash.clone(),
+        });
+    }
+}
+
+/// Handles a `PutObject` request, creating or replacing a file, or creating a folder for a key
+/// ending in `/`. Missing folders in the key are created.
+///
+/// # Errors
+///
+/// Returns an error if the bucket doesn't exist, the key is invalid, the payload can't be read or
+/// doesn't match its signature, or storage or a database query fails.
+pub(super) async fn put(
+    user_id: &[u8],
+    bucket_name: &str,
+    key: &str,
+    payload: Payload,
+) -> Result<Response, Error> {
+    let key = Key::parse(key)?;
+    let mut response = Response::new();
+
+    if key.is_folder {
+        payload.read_to_end(0).await.map_err(|error| match error {
+            Error::EntityTooLarge => Error::InvalidArgument("folders can't have content"),
+            error => error,
+        })?;
+
+        db::transaction!(async |tx| -> TxResult<_, Error> {
+            let bucket = bucket::resolve(tx, user_id, bucket_name).await?;
+            ensure_folders(tx, user_id, &bucket, &key.segments).await?;
+
+            Ok(())
+        })
+        .await?;
+
+        response.header_valid(ETAG, etag(&Sha256::digest([])));
+
+        return Ok(response);
+    }
+
+    let (parent_segments, name) = key.split_last();
+
+    // Check this before storing anything, so a bad bucket doesn't waste a whole upload.
+    db::transaction!(async |tx| -> TxResult<_, Error> {
+        bucket::resolve(tx, user_id, bucket_name).await
+    })
+    .await?;
+
+    let content_id = NewFileContentId::generate();
+
+    let result = async {
+        let content = storage::write_stream(content_id.as_slice(), payload.into_stream()).await?;
+
+        let file_type = sniff_content(name, content_id.as_slice(), content.part_count()).await?;
+
+        db::transaction!(async |tx| -> TxResult<_, Error> {
+            let bucket = bucket::resolve(tx, user_id, bucket_name).await?;
+            let parent_id = ensure_folders(tx, user_id, &bucket, parent_segments).await?;
+
+            insert_written_content::<Error>(tx, content_id.as_slice(), &content).await?;
+
+            put_file(
+                tx,
+                user_id,
+                parent_id.as_deref(),
+                name,
+                content_id.as_slice(),
+                content.size,
+                &file_type,
+            )
+            .await?;
+
+            Ok(())
+        })
+        .await?;
+
+        process_content(&file_type, content_id.as_slice(), &content);
+
+        Ok(content)
+    }
+    .await;
+
+    let content = match result {
+        Ok(content) => content,
+        Err(error) => {
+            let _ = storage::delete_content(content_id.as_slice()).await;
+            return Err(error);
+        }
+    };
+
+    response.header_valid(ETAG, etag(&content.hash));
+
+    Ok(response)
+}
+
+/// Handles a `DeleteObject` request. A key ending in `/` only deletes its folder if the folder is
+/// empty. Deleting a key that doesn't exist succeeds, as in S3.
+///
+/// # Errors
+///
+/// Returns an error if the bucket doesn't exist, the key is invalid, or a database query fails.
+pub(super) async fn delete(
+    user_id: &[u8],
+    bucket_name: &str,
+    key: &str,
+) -> Result<Response, Error> {
+    let key = Key::parse(key)?;
+
+    db::transaction!(async |tx| -> TxResult<_, Error> {
+        let bucket = bucket::resolve(tx, user_id, bucket_name).await?;
+
+        if !key.is_folder {
+            if let Some(file) = find_file(tx, user_id, &bucket, &key).await? {
+                delete_file(tx, user_id, &file.id, &IfMatch::default()).await?;
+            }
+
+            return Ok(());
+        }
+
+        let Some(folder) = find_folder(tx, user_id, &bucket, &key.segments).await? else {
+            return Ok(());
+        };
+
+        let is_empty = sqlx::query!(
+            r#"SELECT NOT (
+                EXISTS (SELECT 1 FROM folders WHERE owner_id = $1 AND parent_id_path = $2)
+                OR EXISTS (SELECT 1 FROM files WHERE owner_id = $1 AND parent_id_path = $2)
+            ) AS "is_empty!""#,
+            user_id,
+            folder.id_path.as_slice(),
+        )
+        .fetch_one(tx.as_mut())
+        .await?
+        .is_empty;
+
+        if is_empty {
+            delete_folder(tx, user_id, &folder.id).await?;
+        }
+
+        Ok(())
+    })
+    .await?;
+
+    let mut response = Response::new();
+    response.status(StatusCode::NO_CONTENT);
+
+    Ok(response)
+}
+
+/// Handles a `CopyObject` request, copying a file to a key. The copy shares the original's content.
+///
+/// # Errors
+///
+/// Returns an error if the copy source is invalid or doesn't exist, the destination bucket doesn't
+/// exist or its key is invalid, or a database query fails.
+pub(super) async fn copy(
+    user_id: &[u8],
+    bucket_name: &str,
+    key: &str,
+    headers: &HeaderMap,
+) -> Result<Response, Error> {
+    let key = Key::parse(key)?;
+
+    let copy_source = headers
+        .get(COPY_SOURCE)
+        .and_then(|copy_source| copy_source.to_str().ok())
+        .unwrap_or_default();
+    let copy_source = copy_source.strip_prefix('/').unwrap_or(copy_source);
+
+    if copy_source.contains('?') {
+        return Err(Error::NotImplemented);
+    }
+
+    let Some((source_bucket_name, source_key)) = copy_source.split_once('/') else {
+        return Err(Error::InvalidArgument(
+            "the copy source must be a bucket and key",
+        ));
+    };
+
+    let invalid_source = || Error::InvalidArgument("the copy source must be valid UTF-8");
+    let source_bucket_name = percent_decode_str(source_bucket_name)
+        .decode_utf8()
+        .map_err(|_| invalid_source())?;
+    let source_key = percent_decode_str(source_key)
+        .decode_utf8()
+        .map_err(|_| invalid_source())?;
+    let source_key = Key::parse(&source_key)?;
+
+    if key.is_folder || source_key.is_folder {
+        return Err(Error::InvalidArgument("folders can't be copied"));
+    }
+
+    let (parent_segments, name) = key.split_last();
+
+    let file = db::transaction!(async |tx| -> TxResult<_, Error> {
+        let source_bucket = bucket::resolve(tx, user_id, &source_bucket_name).await?;
+        let Some(file) = find_file(tx, user_id, &source_bucket, &source_key).await? else {
+            return Err(TxError::Abort(Error::NoSuchKey));
+        };
+
+        let bucket = bucket::resolve(tx, user_id, bucket_name).await?;
+        let parent_id = ensure_folders(tx, user_id, &bucket, parent_segments).await?;
+
+        put_file(
+            tx,
+            user_id,
+            parent_id.as_deref(),
+            name,
+            &file.content_id,
+            file.size,
+            &SniffedType {
+                mime_type: file.r#type.clone(),
+                dangerous: file.dangerous,
+            },
+        )
+        .await?;
+
+        Ok(file)
+    })
+    .await?;
+
+    Ok(xml_response(
+        "CopyObjectResult",
+        &format!(
+            "<LastModified>{}</LastModified><ETag>{}</ETag>",
+            format_timestamp(Utc::now()),
+            xml::escape(&etag(&file.hash)),
+        ),
+    ))
+}
@@ -0,0 +1,428 @@
+//! A background job that periodically verifies stored file contents against their hashes, so
+//! corruption in storage is detected before the last good copy of a file is gone.
+
+use std::{
+    io::{self, Read},
+    time::Duration,
+};
+
+use sha2::{Digest, Sha256};
+
+use crate::{
+    db::{self, TxResult},
+    id::Id,
+    storage,
+};
+
+/// How long to wait before checking for more file contents to scrub when there are none due.
+const IDLE_DELAY: Duration = Duration::from_secs(60 * 60);
+
+/// How long to wait before retrying after a scrub fails for a reason other than corruption.
+const RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// The shortest time to wait between scrubbing one file content and the next.
+const MIN_CONTENT_DELAY: Duration = Duration::from_millis(100);
+
+/// The most bytes per second the scrub job reads from storage on average, so it doesn't saturate
+/// storage I/O. After each file content, the job waits long enough to stay under this.
+const MAX_BYTES_PER_SEC: f64 = 16.0 * 1024.0 * 1024.0;
+
+/// A stored file content to verify.
+struct ContentToScrub {
+    /// The file content's ID.
+    id: Vec<u8>,
+
+    /// The file content's expected SHA-256 hash.
+    hash: Vec<u8>,
+
+    /// The encoding the file content's parts are stored in, if any.
+    encoding: Option<String>,
+
+    /// The number of parts the file content is stored in.
+    part_count: i32,
+
+    /// The size of the file content in bytes after decoding.
+    original_size: i64,
+
+    /// The size of every decoded part in bytes, if they're all the same size.
+    decoded_part_size: Option<i32>,
+
+    /// The size of each decoded part in bytes, if they're not all the same size.
+    decoded_part_sizes: Option<Vec<i32>>,
+}
+
+/// The outcome of verifying a file content.
+#[derive(Clone, PartialEq, Eq, Debug)]
+enum Verification {
+    /// The file content matches its hash.
+    Valid,
+
+    /// The file content is corrupt, for the specified reason.
+    Corrupt(String),
+
+    /// The file content can't be verified because its encoding isn't supported.
+    Unsupported,
+}
+
+/// Runs the scrub job forever, verifying each complete file content once per 30 days, least
+/// recently verified first.
+#[expect(
+    clippy::infinite_loop,
+    reason = "the scrub job runs for as long as the server does"
+)]
+pub(crate) async fn run() {
+    loop {
+        match scrub_next().await {
+            Ok(Some(size)) => tokio::time::sleep(content_delay(size)).await,
+            Ok(None) => tokio::time::sleep(IDLE_DELAY).await,
+            Err(error) => {
+                eprintln!("Scrubbing failed: {error}");
+                tokio::time::sleep(RETRY_DELAY).await;
+            }
+        }
+    }
+}
+
+/// Gets how long to wait after scrubbing a file content of a size (in bytes) before scrubbing the
+/// next one.
+fn content_delay(size: i64) -> Duration {
+    #[expect(
+        clippy::cast_precision_loss,
+        reason = "the delay doesn't need to be precise"
+    )]
+    let read_secs = size as f64 / MAX_BYTES_PER_SEC;
+
+    MIN_CONTENT_DELAY.max(Duration::from_secs_f64(read_secs.max(0.0)))
+}
+
+/// Verifies the file content that's most overdue to be scrubbed, recording the result.
+///
+/// Returns the size in bytes of the file content scrubbed, or [`None`] if none was due.
+///
+/// # Errors
+///
+/// Returns an error if a database query fails or a corrupt derived file content can't be deleted
+/// from storage.
+async fn scrub_next() -> anyhow::Result<Option<i64>> {
+    let Some(content) = db::transaction!(async |tx| -> TxResult<_> {
+        Ok(sqlx::query_as!(
+            ContentToScrub,
+            r#"SELECT
+                id,
+                hash AS "hash!",
+                encoding::text,
+                part_count,
+                original_size,
+                decoded_part_size,
+                decoded_part_sizes
+                FROM file_contents
+                WHERE complete
+                    AND (scrubbed_at IS NULL OR scrubbed_at < now() - interval '30 days')
+                ORDER BY scrubbed_at NULLS FIRST
+                LIMIT 1"#,
+        )
+        .fetch_optional(tx.as_mut())
+        .await?)
+    })
+    .await?
+    else {
+        return Ok(None);
+    };
+
+    let verification = verify(&content).await;
+
+    let deleted_derived_content = db::transaction!(async |tx| -> TxResult<_> {
+        sqlx::query!(
+            "UPDATE file_contents
+                SET scrubbed_at = now()
+                WHERE id = $1",
+            content.id,
+        )
+        .execute(tx.as_mut())
+        .await?;
+
+        match &verification {
+            Verification::Valid => {
+                sqlx::query!(
+                    "DELETE FROM corrupt_file_contents
+                        WHERE content_id = $1",
+                    content.id,
+                )
+                .execute(tx.as_mut())
+                .await?;
+            }
+            Verification::Corrupt(reason) => {
+                // Derived file contents can be generated again, so a corrupt one is deleted rather
+                // than recorded.
+                let deleted = sqlx::query!(
+                    "DELETE FROM file_contents
+                        WHERE id = $1 AND EXISTS (
+                            SELECT 1 FROM derived_file_contents
+                                WHERE content_id = $1
+                        )",
+                    content.id,
+                )
+                .execute(tx.as_mut())
+                .await?;
+
+                if deleted.rows_affected() != 0 {
+                    return Ok(true);
+                }
+
+                sqlx::query!(
+                    "INSERT INTO corrupt_file_contents (content_id, reason)
+                        VALUES ($1, $2)
+                        ON CONFLICT (content_id) DO UPDATE
+                            SET reason = excluded.reason",
+                    content.id,
+                    reason,
+                )
+                .execute(tx.as_mut())
+                .await?;
+            }
+            Verification::Unsupported => {}
+        }
+
+        Ok(false)
+    })
+    .await?;
+
+    let content_id = Id::from(content.id.as_slice());
+
+    match verification {
+        Verification::Valid => {}
+        Verification::Corrupt(reason) => {
+            eprintln!("File content {content_id} is corrupt: {reason}");
+        }
+        Verification::Unsupported => {
+            eprintln!(
+                "File content {content_id} can't be verified: unsupported encoding `{}`",
+                content.encoding.as_deref().unwrap_or_default(),
+            );
+        }
+    }
+
+    if deleted_derived_content {
+        storage::delete_content(&content.id).await?;
+    }
+
+    Ok(Some(content.original_size))
+}
+
+/// Reads a file content's parts from storage, decodes them, and checks their sizes and hash.
+///
+/// Any part that can't be read counts as corruption, since failing reads (e.g., from bad sectors)
+/// are among what scrubbing is meant to find.
+async fn verify(content: &ContentToScrub) -> Verification {
+    let mut verifier = Verifier::new(content);
+
+    for part_index in 0..content.part_count {
+        let part = storage::read_part(&content.id, part_index).await;
+
+        if let Some(verification) = verifier.check_part(part_index, part) {
+            return verification;
+        }
+    }
+
+    verifier.finish()
+}
+
+/// Checks a file content's parts one at a time, in order.
+struct Verifier<'a> {
+    /// The file content being checked.
+    content: &'a ContentToScrub,
+
+    /// The hash of the decoded parts checked so far.
+    hasher: Sha256,
+
+    /// The total size in bytes of the decoded parts checked so far.
+    size: usize,
+}
+
+impl<'a> Verifier<'a> {
+    /// Starts checking a file content.
+    fn new(content: &'a ContentToScrub) -> Self {
+        Self {
+            content,
+            hasher: Sha256::new(),
+            size: 0,
+        }
+    }
+
+    /// Checks the next part given the result of reading it from storage, returning the outcome if
+    /// it's known without checking further parts.
+    fn check_part(&mut self, part_index: i32, part: io::Result<Vec<u8>>) -> Option<Verification> {
+        let part = match part {
+            Ok(part) => part,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                return Some(Verification::Corrupt(format!(
+                    "part {part_index} is missing",
+                )));
+            }
+            Err(error) => {
+                return Some(Verification::Corrupt(format!(
+                    "part {part_index} can't be read: {error}",
+                )));
+            }
+        };
+
+        let part = match self.content.encoding.as_deref() {
+            None => part,
+            Some("br") => {
+                let mut decoded = Vec::new();
+
+                if brotli_decompressor::Decompressor::new(part.as_slice(), 4096)
+                    .read_to_end(&mut decoded)
+                    .is_err()
+                {
+                    return Some(Verification::Corrupt(format!(
+                        "part {part_index} can't be decoded",
+                    )));
+                }
+
+                decoded
+            }
+            Some(_) => return Some(Verification::Unsupported),
+        };
+
+        let expected_part_size = match &self.content.decoded_part_sizes {
+            Some(sizes) => usize::try_from(part_index)
+                .ok()
+                .and_then(|index| sizes.get(index).copied()),
+            None => self.content.decoded_part_size,
+        };
+
+        if expected_part_size.and_then(|size| usize::try_from(size).ok()) != Some(part.len()) {
+            return Some(Verification::Corrupt(format!(
+                "part {part_index} has the wrong size",
+            )));
+        }
+
+        self.hasher.update(&part);
+        self.size += part.len();
+
+        None
+    }
+
+    /// Gets the outcome once every part has been checked.
+    fn finish(self) -> Verification {
+        if i64::try_from(self.size).ok() != Some(self.content.original_size) {
+            return Verification::Corrupt("content has the wrong size".into());
+        }
+
+        if self.hasher.finalize().as_slice() != self.content.hash {
+            return Verification::Corrupt("content doesn't match its hash".into());
+        }
+
+        Verification::Valid
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Gets a file content stored unencoded in the specified parts.
+    fn content(parts: &[&[u8]]) -> ContentToScrub {
+        let mut hasher = Sha256::new();
+        for part in parts {
+            hasher.update(part);
+        }
+
+        let part_sizes: Vec<i32> = parts
+            .iter()
+            .map(|part| i32::try_from(part.len()).expect("part size should fit in `i32`"))
+            .collect();
+
+        ContentToScrub {
+            id: vec![0; 16],
+            hash: hasher.finalize().to_vec(),
+            encoding: None,
+            part_count: i32::try_from(parts.len()).expect("part count should fit in `i32`"),
+            original_size: part_sizes.iter().copied().map(i64::from).sum(),
+            decoded_part_size: None,
+            decoded_part_sizes: Some(part_sizes),
+        }
+    }
+
+    /// Verifies a file content against the results of reading its parts.
+    fn verify_parts(
+        content: &ContentToScrub,
+        parts: impl IntoIterator<Item = io::Result<Vec<u8>>>,
+    ) -> Verification {
+        let mut verifier = Verifier::new(content);
+
+        for (part_index, part) in (0..).zip(parts) {
+            if let Some(verification) = verifier.check_part(part_index, part) {
+                return verification;
+            }
+        }
+
+        verifier.finish()
+    }
+
+    #[test]
+    fn valid_content() {
+        let content = content(&[b"hello ", b"world"]);
+
+        assert_eq!(
+            verify_parts(&content, [Ok(b"hello ".to_vec()), Ok(b"world".to_vec())]),
+            Verification::Valid,
+        );
+    }
+
+    #[test]
+    fn unreadable_parts_are_corrupt() {
+        let content = content(&[b"hello ", b"world"]);
+
+        assert_eq!(
+            verify_parts(
+                &content,
+                [Ok(b"hello ".to_vec()), Err(io::ErrorKind::NotFound.into())],
+            ),
+            Verification::Corrupt("part 1 is missing".into()),
+        );
+        assert!(matches!(
+            verify_parts(&content, [Err(io::Error::other("I/O error"))]),
+            Verification::Corrupt(reason) if reason.starts_with("part 0 can't be read"),
+        ));
+    }
+
+    #[test]
+    fn changed_parts_are_corrupt() {
+        let content = content(&[b"hello ", b"world"]);
+
+        assert_eq!(
+            verify_parts(&content, [Ok(b"hello ".to_vec()), Ok(b"worl".to_vec())]),
+            Verification::Corrupt("part 1 has the wrong size".into()),
+        );
+        assert_eq!(
+            verify_parts(&content, [Ok(b"hello ".to_vec()), Ok(b"World".to_vec())]),
+            Verification::Corrupt("content doesn't match its hash".into()),
+        );
+    }
+
+    #[test]
+    fn encoded_parts() {
+        let mut content = content(&[b"hello"]);
+        content.encoding = Some("br".into());
+
+        assert_eq!(
+            verify_parts(&content, [Ok(b"not brotli".to_vec())]),
+            Verification::Corrupt("part 0 can't be decoded".into()),
+        );
+
+        content.encoding = Some("zstd".into());
+
+        assert_eq!(
+            verify_parts(&content, [Ok(b"hello".to_vec())]),
+            Verification::Unsupported,
+        );
+    }
+
+    #[test]
+    fn delays_scale_with_size() {
+        assert_eq!(content_delay(0), MIN_CONTENT_DELAY);
+        assert_eq!(content_delay(64 * 1024 * 1024), Duration::from_secs(4));
+    }
+}
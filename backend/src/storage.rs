@@ -0,0 +1,194 @@
+//! Utilities for storing and reading the parts of file contents.
+//!
+//! Each file content's parts are stored as separate files in a directory named after the file
+//! content's ID, under the directory set by the `STORAGE_PATH` environment variable.
+
+use std::{io, path::PathBuf, sync::LazyLock};
+
+use futures_util::{Stream, StreamExt, stream};
+use sha2::{Digest, Sha256};
+
+use crate::id::Id;
+
+/// The size of each part (except the last) when a file content is stored from a stream rather than
+/// uploaded in parts.
+pub(crate) const STREAMED_PART_SIZE: usize = 16 * 1024 * 1024;
+
+/// The directory to store file contents in.
+static STORAGE_PATH: LazyLock<PathBuf> = LazyLock::new(|| {
+    dotenvy::var("STORAGE_PATH")
+        .expect("environment variable `STORAGE_PATH` should be a valid string")
+        .into()
+});
+
+/// Gets the path of the directory containing a file content's parts.
+fn content_path(content_id: &[u8]) -> PathBuf {
+    STORAGE_PATH.join(Id::from(content_id).to_string())
+}
+
+/// Gets the path of one of a file content's parts.
+fn part_path(content_id: &[u8], part_index: i32) -> PathBuf {
+    content_path(content_id).join(part_index.to_string())
+}
+
+/// Stores one of a file content's parts, overwriting any part previously stored at the same index.
+///
+/// # Errors
+///
+/// Returns an error if writing to the file system fails.
+pub(crate) async fn write_part(content_id: &[u8], part_index: i32, bytes: &[u8]) -> io::Result<()> {
+    tokio::fs::create_dir_all(content_path(content_id)).await?;
+    tokio::fs::write(part_path(content_id, part_index), bytes).await
+}
+
+/// A file content stored from a stream by [`write_stream`].
+#[derive(Debug)]
+pub(crate) struct WrittenContent {
+    /// The SHA-256 hash of the content.
+    pub(crate) hash: Vec<u8>,
+
+    /// The size of the content in bytes.
+    pub(crate) size: i64,
+
+    /// The size of each part the content is stored in.
+    pub(crate) part_sizes: Vec<i32>,
+}
+
+impl WrittenContent {
+    /// Gets the number of parts the content is stored in.
+    pub(crate) fn part_count(&self) -> i32 {
+        self.part_sizes.len() as i32
+    }
+}
+
+/// Stores a file content from a stream of bytes, hashing it and splitting it into parts of
+/// [`STREAMED_PART_SIZE`].
+///
+/// # Errors
+///
+/// Returns an error if the stream fails or writing to the file system fails. Any parts already
+/// stored are left in place.
+pub(crate) async fn write_stream<S, B, E>(content_id: &[u8], stream: S) -> Result<WrittenContent, E>
+where
+    S: Stream<Item = Result<B, E>>,
+    B: AsRef<[u8]>,
+    E: From<io::Error>,
+{
+    let mut stream = std::pin::pin!(stream);
+    let mut hasher = Sha256::new();
+    let mut part_sizes = Vec::new();
+    let mut part = Vec::new();
+    let mut size = 0;
+
+    loop {
+        let chunk = stream.next().await.transpose()?;
+
+        if let Some(chunk) = &chunk {
+            let chunk = chunk.as_ref();
+
+            hasher.update(chunk);
+            size += chunk.len() as i64;
+            part.extend_from_slice(chunk);
+        }
+
+        while part.len() >= STREAMED_PART_SIZE || (chunk.is_none() && !part.is_empty()) {
+            let part_length = part.len().min(STREAMED_PART_SIZE);
+
+            write_part(content_id, part_sizes.len() as i32, &part[..part_length]).await?;
+            part_sizes.push(part_length as i32);
+            part.drain(..part_length);
+        }
+
+        if chunk.is_none() {
+            break;
+        }
+    }
+
+    Ok(WrittenContent {
+        hash: hasher.finalize().to_vec(),
+        size,
+        part_sizes,
+    })
+}
+
+/// Moves one of a file content's parts to a different index, overwriting any part previously
+/// stored there.
+///
+/// # Errors
+///
+/// Returns an error if renaming in the file system fails.
+pub(crate) async fn move_part(content_id: &[u8], from_index: i32, to_index: i32) -> io::Result<()> {
+    tokio::fs::rename(
+        part_path(content_id, from_index),
+        part_path(content_id, to_index),
+    )
+    .await
+}
+
+/// Deletes one of a file content's parts, if it exists.
+///
+/// # Errors
+///
+/// Returns an error if deleting from the file system fails.
+pub(crate) async fn delete_part(content_id: &[u8], part_index: i32) -> io::Result<()> {
+    match tokio::fs::remove_file(part_path(content_id, part_index)).await {
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+/// Reads one of a file content's parts.
+///
+/// # Errors
+///
+/// Returns an error if reading from the file system fails.
+pub(crate) async fn read_part(content_id: &[u8], part_index: i32) -> io::Result<Vec<u8>> {
+    tokio::fs::read(part_path(content_id, part_index)).await
+}
+
+/// Reads the first `length` bytes of a file content (or fewer if the content is shorter).
+///
+/// # Errors
+///
+/// Returns an error if reading from the file system fails.
+pub(crate) async fn read_head(
+    content_id: &[u8],
+    part_count: i32,
+    length: usize,
+) -> io::Result<Vec<u8>> {
+    let mut head = Vec::new();
+
+    for part_index in 0..part_count {
+        if head.len() >= length {
+            break;
+        }
+
+        head.extend(read_part(content_id, part_index).await?);
+    }
+
+    head.truncate(length);
+    Ok(head)
+}
+
+/// Reads all of a file content's parts in order as a stream.
+pub(crate) fn read_parts(
+    content_id: Vec<u8>,
+    part_count: i32,
+) -> impl Stream<Item = io::Result<Vec<u8>>> + Send {
+    stream::iter(0..part_count).then(move |part_index| {
+        let content_id = content_id.clone();
+        async move { read_part(&content_id, part_index).await }
+    })
+}
+
+/// Deletes all of a file content's parts.
+///
+/// # Errors
+///
+/// Returns an error if deleting from the file system fails.
+pub(crate) async fn delete_content(content_id: &[u8]) -> io::Result<()> {
+    match tokio::fs::remove_dir_all(content_path(content_id)).await {
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
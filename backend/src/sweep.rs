@@ -0,0 +1,227 @@
+//! A background job that periodically cleans up state that has expired.
+//!
+//! Expired state is already treated as expired wherever it's read, so this only needs to run often
+//! enough to keep what's stored accurate.
+
+use std::{env::VarError, sync::LazyLock, time::Duration};
+
+use crate::{
+    api::{
+        db_helpers::{discard_link_uploads, mark_content_maybe_unused},
+        extract::MAX_SESSION_IDLE_DAYS,
+    },
+    db::{self, TxResult},
+};
+
+/// How long to wait between sweeps.
+const INTERVAL: Duration = Duration::from_secs(60);
+
+/// How many days to keep each previous version of a file's content before pruning it.
+static MAX_FILE_VERSION_AGE_DAYS: LazyLock<i32> = LazyLock::new(|| {
+    match dotenvy::var("MAX_FILE_VERSION_AGE_DAYS") {
+        Err(dotenvy::Error::EnvVar(VarError::NotPresent)) => 30,
+
+        max_age => max_age
+            .ok()
+            .and_then(|max_age| max_age.parse().ok())
+            .filter(|max_age| *max_age >= 0)
+            .expect(
+                "environment variable `MAX_FILE_VERSION_AGE_DAYS` should be a non-negative integer if set",
+            ),
+    }
+});
+
+/// Runs the sweep job forever.
+#[expect(
+    clippy::infinite_loop,
+    reason = "the sweep job runs for as long as the server does"
+)]
+pub(crate) async fn run() {
+    loop {
+        if let Err(error) = sweep().await {
+            eprintln!("Sweeping failed: {error}");
+        }
+
+        tokio::time::sleep(INTERVAL).await;
+    }
+}
+
+/// Cleans up everything that has expired since the last sweep.
+///
+/// # Errors
+///
+/// Returns an error if a database query fails.
+async fn sweep() -> sqlx::Result<()> {
+    db::transaction!(async |tx| -> TxResult<_> {
+        sqlx::query!(
+            "UPDATE files
+                SET shared = FALSE, share_expires_at = NULL, share_password_hash = NULL
+                WHERE share_expires_at <= now()",
+        )
+        .execute(tx.as_mut())
+        .await?;
+
+        sqlx::query!(
+            "UPDATE folders
+                SET shared = FALSE, share_expires_at = NULL, share_password_hash = NULL
+                WHERE share_expires_at <= now()",
+        )
+        .execute(tx.as_mut())
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM share_unlocks
+                WHERE expires_at <= now()",
+        )
+        .execute(tx.as_mut())
+        .await?;
+
+        // Attempts older than this no longer count toward the rate limit.
+        sqlx::query!(
+            "DELETE FROM share_unlock_attempts
+                WHERE attempted_at <= now() - interval '15 minutes'",
+        )
+        .execute(tx.as_mut())
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM sessions
+                WHERE expires_at <= now()",
+        )
+        .execute(tx.as_mut())
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM sessions
+                WHERE accessed_at <= now() - make_interval(days => $1) AND scopes IS NULL",
+            *MAX_SESSION_IDLE_DAYS,
+        )
+        .execute(tx.as_mut())
+        .await?;
+
+        // Failure counts reset a day after the last failure.
+        sqlx::query!(
+            "DELETE FROM auth_failures
+                WHERE failed_at <= now() - interval '1 day'
+                    AND (locked_until IS NULL OR locked_until <= now())",
+        )
+        .execute(tx.as_mut())
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM security_notices
+                WHERE created_at <= now() - interval '7 days'",
+        )
+        .execute(tx.as_mut())
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM webauthn_challenges
+                WHERE created_at <= now() - interval '5 minutes'",
+        )
+        .execute(tx.as_mut())
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM oauth_authorization_codes
+                WHERE created_at <= now() - interval '10 minutes'",
+        )
+        .execute(tx.as_mut())
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM oidc_authorizations
+                WHERE created_at <= now() - interval '10 minutes'",
+        )
+        .execute(tx.as_mut())
+        .await?;
+
+        let expired_upload_link_ids = sqlx::query_scalar!(
+            "SELECT id FROM upload_links
+                WHERE expires_at <= now()",
+        )
+        .fetch_all(tx.as_mut())
+        .await?;
+
+        discard_link_uploads(tx, &expired_upload_link_ids).await?;
+
+        sqlx::query!(
+            "DELETE FROM upload_links
+                WHERE id = ANY($1)",
+            expired_upload_link_ids.as_slice(),
+        )
+        .execute(tx.as_mut())
+        .await?;
+
+        let old_version_content_ids = sqlx::query_scalar!(
+            "DELETE FROM file_versions
+                WHERE created_at <= now() - make_interval(days => $1)
+                RETURNING content_id",
+            *MAX_FILE_VERSION_AGE_DAYS,
+        )
+        .fetch_all(tx.as_mut())
+        .await?;
+
+        for content_id in old_version_content_ids {
+            mark_content_maybe_unused(tx, &content_id).await?;
+        }
+
+        // Derived contents and records of unprocessable contents are only kept while some file
+        // still has their source content.
+        let orphaned_derived_content_ids = sqlx::query_scalar!(
+            "DELETE FROM derived_file_contents
+                WHERE NOT EXISTS (
+                    SELECT FROM file_contents
+                        WHERE file_contents.hash = derived_file_contents.source_content_hash
+                            AND (
+                                EXISTS (
+                                    SELECT FROM files
+                                        WHERE files.content_id = file_contents.id
+                                )
+                                OR EXISTS (
+                                    SELECT FROM trashed_files
+                                        WHERE trashed_files.content_id = file_contents.id
+                                )
+                                OR EXISTS (
+                                    SELECT FROM file_versions
+                                        WHERE file_versions.content_id = file_contents.id
+                                )
+                            )
+                )
+                RETURNING content_id",
+        )
+        .fetch_all(tx.as_mut())
+        .await?;
+
+        for content_id in orphaned_derived_content_ids {
+            mark_content_maybe_unused(tx, &content_id).await?;
+        }
+
+        sqlx::query!(
+            "DELETE FROM unprocessable_file_contents
+                WHERE NOT EXISTS (
+                    SELECT FROM file_contents
+                        WHERE file_contents.hash = unprocessable_file_contents.source_content_hash
+                            AND (
+                                EXISTS (
+                                    SELECT FROM files
+                                        WHERE files.content_id = file_contents.id
+                                )
+                                OR EXISTS (
+                                    SELECT FROM trashed_files
+                                        WHERE trashed_files.content_id = file_contents.id
+                                )
+                                OR EXISTS (
+                                    SELECT FROM file_versions
+                                        WHERE file_versions.content_id = file_contents.id
+                                )
+                            )
+                )",
+        )
+        .execute(tx.as_mut())
+        .await?;
+
+        Ok(())
+    })
+    .await
+}
@@ -0,0 +1,526 @@
+//! Verification of WebAuthn credential registrations and assertions, for authenticating with
+//! security keys.
+//!
+//! Attestation statements aren't verified, since users may register whatever authenticator they
+//! like. Challenges are stored and consumed by the API routes, not here.
+
+use std::sync::LazyLock;
+
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
+use ring::{
+    digest::{SHA256, digest},
+    signature::{
+        ECDSA_P256_SHA256_ASN1, ED25519, RSA_PKCS1_2048_8192_SHA256, RsaPublicKeyComponents,
+        UnparsedPublicKey,
+    },
+};
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::WEBSITE_ORIGIN;
+
+use self::cbor::Value;
+
+mod cbor;
+
+/// The WebAuthn relying party ID, which is the website's domain.
+pub(crate) static RELYING_PARTY_ID: LazyLock<String> = LazyLock::new(|| {
+    let host = WEBSITE_ORIGIN
+        .split_once("://")
+        .map_or(WEBSITE_ORIGIN.as_str(), |(_, host)| host);
+
+    host.split(':').next().unwrap_or(host).to_owned()
+});
+
+/// The WebAuthn relying party name shown to users by their authenticators.
+pub(crate) const RELYING_PARTY_NAME: &str = "File Garden";
+
+/// The COSE algorithm identifiers of the signature algorithms supported, in order of preference.
+pub(crate) const SUPPORTED_ALGORITHMS: [i64; 3] = [
+    COSE_ALGORITHM_ES256,
+    COSE_ALGORITHM_EDDSA,
+    COSE_ALGORITHM_RS256,
+];
+
+/// The COSE algorithm identifier for ECDSA with P-256 and SHA-256.
+const COSE_ALGORITHM_ES256: i64 = -7;
+
+/// The COSE algorithm identifier for EdDSA.
+const COSE_ALGORITHM_EDDSA: i64 = -8;
+
+/// The COSE algorithm identifier for RSASSA-PKCS1-v1_5 with SHA-256.
+const COSE_ALGORITHM_RS256: i64 = -257;
+
+/// The authenticator data flag set if the user was present.
+const FLAG_USER_PRESENT: u8 = 1 << 0;
+
+/// The authenticator data flag set if the user was verified (e.g. by PIN or biometrics).
+const FLAG_USER_VERIFIED: u8 = 1 << 2;
+
+/// The authenticator data flag set if attested credential data is included.
+const FLAG_ATTESTED_CREDENTIAL_DATA: u8 = 1 << 6;
+
+/// An error verifying a WebAuthn registration or assertion.
+#[derive(Error, Clone, Copy, PartialEq, Eq, Debug)]
+#[non_exhaustive]
+pub(crate) enum Error {
+    /// The client data is malformed or doesn't match what was expected.
+    #[error("invalid client data")]
+    ClientData,
+
+    /// The authenticator data or attestation object is malformed.
+    #[error("invalid authenticator data")]
+    AuthenticatorData,
+
+    /// The authenticator data is for a different relying party.
+    #[error("relying party ID mismatch")]
+    RelyingParty,
+
+    /// The authenticator didn't confirm the user was present, or verified if required.
+    #[error("user not present or verified")]
+    User,
+
+    /// The credential's public key is malformed or uses an unsupported algorithm.
+    #[error("unsupported public key")]
+    PublicKey,
+
+    /// The signature is invalid.
+    #[error("invalid signature")]
+    Signature,
+}
+
+/// The kind of WebAuthn ceremony some client data is from.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Ceremony {
+    /// Registering a new credential (`navigator.credentials.create`).
+    Create,
+
+    /// Authenticating with an existing credential (`navigator.credentials.get`).
+    Get,
+}
+
+impl Ceremony {
+    /// Gets the client data `type` for this ceremony.
+    const fn client_data_type(self) -> &'static str {
+        match self {
+            Self::Create => "webauthn.create",
+            Self::Get => "webauthn.get",
+        }
+    }
+}
+
+/// The members of a WebAuthn `clientDataJSON` that need verifying.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ClientData {
+    /// The kind of ceremony.
+    r#type: String,
+
+    /// The challenge the relying party issued, in `base64url`.
+    challenge: String,
+
+    /// The origin of the page that called the WebAuthn API.
+    origin: String,
+
+    /// Whether the WebAuthn API was called from a cross-origin iframe.
+    #[serde(default)]
+    cross_origin: bool,
+}
+
+/// Verifies a WebAuthn `clientDataJSON` is from the website for the expected ceremony, and returns
+/// the challenge it signs, which the caller must check was issued and not yet used.
+///
+/// # Errors
+///
+/// Returns an error if the client data is malformed or doesn't match what was expected.
+pub(crate) fn verify_client_data(
+    client_data_json: &[u8],
+    ceremony: Ceremony,
+) -> Result<Vec<u8>, Error> {
+    let client_data: ClientData =
+        serde_json::from_slice(client_data_json).map_err(|_| Error::ClientData)?;
+
+    if client_data.r#type != ceremony.client_data_type()
+        || client_data.origin != *WEBSITE_ORIGIN
+        || client_data.cross_origin
+    {
+        return Err(Error::ClientData);
+    }
+
+    URL_SAFE_NO_PAD
+        .decode(client_data.challenge)
+        .map_err(|_| Error::ClientData)
+}
+
+/// A new credential from a verified WebAuthn registration.
+#[derive(Clone, Debug)]
+pub(crate) struct Registration {
+    /// The credential's ID.
+    pub(crate) credential_id: Vec<u8>,
+
+    /// The credential's public key, as a COSE key.
+    pub(crate) public_key: Vec<u8>,
+
+    /// The authenticator's signature counter.
+    pub(crate) sign_count: u32,
+}
+
+/// Verifies a WebAuthn attestation object from a registration for a relying party (normally
+/// [`RELYING_PARTY_ID`]), returning the new credential. The client data must be verified separately
+/// with [`verify_client_data`].
+///
+/// # Errors
+///
+/// Returns an error if the attestation object is malformed, is for a different relying party,
+/// doesn't confirm the user's presence (or verification if `require_user_verification` is set), or
+/// has an unsupported public key.
+pub(crate) fn verify_registration(
+    relying_party_id: &str,
+    attestation_object: &[u8],
+    require_user_verification: bool,
+) -> Result<Registration, Error> {
+    let (attestation_object, _) =
+        cbor::decode(attestation_object).map_err(|_| Error::AuthenticatorData)?;
+
+    let Some(authenticator_data) = attestation_object
+        .get_by_text("authData")
+        .and_then(Value::as_bytes)
+    else {
+        return Err(Error::AuthenticatorData);
+    };
+
+    let authenticator_data = AuthenticatorData::parse(
+        authenticator_data,
+        relying_party_id,
+        require_user_verification,
+    )?;
+
+    let Some((credential_id, public_key)) = authenticator_data.attested_credential else {
+        return Err(Error::AuthenticatorData);
+    };
+
+    // Check the public key is supported now so it can't fail later.
+    PublicKey::from_cose(public_key)?;
+
+    Ok(Registration {
+        credential_id: credential_id.to_vec(),
+        public_key: public_key.to_vec(),
+        sign_count: authenticator_data.sign_count,
+    })
+}
+
+/// Verifies a WebAuthn assertion for a relying party (normally [`RELYING_PARTY_ID`]) was signed by
+/// a credential's public key, returning the authenticator's new signature counter. The client data
+/// must be verified separately with [`verify_client_data`].
+///
+/// # Errors
+///
+/// Returns an error if the authenticator data is malformed, is for a different relying party,
+/// doesn't confirm the user's presence (or verification if `require_user_verification` is set), or
+/// if the signature is invalid.
+pub(crate) fn verify_assertion(
+    relying_party_id: &str,
+    public_key: &[u8],
+    client_data_json: &[u8],
+    authenticator_data: &[u8],
+    signature: &[u8],
+    require_user_verification: bool,
+) -> Result<u32, Error> {
+    let parsed_authenticator_data = AuthenticatorData::parse(
+        authenticator_data,
+        relying_party_id,
+        require_user_verification,
+    )?;
+
+    let mut signed_data = authenticator_data.to_vec();
+    signed_data.extend_from_slice(digest(&SHA256, client_data_json).as_ref());
+
+    PublicKey::from_cose(public_key)?.verify(&signed_data, signature)?;
+
+    Ok(parsed_authenticator_data.sign_count)
+}
+
+/// Parsed WebAuthn authenticator data.
+struct AuthenticatorData<'a> {
+    /// The authenticator's signature counter, or 0 if it doesn't have one.
+    sign_count: u32,
+
+    /// The ID and COSE public key of a newly registered credential, if included.
+    attested_credential: Option<(&'a [u8], &'a [u8])>,
+}
+
+impl<'a> AuthenticatorData<'a> {
+    /// The length of the relying party ID hash, flags, and signature counter at the start.
+    const HEADER_LENGTH: usize = 37;
+
+    /// The length of the AAGUID at the start of attested credential data.
+    const AAGUID_LENGTH: usize = 16;
+
+    /// Parses authenticator data, checking it's for the relying party and has the required user
+    /// presence and verification flags.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::RelyingParty`] or [`Error::User`] if a check fails, or
+    /// [`Error::AuthenticatorData`] if the data is malformed.
+    fn parse(
+        data: &'a [u8],
+        relying_party_id: &str,
+        require_user_verification: bool,
+    ) -> Result<Self, Error> {
+        let Some((header, rest)) = data.split_at_checked(Self::HEADER_LENGTH) else {
+            return Err(Error::AuthenticatorData);
+        };
+
+        let (relying_party_id_hash, rest_of_header) = header.split_at(32);
+
+        if relying_party_id_hash != digest(&SHA256, relying_party_id.as_bytes()).as_ref() {
+            return Err(Error::RelyingParty);
+        }
+
+        let flags = rest_of_header[0];
+
+        if flags & FLAG_USER_PRESENT == 0
+            || (require_user_verification && flags & FLAG_USER_VERIFIED == 0)
+        {
+            return Err(Error::User);
+        }
+
+        let sign_count = u32::from_be_bytes(
+            rest_of_header[1..]
+                .try_into()
+                .expect("signature counter should be 4 bytes"),
+        );
+
+        let attested_credential = if flags & FLAG_ATTESTED_CREDENTIAL_DATA == 0 {
+            None
+        } else {
+            let (credential_id_length, rest) = rest
+                .get(Self::AAGUID_LENGTH..)
+                .and_then(|rest| rest.split_at_checked(2))
+                .ok_or(Error::AuthenticatorData)?;
+
+            let credential_id_length = u16::from_be_bytes(
+                credential_id_length
+                    .try_into()
+                    .expect("credential ID length should be 2 bytes"),
+            );
+
+            let (credential_id, rest) = rest
+                .split_at_checked(credential_id_length.into())
+                .ok_or(Error::AuthenticatorData)?;
+
+            let (_, public_key_length) =
+                cbor::decode(rest).map_err(|_| Error::AuthenticatorData)?;
+
+            Some((credential_id, &rest[..public_key_length]))
+        };
+
+        Ok(Self {
+            sign_count,
+            attested_credential,
+        })
+    }
+}
+
+/// A credential's public key.
+enum PublicKey {
+    /// An ECDSA P-256 key, as an uncompressed point.
+    Es256(Vec<u8>),
+
+    /// An Ed25519 key.
+    Ed25519(Vec<u8>),
+
+    /// An RSA key.
+    Rs256 {
+        /// The modulus.
+        n: Vec<u8>,
+
+        /// The public exponent.
+        e: Vec<u8>,
+    },
+}
+
+impl PublicKey {
+    /// Parses a COSE key (RFC 9053) with a supported algorithm.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::PublicKey`] if the key is malformed or its algorithm is unsupported.
+    fn from_cose(cose_key: &[u8]) -> Result<Self, Error> {
+        let (key, _) = cbor::decode(cose_key).map_err(|_| Error::PublicKey)?;
+
+        let int = |label| key.get_by_int(label).and_then(Value::as_int);
+        let bytes = |label| {
+            key.get_by_int(label)
+                .and_then(Value::as_bytes)
+                .ok_or(Error::PublicKey)
+        };
+
+        let Some(algorithm) = int(3).and_then(|algorithm| i64::try_from(algorithm).ok()) else {
+            return Err(Error::PublicKey);
+        };
+
+        // The key type must match the algorithm, and the curve must match for elliptic curve keys.
+        match (algorithm, int(1), int(-1)) {
+            (COSE_ALGORITHM_ES256, Some(2), Some(1)) => {
+                let (x, y) = (bytes(-2)?, bytes(-3)?);
+
+                if x.len() != 32 || y.len() != 32 {
+                    return Err(Error::PublicKey);
+                }
+
+                let mut point = vec![0x04];
+                point.extend_from_slice(x);
+                point.extend_from_slice(y);
+
+                Ok(Self::Es256(point))
+            }
+            (COSE_ALGORITHM_EDDSA, Some(1), Some(6)) => Ok(Self::Ed25519(bytes(-2)?.to_vec())),
+            (COSE_ALGORITHM_RS256, Some(3), _) => Ok(Self::Rs256 {
+                n: bytes(-1)?.to_vec(),
+                e: bytes(-2)?.to_vec(),
+            }),
+            _ => Err(Error::PublicKey),
+        }
+    }
+
+    /// Verifies a signature of a message was made with this key.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Signature`] if the signature is invalid.
+    fn verify(&self, message: &[u8], signature: &[u8]) -> Result<(), Error> {
+        match self {
+            Self::Es256(point) => {
+                UnparsedPublicKey::new(&ECDSA_P256_SHA256_ASN1, point).verify(message, signature)
+            }
+            Self::Ed25519(key) => UnparsedPublicKey::new(&ED25519, key).verify(message, signature),
+            Self::Rs256 { n, e } => RsaPublicKeyComponents { n, e }.verify(
+                &RSA_PKCS1_2048_8192_SHA256,
+                message,
+                signature,
+            ),
+        }
+        .map_err(|_| Error::Signature)
+    }
+}
+
+#[cfg(test)]
+#[expect(clippy::missing_errors_doc, reason = "See rust-lang/rust-clippy#13391")]
+mod tests {
+    use ring::{
+        rand::SystemRandom,
+        signature::{ECDSA_P256_SHA256_ASN1_SIGNING, EcdsaKeyPair, KeyPair},
+    };
+
+    use super::*;
+
+    /// Encodes a P-256 public key point as a COSE key.
+    fn cose_key(point: &[u8]) -> Vec<u8> {
+        let mut key = vec![0xa5, 0x01, 0x02, 0x03, 0x26, 0x20, 0x01, 0x21, 0x58, 0x20];
+        key.extend_from_slice(&point[1..33]);
+        key.extend_from_slice(&[0x22, 0x58, 0x20]);
+        key.extend_from_slice(&point[33..]);
+        key
+    }
+
+    /// The relying party ID to test with.
+    const TEST_RELYING_PARTY_ID: &str = "filegarden.com";
+
+    /// Creates authenticator data for the test relying party.
+    fn authenticator_data(flags: u8, sign_count: u32, attested: Option<(&[u8], &[u8])>) -> Vec<u8> {
+        let mut data = digest(&SHA256, TEST_RELYING_PARTY_ID.as_bytes())
+            .as_ref()
+            .to_vec();
+        data.push(flags);
+        data.extend_from_slice(&sign_count.to_be_bytes());
+
+        if let Some((credential_id, public_key)) = attested {
+            data.extend_from_slice(&[0; 16]);
+            data.extend_from_slice(
+                &u16::try_from(credential_id.len())
+                    .expect("credential ID should be short")
+                    .to_be_bytes(),
+            );
+            data.extend_from_slice(credential_id);
+            data.extend_from_slice(public_key);
+        }
+
+        data
+    }
+
+    #[test]
+    fn registers_and_verifies_es256_credential() -> Result<(), Error> {
+        let rng = SystemRandom::new();
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &rng)
+            .expect("key generation should succeed");
+        let key_pair =
+            EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, pkcs8.as_ref(), &rng)
+                .expect("generated key should be valid");
+        let public_key = cose_key(key_pair.public_key().as_ref());
+
+        // {"fmt": "none", "attStmt": {}, "authData": <authenticator data>}
+        let registration_data = authenticator_data(
+            FLAG_USER_PRESENT | FLAG_ATTESTED_CREDENTIAL_DATA,
+            0,
+            Some((b"credential", &public_key)),
+        );
+        let mut attestation_object = vec![0xa3, 0x63, b'f', b'm', b't', 0x64, b'n', b'o', b'n'];
+        attestation_object.extend_from_slice(&[b'e', 0x67]);
+        attestation_object.extend_from_slice(b"attStmt");
+        attestation_object.extend_from_slice(&[0xa0, 0x68]);
+        attestation_object.extend_from_slice(b"authData");
+        attestation_object.push(0x59);
+        attestation_object.extend_from_slice(
+            &u16::try_from(registration_data.len())
+                .expect("authenticator data should be short")
+                .to_be_bytes(),
+        );
+        attestation_object.extend_from_slice(&registration_data);
+
+        let registration = verify_registration(TEST_RELYING_PARTY_ID, &attestation_object, false)?;
+        assert_eq!(registration.credential_id, b"credential");
+        assert_eq!(registration.public_key, public_key);
+        assert_eq!(
+            verify_registration(TEST_RELYING_PARTY_ID, &attestation_object, true).err(),
+            Some(Error::User),
+        );
+        assert_eq!(
+            verify_registration("example.com", &attestation_object, false).err(),
+            Some(Error::RelyingParty),
+        );
+
+        let client_data_json = br#"{"type":"webauthn.get","challenge":"AAAA","origin":"x"}"#;
+        let assertion_data = authenticator_data(FLAG_USER_PRESENT, 7, None);
+        let mut signed_data = assertion_data.clone();
+        signed_data.extend_from_slice(digest(&SHA256, client_data_json).as_ref());
+        let signature = key_pair
+            .sign(&rng, &signed_data)
+            .expect("signing should succeed");
+
+        let sign_count = verify_assertion(
+            TEST_RELYING_PARTY_ID,
+            &registration.public_key,
+            client_data_json,
+            &assertion_data,
+            signature.as_ref(),
+            false,
+        )?;
+        assert_eq!(sign_count, 7);
+
+        assert_eq!(
+            verify_assertion(
+                TEST_RELYING_PARTY_ID,
+                &registration.public_key,
+                b"{}",
+                &assertion_data,
+                signature.as_ref(),
+                false,
+            ),
+            Err(Error::Signature),
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,276 @@
+//! A minimal CBOR (RFC 8949) decoder, supporting only what WebAuthn attestation objects and COSE
+//! keys use.
+
+use thiserror::Error;
+
+/// How deeply arrays and maps can be nested, to keep malicious input from overflowing the stack.
+const MAX_DEPTH: usize = 16;
+
+/// A decoded CBOR data item.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub(super) enum Value {
+    /// An unsigned or negative integer.
+    Integer(i128),
+
+    /// A byte string.
+    Bytes(Vec<u8>),
+
+    /// A UTF-8 text string.
+    Text(String),
+
+    /// An array of data items.
+    Array(Vec<Self>),
+
+    /// A map of key-value pairs, in the order they were encoded.
+    Map(Vec<(Self, Self)>),
+
+    /// A Boolean.
+    Bool(bool),
+
+    /// `null` or `undefined`.
+    Null,
+}
+
+impl Value {
+    /// Gets the value for an integer key if this is a map containing it.
+    pub(super) fn get_by_int(&self, key: i128) -> Option<&Self> {
+        self.get(|map_key| *map_key == Self::Integer(key))
+    }
+
+    /// Gets the value for a text key if this is a map containing it.
+    pub(super) fn get_by_text(&self, key: &str) -> Option<&Self> {
+        self.get(|map_key| matches!(map_key, Self::Text(text) if text == key))
+    }
+
+    /// Gets the value for the first key matching a predicate if this is a map.
+    fn get(&self, predicate: impl Fn(&Self) -> bool) -> Option<&Self> {
+        let Self::Map(entries) = self else {
+            return None;
+        };
+
+        entries
+            .iter()
+            .find(|(key, _)| predicate(key))
+            .map(|(_, value)| value)
+    }
+
+    /// Gets the integer if this is one.
+    pub(super) const fn as_int(&self) -> Option<i128> {
+        match self {
+            Self::Integer(int) => Some(*int),
+            _ => None,
+        }
+    }
+
+    /// Gets the bytes if this is a byte string.
+    pub(super) fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Self::Bytes(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+}
+
+/// An error decoding CBOR.
+#[derive(Error, Clone, Copy, PartialEq, Eq, Debug)]
+pub(super) enum Error {
+    /// The input ended in the middle of a data item.
+    #[error("unexpected end of input")]
+    UnexpectedEnd,
+
+    /// The input uses a CBOR feature this decoder doesn't support, such as floats, tags, or
+    /// indefinite lengths.
+    #[error("unsupported CBOR feature")]
+    Unsupported,
+
+    /// A text string isn't valid UTF-8.
+    #[error("invalid UTF-8 in text string")]
+    InvalidUtf8,
+
+    /// Arrays or maps are nested too deeply.
+    #[error("nested too deeply")]
+    TooDeep,
+}
+
+/// Decodes the data item at the start of `bytes`, returning it along with how many bytes it took
+/// up. Anything after the data item is ignored.
+///
+/// # Errors
+///
+/// Returns an error if the data item is invalid or unsupported.
+pub(super) fn decode(bytes: &[u8]) -> Result<(Value, usize), Error> {
+    let mut decoder = Decoder { bytes, position: 0 };
+    let value = decoder.decode_value(0)?;
+
+    Ok((value, decoder.position))
+}
+
+/// The state of a CBOR decoder reading through its input.
+struct Decoder<'a> {
+    /// The input being decoded.
+    bytes: &'a [u8],
+
+    /// The index of the next byte to read.
+    position: usize,
+}
+
+impl<'a> Decoder<'a> {
+    /// Reads a number of bytes, advancing past them.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::UnexpectedEnd`] if there aren't enough bytes left.
+    fn take(&mut self, length: usize) -> Result<&'a [u8], Error> {
+        let end = self
+            .position
+            .checked_add(length)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or(Error::UnexpectedEnd)?;
+
+        let taken = &self.bytes[self.position..end];
+        self.position = end;
+
+        Ok(taken)
+    }
+
+    /// Reads the argument of a data item's initial byte, which is a length, a count, or an
+    /// integer's value depending on the data item's major type.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the argument is truncated or uses an indefinite length.
+    fn decode_argument(&mut self, additional_info: u8) -> Result<u64, Error> {
+        Ok(match additional_info {
+            0..=23 => additional_info.into(),
+            24 => self.take(1)?[0].into(),
+            25 => u16::from_be_bytes(self.take(2)?.try_into().expect("should be 2 bytes")).into(),
+            26 => u32::from_be_bytes(self.take(4)?.try_into().expect("should be 4 bytes")).into(),
+            27 => u64::from_be_bytes(self.take(8)?.try_into().expect("should be 8 bytes")),
+            _ => return Err(Error::Unsupported),
+        })
+    }
+
+    /// Reads a length or count argument, checking it can't exceed the remaining input, since every
+    /// byte or nested data item takes at least one byte.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the argument is invalid or too large.
+    fn decode_length(&mut self, additional_info: u8) -> Result<usize, Error> {
+        let length = self.decode_argument(additional_info)?;
+
+        usize::try_from(length)
+            .ok()
+            .filter(|length| *length <= self.bytes.len() - self.position)
+            .ok_or(Error::UnexpectedEnd)
+    }
+
+    /// Reads a data item, advancing past it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the data item is invalid or unsupported.
+    fn decode_value(&mut self, depth: usize) -> Result<Value, Error> {
+        if depth > MAX_DEPTH {
+            return Err(Error::TooDeep);
+        }
+
+        let initial_byte = self.take(1)?[0];
+        let major_type = initial_byte >> 5;
+        let additional_info = initial_byte & 0b1_1111;
+
+        Ok(match major_type {
+            0 => Value::Integer(self.decode_argument(additional_info)?.into()),
+            1 => Value::Integer(-1 - i128::from(self.decode_argument(additional_info)?)),
+            2 => {
+                let length = self.decode_length(additional_info)?;
+                Value::Bytes(self.take(length)?.to_vec())
+            }
+            3 => {
+                let length = self.decode_length(additional_info)?;
+                let text = str::from_utf8(self.take(length)?).map_err(|_| Error::InvalidUtf8)?;
+                Value::Text(text.to_owned())
+            }
+            4 => {
+                let count = self.decode_length(additional_info)?;
+                let mut items = Vec::with_capacity(count);
+
+                for _ in 0..count {
+                    items.push(self.decode_value(depth + 1)?);
+                }
+
+                Value::Array(items)
+            }
+            5 => {
+                let count = self.decode_length(additional_info)?;
+                let mut entries = Vec::with_capacity(count);
+
+                for _ in 0..count {
+                    let key = self.decode_value(depth + 1)?;
+                    let value = self.decode_value(depth + 1)?;
+                    entries.push((key, value));
+                }
+
+                Value::Map(entries)
+            }
+            7 => match additional_info {
+                20 => Value::Bool(false),
+                21 => Value::Bool(true),
+                22 | 23 => Value::Null,
+                _ => return Err(Error::Unsupported),
+            },
+            _ => return Err(Error::Unsupported),
+        })
+    }
+}
+
+#[cfg(test)]
+#[expect(clippy::missing_errors_doc, reason = "See rust-lang/rust-clippy#13391")]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_cose_key() -> Result<(), Error> {
+        // {1: 2, 3: -7, -1: 1, -2: h'0102', "fmt": "none", "ok": true}, followed by an extra byte.
+        let bytes = [
+            0xa6, 0x01, 0x02, 0x03, 0x26, 0x20, 0x01, 0x21, 0x42, 0x01, 0x02, 0x63, b'f', b'm',
+            b't', 0x64, b'n', b'o', b'n', b'e', 0x62, b'o', b'k', 0xf5, 0xff,
+        ];
+
+        let (value, length) = decode(&bytes)?;
+
+        assert_eq!(length, bytes.len() - 1);
+        assert_eq!(value.get_by_int(1), Some(&Value::Integer(2)));
+        assert_eq!(value.get_by_int(3).and_then(Value::as_int), Some(-7));
+        assert_eq!(
+            value.get_by_int(-2).and_then(Value::as_bytes),
+            Some(&[1, 2][..])
+        );
+        assert_eq!(
+            value.get_by_text("fmt"),
+            Some(&Value::Text("none".to_owned())),
+        );
+        assert_eq!(value.get_by_text("ok"), Some(&Value::Bool(true)));
+        assert_eq!(value.get_by_int(4), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_invalid_input() {
+        // A byte string claiming to be longer than the input.
+        assert_eq!(
+            decode(&[0x5a, 0xff, 0xff, 0xff, 0xff]),
+            Err(Error::UnexpectedEnd)
+        );
+
+        // An indefinite-length array.
+        assert_eq!(decode(&[0x9f, 0xff]), Err(Error::Unsupported));
+
+        // A float.
+        assert_eq!(decode(&[0xf9, 0x3c, 0x00]), Err(Error::Unsupported),);
+
+        // Arrays nested deeper than allowed.
+        assert_eq!(decode(&[0x81; MAX_DEPTH + 2]), Err(Error::TooDeep));
+    }
+}
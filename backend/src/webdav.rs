@@ -0,0 +1,207 @@
+//! A WebDAV server (RFC 4918) over each user's files and folders, so they can be mounted as a
+//! network drive. File Garden exposes this via `https://filegarden.com/dav/`.
+//!
+//! Clients sign in with HTTP Basic authentication, using the user's email as the username and
+//! their WebDAV token as the password. Locks aren't supported, but `LOCK` and `UNLOCK` requests
+//! succeed without effect since some clients refuse to write without them.
+
+use axum::{
+    body::Body,
+    extract::Request,
+    http::{
+        HeaderMap, HeaderName, Method, StatusCode,
+        header::{ALLOW, AUTHORIZATION, CONTENT_TYPE, WWW_AUTHENTICATE},
+    },
+};
+use base64::{Engine as _, engine::general_purpose::STANDARD};
+
+use crate::{
+    api,
+    crypto::hash_without_salt,
+    db::{self, TxError, TxResult},
+    id::{Id, Token},
+    response::Response,
+    xml,
+};
+
+mod path;
+mod read;
+mod write;
+
+use path::DavPath;
+
+/// The URI path the WebDAV server is exposed under.
+pub(crate) const PATH_PREFIX: &str = "/dav";
+
+/// The `DAV` header name.
+const DAV: HeaderName = HeaderName::from_static("dav");
+
+/// The `Depth` header name.
+const DEPTH: HeaderName = HeaderName::from_static("depth");
+
+/// The `Lock-Token` header name.
+const LOCK_TOKEN: HeaderName = HeaderName::from_static("lock-token");
+
+/// The methods the WebDAV server allows.
+const ALLOWED_METHODS: &str =
+    "OPTIONS, PROPFIND, GET, HEAD, PUT, MKCOL, DELETE, MOVE, COPY, LOCK, UNLOCK";
+
+/// An error response for a WebDAV request.
+#[derive(Clone, Copy, Debug)]
+struct Error(StatusCode);
+
+impl From<api::Error> for Error {
+    fn from(error: api::Error) -> Self {
+        Self(error.status())
+    }
+}
+
+impl From<sqlx::Error> for Error {
+    fn from(_: sqlx::Error) -> Self {
+        Self(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(_: std::io::Error) -> Self {
+        Self(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+}
+
+impl From<TxError<api::Error>> for TxError<Error> {
+    fn from(error: TxError<api::Error>) -> Self {
+        match error {
+            TxError::Abort(error) => Self::Abort(error.into()),
+            TxError::Retry => Self::Retry,
+        }
+    }
+}
+
+/// The service function to handle incoming requests for the WebDAV server.
+pub(super) async fn handle(request: Request) -> Response {
+    let (request, body) = request.into_parts();
+    let mut response = Response::new();
+
+    response.header_valid(DAV, "1, 2");
+
+    if request.method == Method::OPTIONS {
+        response.header_valid(ALLOW, ALLOWED_METHODS);
+        return response;
+    }
+
+    let user_id = match authenticate(&request.headers).await {
+        Ok(Some(user_id)) => user_id,
+        Ok(None) => {
+            response.header_valid(
+                WWW_AUTHENTICATE,
+                "Basic realm=\"File Garden\", charset=\"UTF-8\"",
+            );
+            return response.plain_error(StatusCode::UNAUTHORIZED);
+        }
+        Err(_) => return response.plain_error(StatusCode::INTERNAL_SERVER_ERROR),
+    };
+
+    let Some(path) = DavPath::parse(request.uri.path()) else {
+        return response.plain_error(StatusCode::NOT_FOUND);
+    };
+
+    let result = match request.method.as_str() {
+        "PROPFIND" => read::propfind(response, &request.headers, &user_id, &path).await,
+        "GET" => read::get(response, &user_id, &path, true).await,
+        "HEAD" => read::get(response, &user_id, &path, false).await,
+        "PUT" => write::put(response, &user_id, &path, body).await,
+        "MKCOL" => write::mkcol(response, &request.headers, &user_id, &path).await,
+        "DELETE" => write::delete(response, &user_id, &path).await,
+        "MOVE" => write::move_or_copy(response, &request.headers, &user_id, &path, false).await,
+        "COPY" => write::move_or_copy(response, &request.headers, &user_id, &path, true).await,
+        "LOCK" => Ok(lock(response, &path)),
+        "UNLOCK" => {
+            response.status(StatusCode::NO_CONTENT);
+            Ok(response)
+        }
+        _ => {
+            let mut response = Response::new();
+            response.header_valid(ALLOW, ALLOWED_METHODS);
+            Ok(response.plain_error(StatusCode::METHOD_NOT_ALLOWED))
+        }
+    };
+
+    match result {
+        Ok(response) => response,
+        Err(Error(status)) => {
+            let mut response = Response::new();
+            response.header_valid(DAV, "1, 2");
+            response.plain_error(status)
+        }
+    }
+}
+
+/// Authenticates a request using HTTP Basic authentication with a user's email and WebDAV token,
+/// returning the user's ID.
+///
+/// Returns [`None`] if the credentials are missing or incorrect.
+///
+/// # Errors
+///
+/// Returns an error if a database query fails.
+async fn authenticate(headers: &HeaderMap) -> sqlx::Result<Option<Vec<u8>>> {
+    let Some(credentials) = headers
+        .get(AUTHORIZATION)
+        .and_then(|authorization| authorization.to_str().ok())
+        .and_then(|authorization| authorization.strip_prefix("Basic "))
+        .and_then(|credentials| STANDARD.decode(credentials.trim()).ok())
+        .and_then(|credentials| String::from_utf8(credentials).ok())
+    else {
+        return Ok(None);
+    };
+
+    let Some((email, token)) = credentials.split_once(':') else {
+        return Ok(None);
+    };
+
+    let Ok(token) = token.parse::<Token>() else {
+        return Ok(None);
+    };
+    let token_hash = hash_without_salt(&token);
+
+    db::transaction!(async |tx| -> TxResult<_> {
+        Ok(sqlx::query!(
+            "SELECT users.id FROM webdav_tokens
+                INNER JOIN users ON users.id = webdav_tokens.user_id
+                WHERE webdav_tokens.token_hash = $1 AND users.email = $2",
+            token_hash.as_ref(),
+            email,
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        .map(|user| user.id))
+    })
+    .await
+}
+
+/// Responds to a `LOCK` request as if a lock were granted, without actually locking anything.
+fn lock(mut response: Response, path: &DavPath) -> Response {
+    let uuid: String = Id::<[u8; 16]>::generate()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+    let lock_token = format!(
+        "opaquelocktoken:{}-{}-{}-{}-{}",
+        &uuid[..8],
+        &uuid[8..12],
+        &uuid[12..16],
+        &uuid[16..20],
+        &uuid[20..],
+    );
+
+    response
+        .header_valid(CONTENT_TYPE, "application/xml; charset=utf-8")
+        .header_valid(LOCK_TOKEN, format!("<{lock_token}>"));
+
+    response.body(Body::from(format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<D:prop xmlns:D="DAV:"><D:lockdiscovery><D:activelock><D:locktype><D:write/></D:locktype><D:lockscope><D:exclusive/></D:lockscope><D:depth>infinity</D:depth><D:timeout>Second-3600</D:timeout><D:locktoken><D:href>{}</D:href></D:locktoken><D:lockroot><D:href>{}</D:href></D:lockroot></D:activelock></D:lockdiscovery></D:prop>"#,
+        xml::escape(&lock_token),
+        xml::escape(&path.href(false)),
+    )))
+}
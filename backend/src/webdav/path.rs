@@ -0,0 +1,210 @@
+//! Mapping WebDAV request paths onto users' files and folders.
+
+use chrono::{DateTime, Utc};
+use percent_encoding::{percent_decode_str, utf8_percent_encode};
+use sqlx::PgTransaction;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::{db::TxResult, percent_encoding::COMPONENT_IGNORING_SLASH};
+
+use super::{Error, PATH_PREFIX};
+
+/// A path on the WebDAV server, as a sequence of names relative to the user's root folder.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub(super) struct DavPath {
+    /// The names in the path. Empty for the root folder.
+    pub(super) segments: Vec<String>,
+}
+
+impl DavPath {
+    /// Parses a percent-encoded URI path on the WebDAV server. Returns [`None`] if the path isn't
+    /// under [`PATH_PREFIX`] or is invalid.
+    pub(super) fn parse(encoded_path: &str) -> Option<Self> {
+        let encoded_path = encoded_path.strip_prefix(PATH_PREFIX)?;
+
+        if !(encoded_path.is_empty() || encoded_path.starts_with('/')) {
+            return None;
+        }
+
+        let path = percent_decode_str(encoded_path).decode_utf8().ok()?;
+
+        // Decoding can produce null bytes from `%00`, so disallow them as a defensive measure.
+        if path.contains('\x00') {
+            return None;
+        }
+
+        let segments = path.trim_end_matches('/').split('/').skip(1);
+        let mut parsed_segments = Vec::new();
+
+        for segment in segments {
+            if segment.is_empty() || segment == "." || segment == ".." {
+                return None;
+            }
+
+            // File names are stored normalized to NFC, so they must be looked up that way too.
+            parsed_segments.push(segment.nfc().collect());
+        }
+
+        Some(Self {
+            segments: parsed_segments,
+        })
+    }
+
+    /// Splits the path into its parent folder's path and its name, or returns [`None`] for the root
+    /// folder.
+    pub(super) fn split_last(&self) -> Option<(&[String], &str)> {
+        let (name, parent) = self.segments.split_last()?;
+
+        Some((parent, name))
+    }
+
+    /// Checks whether this path is strictly inside another path.
+    pub(super) fn is_inside(&self, other: &Self) -> bool {
+        self.segments.len() > other.segments.len() && self.segments.starts_with(&other.segments)
+    }
+
+    /// Gets the percent-encoded URI path of this path. Collections end with a `/`.
+    pub(super) fn href(&self, is_collection: bool) -> String {
+        href(&self.segments, is_collection)
+    }
+}
+
+/// Gets the percent-encoded URI path of a sequence of names relative to the user's root folder.
+/// Collections end with a `/`.
+pub(super) fn href(segments: &[String], is_collection: bool) -> String {
+    let mut href = String::from(PATH_PREFIX);
+
+    for segment in segments {
+        href.push('/');
+        href.extend(utf8_percent_encode(segment, COMPONENT_IGNORING_SLASH));
+    }
+
+    if is_collection || segments.is_empty() {
+        href.push('/');
+    }
+
+    href
+}
+
+/// A folder on the WebDAV server.
+#[derive(Debug)]
+pub(super) struct Folder {
+    /// The folder's ID.
+    pub(super) id: Vec<u8>,
+
+    /// The IDs of the folder's ancestors, from the outermost inward.
+    pub(super) parent_id_path: Vec<Vec<u8>>,
+
+    /// When the folder was created.
+    pub(super) created_at: DateTime<Utc>,
+}
+
+/// A complete file on the WebDAV server.
+#[derive(Debug)]
+pub(super) struct File {
+    /// The file's ID.
+    pub(super) id: Vec<u8>,
+
+    /// When the file was created.
+    pub(super) created_at: DateTime<Utc>,
+
+    /// When the file was last modified.
+    pub(super) modified_at: DateTime<Utc>,
+
+    /// The size of the file's content in bytes.
+    pub(super) size: i64,
+
+    /// The file's MIME type.
+    pub(super) r#type: String,
+
+    /// The file's content ID.
+    pub(super) content_id: Vec<u8>,
+
+    /// The SHA-256 hash of the file's content.
+    pub(super) hash: Vec<u8>,
+
+    /// The number of parts the file's content is stored in.
+    pub(super) part_count: i32,
+}
+
+/// Something a path on the WebDAV server can refer to.
+#[derive(Debug)]
+pub(super) enum Resource {
+    /// The user's root folder.
+    Root,
+
+    /// A folder.
+    Folder(Folder),
+
+    /// A complete file.
+    File(File),
+}
+
+impl Resource {
+    /// Gets the resource's ID as a parent folder: `Some(None)` for the root folder,
+    /// `Some(Some(id))` for a folder, or [`None`] for a file since files can't contain anything.
+    #[expect(
+        clippy::option_option,
+        reason = "the inner `Option` matches how parent folder IDs are passed everywhere else"
+    )]
+    pub(super) fn as_parent_id(&self) -> Option<Option<&[u8]>> {
+        match self {
+            Self::Root => Some(None),
+            Self::Folder(folder) => Some(Some(&folder.id)),
+            Self::File(_) => None,
+        }
+    }
+}
+
+/// Looks up what a path on the WebDAV server refers to among a user's files and folders. Returns
+/// [`None`] if nothing exists at the path.
+///
+/// # Errors
+///
+/// Returns an error if a database query fails.
+pub(super) async fn resolve(
+    tx: &mut PgTransaction<'static>,
+    user_id: &[u8],
+    path: &DavPath,
+) -> TxResult<Option<Resource>, Error> {
+    let Some((parent_name_path, name)) = path.split_last() else {
+        return Ok(Some(Resource::Root));
+    };
+
+    if let Some(folder) = sqlx::query_as!(
+        Folder,
+        "SELECT id, parent_id_path, created_at FROM folders
+            WHERE owner_id = $1 AND parent_name_path = $2 AND name = $3",
+        user_id,
+        parent_name_path,
+        name,
+    )
+    .fetch_optional(tx.as_mut())
+    .await?
+    {
+        return Ok(Some(Resource::Folder(folder)));
+    }
+
+    Ok(sqlx::query_as!(
+        File,
+        r#"SELECT
+            files.id,
+            files.created_at,
+            files.modified_at,
+            files.size,
+            files.type,
+            files.content_id,
+            file_contents.hash AS "hash!",
+            file_contents.part_count
+            FROM files
+            INNER JOIN file_contents ON file_contents.id = files.content_id
+            WHERE files.owner_id = $1 AND files.parent_name_path = $2 AND files.name = $3
+                AND files.complete"#,
+        user_id,
+        parent_name_path,
+        name,
+    )
+    .fetch_optional(tx.as_mut())
+    .await?
+    .map(Resource::File))
+}
@@ -0,0 +1,296 @@
+//! Handlers for WebDAV methods that read files and folders.
+
+use std::fmt::Write as _;
+
+use axum::{
+    body::Body,
+    http::{
+        HeaderMap, HeaderValue, StatusCode,
+        header::{
+            CONTENT_DISPOSITION, CONTENT_LENGTH, CONTENT_SECURITY_POLICY, CONTENT_TYPE, ETAG,
+            LAST_MODIFIED, X_CONTENT_TYPE_OPTIONS,
+        },
+    },
+};
+use chrono::{DateTime, SecondsFormat, Utc};
+
+use crate::{
+    db::{self, TxError, TxResult},
+    response::{Response, etag, format_http_date},
+    storage, xml,
+};
+
+use super::{
+    DEPTH, Error,
+    path::{DavPath, Resource, href, resolve},
+};
+
+/// The properties of a file or folder to list in a `PROPFIND` response.
+struct Entry {
+    /// The entry's path relative to the user's root folder.
+    segments: Vec<String>,
+
+    /// When the entry was created, or [`None`] for the root folder.
+    created_at: Option<DateTime<Utc>>,
+
+    /// The entry's file properties, or [`None`] if it's a folder.
+    file: Option<FileProperties>,
+}
+
+/// The properties specific to a file in a `PROPFIND` response.
+struct FileProperties {
+    /// When the file was last modified.
+    modified_at: DateTime<Utc>,
+
+    /// The size of the file's content in bytes.
+    size: i64,
+
+    /// The file's MIME type.
+    r#type: String,
+
+    /// The SHA-256 hash of the file's content.
+    hash: Vec<u8>,
+}
+
+/// Handles a `PROPFIND` request, listing all supported properties of a file or folder and (with
+/// `Depth: 1`) its children. `Depth: infinity` isn't supported.
+///
+/// # Errors
+///
+/// Returns an error if nothing exists at the path or a database query fails.
+pub(super) async fn propfind(
+    mut response: Response,
+    headers: &HeaderMap,
+    user_id: &[u8],
+    path: &DavPath,
+) -> Result<Response, Error> {
+    let include_children = match headers.get(DEPTH).map(HeaderValue::as_bytes) {
+        Some(b"0") => false,
+        Some(b"1") => true,
+        _ => {
+            response
+                .status(StatusCode::FORBIDDEN)
+                .header_valid(CONTENT_TYPE, "application/xml; charset=utf-8");
+
+            return Ok(response.body(
+                r#"<?xml version="1.0" encoding="utf-8"?>
+<D:error xmlns:D="DAV:"><D:propfind-finite-depth/></D:error>"#,
+            ));
+        }
+    };
+
+    let entries = db::transaction!(async |tx| -> TxResult<_, Error> {
+        let Some(resource) = resolve(tx, user_id, path).await? else {
+            return Err(TxError::Abort(Error(StatusCode::NOT_FOUND)));
+        };
+
+        let (entry, folder_id_path) = match resource {
+            Resource::Root => (
+                Entry {
+                    segments: vec![],
+                    created_at: None,
+                    file: None,
+                },
+                Some(vec![]),
+            ),
+            Resource::Folder(folder) => {
+                let mut folder_id_path = folder.parent_id_path;
+                folder_id_path.push(folder.id);
+
+                (
+                    Entry {
+                        segments: path.segments.clone(),
+                        created_at: Some(folder.created_at),
+                        file: None,
+                    },
+                    Some(folder_id_path),
+                )
+            }
+            Resource::File(file) => (
+                Entry {
+                    segments: path.segments.clone(),
+                    created_at: Some(file.created_at),
+                    file: Some(FileProperties {
+                        modified_at: file.modified_at,
+                        size: file.size,
+                        r#type: file.r#type,
+                        hash: file.hash,
+                    }),
+                },
+                None,
+            ),
+        };
+
+        let mut entries = vec![entry];
+
+        let Some(folder_id_path) = folder_id_path.filter(|_| include_children) else {
+            return Ok(entries);
+        };
+
+        let child_segments = |name: String| {
+            let mut segments = path.segments.clone();
+            segments.push(name);
+            segments
+        };
+
+        let folders = sqlx::query!(
+            "SELECT name, created_at FROM folders
+                WHERE owner_id = $1 AND parent_id_path = $2",
+            user_id,
+            folder_id_path.as_slice(),
+        )
+        .fetch_all(tx.as_mut())
+        .await?;
+
+        entries.extend(folders.into_iter().map(|folder| Entry {
+            segments: child_segments(folder.name),
+            created_at: Some(folder.created_at),
+            file: None,
+        }));
+
+        let files = sqlx::query!(
+            r#"SELECT
+                files.name,
+                files.created_at,
+                files.modified_at,
+                files.size,
+                files.type,
+                file_contents.hash AS "hash!"
+                FROM files
+                INNER JOIN file_contents ON file_contents.id = files.content_id
+                WHERE files.owner_id = $1 AND files.parent_id_path = $2 AND files.complete"#,
+            user_id,
+            folder_id_path.as_slice(),
+        )
+        .fetch_all(tx.as_mut())
+        .await?;
+
+        entries.extend(files.into_iter().map(|file| Entry {
+            segments: child_segments(file.name),
+            created_at: Some(file.created_at),
+            file: Some(FileProperties {
+                modified_at: file.modified_at,
+                size: file.size,
+                r#type: file.r#type,
+                hash: file.hash,
+            }),
+        }));
+
+        Ok(entries)
+    })
+    .await?;
+
+    let mut xml = String::from(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<D:multistatus xmlns:D="DAV:">"#,
+    );
+
+    for entry in entries {
+        write_entry(&mut xml, &entry);
+    }
+
+    xml.push_str("</D:multistatus>");
+
+    response
+        .status(StatusCode::MULTI_STATUS)
+        .header_valid(CONTENT_TYPE, "application/xml; charset=utf-8");
+
+    Ok(response.body(xml))
+}
+
+/// Appends an entry's `<D:response>` element to a `PROPFIND` response body.
+fn write_entry(xml: &mut String, entry: &Entry) {
+    let display_name = entry
+        .segments
+        .last()
+        .map(String::as_str)
+        .unwrap_or_default();
+
+    xml.push_str("<D:response>");
+
+    // Writing to a `String` can't fail.
+    let _ = write!(
+        xml,
+        "<D:href>{}</D:href><D:propstat><D:prop><D:displayname>{}</D:displayname>",
+        xml::escape(&href(&entry.segments, entry.file.is_none())),
+        xml::escape(display_name),
+    );
+
+    if let Some(created_at) = entry.created_at {
+        let _ = write!(
+            xml,
+            "<D:creationdate>{}</D:creationdate>",
+            created_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+        );
+    }
+
+    match &entry.file {
+        Some(file) => {
+            let _ = write!(
+                xml,
+                "<D:resourcetype/>\
+                <D:getlastmodified>{}</D:getlastmodified>\
+                <D:getcontentlength>{}</D:getcontentlength>\
+                <D:getcontenttype>{}</D:getcontenttype>\
+                <D:getetag>{}</D:getetag>",
+                format_http_date(file.modified_at),
+                file.size,
+                xml::escape(&file.r#type),
+                xml::escape(&etag(&file.hash)),
+            );
+        }
+        None => xml.push_str("<D:resourcetype><D:collection/></D:resourcetype>"),
+    }
+
+    xml.push_str(
+        "<D:supportedlock><D:lockentry>\
+        <D:lockscope><D:exclusive/></D:lockscope><D:locktype><D:write/></D:locktype>\
+        </D:lockentry></D:supportedlock>\
+        </D:prop><D:status>HTTP/1.1 200 OK</D:status></D:propstat></D:response>",
+    );
+}
+
+/// Handles a `GET` or `HEAD` request, responding with a file's content. Folders can't be
+/// downloaded.
+///
+/// # Errors
+///
+/// Returns an error if no file exists at the path or a database query fails.
+pub(super) async fn get(
+    mut response: Response,
+    user_id: &[u8],
+    path: &DavPath,
+    include_body: bool,
+) -> Result<Response, Error> {
+    let resource =
+        db::transaction!(async |tx| -> TxResult<_, Error> { resolve(tx, user_id, path).await })
+            .await?;
+
+    let file = match resource {
+        Some(Resource::File(file)) => file,
+        Some(Resource::Root | Resource::Folder(_)) => {
+            return Err(Error(StatusCode::METHOD_NOT_ALLOWED));
+        }
+        None => return Err(Error(StatusCode::NOT_FOUND)),
+    };
+
+    // This is served from the website's origin, so nothing can be allowed to render inline and run
+    // scripts there.
+    response
+        .header_valid(CONTENT_TYPE, file.r#type)
+        .header_valid(CONTENT_LENGTH, file.size)
+        .header_valid(CONTENT_DISPOSITION, "attachment")
+        .header_valid(CONTENT_SECURITY_POLICY, "sandbox")
+        .header_valid(X_CONTENT_TYPE_OPTIONS, "nosniff")
+        .header_valid(ETAG, etag(&file.hash))
+        .header_valid(LAST_MODIFIED, format_http_date(file.modified_at));
+
+    if !include_body {
+        return Ok(response);
+    }
+
+    Ok(response.body(Body::from_stream(storage::read_parts(
+        file.content_id,
+        file.part_count,
+    ))))
+}
@@ -0,0 +1,581 @@
+//! Handlers for WebDAV methods that modify files and folders.
+
+use std::collections::HashMap;
+
+use axum::{
+    body::Body,
+    http::{
+        HeaderMap, HeaderName, HeaderValue, StatusCode, Uri,
+        header::{CONTENT_LENGTH, TRANSFER_ENCODING},
+    },
+};
+use futures_util::StreamExt;
+use sqlx::PgTransaction;
+
+use crate::{
+    api::{
+        db_helpers::{
+            FolderRole, add_to_folder_sizes, check_file_name, create_folder, delete_file,
+            insert_written_content, move_file, move_folder, put_file,
+            query_folder_paths_to_modify_contents, trash_folder,
+        },
+        extract::IfMatch,
+        validation::FileName,
+    },
+    db::{self, TxError, TxResult},
+    file_type::sniff_content,
+    id::{FolderBrowseKey, NewFileContentId, NewFileId, NewFolderId},
+    processing::{self, SourceContent},
+    response::Response,
+    storage,
+};
+
+use super::{
+    DEPTH, Error,
+    path::{DavPath, Folder, Resource, resolve},
+};
+
+/// The `Destination` header name.
+const DESTINATION: HeaderName = HeaderName::from_static("destination");
+
+/// The `Overwrite` header name.
+const OVERWRITE: HeaderName = HeaderName::from_static("overwrite");
+
+/// Parses a path to a new file or folder into its parent folder's path and its validated name.
+///
+/// # Errors
+///
+/// Returns an error if the path is the root folder or the name is invalid.
+fn split_new_path(path: &DavPath) -> Result<(DavPath, FileName), Error> {
+    let Some((parent_segments, name)) = path.split_last() else {
+        return Err(Error(StatusCode::METHOD_NOT_ALLOWED));
+    };
+
+    let name = FileName::try_from(name.to_owned()).map_err(|_| Error(StatusCode::BAD_REQUEST))?;
+
+    let parent_path = DavPath {
+        segments: parent_segments.to_vec(),
+    };
+
+    Ok((parent_path, name))
+}
+
+/// Gets the ID and name paths of a folder that's a parent of a new file or folder, or of the root
+/// folder if `parent_id` is [`None`].
+///
+/// # Errors
+///
+/// Returns an error if a database query fails.
+async fn query_parent_paths(
+    tx: &mut PgTransaction<'static>,
+    user_id: &[u8],
+    parent_id: Option<&[u8]>,
+) -> TxResult<(Vec<Vec<u8>>, Vec<String>), Error> {
+    let parent =
+        query_folder_paths_to_modify_contents(tx, user_id, parent_id, FolderRole::Editor).await?;
+
+    Ok((parent.id_path, parent.name_path))
+}
+
+/// Handles a `PUT` request, creating a file or replacing an existing file's content.
+///
+/// # Errors
+///
+/// Returns an error if the parent folder doesn't exist, a folder exists at the path, the request
+/// body can't be read, or storage or a database query fails.
+pub(super) async fn put(
+    mut response: Response,
+    user_id: &[u8],
+    path: &DavPath,
+    body: Body,
+) -> Result<Response, Error> {
+    let (parent_path, name) = split_new_path(path)?;
+
+    // Check this before storing anything, so a bad path doesn't waste a whole upload.
+    db::transaction!(async |tx| -> TxResult<_, Error> {
+        let parent = resolve(tx, user_id, &parent_path).await?;
+
+        if parent.as_ref().and_then(Resource::as_parent_id).is_none() {
+            return Err(TxError::Abort(Error(StatusCode::CONFLICT)));
+        }
+
+        if let Some(Resource::Folder(_)) = resolve(tx, user_id, path).await? {
+            return Err(TxError::Abort(Error(StatusCode::METHOD_NOT_ALLOWED)));
+        }
+
+        Ok(())
+    })
+    .await?;
+
+    let content_id = NewFileContentId::generate();
+
+    let result = async {
+        let body = body
+            .into_data_stream()
+            .map(|chunk| chunk.map_err(|_| Error(StatusCode::BAD_REQUEST)));
+        let content = storage::write_stream(content_id.as_slice(), body).await?;
+
+        let file_type = sniff_content(&name, content_id.as_slice(), content.part_count()).await?;
+
+        let created = db::transaction!(async |tx| -> TxResult<_, Error> {
+            let parent = resolve(tx, user_id, &parent_path).await?;
+            let Some(parent_id) = parent.as_ref().and_then(Resource::as_parent_id) else {
+                return Err(TxError::Abort(Error(StatusCode::CONFLICT)));
+            };
+
+            if let Some(Resource::Folder(_)) = resolve(tx, user_id, path).await? {
+                return Err(TxError::Abort(Error(StatusCode::METHOD_NOT_ALLOWED)));
+            }
+
+            insert_written_content::<Error>(tx, content_id.as_slice(), &content).await?;
+
+            Ok(put_file(
+                tx,
+                user_id,
+                parent_id,
+                &name,
+                content_id.as_slice(),
+                content.size,
+                &file_type,
+            )
+            .await?)
+        })
+        .await?;
+
+        if processing::is_decodable_image(&file_type.mime_type) {
+            processing::generate_thumbnails(SourceContent {
+                id: content_id.to_vec(),
+                part_count: content.part_count(),
+                size: content.size,
+                hash: content.hash,
+            });
+        }
+
+        Ok(created)
+    }
+    .await;
+
+    let created = match result {
+        Ok(created) => created,
+        Err(error) => {
+            let _ = storage::delete_content(content_id.as_slice()).await;
+            return Err(error);
+        }
+    };
+
+    response.status(if created {
+        StatusCode::CREATED
+    } else {
+        StatusCode::NO_CONTENT
+    });
+
+    Ok(response)
+}
+
+/// Handles a `MKCOL` request, creating a folder.
+///
+/// # Errors
+///
+/// Returns an error if the request has a body, something already exists at the path, the parent
+/// folder doesn't exist, or a database query fails.
+pub(super) async fn mkcol(
+    mut response: Response,
+    headers: &HeaderMap,
+    user_id: &[u8],
+    path: &DavPath,
+) -> Result<Response, Error> {
+    let has_body = headers.contains_key(TRANSFER_ENCODING)
+        || headers
+            .get(CONTENT_LENGTH)
+            .is_some_and(|content_length| content_length != "0");
+
+    if has_body {
+        return Err(Error(StatusCode::UNSUPPORTED_MEDIA_TYPE));
+    }
+
+    let (parent_path, name) = split_new_path(path)?;
+
+    db::transaction!(async |tx| -> TxResult<_, Error> {
+        if resolve(tx, user_id, path).await?.is_some() {
+            return Err(TxError::Abort(Error(StatusCode::METHOD_NOT_ALLOWED)));
+        }
+
+        let parent = resolve(tx, user_id, &parent_path).await?;
+        let Some(parent_id) = parent.as_ref().and_then(Resource::as_parent_id) else {
+            return Err(TxError::Abort(Error(StatusCode::CONFLICT)));
+        };
+
+        create_folder(tx, user_id, parent_id, &name).await?;
+
+        Ok(())
+    })
+    .await?;
+
+    response.status(StatusCode::CREATED);
+
+    Ok(response)
+}
+
+/// Handles a `DELETE` request, deleting a file or moving a folder and everything inside it to the
+/// trash.
+///
+/// # Errors
+///
+/// Returns an error if the path is the root folder, nothing exists at the path, or a database query
+/// fails.
+pub(super) async fn delete(
+    mut response: Response,
+    user_id: &[u8],
+    path: &DavPath,
+) -> Result<Response, Error> {
+    db::transaction!(async |tx| -> TxResult<_, Error> { delete_resource(tx, user_id, path).await })
+        .await?;
+
+    response.status(StatusCode::NO_CONTENT);
+
+    Ok(response)
+}
+
+/// Deletes whatever is at a path, keeping folder sizes consistent. A folder is moved to the trash
+/// rather than deleted.
+///
+/// # Errors
+///
+/// Returns an error if the path is the root folder, nothing exists at the path, or a database query
+/// fails.
+async fn delete_resource(
+    tx: &mut PgTransaction<'static>,
+    user_id: &[u8],
+    path: &DavPath,
+) -> TxResult<(), Error> {
+    match resolve(tx, user_id, path).await? {
+        Some(Resource::Root) => Err(TxError::Abort(Error(StatusCode::FORBIDDEN))),
+        Some(Resource::Folder(folder)) => Ok(trash_folder(tx, user_id, &folder.id).await?),
+        Some(Resource::File(file)) => {
+            Ok(delete_file(tx, user_id, &file.id, &IfMatch::default()).await?)
+        }
+        None => Err(TxError::Abort(Error(StatusCode::NOT_FOUND))),
+    }
+}
+
+/// Handles a `MOVE` or `COPY` request, moving or copying a file or folder to the path in the
+/// `Destination` header. Copying a folder with `Depth: 0` copies only the folder, not its contents.
+///
+/// # Errors
+///
+/// Returns an error if the destination is invalid, overlaps the source, or already exists without
+/// `Overwrite` allowed, if nothing exists at the source, if the destination's parent folder doesn't
+/// exist, or if a database query fails.
+pub(super) async fn move_or_copy(
+    mut response: Response,
+    headers: &HeaderMap,
+    user_id: &[u8],
+    path: &DavPath,
+    is_copy: bool,
+) -> Result<Response, Error> {
+    let Some(destination) = headers
+        .get(DESTINATION)
+        .and_then(|destination| destination.to_str().ok())
+        .and_then(|destination| destination.parse::<Uri>().ok())
+    else {
+        return Err(Error(StatusCode::BAD_REQUEST));
+    };
+
+    // A destination outside this server's WebDAV paths must be on some other server.
+    let Some(destination) = DavPath::parse(destination.path()) else {
+        return Err(Error(StatusCode::BAD_GATEWAY));
+    };
+
+    let overwrite = headers.get(OVERWRITE) != Some(&HeaderValue::from_static("F"));
+    let recursive = headers.get(DEPTH) != Some(&HeaderValue::from_static("0"));
+
+    if path.segments.is_empty()
+        || destination.segments.is_empty()
+        || path == &destination
+        || path.is_inside(&destination)
+        || destination.is_inside(path)
+    {
+        return Err(Error(StatusCode::FORBIDDEN));
+    }
+
+    let (destination_parent_path, destination_name) = split_new_path(&destination)?;
+
+    let overwritten = db::transaction!(async |tx| -> TxResult<_, Error> {
+        let Some(source) = resolve(tx, user_id, path).await? else {
+            return Err(TxError::Abort(Error(StatusCode::NOT_FOUND)));
+        };
+
+        let destination_parent = resolve(tx, user_id, &destination_parent_path).await?;
+        let Some(destination_parent_id) =
+            destination_parent.as_ref().and_then(Resource::as_parent_id)
+        else {
+            return Err(TxError::Abort(Error(StatusCode::CONFLICT)));
+        };
+
+        let overwritten = resolve(tx, user_id, &destination).await?.is_some();
+
+        if overwritten {
+            if !overwrite {
+                return Err(TxError::Abort(Error(StatusCode::PRECONDITION_FAILED)));
+            }
+
+            delete_resource(tx, user_id, &destination).await?;
+        }
+
+        match (source, is_copy) {
+            (Resource::Root, _) => return Err(TxError::Abort(Error(StatusCode::FORBIDDEN))),
+            (Resource::File(file), false) => {
+                move_file(
+                    tx,
+                    user_id,
+                    &file.id,
+                    destination_parent_id,
+                    Some(&destination_name),
+                    &IfMatch::default(),
+                )
+                .await?;
+            }
+            (Resource::Folder(folder), false) => {
+                move_folder(
+                    tx,
+                    user_id,
+                    &folder.id,
+                    destination_parent_id,
+                    Some(&destination_name),
+                    &IfMatch::default(),
+                )
+                .await?;
+            }
+            (Resource::File(file), true) => {
+                let (parent_id_path, parent_name_path) =
+                    query_parent_paths(tx, user_id, destination_parent_id).await?;
+
+                check_file_name(tx, user_id, &destination_name).await?;
+
+                copy_file(
+                    tx,
+                    &file.id,
+                    Some(&destination_name),
+                    &parent_id_path,
+                    &parent_name_path,
+                )
+                .await?;
+
+                add_to_folder_sizes::<Error>(tx, &parent_id_path, file.size).await?;
+            }
+            (Resource::Folder(folder), true) => {
+                copy_folder(
+                    tx,
+                    user_id,
+                    &folder,
+                    destination_parent_id,
+                    &destination_name,
+                    recursive,
+                )
+                .await?;
+            }
+        }
+
+        Ok(overwritten)
+    })
+    .await?;
+
+    response.status(if overwritten {
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::CREATED
+    });
+
+    Ok(response)
+}
+
+/// Copies a complete file into a folder, optionally with a new name. The copy shares the original's
+/// content. Folder sizes aren't updated.
+///
+/// # Errors
+///
+/// Returns an error if a database query fails or something with the same name already exists in
+/// the folder.
+async fn copy_file(
+    tx: &mut PgTransaction<'static>,
+    file_id: &[u8],
+    new_name: Option<&str>,
+    parent_id_path: &[Vec<u8>],
+    parent_name_path: &[String],
+) -> TxResult<(), Error> {
+    let new_file_id = NewFileId::generate();
+
+    match sqlx::query!(
+        "INSERT INTO files (
+            created_at,
+            id,
+            complete,
+            name,
+            owner_id,
+            parent_id_path,
+            parent_name_path,
+            size,
+            content_id,
+            type,
+            dangerous
+        )
+            SELECT now(), $1, TRUE, coalesce($2, name), owner_id, $3, $4, size, content_id, type,
+                dangerous
+                FROM files
+                WHERE id = $5 AND complete",
+        new_file_id.as_slice(),
+        new_name,
+        parent_id_path,
+        parent_name_path,
+        file_id,
+    )
+    .execute(tx.as_mut())
+    .await
+    {
+        Err(sqlx::Error::Database(error)) if error.constraint() == Some("files_pkey") => {
+            Err(TxError::Retry)
+        }
+        Err(sqlx::Error::Database(error)) if error.constraint() == Some("files_by_name_path") => {
+            Err(TxError::Abort(Error(StatusCode::CONFLICT)))
+        }
+        result => {
+            result?;
+            Ok(())
+        }
+    }
+}
+
+/// Copies a folder into another folder (or the root folder if `parent_id` is [`None`]) with a new
+/// name, along with everything inside it if `recursive` is set, keeping folder sizes consistent.
+///
+/// # Errors
+///
+/// Returns an error if a database query fails or something with the same name already exists in
+/// the parent folder.
+async fn copy_folder(
+    tx: &mut PgTransaction<'static>,
+    user_id: &[u8],
+    folder: &Folder,
+    parent_id: Option<&[u8]>,
+    name: &FileName,
+    recursive: bool,
+) -> TxResult<(), Error> {
+    let (new_folder_id, _, _) = create_folder(tx, user_id, parent_id, name).await?;
+
+    if !recursive {
+        return Ok(());
+    }
+
+    let (parent_id_path, parent_name_path) = query_parent_paths(tx, user_id, parent_id).await?;
+
+    let mut old_folder_id_path = folder.parent_id_path.clone();
+    old_folder_id_path.push(folder.id.clone());
+
+    let mut new_folder_id_path = parent_id_path.clone();
+    new_folder_id_path.push(new_folder_id.to_vec());
+
+    let mut new_folder_name_path = parent_name_path;
+    new_folder_name_path.push(name.to_string());
+
+    let size = sqlx::query!(
+        "UPDATE folders
+            SET size = (SELECT size FROM folders WHERE id = $1)
+            WHERE id = $2
+            RETURNING size",
+        folder.id,
+        new_folder_id.as_slice(),
+    )
+    .fetch_one(tx.as_mut())
+    .await?
+    .size;
+
+    add_to_folder_sizes::<Error>(tx, &parent_id_path, size).await?;
+
+    // Each copied path is the copied folder's new path followed by the path's old segments below
+    // the copied folder, with folder IDs swapped for their copies' IDs.
+    let old_depth = old_folder_id_path.len();
+    let mut new_ids = HashMap::new();
+    new_ids.insert(folder.id.clone(), new_folder_id.to_vec());
+
+    let map_paths =
+        |new_ids: &HashMap<Vec<u8>, Vec<u8>>, old_id_path: &[Vec<u8>], old_name_path: &[String]| {
+            let mut id_path = new_folder_id_path.clone();
+            id_path.extend(
+                old_id_path[old_depth..]
+                    .iter()
+                    .map(|id| new_ids.get(id).cloned().unwrap_or_default()),
+            );
+
+            let mut name_path = new_folder_name_path.clone();
+            name_path.extend_from_slice(&old_name_path[old_depth..]);
+
+            (id_path, name_path)
+        };
+
+    // Ordering by depth ensures each folder's parent is copied before it.
+    let descendant_folders = sqlx::query!(
+        "SELECT id, name, parent_id_path, parent_name_path, size FROM folders
+            WHERE owner_id = $1 AND parent_id_path >= $2 AND parent_id_path < $2 || NULL
+            ORDER BY cardinality(parent_id_path)",
+        user_id,
+        old_folder_id_path.as_slice(),
+    )
+    .fetch_all(tx.as_mut())
+    .await?;
+
+    for descendant in descendant_folders {
+        let (id_path, name_path) = map_paths(
+            &new_ids,
+            &descendant.parent_id_path,
+            &descendant.parent_name_path,
+        );
+
+        let new_id = NewFolderId::generate();
+        let browse_key = FolderBrowseKey::generate();
+
+        match sqlx::query!(
+            "INSERT INTO folders
+                (id, name, owner_id, parent_id_path, parent_name_path, browse_key, size)
+                VALUES ($1, $2, $3, $4, $5, $6, $7)",
+            new_id.as_slice(),
+            descendant.name,
+            user_id,
+            id_path.as_slice(),
+            name_path.as_slice(),
+            browse_key.as_slice(),
+            descendant.size,
+        )
+        .execute(tx.as_mut())
+        .await
+        {
+            Err(sqlx::Error::Database(error)) if error.constraint() == Some("folders_pkey") => {
+                return Err(TxError::Retry);
+            }
+            result => result?,
+        };
+
+        new_ids.insert(descendant.id, new_id.to_vec());
+    }
+
+    let descendant_files = sqlx::query!(
+        "SELECT id, parent_id_path, parent_name_path FROM files
+            WHERE owner_id = $1 AND parent_id_path >= $2 AND parent_id_path < $2 || NULL
+                AND complete",
+        user_id,
+        old_folder_id_path.as_slice(),
+    )
+    .fetch_all(tx.as_mut())
+    .await?;
+
+    for descendant in descendant_files {
+        let (id_path, name_path) = map_paths(
+            &new_ids,
+            &descendant.parent_id_path,
+            &descendant.parent_name_path,
+        );
+
+        copy_file(tx, &descendant.id, None, &id_path, &name_path).await?;
+    }
+
+    Ok(())
+}